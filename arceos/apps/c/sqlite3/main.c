@@ -37,13 +37,8 @@ void query(sqlite3 *db, char *sql)
     }
 }
 
-int main()
+void run(sqlite3 *db)
 {
-    printf("sqlite version%s\n", sqlite3_libversion());
-    sqlite3 *db;
-    int ret = sqlite3_open(":memory:", &db);
-    printf("sqlite open memory status %d \n", ret);
-
     printf("init user table\n");
     exec(db, "create table user("
              "id INTEGER PRIMARY KEY AUTOINCREMENT,"
@@ -61,6 +56,29 @@ int main()
 
     printf("select id = 2");
     query(db, "select * from user where id = 2");
+}
+
+int main()
+{
+    printf("sqlite version%s\n", sqlite3_libversion());
+    sqlite3 *db;
+    int ret = sqlite3_open(":memory:", &db);
+    printf("sqlite open memory status %d \n", ret);
+    run(db);
+    sqlite3_close(db);
+
+    // The same on disk. Reopening the file shows the rows were written out.
+    ret = sqlite3_open("/test.db", &db);
+    printf("sqlite open /test.db status %d \n", ret);
+    exec(db, "drop table if exists user");
+    run(db);
+    sqlite3_close(db);
+
+    ret = sqlite3_open("/test.db", &db);
+    printf("sqlite reopen /test.db status %d \n", ret);
+    printf("select all");
+    query(db, "select * from user");
+    sqlite3_close(db);
 
     return 0;
 }
@@ -14,34 +14,34 @@ extern crate alloc;
 
 fn test_directory() {
     // create a test directory
-    libax::fs::create_dir("/totop\0".into()).expect("can't create directory");
+    libax::fs::create_dir("/totop").expect("can't create directory");
 
     // whether the test directory exists
-    // let finded: Vec<String> = libax::fs::read_dir("/".into())
+    // let finded: Vec<String> = libax::fs::read_dir("/")
     //     .expect("can't read directory")
     //     .into_iter()
     //     .filter(|x| x == "totop")
     //     .collect();
     // assert_eq!(finded.len(), 1);
-    libax::fs::read_dir("/".into())
+    libax::fs::read_dir("/")
         .map(|x| {
-            for file_name in x {
-                libax::println!("{}", file_name);
+            for entry in x {
+                libax::println!("{}", entry.expect("can't read directory entry").file_name());
             }
         })
         .expect("can't read root directory");
     // remove the directory
-    libax::fs::remove_dir("/totop\0".into()).expect("can't remove directory");
+    libax::fs::remove_dir("/totop").expect("can't remove directory");
     info!("end remove dir");
 }
 
 fn test_list_files() {
     // list files in the root directory
     libax::println!("{:=^30}", " file list ");
-    libax::fs::read_dir("/".into())
+    libax::fs::read_dir("/")
         .map(|x| {
-            for file_name in x {
-                libax::println!("{}", file_name);
+            for entry in x {
+                libax::println!("{}", entry.expect("can't read directory entry").file_name());
             }
         })
         .expect("can't read root directory");
@@ -50,30 +50,30 @@ fn test_list_files() {
 
 fn test_file() {
     // write a test file, if the file not exists, then create it
-    libax::fs::write("/test\0".into(), b" Hello fs\n").expect("can't write to test file");
+    libax::fs::write("/test", b" Hello fs\n").expect("can't write to test file");
     libax::println!("end write");
     // read the file from the file
-    let file_content = libax::fs::read("/test\0".into()).expect("can't read the test file");
+    let file_content = libax::fs::read("/test").expect("can't read the test file");
     assert_eq!(file_content, b" Hello fs\n");
 
     // whether the file exists
-    // let finded: Vec<String> = libax::fs::read_dir("/".into())
+    // let finded: Vec<String> = libax::fs::read_dir("/")
     //     .expect("can't read directory")
     //     .into_iter()
-    //     .filter(|x| x == "test\0")
+    //     .filter(|x| x == "test")
     //     .collect();
     // assert_eq!(finded.len(), 1);
-    libax::fs::read_dir("/".into())
+    libax::fs::read_dir("/")
     .map(|x| {
-        for file_name in x {
-            libax::println!("{}", file_name);
+        for entry in x {
+            libax::println!("{}", entry.expect("can't read directory entry").file_name());
         }
     })
     .expect("can't read root directory");
     // remove the file
-    let res=libax::fs::read("/test\0".into()).unwrap();
+    let res=libax::fs::read("/test").unwrap();
     libax::println!("{:?}",core::str::from_utf8(&res.as_slice()));
-    libax::fs::remove_file("/test\0".into()).expect("can't remove test file");
+    libax::fs::remove_file("/test").expect("can't remove test file");
 }
 
 fn test_sleep_lock(){
@@ -90,21 +90,21 @@ fn test_concurrent_fs(){
     for i in 0..4{
         task::spawn(move||{
             let mut path="/test";
-            let new_path=path.to_owned()+&i.to_string()+&"\0".to_owned();
-            libax::fs::write(new_path.as_str().into(), b" Hello fs\n").expect("can't write to test file");
+            let new_path=path.to_owned()+&i.to_string();
+            libax::fs::write(&new_path, b" Hello fs\n").expect("can't write to test file");
             libax::println!("end write");
-            let file_content = libax::fs::read(new_path.as_str().into()).expect("can't read the test file");
+            let file_content = libax::fs::read(&new_path).expect("can't read the test file");
             assert_eq!(file_content, b" Hello fs\n");
             task::yield_now();
-            libax::fs::read_dir("/".into())
+            libax::fs::read_dir("/")
             .map(|x| {
-                for file_name in x {
-                    libax::println!("{}", file_name);
+                for entry in x {
+                    libax::println!("{}", entry.expect("can't read directory entry").file_name());
                 }
             })
             .expect("can't read root directory");
             COUNTER.fetch_add(1, core::sync::atomic::Ordering::Acquire);
-            libax::fs::remove_file(new_path.as_str().into());
+            libax::fs::remove_file(&new_path);
         });
     }
     loop {
@@ -113,10 +113,10 @@ fn test_concurrent_fs(){
         }
         task::yield_now();
     } 
-    libax::fs::read_dir("/".into())
+    libax::fs::read_dir("/")
             .map(|x| {
-                for file_name in x {
-                    libax::println!("{}", file_name);
+                for entry in x {
+                    libax::println!("{}", entry.expect("can't read directory entry").file_name());
                 }
             })
             .expect("can't read root directory");
@@ -126,10 +126,10 @@ fn test_concurrent_fs(){
 
 fn test_huge_write(){
     libax::println!("{:=^30}", " file list ");
-    libax::fs::read_dir("/".into())
+    libax::fs::read_dir("/")
             .map(|x| {
-                for file_name in x {
-                    libax::println!("{}", file_name);
+                for entry in x {
+                    libax::println!("{}", entry.expect("can't read directory entry").file_name());
                 }
             })
             .expect("can't read root directory");
@@ -139,19 +139,19 @@ fn test_huge_write(){
     for _ in 0..20000{//bitmap分配这里有问题捏
         text=text.to_owned()+&text2.clone().to_owned();
     }
-    libax::fs::write("/test\0".into(), text.as_bytes()).expect("can't write to test file");
-    libax::fs::write("/test1\0".into(), text.as_bytes()).expect("can't write to test file");
-    libax::fs::write("/test2\0".into(), text.as_bytes()).expect("can't write to test file");
+    libax::fs::write("/test", text.as_bytes()).expect("can't write to test file");
+    libax::fs::write("/test1", text.as_bytes()).expect("can't write to test file");
+    libax::fs::write("/test2", text.as_bytes()).expect("can't write to test file");
     libax::println!("end write");
     // read the file from the file
-    libax::fs::remove_file("/test\0".into()).expect("can't remove test file");
-    libax::fs::remove_file("/test1\0".into()).expect("can't remove test file");
-    libax::fs::remove_file("/test2\0".into()).expect("can't remove test file");
+    libax::fs::remove_file("/test").expect("can't remove test file");
+    libax::fs::remove_file("/test1").expect("can't remove test file");
+    libax::fs::remove_file("/test2").expect("can't remove test file");
     libax::println!("{:=^30}", " file list ");
-    libax::fs::read_dir("/".into())
+    libax::fs::read_dir("/")
             .map(|x| {
-                for file_name in x {
-                    libax::println!("{}", file_name);
+                for entry in x {
+                    libax::println!("{}", entry.expect("can't read directory entry").file_name());
                 }
             })
             .expect("can't read root directory");
@@ -0,0 +1,97 @@
+//! A disk image in a host file.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+
+use crate::{BlockDriverOps, BLOCK_SIZE};
+
+/// A disk image in a host file, for tools that build or inspect images and
+/// for tests. A trailing part block of the file is not on the disk.
+pub struct FileDisk {
+    file: Mutex<File>,
+    num_blocks: u64,
+}
+
+impl FileDisk {
+    /// The image in `file`, which must be open for reading and writing.
+    pub fn new(file: File) -> io::Result<Self> {
+        let num_blocks = file.metadata()?.len() / BLOCK_SIZE as u64;
+        Ok(Self {
+            file: Mutex::new(file),
+            num_blocks,
+        })
+    }
+
+    /// The existing image at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::new(OpenOptions::new().read(true).write(true).open(path)?)
+    }
+
+    /// A new image at `path` of `num_blocks` zeroed blocks, replacing
+    /// whatever was there.
+    pub fn create<P: AsRef<Path>>(path: P, num_blocks: u64) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(num_blocks * BLOCK_SIZE as u64)?;
+        Self::new(file)
+    }
+
+    /// Seek to block `block_id`, if `len` bytes from it are on the disk.
+    fn seek(&self, file: &mut File, block_id: usize, len: usize) -> DevResult {
+        let end = (block_id as u64).checked_add(len.div_ceil(BLOCK_SIZE) as u64);
+        if len % BLOCK_SIZE != 0 || !end.is_some_and(|end| end <= self.num_blocks) {
+            return Err(DevError::InvalidParam);
+        }
+        file.seek(SeekFrom::Start(block_id as u64 * BLOCK_SIZE as u64))
+            .map_err(|_| DevError::Io)?;
+        Ok(())
+    }
+}
+
+impl BaseDriverOps for FileDisk {
+    fn device_name(&self) -> &str {
+        "file"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl BlockDriverOps for FileDisk {
+    fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> DevResult {
+        self.read_blocks(block_id, buf)
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> DevResult {
+        self.write_blocks(block_id, buf)
+    }
+
+    fn flush(&self) -> DevResult {
+        self.file.lock().unwrap().sync_data().map_err(|_| DevError::Io)
+    }
+
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) -> DevResult {
+        let mut file = self.file.lock().unwrap();
+        self.seek(&mut file, start_block, buf.len())?;
+        file.read_exact(buf).map_err(|_| DevError::Io)
+    }
+
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) -> DevResult {
+        let mut file = self.file.lock().unwrap();
+        self.seek(&mut file, start_block, buf.len())?;
+        file.write_all(buf).map_err(|_| DevError::Io)
+    }
+}
@@ -1,9 +1,46 @@
-#![no_std]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod file;
+pub mod partition;
+mod ramdisk;
 
 use driver_common::{BaseDriverOps, DevResult};
 
+#[cfg(feature = "std")]
+pub use file::FileDisk;
+pub use partition::Partition;
+pub use ramdisk::RamDisk;
+
+/// Size of one block in bytes, the same for every device.
+pub const BLOCK_SIZE: usize = 512;
+
 pub trait BlockDriverOps: BaseDriverOps {
+    /// Number of blocks on the device.
+    fn num_blocks(&self) -> u64;
+
     fn read_block(&self, block_id: usize, buf: &mut [u8]) -> DevResult;
     fn write_block(&self, block_id: usize, buf: &[u8]) -> DevResult;
-    fn flush(&mut self) -> DevResult;
+    /// Wait until all completed writes are on stable storage.
+    fn flush(&self) -> DevResult;
+
+    /// Read consecutive blocks starting at `start_block` into `buf`,
+    /// whose length must be a multiple of `BLOCK_SIZE`.
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) -> DevResult {
+        for (i, block) in buf.chunks_mut(BLOCK_SIZE).enumerate() {
+            self.read_block(start_block + i, block)?;
+        }
+        Ok(())
+    }
+
+    /// Write consecutive blocks starting at `start_block` from `buf`,
+    /// whose length must be a multiple of `BLOCK_SIZE`.
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) -> DevResult {
+        for (i, block) in buf.chunks(BLOCK_SIZE).enumerate() {
+            self.write_block(start_block + i, block)?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,392 @@
+//! Partitions of a disk, from its MBR or GPT partition table.
+//!
+//! [`read_partitions`] finds the partitions and [`Partition`] makes each a
+//! block device of its own. MBR logical partitions, in the extended
+//! partition, are numbered from 5 as on Linux.
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+
+use crate::{BlockDriverOps, BLOCK_SIZE};
+
+/// Where a partition lies on its disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// Number of the partition, from 1, as in the `1` of `vda1`.
+    pub number: usize,
+    /// First block.
+    pub start: u64,
+    pub num_blocks: u64,
+}
+
+/// MBR partition type of a GPT disk's protective partition.
+const MBR_TYPE_GPT: u8 = 0xee;
+/// MBR partition types of extended partitions.
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+/// Most logical partitions followed, in case the chain loops.
+const MAX_LOGICAL: usize = 128;
+/// Largest GPT partition entry array read, in bytes.
+const MAX_GPT_ENTRIES_SIZE: usize = 1 << 20;
+
+/// The partitions on `disk`, empty if it has no partition table.
+pub fn read_partitions(disk: &dyn BlockDriverOps) -> DevResult<Vec<PartitionInfo>> {
+    let mut mbr = [0u8; BLOCK_SIZE];
+    disk.read_block(0, &mut mbr)?;
+    let Some(entries) = mbr_entries(&mbr, disk.num_blocks()) else {
+        return Ok(Vec::new());
+    };
+    if entries.iter().any(|entry| entry.kind == MBR_TYPE_GPT) {
+        return read_gpt(disk);
+    }
+    let mut partitions = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.kind == 0 {
+            continue;
+        }
+        if MBR_TYPES_EXTENDED.contains(&entry.kind) {
+            read_logical(disk, entry.start, &mut partitions)?;
+        } else {
+            partitions.push(PartitionInfo {
+                number: i + 1,
+                start: entry.start,
+                num_blocks: entry.num_blocks,
+            });
+        }
+    }
+    Ok(partitions)
+}
+
+#[derive(Clone, Copy)]
+struct MbrEntry {
+    kind: u8,
+    start: u64,
+    num_blocks: u64,
+}
+
+/// The four entries of the MBR, or EBR, in `block`, with their start
+/// relative to the block. `None` if it is not one: a FAT or other boot
+/// sector also ends in 0x55AA, but holds code where the entries would be,
+/// which rarely looks like them.
+fn mbr_entries(block: &[u8], num_blocks: u64) -> Option<[MbrEntry; 4]> {
+    if block[510..512] != [0x55, 0xaa] {
+        return None;
+    }
+    let mut entries = [MbrEntry {
+        kind: 0,
+        start: 0,
+        num_blocks: 0,
+    }; 4];
+    for (i, entry) in entries.iter_mut().enumerate() {
+        let raw = &block[446 + i * 16..446 + (i + 1) * 16];
+        let status = raw[0];
+        let kind = raw[4];
+        let start = u32::from_le_bytes(raw[8..12].try_into().unwrap()) as u64;
+        let len = u32::from_le_bytes(raw[12..16].try_into().unwrap()) as u64;
+        if status != 0 && status != 0x80 {
+            return None;
+        }
+        if kind == 0 {
+            continue;
+        }
+        // The GPT protective partition may claim more than the disk has.
+        if start == 0 || (kind != MBR_TYPE_GPT && start + len > num_blocks) {
+            return None;
+        }
+        *entry = MbrEntry {
+            kind,
+            start,
+            num_blocks: len,
+        };
+    }
+    Some(entries)
+}
+
+/// Follow the chain of EBRs in the extended partition at `extended`.
+fn read_logical(
+    disk: &dyn BlockDriverOps,
+    extended: u64,
+    partitions: &mut Vec<PartitionInfo>,
+) -> DevResult {
+    let mut ebr_at = extended;
+    let mut block = [0u8; BLOCK_SIZE];
+    for number in 5..5 + MAX_LOGICAL {
+        disk.read_block(ebr_at as usize, &mut block)?;
+        let Some(entries) = mbr_entries(&block, disk.num_blocks()) else {
+            break;
+        };
+        // The first entry is the partition, relative to its EBR, the second
+        // the next EBR, relative to the extended partition. `mbr_entries`
+        // only checked them against the disk as if they were absolute.
+        if entries[0].kind != 0 {
+            let start = ebr_at + entries[0].start;
+            if start + entries[0].num_blocks > disk.num_blocks() {
+                break;
+            }
+            partitions.push(PartitionInfo {
+                number,
+                start,
+                num_blocks: entries[0].num_blocks,
+            });
+        }
+        if entries[1].kind == 0 {
+            break;
+        }
+        ebr_at = extended + entries[1].start;
+        if ebr_at >= disk.num_blocks() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The partitions in the GPT, from the primary header or, if that is
+/// damaged, the backup one at the end of the disk.
+fn read_gpt(disk: &dyn BlockDriverOps) -> DevResult<Vec<PartitionInfo>> {
+    match read_gpt_at(disk, 1) {
+        Ok(partitions) => Ok(partitions),
+        Err(_) => read_gpt_at(disk, disk.num_blocks().saturating_sub(1)),
+    }
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn le_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn read_gpt_at(disk: &dyn BlockDriverOps, lba: u64) -> DevResult<Vec<PartitionInfo>> {
+    let mut header = [0u8; BLOCK_SIZE];
+    disk.read_block(lba as usize, &mut header)?;
+    let header_size = le_u32(&header, 12) as usize;
+    if &header[0..8] != b"EFI PART" || !(92..=BLOCK_SIZE).contains(&header_size) {
+        return Err(DevError::BadState);
+    }
+    let mut checked = header;
+    checked[16..20].fill(0);
+    if crc32(&checked[..header_size]) != le_u32(&header, 16) {
+        return Err(DevError::BadState);
+    }
+
+    let entries_lba = le_u64(&header, 72);
+    let count = le_u32(&header, 80) as usize;
+    let entry_size = le_u32(&header, 84) as usize;
+    let size = count.checked_mul(entry_size).ok_or(DevError::BadState)?;
+    if entry_size < 128 || size > MAX_GPT_ENTRIES_SIZE {
+        return Err(DevError::BadState);
+    }
+    let mut entries = vec![0u8; size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE];
+    disk.read_blocks(entries_lba as usize, &mut entries)?;
+    if crc32(&entries[..size]) != le_u32(&header, 88) {
+        return Err(DevError::BadState);
+    }
+
+    let mut partitions = Vec::new();
+    for (i, entry) in entries[..size].chunks_exact(entry_size).enumerate() {
+        // An all-zero type GUID marks an unused entry.
+        if entry[..16].iter().all(|&b| b == 0) {
+            continue;
+        }
+        let (first, last) = (le_u64(entry, 32), le_u64(entry, 40));
+        if first > last || last >= disk.num_blocks() {
+            continue;
+        }
+        partitions.push(PartitionInfo {
+            number: i + 1,
+            start: first,
+            num_blocks: last - first + 1,
+        });
+    }
+    Ok(partitions)
+}
+
+/// CRC-32 as GPT uses it, the one of zlib.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// A partition of a disk, as a block device of its own.
+pub struct Partition {
+    disk: Arc<dyn BlockDriverOps>,
+    start: u64,
+    num_blocks: u64,
+}
+
+impl Partition {
+    pub fn new(disk: Arc<dyn BlockDriverOps>, info: &PartitionInfo) -> Self {
+        Self {
+            disk,
+            start: info.start,
+            num_blocks: info.num_blocks,
+        }
+    }
+
+    /// Block `block_id` of the partition on the disk, if `count` blocks
+    /// from it are inside the partition.
+    fn disk_block(&self, block_id: usize, count: usize) -> DevResult<usize> {
+        match (block_id as u64).checked_add(count as u64) {
+            Some(end) if end <= self.num_blocks => Ok((self.start + block_id as u64) as usize),
+            _ => Err(DevError::InvalidParam),
+        }
+    }
+}
+
+impl BaseDriverOps for Partition {
+    fn device_name(&self) -> &str {
+        self.disk.device_name()
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl BlockDriverOps for Partition {
+    fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> DevResult {
+        self.disk.read_block(self.disk_block(block_id, 1)?, buf)
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> DevResult {
+        self.disk.write_block(self.disk_block(block_id, 1)?, buf)
+    }
+
+    fn flush(&self) -> DevResult {
+        self.disk.flush()
+    }
+
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) -> DevResult {
+        let start = self.disk_block(start_block, buf.len().div_ceil(BLOCK_SIZE))?;
+        self.disk.read_blocks(start, buf)
+    }
+
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) -> DevResult {
+        let start = self.disk_block(start_block, buf.len().div_ceil(BLOCK_SIZE))?;
+        self.disk.write_blocks(start, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RamDisk;
+
+    /// Fill in entry `i` of the MBR or EBR at block `at`.
+    fn set_entry(disk: &RamDisk, at: u64, i: usize, kind: u8, start: u32, len: u32) {
+        let mut block = [0u8; BLOCK_SIZE];
+        disk.read_block(at as usize, &mut block).unwrap();
+        let raw = &mut block[446 + i * 16..446 + (i + 1) * 16];
+        raw[4] = kind;
+        raw[8..12].copy_from_slice(&start.to_le_bytes());
+        raw[12..16].copy_from_slice(&len.to_le_bytes());
+        block[510..512].copy_from_slice(&[0x55, 0xaa]);
+        disk.write_block(at as usize, &block).unwrap();
+    }
+
+    fn info(number: usize, start: u64, num_blocks: u64) -> PartitionInfo {
+        PartitionInfo {
+            number,
+            start,
+            num_blocks,
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_no_table() {
+        let disk = RamDisk::new(64 * BLOCK_SIZE);
+        assert_eq!(read_partitions(&disk).unwrap(), []);
+        // A FAT boot sector ends in 0x55AA too, with code in the table.
+        let mut boot = [0u8; BLOCK_SIZE];
+        boot[0] = 0xeb;
+        boot[446] = 0x12;
+        boot[510..512].copy_from_slice(&[0x55, 0xaa]);
+        disk.write_block(0, &boot).unwrap();
+        assert_eq!(read_partitions(&disk).unwrap(), []);
+    }
+
+    #[test]
+    fn test_mbr() {
+        let disk = RamDisk::new(8192 * BLOCK_SIZE);
+        set_entry(&disk, 0, 0, 0x83, 2048, 100);
+        set_entry(&disk, 0, 1, 0x05, 4096, 1000);
+        set_entry(&disk, 4096, 0, 0x83, 63, 100);
+        set_entry(&disk, 4096, 1, 0x05, 200, 300);
+        set_entry(&disk, 4296, 0, 0x0c, 63, 200);
+        assert_eq!(
+            read_partitions(&disk).unwrap(),
+            [info(1, 2048, 100), info(5, 4159, 100), info(6, 4359, 200)]
+        );
+        // A logical partition that only fits relative to its EBR ends the
+        // chain.
+        set_entry(&disk, 4296, 1, 0x05, 400, 100);
+        set_entry(&disk, 4496, 0, 0x83, 63, 4000);
+        assert_eq!(
+            read_partitions(&disk).unwrap(),
+            [info(1, 2048, 100), info(5, 4159, 100), info(6, 4359, 200)]
+        );
+        // A partition past the end of the disk means it is no MBR at all.
+        set_entry(&disk, 0, 2, 0x83, 8000, 1000);
+        assert_eq!(read_partitions(&disk).unwrap(), []);
+    }
+
+    #[test]
+    fn test_gpt_backup() {
+        let num_blocks = 8192;
+        let disk = RamDisk::new(num_blocks * BLOCK_SIZE);
+        set_entry(&disk, 0, 0, MBR_TYPE_GPT, 1, u32::MAX);
+        let mut entries = vec![0u8; 128 * 128];
+        for (i, first, last) in [(0, 34u64, 1000u64), (2, 2000, 2999)] {
+            let entry = &mut entries[i * 128..(i + 1) * 128];
+            entry[0] = 1;
+            entry[32..40].copy_from_slice(&first.to_le_bytes());
+            entry[40..48].copy_from_slice(&last.to_le_bytes());
+        }
+        disk.write_blocks(2, &entries).unwrap();
+        let mut header = [0u8; BLOCK_SIZE];
+        header[0..8].copy_from_slice(b"EFI PART");
+        header[12..16].copy_from_slice(&92u32.to_le_bytes());
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&128u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        header[88..92].copy_from_slice(&crc32(&entries).to_le_bytes());
+        let crc = crc32(&header[..92]);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        disk.write_block(num_blocks - 1, &header).unwrap();
+        // Damage the primary header, so only the backup is good.
+        header[20] ^= 1;
+        disk.write_block(1, &header).unwrap();
+        assert_eq!(
+            read_partitions(&disk).unwrap(),
+            [info(1, 34, 967), info(3, 2000, 1000)]
+        );
+    }
+
+    #[test]
+    fn test_partition_bounds() {
+        let disk = Arc::new(RamDisk::new(1024 * BLOCK_SIZE));
+        let part = Partition::new(disk.clone(), &info(1, 100, 200));
+        assert_eq!(part.num_blocks(), 200);
+        part.write_block(199, &[7; BLOCK_SIZE]).unwrap();
+        let mut block = [0u8; BLOCK_SIZE];
+        disk.read_block(299, &mut block).unwrap();
+        assert_eq!(block, [7; BLOCK_SIZE]);
+        assert!(part.read_block(200, &mut block).is_err());
+        assert!(part.read_blocks(199, &mut [0; 2 * BLOCK_SIZE]).is_err());
+    }
+}
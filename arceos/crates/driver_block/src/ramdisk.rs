@@ -0,0 +1,84 @@
+//! A disk in memory.
+
+use alloc::{vec, vec::Vec};
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+use spin::Mutex;
+
+use crate::{BlockDriverOps, BLOCK_SIZE};
+
+/// A disk kept in memory, lost when it is dropped.
+pub struct RamDisk {
+    data: Mutex<Vec<u8>>,
+    /// Length of `data`, which never changes.
+    size: usize,
+}
+
+impl RamDisk {
+    /// A zeroed disk of `size` bytes, rounded up to whole blocks.
+    pub fn new(size: usize) -> Self {
+        let size = size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        Self {
+            data: Mutex::new(vec![0; size]),
+            size,
+        }
+    }
+
+    /// A disk holding a copy of `image`, zero-padded to whole blocks and
+    /// to at least `size` bytes. The copy is what gets written to, so
+    /// `image` may be read-only, such as one from `include_bytes!`.
+    pub fn from_image(image: &[u8], size: usize) -> Self {
+        let disk = Self::new(size.max(image.len()));
+        disk.data.lock()[..image.len()].copy_from_slice(image);
+        disk
+    }
+
+    /// Bytes of blocks `block_id..block_id + len / BLOCK_SIZE`, if they are
+    /// all on the disk and `len` is a multiple of the block size.
+    fn range(&self, block_id: usize, len: usize) -> DevResult<core::ops::Range<usize>> {
+        let start = block_id.checked_mul(BLOCK_SIZE).ok_or(DevError::InvalidParam)?;
+        match start.checked_add(len) {
+            Some(end) if len % BLOCK_SIZE == 0 && end <= self.size => Ok(start..end),
+            _ => Err(DevError::InvalidParam),
+        }
+    }
+}
+
+impl BaseDriverOps for RamDisk {
+    fn device_name(&self) -> &str {
+        "ramdisk"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl BlockDriverOps for RamDisk {
+    fn num_blocks(&self) -> u64 {
+        (self.size / BLOCK_SIZE) as u64
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> DevResult {
+        self.read_blocks(block_id, buf)
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> DevResult {
+        self.write_blocks(block_id, buf)
+    }
+
+    fn flush(&self) -> DevResult {
+        Ok(())
+    }
+
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) -> DevResult {
+        let range = self.range(start_block, buf.len())?;
+        buf.copy_from_slice(&self.data.lock()[range]);
+        Ok(())
+    }
+
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) -> DevResult {
+        let range = self.range(start_block, buf.len())?;
+        self.data.lock()[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
@@ -1,5 +1,4 @@
 #![no_std]
-#![feature(const_trait_impl)]
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum DeviceType {
@@ -31,7 +30,6 @@ pub enum DevError {
 
 pub type DevResult<T = ()> = Result<T, DevError>;
 
-#[const_trait]
 pub trait BaseDriverOps: Send + Sync {
     fn device_name(&self) -> &str;
     fn device_type(&self) -> DeviceType;
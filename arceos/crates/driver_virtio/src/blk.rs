@@ -19,7 +19,7 @@ impl<H: Hal, T: Transport> VirtIoBlkDev<H, T> {
     }
 }
 
-impl<H: Hal, T: Transport> const BaseDriverOps for VirtIoBlkDev<H, T> {
+impl<H: Hal, T: Transport> BaseDriverOps for VirtIoBlkDev<H, T> {
     fn device_name(&self) -> &str {
         "virtio-blk"
     }
@@ -30,6 +30,10 @@ impl<H: Hal, T: Transport> const BaseDriverOps for VirtIoBlkDev<H, T> {
 }
 
 impl<H: Hal, T: Transport> BlockDriverOps for VirtIoBlkDev<H, T> {
+    fn num_blocks(&self) -> u64 {
+        self.inner.lock().capacity()
+    }
+
     fn read_block(&self, block_id: usize, buf: &mut [u8]) -> DevResult {
         self.inner.lock().read_block(block_id, buf).map_err(as_dev_err)
     }
@@ -38,7 +42,13 @@ impl<H: Hal, T: Transport> BlockDriverOps for VirtIoBlkDev<H, T> {
         self.inner.lock().write_block(block_id, buf).map_err(as_dev_err)
     }
 
-    fn flush(&mut self) -> DevResult {
+    // `read_blocks` and `write_blocks` keep the default one request per
+    // sector: the pinned virtio-drivers only takes single-sector buffers.
+
+    fn flush(&self) -> DevResult {
+        // The pinned virtio-drivers does not negotiate VIRTIO_BLK_F_FLUSH,
+        // so the device runs write-through and a request is durable once
+        // it has completed.
         Ok(())
     }
 }
@@ -1,5 +1,4 @@
 #![no_std]
-#![feature(const_trait_impl)]
 
 #[macro_use]
 extern crate cfg_if;
@@ -1,216 +1,604 @@
 #![cfg_attr(not(test), no_std)]
 
-#[macro_use]
 extern crate alloc;
-#[macro_use]
-extern crate log;
+
+mod time;
 
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
-use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::time::Duration;
 
 use fatfs::{
-    Dir, File, FileSystem, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write,
+    Dir, File, FileAttributes, FileSystem, LossyOemCpConverter, Read, Seek, SeekFrom, Write,
 };
 use spin::Mutex;
-use vfscore::{DiskOperation, VfsFile, VfsFileSystem};
+use vfscore::{
+    AxError, BlockDevice, DirEntry, FileType, Metadata, MountOptions, SetAttr, StatFs, VfsFile,
+    VfsFileSystem, VfsResult, BLOCK_SIZE,
+};
 
-pub struct Fat32FileSystem<T: DiskOperation>(
-    fatfs::FileSystem<DiskCursor<T>, NullTimeProvider, LossyOemCpConverter>,
-);
+pub use time::Clock;
+
+type FatDir = Dir<'static, DiskCursor, Clock, LossyOemCpConverter>;
+type FatFile = File<'static, DiskCursor, Clock, LossyOemCpConverter>;
+type FatDirEntry = fatfs::DirEntry<'static, DiskCursor, Clock, LossyOemCpConverter>;
+
+/// A FAT12, FAT16 or FAT32 file system on a block device.
+///
+/// FAT has no inode numbers. Like Linux's vfat, files are numbered as they
+/// are first seen, and keep their number across renames while mounted.
+pub struct Fat32FileSystem {
+    inner: FileSystem<DiskCursor, Clock, LossyOemCpConverter>,
+    clock: Clock,
+    inos: Mutex<Inos>,
+    read_only: bool,
+    /// Write file data and entries through as soon as they change.
+    sync: bool,
+    /// fatfs keeps its state in `RefCell`s, so every call into it, including
+    /// dropping a file, holds this.
+    lock: Mutex<()>,
+}
 
-unsafe impl<T: DiskOperation> Send for Fat32FileSystem<T> {}
+// Safe as every access to `inner` holds `lock`.
+unsafe impl Send for Fat32FileSystem {}
 
-unsafe impl<T: DiskOperation> Sync for Fat32FileSystem<T> {}
+unsafe impl Sync for Fat32FileSystem {}
 
-impl<T: DiskOperation + 'static> VfsFileSystem for Fat32FileSystem<T> {
-    fn root(&'static self) -> Box<dyn vfscore::VfsFile> {
-        Inode::new_dir(self.0.root_dir())
+impl VfsFileSystem for Fat32FileSystem {
+    fn root(self: Arc<Self>) -> Box<dyn VfsFile> {
+        let dir = {
+            let _fs = self.lock.lock();
+            // SAFETY: the directory goes into an inode that holds `self`.
+            unsafe { self.inner_static() }.root_dir()
+        };
+        Box::new(Inode {
+            fs: self,
+            ino: ROOT_INO,
+            node: ManuallyDrop::new(Mutex::new(Node::Dir(dir))),
+            readonly: false,
+            mtime: Mutex::new(None),
+        })
     }
 
     fn name(&self) -> &str {
         "fat32"
     }
+
+    fn statfs(&self) -> VfsResult<StatFs> {
+        let _fs = self.lock.lock();
+        let stats = self.inner.stats().map_err(ax_error)?;
+        Ok(StatFs {
+            block_size: stats.cluster_size() as usize,
+            blocks: stats.total_clusters() as usize,
+            blocks_free: stats.free_clusters() as usize,
+            // FAT has no inode table.
+            files: 0,
+            files_free: 0,
+            name_max: 255,
+        })
+    }
+
+    fn cache_pages(&self) -> bool {
+        true
+    }
 }
 
-impl<T: DiskOperation> Fat32FileSystem<T> {
-    pub fn new() -> Self {
-        let cursor: DiskCursor<T> = DiskCursor {
-            sector: 0,
-            offset: 0,
-            block_device: PhantomData,
-        };
-        Self(FileSystem::new(cursor, fatfs::FsOptions::new()).expect("open fs wrong"))
+impl Fat32FileSystem {
+    /// Open the file system on `dev`. `clock` gives the time since the Unix
+    /// epoch to stamp changed entries with. Of the mount options it takes
+    /// `ro`, `sync` and `noatime`; the others are for other file systems
+    /// and fail with `InvalidParam`.
+    pub fn new(
+        dev: Arc<dyn BlockDevice>,
+        clock: fn() -> Duration,
+        options: &MountOptions,
+    ) -> VfsResult<Self> {
+        if options.commit_interval.is_some()
+            || options.data_mode.is_some()
+            || options.cache_blocks.is_some()
+            || options.size.is_some()
+        {
+            return Err(AxError::InvalidParam);
+        }
+        let clock = Clock(clock);
+        let cursor = DiskCursor { dev, pos: 0 };
+        // FAT keeps the date of the last access, which reads then write.
+        let fs_options = fatfs::FsOptions::new()
+            .time_provider(clock)
+            .update_accessed_date(!options.noatime && !options.read_only);
+        let inner = FileSystem::new(cursor, fs_options).map_err(ax_error)?;
+        Ok(Self {
+            inner,
+            clock,
+            inos: Mutex::new(Inos::default()),
+            read_only: options.read_only,
+            sync: options.sync,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// `inner` with its lifetime extended, for the `'static` fatfs handles.
+    ///
+    /// # Safety
+    ///
+    /// Whatever is made from it must be dropped before the last `Arc` of
+    /// `self` is.
+    unsafe fn inner_static(&self) -> &'static FileSystem<DiskCursor, Clock, LossyOemCpConverter> {
+        &*(&self.inner as *const _)
+    }
+
+    fn now(&self) -> Duration {
+        (self.clock.0)()
+    }
+
+    fn check_writable(&self) -> VfsResult {
+        if self.read_only {
+            return Err(AxError::ReadOnlyFilesystem);
+        }
+        Ok(())
     }
 }
 
-pub enum Inode<T: DiskOperation + 'static> {
-    /// File
-    File(Arc<Mutex<File<'static, DiskCursor<T>, NullTimeProvider, LossyOemCpConverter>>>),
-    /// Dir
-    Dir(Arc<Mutex<Dir<'static, DiskCursor<T>, NullTimeProvider, LossyOemCpConverter>>>),
+/// Whether `head`, the first bytes of a device, is a FAT boot sector: it
+/// ends with the 0x55AA signature and names its FAT type in the boot record.
+pub fn probe(head: &[u8]) -> bool {
+    if head.len() < 512 || head[510..512] != [0x55, 0xAA] {
+        return false;
+    }
+    // FAT32 keeps the type string at offset 82, FAT12/16 at offset 54.
+    head[82..85] == *b"FAT" || head[54..57] == *b"FAT"
 }
 
-impl<T: DiskOperation> VfsFile for Inode<T> {
-    fn open(&self, path: &str) -> Option<Box<dyn vfscore::VfsFile>> {
-        match self {
-            Inode::File(_) => None,
-            Inode::Dir(dir) => {
-                let dir = dir.lock();
-                if let Ok(f) = dir.open_file(path) {
-                    Some(Inode::new_file(f))
-                } else if let Ok(d) = dir.open_dir(path) {
-                    Some(Inode::new_dir(d))
-                } else {
-                    None
-                }
-            }
+enum Node {
+    File(FatFile),
+    Dir(FatDir),
+}
+
+/// An open file or directory.
+pub struct Inode {
+    /// Keeps the file system alive for `node`, which borrows it.
+    fs: Arc<Fat32FileSystem>,
+    ino: usize,
+    /// Dropped with the file system locked, as dropping a file writes its
+    /// directory entry back, and before `fs`.
+    node: ManuallyDrop<Mutex<Node>>,
+    /// Has the read-only attribute.
+    readonly: bool,
+    /// Time of the last change, `None` for the root, which has no entry.
+    mtime: Mutex<Option<Duration>>,
+}
+
+/// Map a fatfs error to the matching `AxError`.
+fn ax_error(err: fatfs::Error<DiskCursorIoError>) -> AxError {
+    match err {
+        fatfs::Error::Io(DiskCursorIoError::Device(err)) => err,
+        fatfs::Error::NotFound => AxError::NotFound,
+        fatfs::Error::AlreadyExists => AxError::AlreadyExists,
+        fatfs::Error::DirectoryIsNotEmpty => AxError::DirectoryNotEmpty,
+        fatfs::Error::NotEnoughSpace => AxError::StorageFull,
+        fatfs::Error::InvalidInput
+        | fatfs::Error::InvalidFileNameLength
+        | fatfs::Error::UnsupportedFileNameCharacter => AxError::InvalidParam,
+        _ => AxError::Io,
+    }
+}
+
+const ROOT_INO: usize = 1;
+
+/// The inode numbers handed out, by directory and name.
+struct Inos {
+    by_name: BTreeMap<(usize, String), usize>,
+    /// Where each numbered file is, while it has a name.
+    names: BTreeMap<usize, (usize, String)>,
+    next: usize,
+}
+
+impl Default for Inos {
+    fn default() -> Self {
+        Self {
+            by_name: BTreeMap::new(),
+            names: BTreeMap::new(),
+            next: ROOT_INO + 1,
         }
     }
+}
 
-    fn read_dir(&self) -> Vec<String> {
-        match self {
-            Inode::File(_) => vec![],
-            Inode::Dir(dir) => {
-                let mut result = vec![];
-                for f in dir.lock().iter() {
-                    if let Ok(f) = f {
-                        result.push(f.file_name());
-                    }
-                }
+/// FAT matches names without regard to case.
+fn name_key(dir: usize, name: &str) -> (usize, String) {
+    (dir, name.to_lowercase())
+}
 
-                result
-            }
+impl Inos {
+    /// Number of `name` in directory `dir`, given one if it has none yet.
+    fn get(&mut self, dir: usize, name: &str) -> usize {
+        let key = name_key(dir, name);
+        if let Some(&ino) = self.by_name.get(&key) {
+            return ino;
         }
+        let ino = self.next;
+        self.next += 1;
+        self.by_name.insert(key.clone(), ino);
+        self.names.insert(ino, key);
+        ino
     }
 
-    fn read(&self, buf: &mut [u8]) -> usize {
-        match self {
-            Inode::File(file) => {
-                let mut file = file.lock();
-                let file_size = file.seek(fatfs::SeekFrom::End(0)).unwrap();
-                file.seek(fatfs::SeekFrom::Start(0))
-                    .expect("can't seek file");
-                file.read_exact(buf).expect("can't read file exactly");
-                file_size as usize
-            }
-            Inode::Dir(_) => 0,
+    /// Number of the file at `path` below directory `dir`.
+    fn resolve(&mut self, dir: usize, path: &str) -> usize {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .fold(dir, |dir, name| self.get(dir, name))
+    }
+
+    /// `name` in `dir` is gone. Files still open keep its number, but a new
+    /// file of that name gets another.
+    fn remove(&mut self, dir: usize, name: &str) {
+        if let Some(ino) = self.by_name.remove(&name_key(dir, name)) {
+            self.names.remove(&ino);
         }
     }
 
-    fn write(&self, data: &[u8]) -> usize {
-        match self {
-            Inode::File(file) => {
-                let mut file = file.lock();
-                file.write_all(data).expect("can't write file");
-                data.len()
-            }
-            Inode::Dir(_) => 0,
+    fn rename(&mut self, src: (usize, &str), dst: (usize, &str)) {
+        if let Some(ino) = self.by_name.remove(&name_key(src.0, src.1)) {
+            let key = name_key(dst.0, dst.1);
+            self.by_name.insert(key.clone(), ino);
+            self.names.insert(ino, key);
         }
     }
 
-    #[inline]
-    fn close(&self) {
-        // close file
+    fn linked(&self, ino: usize) -> bool {
+        ino == ROOT_INO || self.names.contains_key(&ino)
     }
+}
 
-    fn mkdir(&self, folder_name: &str) -> Option<Box<dyn VfsFile>> {
-        match self {
-            Inode::File(_file) => None,
-            Inode::Dir(dir) => {
-                let file = dir.lock().create_dir(folder_name).map(Inode::new_dir);
-                file.map(Some).unwrap_or(None)
-            }
+/// Directory part and last name of `path`.
+fn split(path: &str) -> (&str, &str) {
+    let path = path.trim_matches('/');
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+/// The entry at `path` below `dir`.
+fn find_entry(dir: &FatDir, path: &str) -> VfsResult<FatDirEntry> {
+    let path = path.trim_matches('/');
+    let (parent, name) = match path.rsplit_once('/') {
+        Some((parent, name)) => (Some(dir.open_dir(parent).map_err(ax_error)?), name),
+        None => (None, path),
+    };
+    for entry in parent.as_ref().unwrap_or(dir).iter() {
+        let entry = entry.map_err(ax_error)?;
+        if entry.file_name().eq_ignore_ascii_case(name)
+            || entry.short_file_name().eq_ignore_ascii_case(name)
+        {
+            return Ok(entry);
         }
     }
+    Err(AxError::NotFound)
+}
 
-    fn create(&self, file_name: &str) -> Option<Box<dyn VfsFile>> {
-        match self {
-            Inode::File(_file) => None,
-            Inode::Dir(dir) => {
-                let file = dir.lock().create_file(file_name).map(Inode::new_file);
-                file.map(Some).unwrap_or(None)
-            }
+impl Inode {
+    fn open(fs: Arc<Fat32FileSystem>, ino: usize, entry: &FatDirEntry) -> Box<dyn VfsFile> {
+        let node = if entry.is_dir() {
+            Node::Dir(entry.to_dir())
+        } else {
+            Node::File(entry.to_file())
+        };
+        Box::new(Self {
+            fs,
+            ino,
+            node: ManuallyDrop::new(Mutex::new(node)),
+            readonly: entry.attributes().contains(FileAttributes::READ_ONLY),
+            mtime: Mutex::new(Some(time::from_fat(entry.modified()))),
+        })
+    }
+
+    /// Run `f` on the directory, with the file system locked.
+    fn with_dir<R>(&self, f: impl FnOnce(&FatDir) -> VfsResult<R>) -> VfsResult<R> {
+        let _fs = self.fs.lock.lock();
+        match &*self.node.lock() {
+            Node::Dir(dir) => f(dir),
+            Node::File(_) => Err(AxError::NotADirectory),
         }
     }
 
-    fn seek(&self, seek: vfscore::SeekFrom) -> usize {
-        if let Inode::File(f) = self {
-            match seek {
-                vfscore::SeekFrom::Start(index) => {
-                    f.lock().seek(SeekFrom::Start(index as u64)).unwrap() as _
-                }
-                vfscore::SeekFrom::Current(index) => {
-                    f.lock().seek(SeekFrom::Current(index as i64)).unwrap() as _
+    /// Run `f` on the file, with the file system locked.
+    fn with_file<R>(&self, f: impl FnOnce(&mut FatFile) -> VfsResult<R>) -> VfsResult<R> {
+        let _fs = self.fs.lock.lock();
+        match &mut *self.node.lock() {
+            Node::File(file) => f(file),
+            Node::Dir(_) => Err(AxError::IsADirectory),
+        }
+    }
+
+    /// Number of `entry`, found at `path` below this directory. It goes by
+    /// the long name of the entry, which may have been found by its short
+    /// one.
+    fn ino_of(&self, path: &str, entry: &FatDirEntry) -> usize {
+        let mut inos = self.fs.inos.lock();
+        let dir = inos.resolve(self.ino, split(path).0);
+        inos.get(dir, &entry.file_name())
+    }
+
+    /// Directory number and long name of the entry at `path` below this
+    /// directory.
+    fn name_of(&self, dir: &FatDir, path: &str) -> VfsResult<(usize, String)> {
+        let name = find_entry(dir, path)?.file_name();
+        let parent = self.fs.inos.lock().resolve(self.ino, split(path).0);
+        Ok((parent, name))
+    }
+
+    /// On a `sync` mount, write the data and entry of `file` out now.
+    fn sync_file(&self, file: &mut FatFile) -> VfsResult {
+        if self.fs.sync {
+            file.flush().map_err(ax_error)?;
+        }
+        Ok(())
+    }
+
+    fn touch(&self) {
+        *self.mtime.lock() = Some(self.fs.now());
+    }
+}
+
+impl Drop for Inode {
+    fn drop(&mut self) {
+        let _fs = self.fs.lock.lock();
+        // SAFETY: `node` is not used again.
+        unsafe { ManuallyDrop::drop(&mut self.node) };
+    }
+}
+
+impl VfsFile for Inode {
+    fn getattr(&self) -> VfsResult<Metadata> {
+        let (file_type, size) = {
+            let _fs = self.fs.lock.lock();
+            match &mut *self.node.lock() {
+                Node::File(file) => {
+                    (FileType::File, file.seek(SeekFrom::End(0)).map_err(ax_error)? as usize)
                 }
-                vfscore::SeekFrom::End(index) => {
-                    f.lock().seek(SeekFrom::End(index as i64)).unwrap() as _
+                Node::Dir(_) => (FileType::Dir, 0),
+            }
+        };
+        // A removed file keeps its clusters only while open.
+        let nlink = self.fs.inos.lock().linked(self.ino) as usize;
+        // FAT has no permission bits, only a read-only attribute.
+        let mode = match (file_type, self.readonly) {
+            (FileType::Dir, false) => 0o755,
+            (FileType::Dir, true) => 0o555,
+            (_, false) => 0o644,
+            (_, true) => 0o444,
+        };
+        Ok(Metadata {
+            ino: self.ino,
+            file_type,
+            mode,
+            size,
+            nlink,
+            rdev: None,
+            mtime: *self.mtime.lock(),
+        })
+    }
+
+    fn setattr(&self, attr: SetAttr) -> VfsResult {
+        if attr.mode.is_some() {
+            return Err(AxError::Unsupported);
+        }
+        if attr.size.is_none() && attr.mtime.is_none() {
+            return Ok(());
+        }
+        self.fs.check_writable()?;
+        self.with_file(|file| {
+            if let Some(size) = attr.size {
+                let size = size as u64;
+                let end = file.seek(SeekFrom::End(0)).map_err(ax_error)?;
+                if size < end {
+                    file.seek(SeekFrom::Start(size)).map_err(ax_error)?;
+                    file.truncate().map_err(ax_error)?;
+                } else {
+                    write_zeros(file, size - end)?;
                 }
             }
-        } else {
-            0
+            if let Some(mtime) = attr.mtime {
+                file.set_modified(time::to_fat(mtime));
+            }
+            self.sync_file(file)
+        })?;
+        match attr.mtime {
+            Some(mtime) => *self.mtime.lock() = Some(mtime),
+            None => self.touch(),
         }
+        Ok(())
     }
 
-    #[inline]
-    fn is_dir(&self) -> bool {
-        match self {
-            Inode::File(_) => false,
-            Inode::Dir(_) => true,
-        }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> VfsResult<usize> {
+        self.with_file(|file| {
+            file.seek(SeekFrom::Start(offset as u64)).map_err(ax_error)?;
+            let mut total = 0;
+            while total < buf.len() {
+                match file.read(&mut buf[total..]).map_err(ax_error)? {
+                    0 => break,
+                    n => total += n,
+                }
+            }
+            Ok(total)
+        })
     }
 
-    #[inline]
-    fn is_file(&self) -> bool {
-        match self {
-            Inode::File(_) => true,
-            Inode::Dir(_) => false,
-        }
+    fn write_at(&self, offset: usize, data: &[u8]) -> VfsResult<usize> {
+        self.fs.check_writable()?;
+        self.with_file(|file| {
+            let end = file.seek(SeekFrom::End(0)).map_err(ax_error)?;
+            if (offset as u64) > end {
+                write_zeros(file, offset as u64 - end)?;
+            } else {
+                file.seek(SeekFrom::Start(offset as u64)).map_err(ax_error)?;
+            }
+            file.write_all(data).map_err(ax_error)?;
+            self.sync_file(file)
+        })?;
+        self.touch();
+        Ok(data.len())
     }
 
-    #[inline]
-    fn remove(&self, filename: &str) {
-        if let Inode::Dir(dir) = self {
-            dir.lock().remove(filename);
+    fn fsync(&self) -> VfsResult {
+        let _fs = self.fs.lock.lock();
+        match &mut *self.node.lock() {
+            Node::File(file) => file.flush().map_err(ax_error),
+            Node::Dir(_) => Ok(()),
         }
     }
 
-    #[inline]
-    fn size(&self) -> usize {
-        // self.seek(seek)
-        if let Inode::File(file) = self {
-            let mut file = file.lock();
-            let current = file.seek(SeekFrom::Current(0)).expect("can't seek file");
-            let file_size = file.seek(SeekFrom::End(0)).expect("can't seek file");
-            file.seek(SeekFrom::Start(current))
-                .expect("can't seek file");
-            file_size as usize
-        } else {
-            0
+    fn lookup(&self, path: &str) -> VfsResult<Box<dyn VfsFile>> {
+        if path.trim_matches('/').is_empty() {
+            let dir = self.with_dir(|dir| Ok(dir.clone()))?;
+            return Ok(Box::new(Self {
+                fs: self.fs.clone(),
+                ino: self.ino,
+                node: ManuallyDrop::new(Mutex::new(Node::Dir(dir))),
+                readonly: self.readonly,
+                mtime: Mutex::new(*self.mtime.lock()),
+            }));
         }
+        self.with_dir(|dir| {
+            let entry = find_entry(dir, path)?;
+            let ino = self.ino_of(path, &entry);
+            Ok(Self::open(self.fs.clone(), ino, &entry))
+        })
     }
-}
 
-impl<T: DiskOperation> Inode<T> {
-    fn new_file(
-        file: File<'static, DiskCursor<T>, NullTimeProvider, LossyOemCpConverter>,
-    ) -> Box<dyn VfsFile> {
-        Box::new(Self::File(Arc::new(Mutex::new(file))))
+    fn create(&self, path: &str, ty: FileType, _mode: u16) -> VfsResult<Box<dyn VfsFile>> {
+        self.fs.check_writable()?;
+        self.with_dir(|dir| {
+            // fatfs opens an existing entry instead of failing.
+            match find_entry(dir, path) {
+                Ok(_) => return Err(AxError::AlreadyExists),
+                Err(AxError::NotFound) => {}
+                Err(err) => return Err(err),
+            }
+            match ty {
+                FileType::File => {
+                    dir.create_file(path).map_err(ax_error)?;
+                }
+                FileType::Dir => {
+                    dir.create_dir(path).map_err(ax_error)?;
+                }
+                _ => return Err(AxError::Unsupported),
+            }
+            let entry = find_entry(dir, path)?;
+            let ino = self.ino_of(path, &entry);
+            Ok(Self::open(self.fs.clone(), ino, &entry))
+        })
     }
 
-    fn new_dir(
-        dir: Dir<'static, DiskCursor<T>, NullTimeProvider, LossyOemCpConverter>,
-    ) -> Box<dyn VfsFile> {
-        Box::new(Self::Dir(Arc::new(Mutex::new(dir))))
+    fn unlink(&self, path: &str) -> VfsResult {
+        self.fs.check_writable()?;
+        self.with_dir(|dir| {
+            if find_entry(dir, path)?.is_dir() {
+                return Err(AxError::IsADirectory);
+            }
+            let (parent, name) = self.name_of(dir, path)?;
+            dir.remove(path).map_err(ax_error)?;
+            self.fs.inos.lock().remove(parent, &name);
+            Ok(())
+        })
+    }
+
+    fn rmdir(&self, path: &str) -> VfsResult {
+        self.fs.check_writable()?;
+        self.with_dir(|dir| {
+            if !find_entry(dir, path)?.is_dir() {
+                return Err(AxError::NotADirectory);
+            }
+            let (parent, name) = self.name_of(dir, path)?;
+            dir.remove(path).map_err(ax_error)?;
+            self.fs.inos.lock().remove(parent, &name);
+            Ok(())
+        })
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.fs.check_writable()?;
+        let (src, dst) = (src_path.trim_matches('/'), dst_path.trim_matches('/'));
+        self.with_dir(|dir| {
+            let src_is_dir = find_entry(dir, src)?.is_dir();
+            if src.eq_ignore_ascii_case(dst) {
+                return Ok(());
+            }
+            // A directory cannot go inside itself.
+            if src_is_dir
+                && dst.len() > src.len()
+                && dst.as_bytes()[src.len()] == b'/'
+                && dst[..src.len()].eq_ignore_ascii_case(src)
+            {
+                return Err(AxError::InvalidParam);
+            }
+            // fatfs refuses to overwrite, so remove what is replaced first.
+            match find_entry(dir, dst) {
+                Ok(old) if old.is_dir() && !src_is_dir => return Err(AxError::IsADirectory),
+                Ok(old) if !old.is_dir() && src_is_dir => return Err(AxError::NotADirectory),
+                Ok(_) => {
+                    let (parent, name) = self.name_of(dir, dst)?;
+                    dir.remove(dst).map_err(ax_error)?;
+                    self.fs.inos.lock().remove(parent, &name);
+                }
+                Err(AxError::NotFound) => {}
+                Err(err) => return Err(err),
+            }
+            let from = self.name_of(dir, src)?;
+            dir.rename(src, dir, dst).map_err(ax_error)?;
+            // The file keeps its number, and so its cached pages.
+            let mut inos = self.fs.inos.lock();
+            let to = inos.resolve(self.ino, split(dst).0);
+            inos.rename((from.0, &from.1), (to, split(dst).1));
+            Ok(())
+        })
+    }
+
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>> {
+        self.with_dir(|dir| {
+            let mut entries = Vec::new();
+            for entry in dir.iter() {
+                let entry = entry.map_err(ax_error)?;
+                let name = entry.file_name();
+                if name == "." || name == ".." {
+                    continue;
+                }
+                entries.push(DirEntry {
+                    ino: self.fs.inos.lock().get(self.ino, &name),
+                    name,
+                    file_type: if entry.is_dir() { FileType::Dir } else { FileType::File },
+                });
+            }
+            Ok(entries)
+        })
     }
 }
 
+/// Append `len` zero bytes to the file, which must be positioned at its end.
+fn write_zeros(file: &mut FatFile, mut len: u64) -> VfsResult {
+    let zeros = [0u8; 512];
+    while len > 0 {
+        let n = len.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n]).map_err(ax_error)?;
+        len -= n as u64;
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub enum DiskCursorIoError {
     UnexpectedEof,
     WriteZero,
+    /// The device failed, or a seek went out of range.
+    Device(AxError),
+}
+
+impl From<AxError> for DiskCursorIoError {
+    fn from(err: AxError) -> Self {
+        Self::Device(err)
+    }
 }
 
 impl fatfs::IoError for DiskCursorIoError {
@@ -227,111 +615,194 @@ impl fatfs::IoError for DiskCursorIoError {
     }
 }
 
-pub struct DiskCursor<T: DiskOperation> {
-    sector: u64,
-    offset: usize,
-    block_device: PhantomData<T>,
+/// The block device as a byte stream, for fatfs.
+pub struct DiskCursor {
+    dev: Arc<dyn BlockDevice>,
+    pos: u64,
 }
 
-unsafe impl<T: DiskOperation> Sync for DiskCursor<T> {}
-
-unsafe impl<T: DiskOperation> Send for DiskCursor<T> {}
-
-impl<T: DiskOperation> DiskCursor<T> {
-    fn get_position(&self) -> usize {
-        (self.sector * 0x200) as usize + self.offset
+impl DiskCursor {
+    fn size(&self) -> u64 {
+        self.dev.num_blocks() * BLOCK_SIZE as u64
     }
 
-    fn set_position(&mut self, position: usize) {
-        self.sector = (position / 0x200) as u64;
-        self.offset = position % 0x200;
-    }
-
-    fn move_cursor(&mut self, amount: usize) {
-        self.set_position(self.get_position() + amount)
+    /// Index of the block at the position, the offset into it, and the
+    /// number of whole blocks from it to the end of the device.
+    fn locate(&self) -> (u64, usize, usize) {
+        let index = self.pos / BLOCK_SIZE as u64;
+        let offset = (self.pos % BLOCK_SIZE as u64) as usize;
+        let left = self.dev.num_blocks().saturating_sub(index);
+        (index, offset, left.min(usize::MAX as u64) as usize)
     }
 }
 
-impl<T: DiskOperation> fatfs::IoBase for DiskCursor<T> {
+impl fatfs::IoBase for DiskCursor {
     type Error = DiskCursorIoError;
 }
 
-impl<T: DiskOperation> fatfs::Read for DiskCursor<T> {
+impl fatfs::Read for DiskCursor {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, DiskCursorIoError> {
-        // 由于读取扇区内容还需要考虑跨 cluster，因此 read 函数只读取一个扇区
-        // 防止读取较多数据时超出限制
-        // 读取所有的数据的功能交给 read_exact 来实现
-
-        // 如果 start 不是 0 或者 len 不是 512
-        let read_size = if self.offset != 0 || buf.len() < 512 {
-            // let mut data = [0u8; 512];
-            let mut data = vec![0u8; 512];
-            T::read_block(self.sector as usize, &mut data);
-            // self.block_device
-
-            let start = self.offset;
-            let end = (self.offset + buf.len()).min(512);
-
-            buf.copy_from_slice(&data[start..end]);
-            end - start
+        let (index, offset, left) = self.locate();
+        if left == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = if offset == 0 && buf.len() >= BLOCK_SIZE {
+            // Whole blocks go straight into `buf`.
+            let blocks = (buf.len() / BLOCK_SIZE).min(left);
+            for (i, block) in buf[..blocks * BLOCK_SIZE].chunks_exact_mut(BLOCK_SIZE).enumerate() {
+                self.dev.read_block(index + i as u64, block)?;
+            }
+            blocks * BLOCK_SIZE
         } else {
-            T::read_block(self.sector as usize, &mut buf[0..512]);
-            512
+            let mut block = [0u8; BLOCK_SIZE];
+            self.dev.read_block(index, &mut block)?;
+            let len = buf.len().min(BLOCK_SIZE - offset);
+            buf[..len].copy_from_slice(&block[offset..offset + len]);
+            len
         };
-
-        self.move_cursor(read_size);
-        Ok(read_size)
+        self.pos += len as u64;
+        Ok(len)
     }
 }
 
-impl<T: DiskOperation> fatfs::Write for DiskCursor<T> {
+impl fatfs::Write for DiskCursor {
     fn write(&mut self, buf: &[u8]) -> Result<usize, DiskCursorIoError> {
-        // 由于写入扇区还需要考虑申请 cluster，因此 write 函数只写入一个扇区
-        // 防止写入较多数据时超出限制
-        // 写入所有的数据的功能交给 write_all 来实现
+        let (index, offset, left) = self.locate();
+        if left == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = if offset == 0 && buf.len() >= BLOCK_SIZE {
+            let blocks = (buf.len() / BLOCK_SIZE).min(left);
+            for (i, block) in buf[..blocks * BLOCK_SIZE].chunks_exact(BLOCK_SIZE).enumerate() {
+                self.dev.write_block(index + i as u64, block)?;
+            }
+            blocks * BLOCK_SIZE
+        } else {
+            // Part of a block: read, change and write it back.
+            let mut block = [0u8; BLOCK_SIZE];
+            self.dev.read_block(index, &mut block)?;
+            let len = buf.len().min(BLOCK_SIZE - offset);
+            block[offset..offset + len].copy_from_slice(&buf[..len]);
+            self.dev.write_block(index, &block)?;
+            len
+        };
+        self.pos += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), DiskCursorIoError> {
+        Ok(self.dev.flush()?)
+    }
+}
+
+impl fatfs::Seek for DiskCursor {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, DiskCursorIoError> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(delta) => self.size().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = pos.ok_or(AxError::InvalidParam)?;
+        Ok(self.pos)
+    }
+}
 
-        // 获取硬盘设备写入器（驱动？）
-        // 如果 start 不是 0 或者 len 不是 512
-        let write_size = if self.offset != 0 || buf.len() < 512 {
-            let mut data = vec![0u8; 512];
-            T::read_block(self.sector as usize, &mut data);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            let start = self.offset;
-            let end = (self.offset + buf.len()).min(512);
+    /// A disk in memory.
+    struct MemDisk(Mutex<Vec<u8>>);
 
-            data[start..end].clone_from_slice(&buf);
-            T::write_block(self.sector as usize, &mut data);
+    impl BlockDevice for MemDisk {
+        fn num_blocks(&self) -> u64 {
+            (self.0.lock().len() / BLOCK_SIZE) as u64
+        }
+        fn read_block(&self, index: u64, buf: &mut [u8]) -> VfsResult {
+            let start = index as usize * BLOCK_SIZE;
+            buf.copy_from_slice(&self.0.lock()[start..start + BLOCK_SIZE]);
+            Ok(())
+        }
+        fn write_block(&self, index: u64, data: &[u8]) -> VfsResult {
+            let start = index as usize * BLOCK_SIZE;
+            self.0.lock()[start..start + BLOCK_SIZE].copy_from_slice(data);
+            Ok(())
+        }
+    }
 
-            end - start
-        } else {
-            T::write_block(self.sector as usize, &buf[0..512]);
-            512
+    /// 2023-06-01 12:34:56 UTC. FAT keeps modification times to 2 seconds.
+    fn clock() -> Duration {
+        Duration::from_secs(1_685_622_896)
+    }
+
+    /// A newly formatted 1M disk.
+    fn format() -> Arc<dyn BlockDevice> {
+        let disk: Arc<dyn BlockDevice> = Arc::new(MemDisk(Mutex::new(vec![0; 1 << 20])));
+        let mut cursor = DiskCursor {
+            dev: disk.clone(),
+            pos: 0,
         };
+        fatfs::format_volume(&mut cursor, fatfs::FormatVolumeOptions::new()).unwrap();
+        disk
+    }
 
-        self.move_cursor(write_size);
-        Ok(write_size)
+    fn mount(disk: &Arc<dyn BlockDevice>) -> Box<dyn VfsFile> {
+        let fs = Fat32FileSystem::new(disk.clone(), clock, &MountOptions::default()).unwrap();
+        Arc::new(fs).root()
     }
 
-    fn flush(&mut self) -> Result<(), DiskCursorIoError> {
-        Ok(())
+    #[test]
+    fn test_round_trip() {
+        let disk = format();
+        let mut head = [0u8; BLOCK_SIZE];
+        disk.read_block(0, &mut head).unwrap();
+        assert!(probe(&head));
+
+        let root = mount(&disk);
+        let dir = root.create("dir", FileType::Dir, 0o755).unwrap();
+        let file = dir.create("Hello.txt", FileType::File, 0o644).unwrap();
+        assert_eq!(file.write_at(0, b"hello"), Ok(5));
+        // Writing past the end fills the gap with zeros.
+        assert_eq!(file.write_at(8, b"world"), Ok(5));
+        let mut buf = [0xff; 16];
+        assert_eq!(file.read_at(0, &mut buf), Ok(13));
+        assert_eq!(&buf[..13], b"hello\0\0\0world");
+
+        let attr = file.getattr().unwrap();
+        assert_eq!(attr.file_type, FileType::File);
+        assert_eq!((attr.size, attr.mode, attr.nlink), (13, 0o644, 1));
+        assert_eq!(attr.mtime, Some(clock()));
+        assert!(attr.ino > ROOT_INO);
+        file.truncate(11).unwrap();
+        drop((file, dir, root));
+
+        // All of it is on the disk, and found again by any case of the name.
+        let root = mount(&disk);
+        let names: Vec<String> = root.readdir().unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["dir"]);
+        let file = root.lookup("DIR/hello.TXT").unwrap();
+        let attr = file.getattr().unwrap();
+        assert_eq!((attr.size, attr.mtime), (11, Some(clock())));
+        assert_eq!(file.read_at(0, &mut buf), Ok(11));
+        assert_eq!(&buf[..11], b"hello\0\0\0wor");
     }
-}
 
-impl<T: DiskOperation> fatfs::Seek for DiskCursor<T> {
-    fn seek(&mut self, pos: fatfs::SeekFrom) -> Result<u64, DiskCursorIoError> {
-        match pos {
-            fatfs::SeekFrom::Start(i) => {
-                self.set_position(i as usize);
-                Ok(i)
-            }
-            fatfs::SeekFrom::End(_) => {
-                todo!("Seek from end")
-            }
-            fatfs::SeekFrom::Current(i) => {
-                let new_pos = (self.get_position() as i64) + i;
-                self.set_position(new_pos as usize);
-                Ok(new_pos as u64)
-            }
-        }
+    #[test]
+    fn test_mtime() {
+        let disk = format();
+        let root = mount(&disk);
+        let file = root.create("a", FileType::File, 0o644).unwrap();
+        let earlier = Duration::from_secs(1_000_000_000);
+        let attr = SetAttr {
+            mtime: Some(earlier),
+            ..Default::default()
+        };
+        file.setattr(attr).unwrap();
+        assert_eq!(file.getattr().unwrap().mtime, Some(earlier));
+        drop((file, root));
+        let root = mount(&disk);
+        assert_eq!(root.lookup("a").unwrap().getattr().unwrap().mtime, Some(earlier));
+        // The root has no entry to keep a time in.
+        assert_eq!(root.getattr().unwrap().mtime, None);
     }
 }
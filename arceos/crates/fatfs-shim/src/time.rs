@@ -0,0 +1,91 @@
+//! Conversion between FAT timestamps and time since the Unix epoch.
+//!
+//! FAT keeps local time with no zone; it is taken to be UTC here. It only
+//! counts years 1980 to 2107, so times outside are clamped to them.
+
+use core::time::Duration;
+
+use fatfs::{Date, DateTime, Time, TimeProvider};
+
+const SECS_PER_DAY: u64 = 86400;
+/// 1980-01-01, the earliest time FAT keeps.
+const MIN_SECS: u64 = 3652 * SECS_PER_DAY;
+/// 2107-12-31 23:59:58, the latest time FAT keeps.
+const MAX_SECS: u64 = 50403 * SECS_PER_DAY - 2;
+
+/// Stamps new and changed entries with the time from a clock counting time
+/// since the Unix epoch.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock(pub fn() -> Duration);
+
+impl TimeProvider for Clock {
+    fn get_current_date(&self) -> Date {
+        to_fat((self.0)()).date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        to_fat((self.0)())
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    // Count from March, so that the leap day ends the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Date of the day `days` after 1970-01-01, as (year, month, day).
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + (month <= 2) as u64;
+    (year, month, day)
+}
+
+/// The FAT timestamp of `time` since the epoch.
+pub fn to_fat(time: Duration) -> DateTime {
+    let secs = time.as_secs().clamp(MIN_SECS, MAX_SECS);
+    let (year, month, day) = civil_from_days(secs / SECS_PER_DAY);
+    let secs_of_day = secs % SECS_PER_DAY;
+    let millis = if (MIN_SECS..MAX_SECS).contains(&time.as_secs()) {
+        time.subsec_millis() as u16
+    } else {
+        0
+    };
+    DateTime::new(
+        Date::new(year as u16, month as u16, day as u16),
+        Time::new(
+            (secs_of_day / 3600) as u16,
+            (secs_of_day / 60 % 60) as u16,
+            (secs_of_day % 60) as u16,
+            millis,
+        ),
+    )
+}
+
+/// Time since the epoch of a FAT timestamp.
+pub fn from_fat(date_time: DateTime) -> Duration {
+    let DateTime { date, time, .. } = date_time;
+    // Entries never stamped hold zero months and days.
+    let month = (date.month as u64).clamp(1, 12);
+    let day = (date.day as u64).clamp(1, 31);
+    let days = days_from_civil(date.year as u64, month, day);
+    let secs = days * SECS_PER_DAY + time.hour as u64 * 3600 + time.min as u64 * 60 + time.sec as u64;
+    Duration::from_secs(secs) + Duration::from_millis(time.millis as u64)
+}
@@ -0,0 +1,602 @@
+//! An in-memory file system.
+//!
+//! Everything lives on the heap and is gone once the file system is
+//! dropped. Meant for scratch space such as `/tmp`, and as the root file
+//! system when there is no disk to mount.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use spin::Mutex;
+use vfscore::{
+    AxError, DirEntry, FileType, Metadata, SetAttr, StatFs, VfsFile, VfsFileSystem, VfsResult,
+};
+
+/// Accounting shared by all nodes of one file system.
+struct FsState {
+    /// Most bytes of file data the file system may hold.
+    limit: usize,
+    used: AtomicUsize,
+    next_ino: AtomicUsize,
+}
+
+impl FsState {
+    /// Reserve up to `bytes` of file data. Returns how many were granted.
+    fn reserve(&self, bytes: usize) -> usize {
+        let mut used = self.used.load(Ordering::Acquire);
+        loop {
+            let granted = bytes.min(self.limit.saturating_sub(used));
+            match self.used.compare_exchange_weak(
+                used,
+                used + granted,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return granted,
+                Err(current) => used = current,
+            }
+        }
+    }
+
+    fn release(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::AcqRel);
+    }
+}
+
+enum Content {
+    File(Vec<u8>),
+    Dir(BTreeMap<String, Arc<Node>>),
+    Fifo,
+    Device(u16, u16),
+    Symlink(String),
+}
+
+struct NodeInner {
+    content: Content,
+    mode: u16,
+    /// Number of directory entries naming this node.
+    nlink: usize,
+    xattrs: BTreeMap<String, Vec<u8>>,
+}
+
+struct Node {
+    ino: usize,
+    fs: Arc<FsState>,
+    inner: Mutex<NodeInner>,
+}
+
+impl Node {
+    fn new(fs: &Arc<FsState>, content: Content, mode: u16) -> Arc<Self> {
+        Arc::new(Self {
+            ino: fs.next_ino.fetch_add(1, Ordering::Relaxed),
+            fs: fs.clone(),
+            inner: Mutex::new(NodeInner {
+                content,
+                mode,
+                nlink: 0,
+                xattrs: BTreeMap::new(),
+            }),
+        })
+    }
+
+    fn file_type(&self) -> FileType {
+        match self.inner.lock().content {
+            Content::File(_) => FileType::File,
+            Content::Dir(_) => FileType::Dir,
+            Content::Fifo => FileType::Fifo,
+            Content::Device(..) => FileType::Device,
+            Content::Symlink(_) => FileType::Symlink,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        self.file_type() == FileType::Dir
+    }
+
+    /// Whether this is a directory without entries.
+    fn is_empty_dir(&self) -> bool {
+        match &self.inner.lock().content {
+            Content::Dir(entries) => entries.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// The node at `path`, relative to this directory.
+    fn lookup(self: &Arc<Self>, path: &str) -> VfsResult<Arc<Node>> {
+        let mut node = self.clone();
+        for name in components(path) {
+            let next = match &node.inner.lock().content {
+                Content::Dir(entries) => entries.get(name).ok_or(AxError::NotFound)?.clone(),
+                _ => return Err(AxError::NotADirectory),
+            };
+            node = next;
+        }
+        Ok(node)
+    }
+
+    /// Add `name` to this directory, which must not have it yet.
+    fn add_entry(&self, name: &str, content: Content, mode: u16) -> VfsResult<Arc<Node>> {
+        let mut inner = self.inner.lock();
+        let entries = match &mut inner.content {
+            Content::Dir(entries) => entries,
+            _ => return Err(AxError::NotADirectory),
+        };
+        if entries.contains_key(name) {
+            return Err(AxError::AlreadyExists);
+        }
+        let node = Node::new(&self.fs, content, mode);
+        node.inner.lock().nlink = 1;
+        entries.insert(name.to_string(), node.clone());
+        Ok(node)
+    }
+
+    /// Point `name` in this directory at `node`, replacing what was there.
+    fn set_entry(&self, name: &str, node: Arc<Node>) -> VfsResult {
+        let mut inner = self.inner.lock();
+        let entries = match &mut inner.content {
+            Content::Dir(entries) => entries,
+            _ => return Err(AxError::NotADirectory),
+        };
+        node.inner.lock().nlink += 1;
+        if let Some(old) = entries.insert(name.to_string(), node) {
+            old.inner.lock().nlink -= 1;
+        }
+        Ok(())
+    }
+
+    /// Remove `name` from this directory and return what it named.
+    fn remove_entry(&self, name: &str) -> VfsResult<Arc<Node>> {
+        let node = match &mut self.inner.lock().content {
+            Content::Dir(entries) => entries.remove(name).ok_or(AxError::NotFound)?,
+            _ => return Err(AxError::NotADirectory),
+        };
+        node.inner.lock().nlink -= 1;
+        Ok(node)
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        if let Content::File(data) = &self.inner.get_mut().content {
+            self.fs.release(data.len());
+        }
+    }
+}
+
+/// Components of `path`, without empty ones and `.`.
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+}
+
+/// The directory holding `path`, relative to `dir`, and the last component.
+fn lookup_parent<'a>(dir: &Arc<Node>, path: &'a str) -> VfsResult<(Arc<Node>, &'a str)> {
+    let path = path.trim_end_matches('/');
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(AxError::InvalidParam);
+    }
+    let parent = dir.lookup(parent)?;
+    if !parent.is_dir() {
+        return Err(AxError::NotADirectory);
+    }
+    Ok((parent, name))
+}
+
+/// An open file or directory.
+pub struct RamFile {
+    node: Arc<Node>,
+}
+
+impl RamFile {
+    fn boxed(node: Arc<Node>) -> Box<dyn VfsFile> {
+        Box::new(Self { node })
+    }
+
+    fn add(&self, path: &str, content: Content, mode: u16) -> VfsResult<Box<dyn VfsFile>> {
+        let (dir, name) = lookup_parent(&self.node, path)?;
+        dir.add_entry(name, content, mode).map(RamFile::boxed)
+    }
+
+    /// Cut or zero-extend the file, failing if the size limit is hit.
+    fn resize(&self, size: usize) -> VfsResult {
+        let mut inner = self.node.inner.lock();
+        let data = match &mut inner.content {
+            Content::File(data) => data,
+            Content::Dir(_) => return Err(AxError::IsADirectory),
+            _ => return Err(AxError::InvalidParam),
+        };
+        if size > data.len() {
+            let wanted = size - data.len();
+            let granted = self.node.fs.reserve(wanted);
+            if granted < wanted {
+                self.node.fs.release(granted);
+                return Err(AxError::StorageFull);
+            }
+        } else {
+            self.node.fs.release(data.len() - size);
+        }
+        data.resize(size, 0);
+        Ok(())
+    }
+}
+
+impl VfsFile for RamFile {
+    fn getattr(&self) -> VfsResult<Metadata> {
+        let inner = self.node.inner.lock();
+        let (size, rdev) = match &inner.content {
+            Content::File(data) => (data.len(), None),
+            Content::Symlink(target) => (target.len(), None),
+            Content::Device(major, minor) => (0, Some((*major, *minor))),
+            _ => (0, None),
+        };
+        let (mode, nlink) = (inner.mode, inner.nlink);
+        drop(inner);
+        Ok(Metadata {
+            ino: self.node.ino,
+            file_type: self.node.file_type(),
+            mode,
+            size,
+            nlink,
+            rdev,
+            mtime: None,
+        })
+    }
+
+    fn setattr(&self, attr: SetAttr) -> VfsResult {
+        if let Some(size) = attr.size {
+            self.resize(size)?;
+        }
+        if let Some(mode) = attr.mode {
+            self.node.inner.lock().mode = mode;
+        }
+        Ok(())
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> VfsResult<usize> {
+        let inner = self.node.inner.lock();
+        let data = match &inner.content {
+            Content::File(data) => data,
+            Content::Dir(_) => return Err(AxError::IsADirectory),
+            _ => return Err(AxError::InvalidParam),
+        };
+        if offset >= data.len() {
+            return Ok(0);
+        }
+        let len = (data.len() - offset).min(buf.len());
+        buf[..len].copy_from_slice(&data[offset..offset + len]);
+        Ok(len)
+    }
+
+    /// Writes as much as fits in the size limit.
+    fn write_at(&self, offset: usize, buf: &[u8]) -> VfsResult<usize> {
+        let mut inner = self.node.inner.lock();
+        let data = match &mut inner.content {
+            Content::File(data) => data,
+            Content::Dir(_) => return Err(AxError::IsADirectory),
+            _ => return Err(AxError::InvalidParam),
+        };
+        let end = offset + buf.len();
+        if end > data.len() {
+            let granted = self.node.fs.reserve(end - data.len());
+            let new_len = data.len() + granted;
+            data.resize(new_len, 0);
+        }
+        let len = data.len().saturating_sub(offset).min(buf.len());
+        if len == 0 {
+            return if buf.is_empty() {
+                Ok(0)
+            } else {
+                Err(AxError::StorageFull)
+            };
+        }
+        data[offset..offset + len].copy_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn lookup(&self, path: &str) -> VfsResult<Box<dyn VfsFile>> {
+        self.node.lookup(path).map(RamFile::boxed)
+    }
+
+    fn create(&self, path: &str, ty: FileType, mode: u16) -> VfsResult<Box<dyn VfsFile>> {
+        let content = match ty {
+            FileType::File => Content::File(Vec::new()),
+            FileType::Dir => Content::Dir(BTreeMap::new()),
+            FileType::Fifo => Content::Fifo,
+            _ => return Err(AxError::Unsupported),
+        };
+        self.add(path, content, mode)
+    }
+
+    fn mknod(&self, path: &str, major: u16, minor: u16) -> VfsResult<Box<dyn VfsFile>> {
+        self.add(path, Content::Device(major, minor), 0o644)
+    }
+
+    fn unlink(&self, path: &str) -> VfsResult {
+        let (dir, name) = lookup_parent(&self.node, path)?;
+        if dir.lookup(name)?.is_dir() {
+            return Err(AxError::IsADirectory);
+        }
+        dir.remove_entry(name).map(|_| ())
+    }
+
+    fn rmdir(&self, path: &str) -> VfsResult {
+        let (dir, name) = lookup_parent(&self.node, path)?;
+        let node = dir.lookup(name)?;
+        if !node.is_dir() {
+            return Err(AxError::NotADirectory);
+        }
+        if !node.is_empty_dir() {
+            return Err(AxError::DirectoryNotEmpty);
+        }
+        dir.remove_entry(name).map(|_| ())
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let (src_dir, src_name) = lookup_parent(&self.node, src_path)?;
+        let (dst_dir, dst_name) = lookup_parent(&self.node, dst_path)?;
+        let node = src_dir.lookup(src_name)?;
+        // There are no links to directories, so a directory is below
+        // another one exactly when its path starts with the other's path.
+        if node.is_dir()
+            && components(dst_path).count() > components(src_path).count()
+            && components(src_path)
+                .zip(components(dst_path))
+                .all(|(a, b)| a == b)
+        {
+            return Err(AxError::InvalidParam);
+        }
+        if let Ok(old) = dst_dir.lookup(dst_name) {
+            if Arc::ptr_eq(&old, &node) {
+                return Ok(());
+            }
+            match (old.is_dir(), node.is_dir()) {
+                (true, false) => return Err(AxError::IsADirectory),
+                (false, true) => return Err(AxError::NotADirectory),
+                (true, true) if !old.is_empty_dir() => return Err(AxError::DirectoryNotEmpty),
+                _ => {}
+            }
+        }
+        src_dir.remove_entry(src_name)?;
+        dst_dir.set_entry(dst_name, node)
+    }
+
+    fn link(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let node = self.node.lookup(src_path)?;
+        if node.is_dir() {
+            return Err(AxError::PermissionDenied);
+        }
+        let (dir, name) = lookup_parent(&self.node, dst_path)?;
+        if dir.lookup(name).is_ok() {
+            return Err(AxError::AlreadyExists);
+        }
+        dir.set_entry(name, node)
+    }
+
+    fn symlink(&self, target: &str, path: &str) -> VfsResult {
+        self.add(path, Content::Symlink(target.to_string()), 0o777)
+            .map(|_| ())
+    }
+
+    fn readlink(&self) -> VfsResult<String> {
+        match &self.node.inner.lock().content {
+            Content::Symlink(target) => Ok(target.clone()),
+            _ => Err(AxError::InvalidParam),
+        }
+    }
+
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>> {
+        match &self.node.inner.lock().content {
+            Content::Dir(entries) => Ok(entries
+                .iter()
+                .map(|(name, node)| DirEntry {
+                    name: name.clone(),
+                    ino: node.ino,
+                    file_type: node.file_type(),
+                })
+                .collect()),
+            _ => Err(AxError::NotADirectory),
+        }
+    }
+
+    fn get_xattr(&self, name: &str) -> VfsResult<Vec<u8>> {
+        self.node
+            .inner
+            .lock()
+            .xattrs
+            .get(name)
+            .cloned()
+            .ok_or(AxError::NotFound)
+    }
+
+    fn set_xattr(&self, name: &str, value: &[u8]) -> VfsResult {
+        let mut inner = self.node.inner.lock();
+        inner.xattrs.insert(name.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn list_xattr(&self) -> VfsResult<Vec<String>> {
+        Ok(self.node.inner.lock().xattrs.keys().cloned().collect())
+    }
+
+    fn remove_xattr(&self, name: &str) -> VfsResult {
+        match self.node.inner.lock().xattrs.remove(name) {
+            Some(_) => Ok(()),
+            None => Err(AxError::NotFound),
+        }
+    }
+}
+
+/// An in-memory file system, optionally limited in size.
+pub struct RamFileSystem {
+    root: Arc<Node>,
+}
+
+impl RamFileSystem {
+    /// A file system that may grow until memory runs out.
+    pub fn new() -> Self {
+        Self::with_limit(usize::MAX)
+    }
+
+    /// A file system holding at most `limit` bytes of file data.
+    pub fn with_limit(limit: usize) -> Self {
+        let fs = Arc::new(FsState {
+            limit,
+            used: AtomicUsize::new(0),
+            next_ino: AtomicUsize::new(1),
+        });
+        let root = Node::new(&fs, Content::Dir(BTreeMap::new()), 0o755);
+        root.inner.lock().nlink = 1;
+        Self { root }
+    }
+
+    /// Bytes of file data currently stored.
+    pub fn used_bytes(&self) -> usize {
+        self.root.fs.used.load(Ordering::Acquire)
+    }
+
+    /// Most bytes of file data the file system may hold.
+    pub fn limit(&self) -> usize {
+        self.root.fs.limit
+    }
+}
+
+impl Default for RamFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsFileSystem for RamFileSystem {
+    fn name(&self) -> &str {
+        "ramfs"
+    }
+
+    fn root(self: Arc<Self>) -> Box<dyn VfsFile> {
+        RamFile::boxed(self.root.clone())
+    }
+
+    /// Counts bytes as blocks.
+    fn statfs(&self) -> VfsResult<StatFs> {
+        Ok(StatFs {
+            block_size: 1,
+            blocks: self.limit(),
+            blocks_free: self.limit().saturating_sub(self.used_bytes()),
+            files: 0,
+            files_free: 0,
+            name_max: 255,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(fs: &RamFileSystem) -> Box<dyn VfsFile> {
+        RamFile::boxed(fs.root.clone())
+    }
+
+    fn names(dir: &dyn VfsFile) -> Vec<String> {
+        dir.readdir().unwrap().into_iter().map(|e| e.name).collect()
+    }
+
+    #[test]
+    fn files_and_dirs() {
+        let fs = RamFileSystem::new();
+        let root = root(&fs);
+        root.create("a", FileType::Dir, 0o755).unwrap();
+        let file = root.create("/a/f", FileType::File, 0o600).unwrap();
+        assert_eq!(file.write_at(0, b"hello"), Ok(5));
+        assert_eq!(file.getattr().unwrap().mode, 0o600);
+        drop(file);
+        assert_eq!(root.create("a", FileType::Dir, 0o755).err(), Some(AxError::AlreadyExists));
+        assert_eq!(root.lookup("a/f/g").err(), Some(AxError::NotADirectory));
+        assert_eq!(root.lookup("b").err(), Some(AxError::NotFound));
+
+        let file = root.lookup("/a/./f").unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(file.read_at(0, &mut buf), Ok(5));
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(file.read_at(3, &mut buf), Ok(2));
+        assert_eq!(file.read_at(9, &mut buf), Ok(0));
+        assert_eq!(names(&*root), ["a"]);
+        assert_eq!(root.readdir().unwrap()[0].file_type, FileType::Dir);
+
+        assert_eq!(root.rmdir("a").err(), Some(AxError::DirectoryNotEmpty));
+        assert_eq!(root.unlink("a").err(), Some(AxError::IsADirectory));
+        assert_eq!(root.rmdir("a/f").err(), Some(AxError::NotADirectory));
+        root.unlink("a/f").unwrap();
+        root.rmdir("a").unwrap();
+        assert!(root.lookup("a/f").is_err());
+        assert_eq!(fs.used_bytes(), 5, "open files keep their data");
+        drop(file);
+        assert_eq!(fs.used_bytes(), 0);
+    }
+
+    #[test]
+    fn rename_and_link() {
+        let fs = RamFileSystem::new();
+        let root = root(&fs);
+        root.create("d", FileType::Dir, 0o755).unwrap();
+        root.create("d/x", FileType::File, 0o644).unwrap().write_at(0, b"x").unwrap();
+        root.link("d/x", "y").unwrap();
+        assert_eq!(root.lookup("y").unwrap().getattr().unwrap().nlink, 2);
+        assert_eq!(root.link("d", "e"), Err(AxError::PermissionDenied), "no links to directories");
+
+        root.rename("y", "d/z").unwrap();
+        assert!(root.lookup("y").is_err());
+        assert_eq!(
+            root.lookup("d/z").unwrap().getattr().unwrap().ino,
+            root.lookup("d/x").unwrap().getattr().unwrap().ino
+        );
+        assert_eq!(
+            root.rename("d", "d/sub"),
+            Err(AxError::InvalidParam),
+            "no moving a directory below itself"
+        );
+        assert_eq!(
+            root.rename("d/x", "d"),
+            Err(AxError::IsADirectory),
+            "no replacing a directory with a file"
+        );
+
+        root.create("empty", FileType::Dir, 0o755).unwrap();
+        root.rename("d", "empty").unwrap();
+        assert_eq!(names(&*root), ["empty"]);
+        assert_eq!(names(&*root.lookup("empty").unwrap()), ["x", "z"]);
+
+        root.symlink("empty/x", "s").unwrap();
+        let link = root.lookup("s").unwrap();
+        assert_eq!(link.getattr().unwrap().file_type, FileType::Symlink);
+        assert_eq!(link.readlink().unwrap(), "empty/x");
+        assert_eq!(root.lookup("empty").unwrap().readlink(), Err(AxError::InvalidParam));
+    }
+
+    #[test]
+    fn size_limit() {
+        let fs = RamFileSystem::with_limit(8);
+        let root = root(&fs);
+        let file = root.create("f", FileType::File, 0o644).unwrap();
+        assert_eq!(file.write_at(0, b"0123456789"), Ok(8));
+        assert_eq!(file.write_at(8, b"x"), Err(AxError::StorageFull));
+        assert_eq!(file.truncate(9), Err(AxError::StorageFull));
+        file.truncate(2).unwrap();
+        assert_eq!(fs.used_bytes(), 2);
+        let g = root.create("g", FileType::File, 0o644).unwrap();
+        assert_eq!(g.write_at(0, b"abcdefgh"), Ok(6));
+        assert_eq!(fs.used_bytes(), 8);
+        drop(g);
+        root.unlink("g").unwrap();
+        assert_eq!(fs.used_bytes(), 2);
+        assert_eq!(fs.statfs().unwrap().blocks_free, 6);
+    }
+}
@@ -2,40 +2,234 @@
 
 extern crate alloc;
 
+mod options;
+
 use alloc::boxed::Box;
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::time::Duration;
+
+pub use axerror::{AxError, AxResult};
+pub use options::{DataMode, MountOptions};
+
+/// Result type of file system operations.
+pub type VfsResult<T = ()> = AxResult<T>;
+
+/// Kind of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Dir,
+    Fifo,
+    Device,
+    Symlink,
+}
+
+/// Attributes of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    pub ino: usize,
+    pub file_type: FileType,
+    /// Permission bits, as in `chmod`.
+    pub mode: u16,
+    pub size: usize,
+    /// Number of directory entries pointing to the file.
+    pub nlink: usize,
+    /// Device number (major, minor) of a device node.
+    pub rdev: Option<(u16, u16)>,
+    /// Time of the last change to the data, since the Unix epoch, if the
+    /// file system keeps one.
+    pub mtime: Option<Duration>,
+}
+
+impl Metadata {
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FileType::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type == FileType::File
+    }
+}
 
-pub enum SeekFrom {
-    Start(usize),
-    Current(isize),
-    End(isize),
+/// Attributes to change in [`VfsFile::setattr`]. `None` leaves one as it is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SetAttr {
+    pub mode: Option<u16>,
+    /// Cut or zero-extend the file to this many bytes.
+    pub size: Option<usize>,
+    /// Time of the last change to the data, since the Unix epoch.
+    pub mtime: Option<Duration>,
 }
 
-// 文件读写操作
+/// An entry of a directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub ino: usize,
+    pub file_type: FileType,
+}
+
+/// Usage of a file system, as in `statfs`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatFs {
+    pub block_size: usize,
+    pub blocks: usize,
+    pub blocks_free: usize,
+    pub files: usize,
+    pub files_free: usize,
+    /// Longest file name allowed.
+    pub name_max: usize,
+}
+
+/// An open file or directory.
+///
+/// Paths handed to directory operations are relative to the directory and
+/// may have several components. Operations a file does not support fail
+/// with [`AxError::Unsupported`].
 pub trait VfsFile {
-    fn open(&self, path: &str) -> Option<Box<dyn VfsFile>>;
-    fn mkdir(&self, folder_name: &str) -> Option<Box<dyn VfsFile>>;
-    fn create(&self, file_name: &str) -> Option<Box<dyn VfsFile>>;
-    fn read_dir(&self) -> Vec<String>;
-    fn read(&self, buf: &mut [u8]) -> usize;
-    fn write(&self, data: &[u8]) -> usize;
-    fn seek(&self, seek: SeekFrom) -> usize;
-    fn is_dir(&self) -> bool;
-    fn is_file(&self) -> bool;
-    fn close(&self);
-    fn remove(&self, file_name: &str);
-    fn size(&self) -> usize;
-}
-
-// 尽量给予比较长的生命周期
+    /// Attributes of this file.
+    fn getattr(&self) -> VfsResult<Metadata>;
+    /// Change the attributes set in `attr`.
+    fn setattr(&self, _attr: SetAttr) -> VfsResult {
+        Err(AxError::Unsupported)
+    }
+    /// Cut or zero-extend this file to `size` bytes.
+    fn truncate(&self, size: usize) -> VfsResult {
+        self.setattr(SetAttr {
+            size: Some(size),
+            ..Default::default()
+        })
+    }
+    /// Read from byte `offset` of the file. Returns 0 at the end of the file.
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> VfsResult<usize>;
+    /// Write at byte `offset` of the file, extending it if needed.
+    fn write_at(&self, offset: usize, data: &[u8]) -> VfsResult<usize>;
+    /// Write this file's data and metadata to stable storage.
+    fn fsync(&self) -> VfsResult {
+        Ok(())
+    }
+    /// Write everything the file system holds to stable storage.
+    fn sync_all(&self) -> VfsResult {
+        self.fsync()
+    }
+    /// Device-specific control.
+    fn ioctl(&self, _cmd: usize, _arg: &mut [u8]) -> VfsResult<usize> {
+        Err(AxError::Unsupported)
+    }
+
+    /// Open the file at `path`. A symbolic link at the end of the path is
+    /// opened itself rather than followed.
+    fn lookup(&self, _path: &str) -> VfsResult<Box<dyn VfsFile>> {
+        Err(AxError::NotADirectory)
+    }
+    /// Create a file, directory or named pipe at `path`.
+    /// Fails with [`AxError::AlreadyExists`] if the path is taken.
+    fn create(&self, _path: &str, _ty: FileType, _mode: u16) -> VfsResult<Box<dyn VfsFile>> {
+        Err(AxError::NotADirectory)
+    }
+    /// Create a device node with the given device number at `path`.
+    fn mknod(&self, _path: &str, _major: u16, _minor: u16) -> VfsResult<Box<dyn VfsFile>> {
+        Err(AxError::Unsupported)
+    }
+    /// Remove the non-directory at `path`.
+    fn unlink(&self, _path: &str) -> VfsResult {
+        Err(AxError::NotADirectory)
+    }
+    /// Remove the empty directory at `path`.
+    fn rmdir(&self, _path: &str) -> VfsResult {
+        Err(AxError::NotADirectory)
+    }
+    /// Move `src_path` to `dst_path`, replacing `dst_path` if it exists.
+    fn rename(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+        Err(AxError::Unsupported)
+    }
+    /// Add `dst_path` as another name for the file at `src_path`.
+    fn link(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+        Err(AxError::Unsupported)
+    }
+    /// Create a symbolic link at `path` pointing to `target`.
+    fn symlink(&self, _target: &str, _path: &str) -> VfsResult {
+        Err(AxError::Unsupported)
+    }
+    /// Target of this symbolic link.
+    fn readlink(&self) -> VfsResult<String> {
+        Err(AxError::InvalidParam)
+    }
+    /// Entries of this directory, without `.` and `..`.
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>> {
+        Err(AxError::NotADirectory)
+    }
+
+    /// Get an extended attribute. Names carry a namespace prefix such as "user.".
+    fn get_xattr(&self, _name: &str) -> VfsResult<Vec<u8>> {
+        Err(AxError::Unsupported)
+    }
+    /// Create or replace an extended attribute.
+    fn set_xattr(&self, _name: &str, _value: &[u8]) -> VfsResult {
+        Err(AxError::Unsupported)
+    }
+    /// Names of all extended attributes.
+    fn list_xattr(&self) -> VfsResult<Vec<String>> {
+        Ok(Vec::new())
+    }
+    /// Remove an extended attribute.
+    fn remove_xattr(&self, _name: &str) -> VfsResult {
+        Err(AxError::Unsupported)
+    }
+}
+
 pub trait VfsFileSystem: Send + Sync {
     fn name(&self) -> &str;
-    fn root(&'static self) -> Box<dyn VfsFile>;
+    /// Open the root directory. Files may keep the file system alive, so it
+    /// is only dropped once the last of them is.
+    fn root(self: Arc<Self>) -> Box<dyn VfsFile>;
+    /// Usage of the file system.
+    fn statfs(&self) -> VfsResult<StatFs> {
+        Err(AxError::Unsupported)
+    }
+    /// Whether file data should go through the page cache. Worth it for
+    /// file systems on a block device, not for ones that keep their data in
+    /// memory or make it up on every read. Inode numbers must be unique, and
+    /// stay the same across renames.
+    fn cache_pages(&self) -> bool {
+        false
+    }
+    /// Called when it is unmounted, with none of its files open, to write
+    /// back what it still holds.
+    fn umount(&self) {}
 }
 
-// 硬盘读写操作
-pub trait DiskOperation {
-    fn read_block(index: usize, buf: &mut [u8]);
-    fn write_block(index: usize, data: &[u8]);
+/// Size in bytes of a block of a [`BlockDevice`].
+pub const BLOCK_SIZE: usize = 512;
+
+/// A disk, or part of one, that a file system is stored on.
+pub trait BlockDevice: Send + Sync {
+    /// Number of blocks on the device.
+    fn num_blocks(&self) -> u64;
+    /// Read block `index` into `buf`, which is [`BLOCK_SIZE`] bytes long.
+    fn read_block(&self, index: u64, buf: &mut [u8]) -> VfsResult;
+    /// Write `data`, which is [`BLOCK_SIZE`] bytes long, to block `index`.
+    fn write_block(&self, index: u64, data: &[u8]) -> VfsResult;
+    /// Read consecutive blocks from `start` into `buf`, whose length is a
+    /// multiple of [`BLOCK_SIZE`].
+    fn read_blocks(&self, start: u64, buf: &mut [u8]) -> VfsResult {
+        for (i, block) in buf.chunks_mut(BLOCK_SIZE).enumerate() {
+            self.read_block(start + i as u64, block)?;
+        }
+        Ok(())
+    }
+    /// Write consecutive blocks from `start` from `data`, whose length is a
+    /// multiple of [`BLOCK_SIZE`].
+    fn write_blocks(&self, start: u64, data: &[u8]) -> VfsResult {
+        for (i, block) in data.chunks(BLOCK_SIZE).enumerate() {
+            self.write_block(start + i as u64, block)?;
+        }
+        Ok(())
+    }
+    /// Wait until all completed writes are on stable storage.
+    fn flush(&self) -> VfsResult {
+        Ok(())
+    }
 }
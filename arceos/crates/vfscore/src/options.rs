@@ -0,0 +1,120 @@
+//! Mount options shared by all file systems.
+
+use core::fmt;
+use core::str::FromStr;
+
+/// How a journaling file system writes file data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataMode {
+    /// File data goes through the journal with the metadata.
+    Journal,
+    /// File data is written in place before the metadata is committed.
+    Ordered,
+}
+
+/// Options a file system is mounted with. `None` means the file system default.
+///
+/// Parsed from a comma separated string such as `"ro,noatime,commit=4"`:
+///
+/// - `ro` / `rw`: read-only or read-write
+/// - `sync` / `async`: make every operation durable before it returns
+/// - `noatime` / `atime`: do not update access times
+/// - `commit=N`: commit the journal every N operations
+/// - `data=journal` / `data=ordered`: how file data is written
+/// - `cache=N`: number of blocks in the buffer cache
+/// - `size=N`: most bytes an in-memory file system may hold
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MountOptions {
+    pub read_only: bool,
+    pub sync: bool,
+    pub noatime: bool,
+    pub commit_interval: Option<u32>,
+    pub data_mode: Option<DataMode>,
+    pub cache_blocks: Option<usize>,
+    pub size: Option<usize>,
+}
+
+impl FromStr for MountOptions {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options = Self::default();
+        for opt in s.split(',').map(str::trim).filter(|opt| !opt.is_empty()) {
+            let (key, value) = match opt.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (opt, None),
+            };
+            match (key, value) {
+                ("ro", None) => options.read_only = true,
+                ("rw", None) => options.read_only = false,
+                ("sync", None) => options.sync = true,
+                ("async", None) => options.sync = false,
+                ("noatime", None) => options.noatime = true,
+                ("atime", None) => options.noatime = false,
+                ("commit", Some(n)) => {
+                    options.commit_interval = Some(n.parse().map_err(|_| "bad commit interval")?)
+                }
+                ("data", Some("journal")) => options.data_mode = Some(DataMode::Journal),
+                ("data", Some("ordered")) => options.data_mode = Some(DataMode::Ordered),
+                ("data", Some(_)) => return Err("unsupported data mode"),
+                ("cache", Some(n)) => {
+                    options.cache_blocks = Some(n.parse().map_err(|_| "bad cache size")?)
+                }
+                ("size", Some(n)) => options.size = Some(n.parse().map_err(|_| "bad size")?),
+                _ => return Err("unknown mount option"),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Prints the options in the form `FromStr` accepts, leaving out defaults.
+impl fmt::Display for MountOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(if self.read_only { "ro" } else { "rw" })?;
+        if self.sync {
+            f.write_str(",sync")?;
+        }
+        if self.noatime {
+            f.write_str(",noatime")?;
+        }
+        if let Some(n) = self.commit_interval {
+            write!(f, ",commit={}", n)?;
+        }
+        match self.data_mode {
+            Some(DataMode::Journal) => f.write_str(",data=journal")?,
+            Some(DataMode::Ordered) => f.write_str(",data=ordered")?,
+            None => {}
+        }
+        if let Some(n) = self.cache_blocks {
+            write!(f, ",cache={}", n)?;
+        }
+        if let Some(n) = self.size {
+            write!(f, ",size={}", n)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let options: MountOptions =
+            "ro, noatime,commit=4,data=ordered,cache=20,size=4096".parse().unwrap();
+        assert!(options.read_only && options.noatime && !options.sync);
+        assert_eq!(options.commit_interval, Some(4));
+        assert_eq!(options.data_mode, Some(DataMode::Ordered));
+        assert_eq!(options.cache_blocks, Some(20));
+        assert_eq!(options.size, Some(4096));
+        assert_eq!("".parse::<MountOptions>(), Ok(MountOptions::default()));
+        assert!("ro,sync,rw".parse::<MountOptions>().map_or(false, |o| !o.read_only && o.sync));
+        assert!("data=writeback".parse::<MountOptions>().is_err());
+        assert!("commit=x".parse::<MountOptions>().is_err());
+        assert!("nosuchopt".parse::<MountOptions>().is_err());
+        assert_eq!(options.to_string(), "ro,noatime,commit=4,data=ordered,cache=20,size=4096");
+        assert_eq!(MountOptions::default().to_string(), "rw");
+    }
+}
@@ -1,74 +1,252 @@
 #![no_std]
-use xv6fs::{file::{VFile,FileType},disk_inode::InodeType,xv6fs::Xv6FS, BlockDevice};
-use vfscore::{VfsFile,SeekFrom,VfsFileSystem};
+use xv6fs::{file::{self,VFile},disk_inode::InodeType,xv6fs::Xv6FS, BlockDevice, MountOptions};
+use xv6fs::fs_const::{BSIZE,DIRSIZ,FSMAGIC,ROOTDEV};
+use xv6fs::options::EROFS;
+use xv6fs::superblock::SUPER_BLOCK;
+use vfscore::{AxError,DirEntry,FileType,Metadata,SetAttr,StatFs,VfsFile,VfsFileSystem,VfsResult};
 extern crate alloc;
-use alloc::{boxed::Box,vec::Vec,string::String};
+use alloc::{boxed::Box,vec::Vec,string::String,sync::Arc};
+use core::sync::atomic::{AtomicBool,Ordering};
 use axlog::info;
+use spin::Mutex;
+
+/// Map an xv6fs error message to the matching `AxError`.
+fn ax_error(err:&'static str)->AxError{
+    match err {
+        file::ENOENT=>AxError::NotFound,
+        file::EEXIST=>AxError::AlreadyExists,
+        file::ENOTDIR=>AxError::NotADirectory,
+        file::EISDIR=>AxError::IsADirectory,
+        file::ENOTEMPTY=>AxError::DirectoryNotEmpty,
+        file::EINVAL|file::ENAMETOOLONG=>AxError::InvalidParam,
+        file::EPERM=>AxError::PermissionDenied,
+        file::ENOSPC=>AxError::StorageFull,
+        file::ENOMEM=>AxError::NoMemory,
+        EROFS=>AxError::ReadOnlyFilesystem,
+        "xattr: no such attribute"=>AxError::NotFound,
+        "xattr: no space left in xattr block"=>AxError::StorageFull,
+        "xattr: name too long"|"xattr: unsupported namespace"=>AxError::InvalidParam,
+        _=>{
+            info!("[xv6fs_shim] {}",err);
+            AxError::Io
+        }
+    }
+}
+
+fn file_type(itype:InodeType)->FileType{
+    match itype {
+        InodeType::Directory=>FileType::Dir,
+        InodeType::Device=>FileType::Device,
+        InodeType::Fifo=>FileType::Fifo,
+        _=>FileType::File,
+    }
+}
 
 pub struct vfsFile{
     vfile:VFile,
 }
+
+impl vfsFile {
+    fn boxed(vfile:VFile)->Box<dyn VfsFile>{
+        Box::new(vfsFile{vfile})
+    }
+}
+
 impl VfsFile for vfsFile {
-    fn open(&self, path: &str) -> Option<Box<dyn VfsFile>>{
+    fn getattr(&self) -> VfsResult<Metadata>{
+        let stat=self.vfile.vfile_stat().map_err(ax_error)?;
+        let file_type=file_type(stat.itype);
+        // xv6 keeps no permission bits.
+        let mode=if file_type==FileType::Dir { 0o755 } else { 0o644 };
+        Ok(Metadata{
+            ino:stat.inum as usize,
+            file_type,
+            mode,
+            size:stat.size,
+            nlink:stat.nlink.max(0) as usize,
+            rdev:self.vfile.vfile_rdev().map(|(major,minor)| (major as u16,minor as u16)),
+            mtime:None,
+        })
+    }
+    fn setattr(&self, attr: SetAttr) -> VfsResult{
+        if attr.mode.is_some() {
+            return Err(AxError::Unsupported);
+        }
+        match attr.size {
+            Some(size)=>self.vfile.vfile_truncate(size).map_err(ax_error),
+            None=>Ok(()),
+        }
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> VfsResult<usize>{
+        if self.vfile.vfile_is_dir() {
+            return Err(AxError::IsADirectory);
+        }
+        self.vfile.vfile_read_at(offset, buf).map_err(ax_error)
+    }
+    fn write_at(&self, offset: usize, data: &[u8]) -> VfsResult<usize>{
+        if self.vfile.vfile_is_dir() {
+            return Err(AxError::IsADirectory);
+        }
+        self.vfile.vfile_write_at(offset, data).map_err(ax_error)
+    }
+    fn fsync(&self) -> VfsResult{
+        self.vfile.vfile_fsync();
+        Ok(())
+    }
+    fn lookup(&self, path: &str) -> VfsResult<Box<dyn VfsFile>>{
         //info!("vfsfile: path is {}",path);
-        let vfs_file=VFile::vfile_open(path,true, true).unwrap();
-        Some(Box::new(vfsFile{vfile:vfs_file}))
+        // Only look the path up: axfs opens mount points and parent
+        // directories through here, and must not create them.
+        let vfile=self.vfile.vfile_lookup_at(path).map_err(ax_error)?;
+        Ok(vfsFile::boxed(vfile))
     }
-    fn mkdir(&self, folder_name: &str) -> Option<Box<dyn VfsFile>>{
-        let vfs_file=self.vfile.vfile_create_under_dir(folder_name,InodeType::Directory);
-        Some(Box::new(vfsFile{vfile:vfs_file}))
+    fn create(&self, path: &str, ty: FileType, _mode: u16) -> VfsResult<Box<dyn VfsFile>>{
+        let itype=match ty {
+            FileType::File=>InodeType::File,
+            FileType::Dir=>InodeType::Directory,
+            FileType::Fifo=>InodeType::Fifo,
+            _=>return Err(AxError::Unsupported),
+        };
+        let vfile=self.vfile.vfile_create_at(path, itype, 2, 1).map_err(ax_error)?;
+        Ok(vfsFile::boxed(vfile))
     }
-    fn create(&self, file_name: &str) -> Option<Box<dyn VfsFile>>{
-        let vfs_file=self.vfile.vfile_create_under_dir(file_name,InodeType::File);
-        Some(Box::new(vfsFile{vfile:vfs_file}))
+    fn mknod(&self, path: &str, major: u16, minor: u16) -> VfsResult<Box<dyn VfsFile>>{
+        let vfile=self.vfile
+            .vfile_create_at(path, InodeType::Device, major as i16, minor as i16)
+            .map_err(ax_error)?;
+        Ok(vfsFile::boxed(vfile))
     }
-    fn read_dir(&self) -> Vec<String>{
-        self.vfile.vfile_readdir().unwrap()
+    fn unlink(&self, path: &str) -> VfsResult{
+        self.vfile.vfile_unlink(path).map_err(ax_error)
     }
-    fn read(&self, buf: &mut [u8]) -> usize{
-        self.vfile.vfile_read(buf.as_mut_ptr() as usize, buf.len()).unwrap()
+    fn rmdir(&self, path: &str) -> VfsResult{
+        self.vfile.vfile_rmdir(path).map_err(ax_error)
     }
-    fn write(&self, data: &[u8]) -> usize{
-        self.vfile.vfile_write(data.as_ptr() as usize, data.len()).unwrap()
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult{
+        self.vfile.vfile_rename(src_path, dst_path).map_err(ax_error)
     }
-    fn seek(&self, seek: SeekFrom) -> usize{
-        0
+    fn link(&self, src_path: &str, dst_path: &str) -> VfsResult{
+        self.vfile.vfile_link(src_path, dst_path).map_err(ax_error)
     }
-    fn is_dir(&self) -> bool{
-        self.vfile.vfile_is_dir()
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>>{
+        let entries=self.vfile.vfile_dir_entries().map_err(ax_error)?;
+        Ok(entries
+            .into_iter()
+            .map(|(name,inum,itype)| DirEntry{ name, ino:inum as usize, file_type:file_type(itype) })
+            .collect())
     }
-    fn is_file(&self) -> bool{
-        self.vfile.vfile_is_file()
+    fn get_xattr(&self, name: &str) -> VfsResult<Vec<u8>>{
+        self.vfile.vfile_getxattr(name).map_err(ax_error)
     }
-    fn close(&self){
-
+    fn set_xattr(&self, name: &str, value: &[u8]) -> VfsResult{
+        self.vfile.vfile_setxattr(name, value).map_err(ax_error)
     }
-    fn remove(&self, file_name: &str){
-        //info!("path = {}",file_name);
-        self.vfile.vfile_remove(file_name);
+    fn list_xattr(&self) -> VfsResult<Vec<String>>{
+        self.vfile.vfile_listxattr().map_err(ax_error)
     }
-    fn size(&self) -> usize{
-        self.vfile.vfile_size()
+    fn remove_xattr(&self, name: &str) -> VfsResult{
+        self.vfile.vfile_removexattr(name).map_err(ax_error)
     }
 }
 
+/// xv6fs keeps its state in globals, so only one can be mounted at a time.
+static XV6FS_OPENED: AtomicBool = AtomicBool::new(false);
+/// Set while the mounted one is initialized.
+static XV6FS_READY: AtomicBool = AtomicBool::new(false);
+
+/// Whether an xv6fs is mounted, so its statistics can be read.
+pub fn is_mounted()->bool{
+    XV6FS_READY.load(Ordering::Acquire)
+}
+
 pub struct VXV6FS{
     pub fs:Xv6FS,
+    /// Whether this one still holds the globals.
+    opened:AtomicBool,
+    /// The root directory, held for as long as the mount, so that handing
+    /// it out never needs a free inode cache slot.
+    root:Mutex<Option<VFile>>,
 }
 
 impl VfsFileSystem for VXV6FS{
     fn name(&self) -> &str{
-        "xv6-log-fs"
+        Self::NAME
     }
-    fn root(&'static self) -> Box<dyn VfsFile>{
-        let vfile=self.fs.get_root_vfile();
+    fn root(self: Arc<Self>) -> Box<dyn VfsFile>{
+        // Only taken by `umount`, after which the mount is gone.
+        let vfile=self.root.lock().clone().expect("xv6fs: root of an unmounted file system");
         Box::new(vfsFile{vfile})
     }
+    fn cache_pages(&self) -> bool{
+        true
+    }
+    fn umount(&self){
+        self.release();
+    }
+    fn statfs(&self) -> VfsResult<StatFs>{
+        let (blocks,files)=unsafe { (SUPER_BLOCK.size(),SUPER_BLOCK.ninodes()) };
+        Ok(StatFs{
+            block_size:BSIZE,
+            blocks:blocks as usize,
+            blocks_free:xv6fs::bitmap::free_blocks(ROOTDEV) as usize,
+            files:files as usize,
+            files_free:xv6fs::bitmap::free_inodes(ROOTDEV) as usize,
+            name_max:DIRSIZ-1,
+        })
+    }
 }
 
 impl VXV6FS {
-    pub fn new()->Self{
-        Self { fs: Xv6FS::new() }
+    pub const NAME:&'static str="xv6-log-fs";
+
+    /// Mount the file system on `dev`. Fails with `ResourceBusy` while
+    /// another one is mounted.
+    pub fn open(dev:Arc<dyn BlockDevice>,options:MountOptions)->VfsResult<Self>{
+        if XV6FS_OPENED.swap(true, Ordering::AcqRel) {
+            return Err(AxError::ResourceBusy);
+        }
+        if let Err(e) = unsafe { xv6fs::init_with_options(dev, 0, options) } {
+            XV6FS_OPENED.store(false, Ordering::Release);
+            return Err(ax_error(e));
+        }
+        let fs=Xv6FS::new();
+        let root=match fs.get_root_vfile() {
+            Ok(root)=>root,
+            Err(e)=>{
+                unsafe { xv6fs::shutdown() };
+                XV6FS_OPENED.store(false, Ordering::Release);
+                return Err(ax_error(e));
+            }
+        };
+        XV6FS_READY.store(true, Ordering::Release);
+        Ok(Self { fs, opened: AtomicBool::new(true), root: Mutex::new(Some(root)) })
+    }
+
+    /// Write everything back and give the globals up for the next mount.
+    fn release(&self){
+        if self.opened.swap(false, Ordering::AcqRel) {
+            XV6FS_READY.store(false, Ordering::Release);
+            self.root.lock().take();
+            unsafe { xv6fs::shutdown() };
+            XV6FS_OPENED.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Dropped without an umount when mounting it failed.
+impl Drop for VXV6FS{
+    fn drop(&mut self){
+        self.release();
+    }
+}
+
+/// Whether `head`, the first blocks of a device, holds an xv6 file system:
+/// the super block in block 1 starts with FSMAGIC.
+pub fn probe(head:&[u8])->bool{
+    let start=BSIZE;
+    match head.get(start..start+4) {
+        Some(magic)=>u32::from_le_bytes([magic[0],magic[1],magic[2],magic[3]])==FSMAGIC,
+        None=>false,
     }
 }
 
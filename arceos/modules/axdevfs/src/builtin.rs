@@ -0,0 +1,78 @@
+use axerror::AxResult;
+
+use crate::Device;
+
+/// `/dev/null`: reads hit EOF, writes are discarded.
+pub struct NullDevice;
+
+/// `/dev/zero`: reads return zeros, writes are discarded.
+pub struct ZeroDevice;
+
+/// `/dev/random`: reads return pseudo-random bytes from the user library's generator.
+pub struct RandomDevice;
+
+/// `/dev/console`: the platform console.
+pub struct ConsoleDevice;
+
+impl Device for NullDevice {
+    fn read(&self, _data: &mut [u8]) -> AxResult<usize> {
+        Ok(0)
+    }
+
+    fn write(&self, data: &[u8]) -> AxResult<usize> {
+        Ok(data.len())
+    }
+}
+
+impl Device for ZeroDevice {
+    fn read(&self, data: &mut [u8]) -> AxResult<usize> {
+        data.fill(0);
+        Ok(data.len())
+    }
+
+    fn write(&self, data: &[u8]) -> AxResult<usize> {
+        Ok(data.len())
+    }
+}
+
+impl Device for RandomDevice {
+    fn read(&self, data: &mut [u8]) -> AxResult<usize> {
+        for chunk in data.chunks_mut(4) {
+            let r = crate::rand_u32().to_le_bytes();
+            chunk.copy_from_slice(&r[..chunk.len()]);
+        }
+        Ok(data.len())
+    }
+
+    fn write(&self, data: &[u8]) -> AxResult<usize> {
+        Ok(data.len())
+    }
+}
+
+impl Device for ConsoleDevice {
+    /// Block until at least one byte is available, then return what is buffered.
+    fn read(&self, data: &mut [u8]) -> AxResult<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let mut n = 0;
+        while n < data.len() {
+            match axhal::console::getchar() {
+                Some(c) => {
+                    data[n] = c;
+                    n += 1;
+                }
+                None if n > 0 => break,
+                None => axtask::yield_now(),
+            }
+        }
+        Ok(n)
+    }
+
+    fn write(&self, data: &[u8]) -> AxResult<usize> {
+        for &c in data {
+            axhal::console::putchar(c);
+        }
+        Ok(data.len())
+    }
+}
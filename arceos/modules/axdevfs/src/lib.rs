@@ -1,10 +1,81 @@
 #![no_std]
 
-pub trait Device {
-    fn open(&self, sign: usize);
-    fn close(&self);
-    fn read(&self, data: &mut [u8]);
-    fn write(&self, data: &[u8]);
-    fn ioctl(&self, cmd: usize, arg: &mut [u8]); // ioctl read/write
-    fn devctl(&self, cmd: usize, arg: &mut [u8]); // devctl read/write
+extern crate alloc;
+#[macro_use]
+extern crate log;
+
+mod builtin;
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use axerror::{ax_err, AxResult};
+use spin::Mutex;
+
+pub use builtin::{ConsoleDevice, NullDevice, RandomDevice, ZeroDevice};
+
+/// Major number of the memory devices (null, zero, random).
+pub const MEM_MAJOR: u16 = 1;
+/// Minor number of `/dev/null`.
+pub const NULL_MINOR: u16 = 3;
+/// Minor number of `/dev/zero`.
+pub const ZERO_MINOR: u16 = 5;
+/// Minor number of `/dev/random`.
+pub const RANDOM_MINOR: u16 = 8;
+/// Major number of the console.
+pub const CONSOLE_MAJOR: u16 = 5;
+/// Minor number of `/dev/console`.
+pub const CONSOLE_MINOR: u16 = 1;
+
+pub trait Device: Send + Sync {
+    fn open(&self, _sign: usize) {}
+    fn close(&self) {}
+    fn read(&self, data: &mut [u8]) -> AxResult<usize>;
+    fn write(&self, data: &[u8]) -> AxResult<usize>;
+    // ioctl read/write
+    fn ioctl(&self, _cmd: usize, _arg: &mut [u8]) -> AxResult<usize> {
+        ax_err!(Unsupported)
+    }
+    // devctl read/write
+    fn devctl(&self, _cmd: usize, _arg: &mut [u8]) -> AxResult<usize> {
+        ax_err!(Unsupported)
+    }
+}
+
+/// Source of random numbers for `/dev/random`, implemented by the user library.
+#[crate_interface::def_interface]
+pub trait RandomIf {
+    fn rand_u32() -> u32;
+}
+
+fn rand_u32() -> u32 {
+    crate_interface::call_interface!(RandomIf::rand_u32)
+}
+
+static DEVICES: Mutex<BTreeMap<(u16, u16), Arc<dyn Device>>> = Mutex::new(BTreeMap::new());
+
+/// Register a driver for the device number (major, minor).
+pub fn register_device(major: u16, minor: u16, dev: Arc<dyn Device>) -> AxResult {
+    let mut devices = DEVICES.lock();
+    if devices.contains_key(&(major, minor)) {
+        return ax_err!(AlreadyExists);
+    }
+    devices.insert((major, minor), dev);
+    Ok(())
+}
+
+/// Remove the driver registered for (major, minor).
+pub fn unregister_device(major: u16, minor: u16) -> Option<Arc<dyn Device>> {
+    DEVICES.lock().remove(&(major, minor))
+}
+
+/// Look up the driver registered for (major, minor).
+pub fn get_device(major: u16, minor: u16) -> Option<Arc<dyn Device>> {
+    DEVICES.lock().get(&(major, minor)).cloned()
+}
+
+/// Register the built-in devices.
+pub fn init() {
+    register_device(MEM_MAJOR, NULL_MINOR, Arc::new(NullDevice)).ok();
+    register_device(MEM_MAJOR, ZERO_MINOR, Arc::new(ZeroDevice)).ok();
+    register_device(MEM_MAJOR, RANDOM_MINOR, Arc::new(RandomDevice)).ok();
+    register_device(CONSOLE_MAJOR, CONSOLE_MINOR, Arc::new(ConsoleDevice)).ok();
 }
@@ -0,0 +1,84 @@
+//! Block devices, and the partitions on them.
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use driver_block::partition::read_partitions;
+use driver_block::{BlockDriverOps, Partition};
+
+use crate::AllDevices;
+
+/// A block device, a whole disk or a partition of one.
+pub struct AxBlockDevice {
+    /// Name as on Linux: `vda`, `vdb`, … for disks and `vda1`, `vda2`, …
+    /// for their partitions.
+    pub name: String,
+    pub dev: Arc<dyn BlockDriverOps>,
+}
+
+/// Every block device found, each disk followed by its partitions.
+pub struct BlockDevices(pub Vec<AxBlockDevice>);
+
+/// Name of disk `index` whose names start with `prefix`: `a` to `z`, then
+/// `aa`, `ab` and so on.
+#[cfg(feature = "virtio-blk")]
+fn disk_name(prefix: &str, mut index: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push(b'a' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    letters.reverse();
+    format!("{}{}", prefix, String::from_utf8(letters).unwrap())
+}
+
+/// Add `disk` and the partitions its partition table lists.
+#[cfg_attr(not(any(feature = "virtio-blk", feature = "ramdisk")), allow(dead_code))]
+fn add_disk(devices: &mut Vec<AxBlockDevice>, name: String, disk: Arc<dyn BlockDriverOps>) {
+    info!("block device {}: {} blocks", name, disk.num_blocks());
+    let partitions = read_partitions(&*disk).unwrap_or_else(|e| {
+        warn!("failed to read the partition table of {}: {:?}", name, e);
+        Vec::new()
+    });
+    devices.push(AxBlockDevice {
+        name: name.clone(),
+        dev: disk.clone(),
+    });
+    for info in partitions {
+        let name = format!("{}{}", name, info.number);
+        info!(
+            "block device {}: blocks {}..{}",
+            name,
+            info.start,
+            info.start + info.num_blocks
+        );
+        devices.push(AxBlockDevice {
+            name,
+            dev: Arc::new(Partition::new(disk.clone(), &info)),
+        });
+    }
+}
+
+impl AllDevices {
+    pub(crate) fn probe_block() -> BlockDevices {
+        #[allow(unused_mut)]
+        let mut devices = Vec::new();
+        #[cfg(feature = "virtio-blk")]
+        for (i, dev) in Self::probe_virtio_blk().into_iter().enumerate() {
+            add_disk(&mut devices, disk_name("vd", i), Arc::new(dev));
+        }
+        #[cfg(feature = "ramdisk")]
+        add_disk(&mut devices, String::from("ram0"), Arc::new(Self::probe_ramdisk()));
+        BlockDevices(devices)
+    }
+
+    #[cfg(feature = "ramdisk")]
+    fn probe_ramdisk() -> driver_block::RamDisk {
+        #[cfg(feature = "ramdisk-image")]
+        let image: &[u8] = include_bytes!(env!("RAMDISK_IMAGE"));
+        #[cfg(not(feature = "ramdisk-image"))]
+        let image: &[u8] = &[];
+        driver_block::RamDisk::from_image(image, axconfig::RAMDISK_SIZE)
+    }
+}
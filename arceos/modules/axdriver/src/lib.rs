@@ -1,14 +1,18 @@
 #![no_std]
 
+extern crate alloc;
 #[macro_use]
 extern crate log;
 
+mod block;
 #[cfg(feature = "virtio")]
 mod virtio;
 
 use lazy_init::LazyInit;
 use tuple_for_each::TupleForEach;
 
+pub use self::block::{AxBlockDevice, BlockDevices};
+
 #[cfg(feature = "virtio-blk")]
 pub use self::virtio::VirtIoBlockDev;
 #[cfg(feature = "virtio-gpu")]
@@ -18,12 +22,6 @@ pub use self::virtio::VirtIoNetDev;
 
 static DEVICES: LazyInit<AllDevices> = LazyInit::new();
 
-#[derive(TupleForEach)]
-pub struct BlockDevices(
-    #[cfg(feature = "virtio-blk")] pub VirtIoBlockDev,
-    // e.g. #[cfg(feature = "nvme")] pub nvme::NVMeDev,
-);
-
 #[derive(TupleForEach)]
 pub struct NetDevices(
     #[cfg(feature = "virtio-net")] pub VirtIoNetDev,
@@ -42,10 +40,7 @@ pub struct AllDevices {
 impl AllDevices {
     fn probe() -> Self {
         Self {
-            block: BlockDevices(
-                #[cfg(feature = "virtio-blk")]
-                Self::probe_virtio_blk().expect("no virtio-blk device found"),
-            ),
+            block: Self::probe_block(),
             net: NetDevices(
                 #[cfg(feature = "virtio-net")]
                 Self::probe_virtio_net().expect("no virtio-net device found"),
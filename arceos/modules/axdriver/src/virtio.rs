@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use core::ptr::NonNull;
 
 use axalloc::global_allocator;
@@ -96,9 +97,36 @@ impl AllDevices {
         None
     }
 
+    /// Like `probe_devices_common`, but every device of the type.
+    #[cfg(feature = "bus-mmio")]
+    fn probe_all_devices_common<D, F>(dev_type: DeviceType, mut ret: F) -> Vec<D>
+    where
+        D: BaseDriverOps,
+        F: FnMut(VirtIoTransport) -> Option<D>,
+    {
+        let mut devs = Vec::new();
+        for reg in axconfig::VIRTIO_MMIO_REGIONS {
+            if let Some(transport) = driver_virtio::probe_mmio_device(
+                phys_to_virt(reg.0.into()).as_mut_ptr(),
+                reg.1,
+                Some(dev_type),
+            ) {
+                if let Some(dev) = ret(transport) {
+                    info!(
+                        "created a new {:?} device: {:?}",
+                        dev.device_type(),
+                        dev.device_name()
+                    );
+                    devs.push(dev);
+                }
+            }
+        }
+        devs
+    }
+
     #[cfg(feature = "virtio-blk")]
-    pub(crate) fn probe_virtio_blk() -> Option<VirtIoBlockDev> {
-        Self::probe_devices_common(DeviceType::Block, |t| VirtIoBlockDev::try_new(t).ok())
+    pub(crate) fn probe_virtio_blk() -> Vec<VirtIoBlockDev> {
+        Self::probe_all_devices_common(DeviceType::Block, |t| VirtIoBlockDev::try_new(t).ok())
     }
 
     #[cfg(feature = "virtio-net")]
@@ -53,6 +53,9 @@
  */
 #define	ENOSYS		38	/* Invalid system call number */
 
+#define	ENOTEMPTY	39	/* Directory not empty */
+#define	ELOOP		40	/* Too many symbolic links encountered */
+
 #define	ENOTCONN	107	/* Transport endpoint is not connected */
 #define	ECONNREFUSED	111	/* Connection refused */
 
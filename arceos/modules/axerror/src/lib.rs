@@ -14,24 +14,44 @@ pub enum AxError {
     Again,
     /// Bad address.
     BadAddress,
+    /// A file descriptor that is not open, or not open for the operation.
+    BadFileDescriptor,
     /// Bad internal state.
     BadState,
+    /// Writing to a pipe or FIFO whose read end has been closed.
+    BrokenPipe,
     /// The connection was refused by the remote server,
     ConnectionRefused,
+    /// A link or rename across file systems.
+    CrossesDevices,
+    /// A directory that must be empty is not.
+    DirectoryNotEmpty,
+    /// Too many symbolic links were followed while resolving a path.
+    FilesystemLoop,
     /// Invalid parameter/argument.
     InvalidParam,
     /// Input/output error.
     Io,
+    /// The operation needs a non-directory, but the file is a directory.
+    IsADirectory,
     /// Not enough space/cannot allocate memory.
     NoMemory,
     /// The network operation failed because it was not connected yet.
     NotConnected,
+    /// The operation needs a directory, but the file is not one.
+    NotADirectory,
     /// The requested entity is not found.
     NotFound,
     /// The operation lacked the necessary privileges to complete.
     PermissionDenied,
+    /// The file system is mounted read-only.
+    ReadOnlyFilesystem,
     /// Device or resource is busy.
     ResourceBusy,
+    /// No space left on the storage device.
+    StorageFull,
+    /// The task or the system has as many files open as it may.
+    TooManyOpenFiles,
     /// This operation is unsupported or unimplemented.
     Unsupported,
 }
@@ -70,14 +90,24 @@ impl From<AxError> for LinuxError {
             AlreadyExists => LinuxError::EEXIST,
             Again => LinuxError::EAGAIN,
             BadAddress | BadState => LinuxError::EFAULT,
+            BadFileDescriptor => LinuxError::EBADF,
+            BrokenPipe => LinuxError::EPIPE,
             ConnectionRefused => LinuxError::ECONNREFUSED,
+            CrossesDevices => LinuxError::EXDEV,
+            DirectoryNotEmpty => LinuxError::ENOTEMPTY,
+            FilesystemLoop => LinuxError::ELOOP,
             InvalidParam => LinuxError::EINVAL,
             Io => LinuxError::EIO,
+            IsADirectory => LinuxError::EISDIR,
             NoMemory => LinuxError::ENOMEM,
             NotConnected => LinuxError::ENOTCONN,
+            NotADirectory => LinuxError::ENOTDIR,
             NotFound => LinuxError::ENOENT,
             PermissionDenied => LinuxError::EPERM,
+            ReadOnlyFilesystem => LinuxError::EROFS,
             ResourceBusy => LinuxError::EBUSY,
+            StorageFull => LinuxError::ENOSPC,
+            TooManyOpenFiles => LinuxError::EMFILE,
             Unsupported => LinuxError::ENOSYS,
         }
     }
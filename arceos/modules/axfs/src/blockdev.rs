@@ -0,0 +1,98 @@
+//! Named block devices file systems can be mounted from.
+//!
+//! Drivers hand their disks and partitions over at boot as `vda`, `vda1`
+//! and so on, [`attach_loop`](crate::attach_loop) adds image files as
+//! `loop0`, and mounting takes a source by that name, with or without a
+//! leading `/dev/`.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use axerror::{ax_err, AxError, AxResult};
+use driver_block::BlockDriverOps;
+use spin::RwLock;
+use vfscore::BlockDevice;
+
+static BLOCK_DEVICES: RwLock<Vec<(String, Arc<dyn BlockDevice>)>> = RwLock::new(Vec::new());
+
+/// Add a block device called `name`.
+pub fn register_block_device(name: &str, dev: Arc<dyn BlockDevice>) -> AxResult {
+    let mut devices = BLOCK_DEVICES.write();
+    if devices.iter().any(|(n, _)| n == name) {
+        return ax_err!(AlreadyExists, "block device already registered");
+    }
+    devices.push((String::from(name), dev));
+    Ok(())
+}
+
+/// Remove the block device called `name`, returning it.
+pub(crate) fn unregister_block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    let mut devices = BLOCK_DEVICES.write();
+    let index = devices.iter().position(|(n, _)| n == name)?;
+    Some(devices.remove(index).1)
+}
+
+/// The block device called `name`, which may start with `/dev/`.
+pub fn block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    let name = name.strip_prefix("/dev/").unwrap_or(name);
+    let devices = BLOCK_DEVICES.read();
+    devices.iter().find(|(n, _)| n == name).map(|(_, dev)| dev.clone())
+}
+
+/// Names of all block devices, in registration order.
+pub fn block_devices() -> Vec<String> {
+    BLOCK_DEVICES.read().iter().map(|(name, _)| name.clone()).collect()
+}
+
+/// A device from `axdriver`, as file systems see block devices.
+pub(crate) struct DriverDevice(pub Arc<dyn BlockDriverOps>);
+
+impl BlockDevice for DriverDevice {
+    fn num_blocks(&self) -> u64 {
+        self.0.num_blocks()
+    }
+
+    fn read_block(&self, index: u64, buf: &mut [u8]) -> AxResult {
+        self.0.read_block(index as usize, buf).map_err(|_| AxError::Io)
+    }
+
+    fn write_block(&self, index: u64, data: &[u8]) -> AxResult {
+        self.0.write_block(index as usize, data).map_err(|_| AxError::Io)
+    }
+
+    fn read_blocks(&self, start: u64, buf: &mut [u8]) -> AxResult {
+        self.0.read_blocks(start as usize, buf).map_err(|_| AxError::Io)
+    }
+
+    fn write_blocks(&self, start: u64, data: &[u8]) -> AxResult {
+        self.0.write_blocks(start as usize, data).map_err(|_| AxError::Io)
+    }
+
+    fn flush(&self) -> AxResult {
+        self.0.flush().map_err(|_| AxError::Io)
+    }
+}
+
+/// A block device as xv6fs takes it. xv6fs cannot handle I/O errors, so
+/// they panic.
+pub(crate) struct Xv6Device(pub Arc<dyn BlockDevice>);
+
+impl xv6fs::BlockDevice for Xv6Device {
+    fn read_block(&self, index: usize, buf: &mut [u8]) {
+        self.0.read_block(index as u64, buf).expect("can't read block");
+    }
+
+    fn write_block(&self, index: usize, data: &[u8]) {
+        self.0.write_block(index as u64, data).expect("can't write block");
+    }
+
+    fn read_blocks(&self, start: usize, buf: &mut [u8]) {
+        self.0.read_blocks(start as u64, buf).expect("can't read blocks");
+    }
+
+    fn write_blocks(&self, start: usize, data: &[u8]) {
+        self.0.write_blocks(start as u64, data).expect("can't write blocks");
+    }
+
+    fn flush(&self) {
+        self.0.flush().expect("can't flush block device");
+    }
+}
@@ -0,0 +1,721 @@
+//! Page cache of file contents.
+//!
+//! Regular files on mounts whose file system asks for it
+//! ([`VfsFileSystem::cache_pages`](vfscore::VfsFileSystem::cache_pages)) are
+//! read and written in pages kept in memory, keyed by mount path, inode
+//! number and page index. The mount layer sends their I/O here.
+//!
+//! Writes inside the file stay in the cache until `fsync`, until the last
+//! open file of the inode goes, or until the page is evicted. A dirty page
+//! that could not be written back then stays until the inode is opened
+//! again, and keeps its mount from being unmounted. Writes that
+//! grow the file go straight through, so the file system always knows the
+//! size and only ever sees holes it made itself.
+//!
+//! The least recently used page is evicted when the cache holds
+//! [`MAX_CACHED_PAGES`], or when fewer than [`MIN_FREE_PAGES`] are left in
+//! the allocator. Pages mapped by [`mmap`](crate::mmap) stay until unmapped.
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use axalloc::global_allocator;
+use axerror::{ax_err, AxResult};
+use axsync::Mutex;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use vfscore::{VfsFile, VfsResult};
+
+pub const PAGE_SIZE: usize = 0x1000;
+
+/// Most pages the cache holds, 8M.
+pub const MAX_CACHED_PAGES: usize = 2048;
+
+/// Pages the cache leaves to the rest of the system.
+pub const MIN_FREE_PAGES: usize = 256;
+
+/// Mount path and inode number of a cached file.
+pub(crate) type Key = (Arc<str>, usize);
+
+/// A page of file data.
+pub(crate) struct Page {
+    vaddr: usize,
+    dirty: AtomicBool,
+    /// When it was last used, for the LRU.
+    tick: AtomicU64,
+}
+
+impl Page {
+    fn new() -> Option<Self> {
+        let vaddr = global_allocator().alloc_pages(1, PAGE_SIZE).ok()?;
+        Some(Self {
+            vaddr,
+            dirty: AtomicBool::new(false),
+            tick: AtomicU64::new(0),
+        })
+    }
+
+    /// Where the page is in the kernel's linear mapping.
+    pub(crate) fn vaddr(&self) -> usize {
+        self.vaddr
+    }
+
+    fn read(&self, offset: usize, buf: &mut [u8]) {
+        assert!(offset + buf.len() <= PAGE_SIZE);
+        // SAFETY: the page is ours and in bounds. Mappings may write it
+        // at any time, as they may write any shared memory.
+        unsafe {
+            let src = (self.vaddr as *const u8).add(offset);
+            core::ptr::copy_nonoverlapping(src, buf.as_mut_ptr(), buf.len());
+        }
+    }
+
+    fn write(&self, offset: usize, data: &[u8]) {
+        assert!(offset + data.len() <= PAGE_SIZE);
+        // SAFETY: as in `read`.
+        unsafe {
+            let dst = (self.vaddr as *mut u8).add(offset);
+            core::ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len());
+        }
+    }
+
+    /// Zero the page from `offset` on.
+    fn zero(&self, offset: usize) {
+        assert!(offset <= PAGE_SIZE);
+        // SAFETY: as in `read`.
+        unsafe { core::ptr::write_bytes((self.vaddr as *mut u8).add(offset), 0, PAGE_SIZE - offset) };
+    }
+
+    /// Whether a mapping holds it, which keeps it from being evicted.
+    fn mapped(self: &Arc<Self>) -> bool {
+        Arc::strong_count(self) > 1
+    }
+}
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        global_allocator().dealloc_pages(self.vaddr, 1);
+    }
+}
+
+#[derive(Default)]
+struct CachedInode {
+    pages: BTreeMap<usize, Arc<Page>>,
+    /// Size of the file, read when the first page is.
+    size: Option<usize>,
+    /// Open files of the inode, to read pages and write them back through.
+    files: Vec<Weak<dyn VfsFile>>,
+}
+
+impl CachedInode {
+    fn size(&mut self, file: &dyn VfsFile) -> VfsResult<usize> {
+        match self.size {
+            Some(size) => Ok(size),
+            None => {
+                let size = file.getattr()?.size;
+                self.size = Some(size);
+                Ok(size)
+            }
+        }
+    }
+
+    fn live_file(&self) -> Option<Arc<dyn VfsFile>> {
+        self.files.iter().find_map(Weak::upgrade)
+    }
+
+    /// Write page `index` to the file through `file`, up to the end of the
+    /// file.
+    fn write_back(&self, file: &dyn VfsFile, index: usize, page: &Page) -> VfsResult {
+        let start = index * PAGE_SIZE;
+        let len = self.size.unwrap_or(0).saturating_sub(start).min(PAGE_SIZE);
+        let mut buf = [0; PAGE_SIZE];
+        page.read(0, &mut buf[..len]);
+        page.dirty.store(false, Ordering::Release);
+        if let Err(e) = write_all(file, start, &buf[..len]) {
+            page.dirty.store(true, Ordering::Release);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Write every dirty page back through `file`, in file order.
+    fn write_back_all(&self, file: &dyn VfsFile) -> VfsResult {
+        for (&index, page) in &self.pages {
+            if page.dirty.load(Ordering::Acquire) {
+                self.write_back(file, index, page)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Cache {
+    inodes: BTreeMap<Key, CachedInode>,
+    /// Every page by the tick it was last used at, oldest first.
+    lru: BTreeMap<u64, (Key, usize)>,
+    next_tick: u64,
+}
+
+// SAFETY: the files are only used under the cache lock, and file systems
+// serialize access to their files with their own locks.
+unsafe impl Send for Cache {}
+
+/// A sleeping lock, as it is held across file system I/O.
+static CACHE: Mutex<Cache> = Mutex::new(Cache {
+    inodes: BTreeMap::new(),
+    lru: BTreeMap::new(),
+    next_tick: 1,
+});
+
+fn low_memory() -> bool {
+    global_allocator().available_pages() < MIN_FREE_PAGES
+}
+
+fn write_all(file: &dyn VfsFile, offset: usize, data: &[u8]) -> VfsResult {
+    let mut done = 0;
+    while done < data.len() {
+        match file.write_at(offset + done, &data[done..])? {
+            0 => return ax_err!(Io, "page cache: short write back"),
+            n => done += n,
+        }
+    }
+    Ok(())
+}
+
+/// Fill `buf` from `offset`, stopping early only at the end of the file.
+fn read_full(file: &dyn VfsFile, offset: usize, buf: &mut [u8]) -> VfsResult<usize> {
+    let mut done = 0;
+    while done < buf.len() {
+        match file.read_at(offset + done, &mut buf[done..])? {
+            0 => break,
+            n => done += n,
+        }
+    }
+    Ok(done)
+}
+
+impl Cache {
+    fn inode(&mut self, key: &Key) -> &mut CachedInode {
+        self.inodes.entry(key.clone()).or_default()
+    }
+
+    fn touch(&mut self, key: &Key, index: usize, page: &Page) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.lru.remove(&page.tick.swap(tick, Ordering::Relaxed));
+        self.lru.insert(tick, (key.clone(), index));
+    }
+
+    fn remove_inode(&mut self, key: &Key) {
+        if let Some(inode) = self.inodes.remove(key) {
+            for page in inode.pages.values() {
+                self.lru.remove(&page.tick.load(Ordering::Relaxed));
+            }
+        }
+    }
+
+    /// Drop the least recently used page no mapping holds, writing it back
+    /// first if it is dirty. Dirty pages with no open file to write them
+    /// through stay. Returns false if there is no page to drop, or if
+    /// writing it back failed.
+    fn evict(&mut self) -> bool {
+        let inodes = &self.inodes;
+        let victim = self
+            .lru
+            .iter()
+            .find(|(_, (key, index))| {
+                let inode = &inodes[key];
+                let page = &inode.pages[index];
+                !page.mapped()
+                    && (!page.dirty.load(Ordering::Acquire) || inode.live_file().is_some())
+            })
+            .map(|(&tick, (key, index))| (tick, key.clone(), *index));
+        let Some((tick, key, index)) = victim else {
+            return false;
+        };
+        let inode = self.inodes.get_mut(&key).unwrap();
+        if inode.pages[&index].dirty.load(Ordering::Acquire) {
+            let file = inode.live_file().unwrap();
+            if let Err(e) = inode.write_back(&*file, index, &inode.pages[&index]) {
+                let ino = key.1;
+                error!("page cache: failed to write back page {} of inode {}: {:?}", index, ino, e);
+                return false;
+            }
+        }
+        self.lru.remove(&tick);
+        inode.pages.remove(&index);
+        if inode.pages.is_empty() && inode.live_file().is_none() {
+            self.inodes.remove(&key);
+        }
+        true
+    }
+
+    /// A new page, if the cache may have one.
+    fn alloc_page(&mut self) -> Option<Page> {
+        while self.lru.len() >= MAX_CACHED_PAGES || low_memory() {
+            if !self.evict() {
+                return None;
+            }
+        }
+        Page::new()
+    }
+
+    /// Page `index` of the inode, read in through `file` unless `fill` is
+    /// false and it is about to be overwritten. `None` if no memory can be
+    /// spared for it.
+    fn page(
+        &mut self,
+        key: &Key,
+        index: usize,
+        file: &dyn VfsFile,
+        fill: bool,
+    ) -> VfsResult<Option<Arc<Page>>> {
+        if let Some(page) = self.inodes.get(key).and_then(|inode| inode.pages.get(&index)) {
+            let page = page.clone();
+            self.touch(key, index, &page);
+            return Ok(Some(page));
+        }
+        let Some(page) = self.alloc_page() else {
+            return Ok(None);
+        };
+        let mut filled = 0;
+        if fill {
+            let start = index * PAGE_SIZE;
+            let len = self.inode(key).size(file)?.saturating_sub(start).min(PAGE_SIZE);
+            let mut buf = [0; PAGE_SIZE];
+            filled = read_full(file, start, &mut buf[..len])?;
+            page.write(0, &buf[..filled]);
+        }
+        page.zero(filled);
+        let page = Arc::new(page);
+        self.inode(key).pages.insert(index, page.clone());
+        self.touch(key, index, &page);
+        Ok(Some(page))
+    }
+}
+
+/// Start caching for `file`, just opened with `key`.
+pub(crate) fn open(key: &Key, file: &Arc<dyn VfsFile>) {
+    CACHE.lock().inode(key).files.push(Arc::downgrade(file));
+}
+
+/// Stop caching for `file`, about to be dropped. The dirty pages of the
+/// inode are written back if no other file has it open, and the pages
+/// are dropped if the inode is gone.
+pub(crate) fn close(key: &Key, file: &Arc<dyn VfsFile>) {
+    let mut cache = CACHE.lock();
+    let Some(inode) = cache.inodes.get_mut(key) else {
+        return;
+    };
+    let this = Arc::as_ptr(file) as *const ();
+    inode
+        .files
+        .retain(|other| other.strong_count() > 0 && Weak::as_ptr(other) as *const () != this);
+    if !inode.files.is_empty() {
+        return;
+    }
+    if file.getattr().is_ok_and(|attr| attr.nlink == 0) {
+        cache.remove_inode(key);
+        return;
+    }
+    if let Err(e) = inode.write_back_all(&**file) {
+        error!("page cache: failed to write back inode {}: {:?}", key.1, e);
+    }
+    if inode.pages.is_empty() {
+        cache.inodes.remove(key);
+    }
+}
+
+/// Drop the pages of an inode that lost its last name, unless it is still
+/// open. Its number may be given to a new file.
+pub(crate) fn discard(key: &Key) {
+    let mut cache = CACHE.lock();
+    if cache
+        .inodes
+        .get(key)
+        .is_some_and(|inode| inode.live_file().is_none())
+    {
+        cache.remove_inode(key);
+    }
+}
+
+/// Drop the pages of every file on the mount at `mount_path`, which is
+/// being unmounted with none of them open. Fails with `ResourceBusy`,
+/// dropping nothing, while any of them is dirty.
+pub(crate) fn drop_mount(mount_path: &str) -> AxResult {
+    let mut cache = CACHE.lock();
+    let keys: Vec<Key> = cache
+        .inodes
+        .keys()
+        .filter(|key| &*key.0 == mount_path)
+        .cloned()
+        .collect();
+    let dirty = keys.iter().any(|key| {
+        cache.inodes[key]
+            .pages
+            .values()
+            .any(|page| page.dirty.load(Ordering::Acquire))
+    });
+    if dirty {
+        return ax_err!(ResourceBusy, "umount: dirty pages not written back");
+    }
+    for key in keys {
+        cache.remove_inode(&key);
+    }
+    Ok(())
+}
+
+/// `read_at` of `file` through the cache.
+pub(crate) fn read(key: &Key, file: &dyn VfsFile, offset: usize, buf: &mut [u8]) -> VfsResult<usize> {
+    let mut cache = CACHE.lock();
+    let size = cache.inode(key).size(file)?;
+    let end = size.min(offset.saturating_add(buf.len()));
+    let mut pos = offset;
+    while pos < end {
+        let in_page = pos % PAGE_SIZE;
+        let n = (PAGE_SIZE - in_page).min(end - pos);
+        let dst = &mut buf[pos - offset..][..n];
+        match cache.page(key, pos / PAGE_SIZE, file, true)? {
+            Some(page) => page.read(in_page, dst),
+            None => {
+                let got = read_full(file, pos, dst)?;
+                if got < n {
+                    return Ok(pos + got - offset);
+                }
+            }
+        }
+        pos += n;
+    }
+    Ok(end.saturating_sub(offset))
+}
+
+/// `write_at` of `file` through the cache.
+pub(crate) fn write(key: &Key, file: &dyn VfsFile, offset: usize, data: &[u8]) -> VfsResult<usize> {
+    let mut cache = CACHE.lock();
+    let size = cache.inode(key).size(file)?;
+    let end = offset.saturating_add(data.len());
+    if end > size {
+        let n = file.write_at(offset, data)?;
+        cache.inode(key).size = Some(size.max(offset + n));
+        // Keep what is cached of it up to date.
+        let mut pos = offset;
+        while pos < offset + n {
+            let in_page = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - in_page).min(offset + n - pos);
+            if let Some(page) = cache.inode(key).pages.get(&(pos / PAGE_SIZE)) {
+                page.write(in_page, &data[pos - offset..][..len]);
+            }
+            pos += len;
+        }
+        return Ok(n);
+    }
+    let mut pos = offset;
+    while pos < end {
+        let in_page = pos % PAGE_SIZE;
+        let n = (PAGE_SIZE - in_page).min(end - pos);
+        let src = &data[pos - offset..][..n];
+        // No need to read what is all overwritten.
+        let whole = in_page == 0 && (n == PAGE_SIZE || pos + n == size);
+        match cache.page(key, pos / PAGE_SIZE, file, !whole)? {
+            Some(page) => {
+                page.write(in_page, src);
+                page.dirty.store(true, Ordering::Release);
+            }
+            None => {
+                let got = file.write_at(pos, src)?;
+                if got < n {
+                    return Ok(pos + got - offset);
+                }
+            }
+        }
+        pos += n;
+    }
+    Ok(data.len())
+}
+
+/// Run `resize`, which makes the file `size` bytes long, and drop the
+/// cached pages past the new end.
+pub(crate) fn truncate(key: &Key, size: usize, resize: impl FnOnce() -> VfsResult) -> VfsResult {
+    let mut cache = CACHE.lock();
+    resize()?;
+    let Some(inode) = cache.inodes.get_mut(key) else {
+        return Ok(());
+    };
+    inode.size = Some(size);
+    let gone = inode.pages.split_off(&size.div_ceil(PAGE_SIZE));
+    if size % PAGE_SIZE != 0 {
+        if let Some(page) = inode.pages.get(&(size / PAGE_SIZE)) {
+            page.zero(size % PAGE_SIZE);
+        }
+    }
+    for page in gone.values() {
+        cache.lru.remove(&page.tick.load(Ordering::Relaxed));
+        // Mappings keep theirs; past the end they read as zeros.
+        if page.mapped() {
+            page.zero(0);
+        }
+    }
+    Ok(())
+}
+
+/// Write the dirty pages of the inode back through `file`.
+pub(crate) fn flush(key: &Key, file: &dyn VfsFile) -> VfsResult {
+    match CACHE.lock().inodes.get(key) {
+        Some(inode) => inode.write_back_all(file),
+        None => Ok(()),
+    }
+}
+
+/// Write back the dirty pages of every file on the mount at `mount_path`.
+/// Fails with `Io` if some are left that have no open file to be written
+/// through, after a failed write back on close.
+pub(crate) fn flush_mount(mount_path: &str) -> VfsResult {
+    let cache = CACHE.lock();
+    let mut stranded = false;
+    for (key, inode) in cache.inodes.iter().filter(|(key, _)| &*key.0 == mount_path) {
+        if let Some(file) = inode.live_file() {
+            inode.write_back_all(&*file)?;
+        } else if inode.pages.values().any(|page| page.dirty.load(Ordering::Acquire)) {
+            error!("page cache: dirty pages of inode {} with no open file", key.1);
+            stranded = true;
+        }
+    }
+    if stranded {
+        return ax_err!(Io, "page cache: dirty pages with no open file");
+    }
+    Ok(())
+}
+
+/// Pages `first..first + count` of the inode, read in and held for a
+/// mapping. `None` for those wholly past the end of the file.
+pub(crate) fn map_pages(key: &Key, first: usize, count: usize) -> AxResult<Vec<Option<Arc<Page>>>> {
+    let mut cache = CACHE.lock();
+    let Some(file) = cache.inodes.get(key).and_then(CachedInode::live_file) else {
+        return ax_err!(Unsupported, "mmap: file is not cached");
+    };
+    let last = cache.inode(key).size(&*file)?.div_ceil(PAGE_SIZE);
+    let mut pages = Vec::with_capacity(count);
+    for index in first..first + count {
+        if index >= last {
+            pages.push(None);
+            continue;
+        }
+        match cache.page(key, index, &*file, true)? {
+            Some(page) => pages.push(Some(page)),
+            None => return ax_err!(NoMemory, "mmap: page cache full"),
+        }
+    }
+    Ok(pages)
+}
+
+/// Mark the pages a mapping holds from page `first` on dirty, as it may
+/// have written them, and write the inode back. Pages no longer in the
+/// cache are skipped.
+pub(crate) fn sync_pages(key: &Key, first: usize, pages: &[Option<Arc<Page>>]) -> VfsResult {
+    let cache = CACHE.lock();
+    let Some(inode) = cache.inodes.get(key) else {
+        return Ok(());
+    };
+    for (index, page) in (first..).zip(pages) {
+        if let (Some(page), Some(cached)) = (page, inode.pages.get(&index)) {
+            if Arc::ptr_eq(page, cached) {
+                page.dirty.store(true, Ordering::Release);
+            }
+        }
+    }
+    match inode.live_file() {
+        Some(file) => inode.write_back_all(&*file),
+        None => ax_err!(BadState, "msync: no open file to write back through"),
+    }
+}
+
+/// Number of pages cached.
+pub fn cached_pages() -> usize {
+    CACHE.lock().lru.len()
+}
+
+/// Number of cached pages not yet written back.
+pub fn dirty_pages() -> usize {
+    let cache = CACHE.lock();
+    cache
+        .inodes
+        .values()
+        .flat_map(|inode| inode.pages.values())
+        .filter(|page| page.dirty.load(Ordering::Acquire))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mount::MountedFileSystem;
+    use crate::RamFileSystem;
+    use alloc::{boxed::Box, string::ToString};
+    use axerror::AxError;
+    use std::sync::Once;
+    use vfscore::{FileType, Metadata, MountOptions, SetAttr, VfsFileSystem};
+
+    static INIT: Once = Once::new();
+    /// Makes every write of a [`TestFile`] fail.
+    static FAIL_WRITES: AtomicBool = AtomicBool::new(false);
+
+    /// A ramfs that has its pages cached.
+    struct TestFs(Arc<RamFileSystem>);
+
+    struct TestFile(Box<dyn VfsFile>);
+
+    impl VfsFileSystem for TestFs {
+        fn name(&self) -> &str {
+            "testfs"
+        }
+        fn root(self: Arc<Self>) -> Box<dyn VfsFile> {
+            Box::new(TestFile(self.0.clone().root()))
+        }
+        fn cache_pages(&self) -> bool {
+            true
+        }
+    }
+
+    impl VfsFile for TestFile {
+        fn getattr(&self) -> VfsResult<Metadata> {
+            self.0.getattr()
+        }
+        fn setattr(&self, attr: SetAttr) -> VfsResult {
+            self.0.setattr(attr)
+        }
+        fn read_at(&self, offset: usize, buf: &mut [u8]) -> VfsResult<usize> {
+            self.0.read_at(offset, buf)
+        }
+        fn write_at(&self, offset: usize, data: &[u8]) -> VfsResult<usize> {
+            if FAIL_WRITES.load(Ordering::Acquire) {
+                return ax_err!(Io);
+            }
+            self.0.write_at(offset, data)
+        }
+        fn lookup(&self, path: &str) -> VfsResult<Box<dyn VfsFile>> {
+            Ok(Box::new(TestFile(self.0.lookup(path)?)))
+        }
+        fn create(&self, path: &str, ty: FileType, mode: u16) -> VfsResult<Box<dyn VfsFile>> {
+            Ok(Box::new(TestFile(self.0.create(path, ty, mode)?)))
+        }
+    }
+
+    /// A new cached ramfs mounted at `path`: its root opened through the
+    /// mount, and the root of the ramfs to see what reached it.
+    fn mount_cached(path: &str) -> (Box<dyn VfsFile>, Box<dyn VfsFile>) {
+        INIT.call_once(|| {
+            // Room for a full cache that still leaves `MIN_FREE_PAGES`.
+            let len = (MAX_CACHED_PAGES + MIN_FREE_PAGES + 64) * PAGE_SIZE;
+            let memory = Box::leak(vec![0u8; len].into_boxed_slice());
+            axalloc::global_init(memory.as_ptr() as usize, len);
+        });
+        let ram = Arc::new(RamFileSystem::new());
+        let fs = Arc::new(TestFs(ram.clone()));
+        let mfs = MountedFileSystem::new(path.to_string(), fs, MountOptions::default(), None);
+        (mfs.track(mfs.root()), ram.root())
+    }
+
+    fn read_byte(file: &dyn VfsFile, offset: usize) -> u8 {
+        let mut buf = [0u8];
+        assert_eq!(file.read_at(offset, &mut buf), Ok(1));
+        buf[0]
+    }
+
+    fn is_cached(path: &str, ino: usize, index: usize) -> bool {
+        let key: Key = (path.into(), ino);
+        CACHE.lock().inodes[&key].pages.contains_key(&index)
+    }
+
+    #[test]
+    fn test_write_back_on_close() {
+        let _scheduler = crate::test_scheduler();
+        let (root, ram) = mount_cached("/close");
+        let file = root.create("a", FileType::File, 0o644).unwrap();
+        // Growing the file goes straight through.
+        assert_eq!(file.write_at(0, &[1; 2 * PAGE_SIZE]), Ok(2 * PAGE_SIZE));
+        assert_eq!(dirty_pages(), 0);
+        assert_eq!(file.write_at(PAGE_SIZE, &[2; 10]), Ok(10));
+        assert_eq!(dirty_pages(), 1);
+        assert_eq!(read_byte(&*file, PAGE_SIZE), 2);
+        let raw = ram.lookup("a").unwrap();
+        assert_eq!(read_byte(&*raw, PAGE_SIZE), 1);
+
+        // Another open file of the inode keeps it from being written back.
+        let other = root.lookup("a").unwrap();
+        drop(file);
+        assert_eq!(dirty_pages(), 1);
+        drop(other);
+        assert_eq!(dirty_pages(), 0);
+        assert_eq!(read_byte(&*raw, PAGE_SIZE), 2);
+        assert_eq!(drop_mount("/close"), Ok(()));
+        assert_eq!(cached_pages(), 0);
+    }
+
+    #[test]
+    fn test_failed_write_back() {
+        let _scheduler = crate::test_scheduler();
+        let (root, ram) = mount_cached("/failed");
+        let file = root.create("a", FileType::File, 0o644).unwrap();
+        file.write_at(0, &[1; PAGE_SIZE]).unwrap();
+        file.write_at(0, &[2; 10]).unwrap();
+        FAIL_WRITES.store(true, Ordering::Release);
+        assert!(file.fsync().is_err());
+        drop(file);
+        FAIL_WRITES.store(false, Ordering::Release);
+
+        // The page is kept, and keeps the mount from being unmounted.
+        assert_eq!(dirty_pages(), 1);
+        assert_eq!(flush_mount("/failed"), Err(AxError::Io));
+        assert_eq!(drop_mount("/failed"), Err(AxError::ResourceBusy));
+        let raw = ram.lookup("a").unwrap();
+        assert_eq!(read_byte(&*raw, 0), 1);
+
+        // Opening the file again gives it something to be written through.
+        let file = root.lookup("a").unwrap();
+        assert_eq!(read_byte(&*file, 0), 2);
+        assert_eq!(flush_mount("/failed"), Ok(()));
+        assert_eq!(dirty_pages(), 0);
+        assert_eq!(read_byte(&*raw, 0), 2);
+        drop(file);
+        assert_eq!(drop_mount("/failed"), Ok(()));
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let _scheduler = crate::test_scheduler();
+        let (root, ram) = mount_cached("/lru");
+        let size = (MAX_CACHED_PAGES + 1) * PAGE_SIZE;
+        let raw = ram.create("a", FileType::File, 0o644).unwrap();
+        raw.write_at(0, &vec![1; size]).unwrap();
+        let file = root.lookup("a").unwrap();
+        let ino = file.getattr().unwrap().ino;
+
+        // Fill the cache with a dirty page 0, least recently used.
+        file.write_at(0, &[2; PAGE_SIZE]).unwrap();
+        for index in 1..MAX_CACHED_PAGES {
+            read_byte(&*file, index * PAGE_SIZE);
+        }
+        assert_eq!(cached_pages(), MAX_CACHED_PAGES);
+        assert_eq!(read_byte(&*raw, 0), 1);
+
+        // One more page evicts page 0, which is written back first.
+        read_byte(&*file, MAX_CACHED_PAGES * PAGE_SIZE);
+        assert_eq!(cached_pages(), MAX_CACHED_PAGES);
+        assert!(!is_cached("/lru", ino, 0));
+        assert_eq!(dirty_pages(), 0);
+        assert_eq!(read_byte(&*raw, 0), 2);
+
+        // Using page 1 again keeps it over page 2.
+        read_byte(&*file, PAGE_SIZE);
+        assert_eq!(read_byte(&*file, 0), 2);
+        assert!(is_cached("/lru", ino, 0));
+        assert!(is_cached("/lru", ino, 1));
+        assert!(!is_cached("/lru", ino, 2));
+        assert_eq!(cached_pages(), MAX_CACHED_PAGES);
+
+        drop(file);
+        assert_eq!(drop_mount("/lru"), Ok(()));
+        assert_eq!(cached_pages(), 0);
+    }
+}
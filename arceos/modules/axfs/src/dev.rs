@@ -0,0 +1,57 @@
+//! Device nodes, dispatched to the drivers registered in `axdevfs`.
+
+use alloc::sync::Arc;
+use axdevfs::Device;
+use axerror::{ax_err, AxResult};
+use vfscore::{FileType, Metadata, VfsFile, VfsResult};
+
+/// An opened device node.
+pub struct DeviceFile {
+    dev: Arc<dyn Device>,
+    rdev: (u16, u16),
+}
+
+impl DeviceFile {
+    /// Open the driver registered for (major, minor).
+    pub fn open(major: u16, minor: u16) -> AxResult<Self> {
+        let dev = match axdevfs::get_device(major, minor) {
+            Some(dev) => dev,
+            None => return ax_err!(NotFound, "no driver for the device"),
+        };
+        dev.open(0);
+        Ok(Self {
+            dev,
+            rdev: (major, minor),
+        })
+    }
+}
+
+impl Drop for DeviceFile {
+    fn drop(&mut self) {
+        self.dev.close();
+    }
+}
+
+impl VfsFile for DeviceFile {
+    fn getattr(&self) -> VfsResult<Metadata> {
+        Ok(Metadata {
+            ino: 0,
+            file_type: FileType::Device,
+            mode: 0o666,
+            size: 0,
+            nlink: 1,
+            rdev: Some(self.rdev),
+            mtime: None,
+        })
+    }
+    /// Devices are streams, so `offset` is ignored.
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> VfsResult<usize> {
+        self.dev.read(buf)
+    }
+    fn write_at(&self, _offset: usize, data: &[u8]) -> VfsResult<usize> {
+        self.dev.write(data)
+    }
+    fn ioctl(&self, cmd: usize, arg: &mut [u8]) -> VfsResult<usize> {
+        self.dev.ioctl(cmd, arg)
+    }
+}
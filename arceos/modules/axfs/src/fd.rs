@@ -0,0 +1,477 @@
+//! File descriptors.
+//!
+//! [`open`] makes an open file and gives the task the lowest free
+//! descriptor for it. There is no system-wide table of open files, only a
+//! count of them, which [`open`] keeps under `NFILE`. Descriptors made by
+//! [`dup`] and [`dup2`] share the open file, and so its offset. Each task
+//! has its own descriptor table; 0, 1 and 2 are left for standard input,
+//! output and error, which only [`dup2`] can put a file at.
+//!
+//! [`flock`] locks belong to the open file. [`set_lock`] byte-range locks
+//! belong to the task, which loses all of them on a file when it closes any
+//! descriptor of it. A task that exits closes all its descriptors.
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use axerror::{ax_err, AxError, AxResult};
+use spin::mutex::Mutex;
+use vfscore::{Metadata, SetAttr, VfsFile};
+
+use crate::lock::{FileLocks, LockKind};
+use xv6fs::fs_const::{NFILE, NOFILE};
+
+/// Open for reading only.
+pub const O_RDONLY: u32 = 0;
+/// Open for writing only.
+pub const O_WRONLY: u32 = 1;
+/// Open for reading and writing.
+pub const O_RDWR: u32 = 2;
+const O_ACCMODE: u32 = 3;
+/// Create the file if it is missing.
+pub const O_CREAT: u32 = 0o100;
+/// With `O_CREAT`, fail if the file exists.
+pub const O_EXCL: u32 = 0o200;
+/// Cut the file to 0 bytes.
+pub const O_TRUNC: u32 = 0o1000;
+/// Write at the end of the file.
+pub const O_APPEND: u32 = 0o2000;
+/// Set `FD_CLOEXEC` on the new descriptor.
+pub const O_CLOEXEC: u32 = 0o2000000;
+
+/// Descriptor flag: close the descriptor when the task executes a new
+/// program.
+pub const FD_CLOEXEC: u32 = 1;
+
+/// `lseek` from the start of the file.
+pub const SEEK_SET: u32 = 0;
+/// `lseek` from the current offset.
+pub const SEEK_CUR: u32 = 1;
+/// `lseek` from the end of the file.
+pub const SEEK_END: u32 = 2;
+
+/// `flock`: take a shared lock.
+pub const LOCK_SH: u32 = 1;
+/// `flock`: take an exclusive lock.
+pub const LOCK_EX: u32 = 2;
+/// `flock`: fail with `Again` instead of waiting.
+pub const LOCK_NB: u32 = 4;
+/// `flock`: release the lock.
+pub const LOCK_UN: u32 = 8;
+
+/// Byte-range read (shared) lock.
+pub const F_RDLCK: u32 = 0;
+/// Byte-range write (exclusive) lock.
+pub const F_WRLCK: u32 = 1;
+/// No byte-range lock.
+pub const F_UNLCK: u32 = 2;
+
+/// A byte-range lock, as in `struct flock`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Flock {
+    /// `F_RDLCK`, `F_WRLCK` or `F_UNLCK`.
+    pub kind: u32,
+    /// What `start` counts from: `SEEK_SET`, `SEEK_CUR` or `SEEK_END`.
+    pub whence: u32,
+    pub start: i64,
+    /// Bytes covered; 0 for up to any end of file, negative for the bytes
+    /// before `start`.
+    pub len: i64,
+    /// The task holding a lock [`get_lock`] found.
+    pub owner: u64,
+}
+
+/// First descriptor `open` and `dup` hand out.
+const FIRST_FD: usize = 3;
+
+/// Number of open files, kept at most `NFILE`.
+static OPEN_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Descriptor tables by task id.
+static FD_TABLES: Mutex<BTreeMap<u64, FdTable>> = Mutex::new(BTreeMap::new());
+
+/// An open file, shared by the descriptors that refer to it.
+pub(crate) struct OpenFile {
+    pub(crate) file: Box<dyn VfsFile>,
+    /// Mount path and inode number.
+    pub(crate) inode: (Arc<str>, usize),
+    locks: FileLocks,
+    /// A sleeping lock: it is held across the read or write.
+    offset: axsync::Mutex<usize>,
+    pub(crate) readable: bool,
+    pub(crate) writable: bool,
+    append: bool,
+}
+
+impl OpenFile {
+    fn new(file: Box<dyn VfsFile>, path: &str, flags: u32) -> AxResult<Arc<Self>> {
+        let (mount, ino) = crate::inode_of(path)?;
+        if OPEN_FILES.fetch_add(1, Ordering::AcqRel) >= NFILE {
+            OPEN_FILES.fetch_sub(1, Ordering::AcqRel);
+            return ax_err!(TooManyOpenFiles, "open: NFILE files open");
+        }
+        let access = flags & O_ACCMODE;
+        Ok(Arc::new(Self {
+            file,
+            locks: FileLocks::new(&mount, ino),
+            inode: (mount.into(), ino),
+            offset: axsync::Mutex::new(0),
+            readable: access != O_WRONLY,
+            writable: access != O_RDONLY,
+            append: flags & O_APPEND != 0,
+        }))
+    }
+}
+
+// SAFETY: file systems serialize access to their files with their own
+// locks; the offset has its own.
+unsafe impl Send for OpenFile {}
+unsafe impl Sync for OpenFile {}
+
+impl Drop for OpenFile {
+    fn drop(&mut self) {
+        OPEN_FILES.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[derive(Clone)]
+struct FdEntry {
+    file: Arc<OpenFile>,
+    flags: u32,
+}
+
+/// Descriptors of one task, indexed by number.
+#[derive(Default)]
+struct FdTable(Vec<Option<FdEntry>>);
+
+impl FdTable {
+    fn get(&self, fd: usize) -> AxResult<&FdEntry> {
+        match self.0.get(fd) {
+            Some(Some(entry)) => Ok(entry),
+            _ => ax_err!(BadFileDescriptor),
+        }
+    }
+
+    /// Put `entry` at the lowest free descriptor from `min` on.
+    fn insert(&mut self, min: usize, entry: FdEntry) -> AxResult<usize> {
+        let fd = (min..NOFILE)
+            .find(|&fd| !matches!(self.0.get(fd), Some(Some(_))))
+            .ok_or(AxError::TooManyOpenFiles)?;
+        self.set(fd, entry);
+        Ok(fd)
+    }
+
+    fn set(&mut self, fd: usize, entry: FdEntry) -> Option<FdEntry> {
+        if self.0.len() <= fd {
+            self.0.resize(fd + 1, None);
+        }
+        self.0[fd].replace(entry)
+    }
+}
+
+fn task_id() -> u64 {
+    axtask::current().id().as_u64()
+}
+
+/// Release the byte-range locks task `owner` held through the descriptors
+/// it closed, then close them.
+fn release(owner: u64, closed: Vec<FdEntry>) {
+    for entry in closed.iter() {
+        entry.file.locks.unlock_ranges(owner);
+    }
+}
+
+/// Run `f` on the calling task's descriptor table.
+fn with_table<T>(f: impl FnOnce(&mut FdTable) -> T) -> T {
+    f(FD_TABLES.lock().entry(task_id()).or_default())
+}
+
+/// Close the descriptors of task `id` as it exits, releasing its
+/// byte-range locks. Run by [`axtask::exit`] once `init_filesystems` has
+/// registered it.
+pub(crate) fn task_exited(id: axtask::TaskId) {
+    let table = FD_TABLES.lock().remove(&id.as_u64());
+    // Closing a file may sleep, so not under the lock.
+    if let Some(table) = table {
+        release(id.as_u64(), table.0.into_iter().flatten().collect());
+    }
+}
+
+/// The open file behind `fd`. The table is not locked while it is used, so
+/// a blocking read does not hold up other descriptors.
+pub(crate) fn file(fd: usize) -> AxResult<Arc<OpenFile>> {
+    with_table(|table| table.get(fd).map(|entry| entry.file.clone()))
+}
+
+/// Open the file at `path` with `O_*` flags and return its descriptor.
+pub fn open(path: &str, flags: u32) -> AxResult<usize> {
+    let access = flags & O_ACCMODE;
+    if access == O_ACCMODE {
+        return ax_err!(InvalidParam, "open: bad access mode");
+    }
+    let file = if flags & O_CREAT != 0 && flags & O_EXCL != 0 {
+        // The file system checks and creates in one go, so of two racing
+        // opens only one succeeds.
+        crate::create(path)?
+    } else {
+        match crate::open(path) {
+            Ok(file) => file,
+            Err(AxError::NotFound) if flags & O_CREAT != 0 => match crate::create(path) {
+                // Someone else created it in between.
+                Err(AxError::AlreadyExists) => crate::open(path)?,
+                file => file?,
+            },
+            Err(e) => return Err(e),
+        }
+    };
+    let attr = file.getattr()?;
+    if access != O_RDONLY && attr.is_dir() {
+        return ax_err!(IsADirectory);
+    }
+    // Devices and FIFOs may be written on any mount.
+    let writes = access != O_RDONLY || flags & O_TRUNC != 0;
+    if writes && attr.is_file() && crate::read_only(path)? {
+        return ax_err!(ReadOnlyFilesystem);
+    }
+    let truncate = flags & O_TRUNC != 0 && access != O_RDONLY && attr.is_file();
+    let entry = FdEntry {
+        file: OpenFile::new(file, path, flags)?,
+        flags: if flags & O_CLOEXEC != 0 { FD_CLOEXEC } else { 0 },
+    };
+    let open_file = entry.file.clone();
+    let fd = with_table(|table| table.insert(FIRST_FD, entry))?;
+    // Only now that the file has a descriptor: a full table must not cost
+    // the file its data.
+    if truncate {
+        if let Err(e) = open_file.file.truncate(0) {
+            close(fd)?;
+            return Err(e);
+        }
+    }
+    Ok(fd)
+}
+
+/// Close `fd`. The file stays open while other descriptors share it.
+pub fn close(fd: usize) -> AxResult {
+    let entry = with_table(|table| {
+        table.get(fd)?;
+        Ok(table.0[fd].take())
+    })?;
+    // Released here, outside the table lock.
+    release(task_id(), entry.into_iter().collect());
+    Ok(())
+}
+
+/// A new descriptor, the lowest free one from 3 on, for the file behind
+/// `fd`.
+pub fn dup(fd: usize) -> AxResult<usize> {
+    with_table(|table| {
+        let file = table.get(fd)?.file.clone();
+        table.insert(FIRST_FD, FdEntry { file, flags: 0 })
+    })
+}
+
+/// Make `new_fd` refer to the file behind `old_fd`, closing what `new_fd`
+/// referred to before.
+pub fn dup2(old_fd: usize, new_fd: usize) -> AxResult<usize> {
+    if new_fd >= NOFILE {
+        return ax_err!(BadFileDescriptor);
+    }
+    let old = with_table(|table| {
+        let file = table.get(old_fd)?.file.clone();
+        if old_fd == new_fd {
+            return Ok(None);
+        }
+        Ok(table.set(new_fd, FdEntry { file, flags: 0 }))
+    })?;
+    release(task_id(), old.into_iter().collect());
+    Ok(new_fd)
+}
+
+/// The descriptor flags of `fd`, `FD_CLOEXEC` or 0.
+pub fn get_flags(fd: usize) -> AxResult<u32> {
+    with_table(|table| table.get(fd).map(|entry| entry.flags))
+}
+
+pub fn set_flags(fd: usize, flags: u32) -> AxResult {
+    with_table(|table| {
+        table.get(fd)?;
+        if let Some(entry) = table.0[fd].as_mut() {
+            entry.flags = flags & FD_CLOEXEC;
+        }
+        Ok(())
+    })
+}
+
+/// Close every descriptor with `FD_CLOEXEC` set, as executing a new
+/// program does.
+pub fn close_on_exec() {
+    let closed: Vec<FdEntry> = with_table(|table| {
+        table
+            .0
+            .iter_mut()
+            .filter(|slot| slot.as_ref().is_some_and(|e| e.flags & FD_CLOEXEC != 0))
+            .filter_map(Option::take)
+            .collect()
+    });
+    release(task_id(), closed);
+}
+
+/// Read at the offset of `fd` and move it past what was read.
+pub fn read(fd: usize, buf: &mut [u8]) -> AxResult<usize> {
+    let file = file(fd)?;
+    if !file.readable {
+        return ax_err!(BadFileDescriptor, "read: not open for reading");
+    }
+    let mut offset = file.offset.lock();
+    let n = file.file.read_at(*offset, buf)?;
+    *offset += n;
+    Ok(n)
+}
+
+/// Write at the offset of `fd`, or at the end with `O_APPEND`, and move
+/// the offset past what was written.
+pub fn write(fd: usize, data: &[u8]) -> AxResult<usize> {
+    let file = file(fd)?;
+    if !file.writable {
+        return ax_err!(BadFileDescriptor, "write: not open for writing");
+    }
+    let mut offset = file.offset.lock();
+    if file.append {
+        *offset = file.file.getattr()?.size;
+    }
+    let n = file.file.write_at(*offset, data)?;
+    *offset += n;
+    Ok(n)
+}
+
+/// Move the offset of `fd` by `offset` from `SEEK_SET`, `SEEK_CUR` or
+/// `SEEK_END`. Returns the new offset.
+pub fn lseek(fd: usize, offset: isize, whence: u32) -> AxResult<usize> {
+    let file = file(fd)?;
+    let mut current = file.offset.lock();
+    let base = match whence {
+        SEEK_SET => 0,
+        SEEK_CUR => *current,
+        SEEK_END => file.file.getattr()?.size,
+        _ => return ax_err!(InvalidParam, "lseek: bad whence"),
+    };
+    *current = base.checked_add_signed(offset).ok_or(AxError::InvalidParam)?;
+    Ok(*current)
+}
+
+/// Metadata of the file behind `fd`.
+pub fn fstat(fd: usize) -> AxResult<Metadata> {
+    file(fd)?.file.getattr()
+}
+
+/// Set the permission bits of the file behind `fd`.
+pub fn fchmod(fd: usize, mode: u16) -> AxResult {
+    file(fd)?.file.setattr(SetAttr {
+        mode: Some(mode),
+        ..Default::default()
+    })
+}
+
+/// Cut or zero-extend the file behind `fd` to `size` bytes.
+pub fn ftruncate(fd: usize, size: usize) -> AxResult {
+    let file = file(fd)?;
+    if !file.writable {
+        return ax_err!(InvalidParam, "ftruncate: not open for writing");
+    }
+    file.file.truncate(size)
+}
+
+/// Flush the file behind `fd` to stable storage.
+pub fn fsync(fd: usize) -> AxResult {
+    file(fd)?.file.sync_all()
+}
+
+/// Take, convert or release (`LOCK_UN`) the whole-file lock of the file
+/// behind `fd`. With `LOCK_NB`, fails with `Again` instead of waiting for
+/// another open file to release a conflicting lock.
+pub fn flock(fd: usize, op: u32) -> AxResult {
+    let file = file(fd)?;
+    let wait = op & LOCK_NB == 0;
+    match op & !LOCK_NB {
+        LOCK_SH => file.locks.lock(LockKind::Shared, wait),
+        LOCK_EX => file.locks.lock(LockKind::Exclusive, wait),
+        LOCK_UN => {
+            file.locks.unlock();
+            Ok(())
+        }
+        _ => ax_err!(InvalidParam, "flock: bad operation"),
+    }
+}
+
+/// The bytes `lock` covers on `file`, as `start..end`.
+fn lock_range(file: &OpenFile, lock: &Flock) -> AxResult<(u64, u64)> {
+    let base = match lock.whence {
+        SEEK_SET => 0,
+        SEEK_CUR => *file.offset.lock() as i64,
+        SEEK_END => file.file.getattr()?.size as i64,
+        _ => return ax_err!(InvalidParam, "bad whence"),
+    };
+    let start = base.checked_add(lock.start).ok_or(AxError::InvalidParam)?;
+    let (start, end) = match lock.len {
+        0 => (start, None),
+        len if len > 0 => (start, Some(start.saturating_add(len))),
+        len => (start.saturating_add(len), Some(start)),
+    };
+    if start < 0 {
+        return ax_err!(InvalidParam, "lock range starts before the file");
+    }
+    Ok((start as u64, end.map_or(u64::MAX, |end| end as u64)))
+}
+
+/// Look for a byte-range lock of another task that would block `lock`.
+/// If there is one, `lock` is overwritten with it; otherwise its kind is
+/// set to `F_UNLCK`.
+pub fn get_lock(fd: usize, lock: &mut Flock) -> AxResult {
+    let file = file(fd)?;
+    let kind = match lock.kind {
+        F_RDLCK => LockKind::Shared,
+        F_WRLCK => LockKind::Exclusive,
+        _ => return ax_err!(InvalidParam, "get_lock: bad lock type"),
+    };
+    let (start, end) = lock_range(&file, lock)?;
+    match file.locks.test_range(task_id(), kind, start, end) {
+        Some(held) => {
+            *lock = Flock {
+                kind: match held.kind {
+                    LockKind::Shared => F_RDLCK,
+                    LockKind::Exclusive => F_WRLCK,
+                },
+                whence: SEEK_SET,
+                start: held.start as i64,
+                len: if held.end == u64::MAX {
+                    0
+                } else {
+                    (held.end - held.start) as i64
+                },
+                owner: held.owner,
+            }
+        }
+        None => lock.kind = F_UNLCK,
+    }
+    Ok(())
+}
+
+/// Take or release (`F_UNLCK`) a byte-range lock on the file behind `fd`
+/// for the calling task. If another task holds a conflicting lock, wait for
+/// it to go if `wait` is set, or fail with `Again`. A read lock needs `fd`
+/// open for reading and a write lock for writing.
+pub fn set_lock(fd: usize, lock: &Flock, wait: bool) -> AxResult {
+    let file = file(fd)?;
+    let (start, end) = lock_range(&file, lock)?;
+    let kind = match lock.kind {
+        F_RDLCK if file.readable => LockKind::Shared,
+        F_WRLCK if file.writable => LockKind::Exclusive,
+        F_RDLCK | F_WRLCK => return ax_err!(BadFileDescriptor, "set_lock: wrong access mode"),
+        F_UNLCK => {
+            file.locks.unlock_range(task_id(), start, end);
+            return Ok(());
+        }
+        _ => return ax_err!(InvalidParam, "set_lock: bad lock type"),
+    };
+    file.locks.lock_range(task_id(), kind, start, end, wait)
+}
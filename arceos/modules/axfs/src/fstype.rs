@@ -0,0 +1,97 @@
+//! File system types axfs knows how to mount, and detection of the one on
+//! a block device.
+
+use alloc::{string::ToString, sync::Arc, vec::Vec};
+use axerror::{ax_err, AxError, AxResult};
+use spin::RwLock;
+use vfscore::{BlockDevice, MountOptions, VfsFileSystem, BLOCK_SIZE};
+
+use crate::blockdev::block_device;
+
+/// Bytes from the start of the device handed to probe functions: the boot
+/// sector and the block after it, which is enough for xv6 and FAT.
+pub const PROBE_SIZE: usize = 1024;
+
+/// Opens a file system on a block device with some mount options.
+pub type OpenFn =
+    fn(Option<Arc<dyn BlockDevice>>, &MountOptions) -> AxResult<Arc<dyn VfsFileSystem>>;
+
+/// A file system driver.
+#[derive(Clone, Copy)]
+pub struct FsType {
+    /// Name to mount it by, as in `mount -t`.
+    pub name: &'static str,
+    /// Whether `head`, the first [`PROBE_SIZE`] bytes of the device, holds
+    /// this file system.
+    pub probe: fn(head: &[u8]) -> bool,
+    /// Open the file system on `dev`, which is `None` for types that keep
+    /// no data on a device, such as ramfs.
+    pub open: OpenFn,
+}
+
+static FS_TYPES: RwLock<Vec<FsType>> = RwLock::new(Vec::new());
+
+/// Add a file system driver. Drivers are probed in registration order.
+pub fn register_fs_type(fs_type: FsType) -> AxResult {
+    let mut fs_types = FS_TYPES.write();
+    if fs_types.iter().any(|t| t.name == fs_type.name) {
+        return ax_err!(AlreadyExists, "file system type already registered");
+    }
+    fs_types.push(fs_type);
+    Ok(())
+}
+
+/// Names of all registered file system types.
+pub fn fs_types() -> Vec<&'static str> {
+    FS_TYPES.read().iter().map(|t| t.name).collect()
+}
+
+/// The registered driver called `name`.
+pub fn find_fs_type(name: &str) -> Option<FsType> {
+    FS_TYPES.read().iter().find(|t| t.name == name).copied()
+}
+
+/// The first registered driver that recognizes the file system on `dev`.
+pub fn detect_fs_type(dev: &dyn BlockDevice) -> Option<FsType> {
+    if dev.num_blocks() < (PROBE_SIZE / BLOCK_SIZE) as u64 {
+        return None;
+    }
+    let mut head = [0u8; PROBE_SIZE];
+    dev.read_blocks(0, &mut head).ok()?;
+    FS_TYPES.read().iter().find(|t| (t.probe)(&head)).copied()
+}
+
+/// Open the file system on `dev`, either as the type called `fs_type` or,
+/// with `None`, as whatever type it is detected to be.
+pub fn open_fs(
+    dev: Option<Arc<dyn BlockDevice>>,
+    fs_type: Option<&str>,
+    options: &MountOptions,
+) -> AxResult<Arc<dyn VfsFileSystem>> {
+    let fs_type = match (fs_type, &dev) {
+        (Some(name), _) => find_fs_type(name).ok_or(AxError::NotFound)?,
+        (None, Some(dev)) => match detect_fs_type(&**dev) {
+            Some(fs_type) => fs_type,
+            None => return ax_err!(InvalidParam, "unknown file system on the block device"),
+        },
+        (None, None) => return ax_err!(InvalidParam, "no file system type or block device"),
+    };
+    info!("open {} file system", fs_type.name);
+    (fs_type.open)(dev, options)
+}
+
+/// Mount the file system on the block device `source` at `path`, as
+/// `mount -t`. `fs_type` of `None` detects the type. `source` names a
+/// device registered with axfs, `vda1` or `/dev/vda1`; it is ignored for
+/// types that need no device, so `none` will do for them.
+pub fn mount_fs(source: &str, path: &str, fs_type: Option<&str>, options: MountOptions) -> AxResult {
+    let dev = block_device(source);
+    if dev.is_none() && fs_type.is_none() {
+        return ax_err!(NotFound, "no such block device");
+    }
+    let name = dev
+        .is_some()
+        .then(|| source.strip_prefix("/dev/").unwrap_or(source).to_string());
+    let fs = open_fs(dev, fs_type, &options)?;
+    crate::ops::mount_from(name, path, fs, options)
+}
@@ -1,7 +1,19 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
+pub mod blockdev;
+pub mod cache;
+mod dev;
+pub mod fd;
+pub mod fstype;
+pub mod lock;
+pub mod loopdev;
+#[cfg(feature = "paging")]
+pub mod mmap;
 pub mod mount;
+pub mod notify;
 mod ops;
+mod pipe;
+mod procfs;
 pub mod sleeplock_shim;
 
 #[macro_use]
@@ -10,24 +22,33 @@ extern crate alloc;
 extern crate axlog;
 
 use alloc::{sync::Arc, vec::Vec};
-use axdriver::{block_devices, BlockDevices};
-use driver_block::BlockDriverOps;
+use axerror::{ax_err, AxResult};
+use axdriver::BlockDevices;
 use fatfs_shim::Fat32FileSystem;
+pub use ramfs::RamFileSystem;
 use sleeplock_shim::FsLockList;
 use spin::mutex::Mutex;
 use spin::rwlock::RwLock;
 use xv6fs::interface::{INTERFACE_MANAGER,InterfaceManager};
 use xv6fs_shim::{VXV6FS};
-use xv6fs::{BlockDevice, interface::FsInterface};
+use xv6fs::interface::FsInterface;
 use lazy_init::LazyInit;
-use vfscore::{DiskOperation, VfsFileSystem};
 
+use crate::blockdev::{DriverDevice, Xv6Device};
 use crate::mount::{MountedFsList, MOUNTEDFS};
 use crate::sleeplock_shim::{FS_LOCK_LIST};
+pub use blockdev::{block_device, block_devices, register_block_device};
+pub use dev::DeviceFile;
+pub use loopdev::{attach_loop, detach_loop, LoopDevice};
+pub use fstype::{register_fs_type, mount_fs, FsType};
 pub use ops::*;
+pub use pipe::{pipe, PipeReader, PipeWriter, PIPE_BUF_SIZE};
+pub use procfs::ProcFileSystem;
+pub use vfscore::{
+    BlockDevice, DataMode, DirEntry, FileType, Metadata, MountOptions, SetAttr, StatFs, VfsFile, VfsFileSystem,
+};
 
 static FILESTSTEMS: LazyInit<FileSystemList> = LazyInit::new();
-static BLOCK_DEV:LazyInit<BlockDevices>=LazyInit::new();
 
 pub struct FileSystemList(Vec<Arc<dyn VfsFileSystem>>);
 
@@ -49,85 +70,162 @@ impl FileSystemList {
     }
 }
 
-// pub fn init_filesystems() {
-//     info!("init filesystems");
-//     // init filesystems
-//     let fat32 = Arc::new(Fat32FileSystem::<DiskOps>::new());
-
-//     // init filesystem list
-//     let mut fs_list = FileSystemList::new();
-//     fs_list.add(fat32.clone());
-//     FILESTSTEMS.init_by(fs_list);
-
-//     // init mounted filesystem list
-//     let mut mounted_list = MountedFsList::new();
-//     mounted_list.mount("/", fat32.clone());
-//     MOUNTEDFS.init_by(mounted_list)
-// }
-
 pub fn init_filesystems(blk_devs: BlockDevices) {
     
     let interface=InterfaceManager{interface:Arc::new(AxFsInterface::new())};
     INTERFACE_MANAGER.init_by(interface);
+    axdevfs::init();
     info!("init block device");
     init_block_dev(blk_devs);
-    info!("init xv6fs");
-    let xfs=Arc::new(VXV6FS::new());
-    unsafe{xv6fs::init(Arc::new(DiskOps), 0);}
-   
+    register_builtin_fs_types();
 
     let lock_list=FsLockList::new();
     FS_LOCK_LIST.init_by(lock_list);
+    axtask::on_exit(fd::task_exited);
+
+    let root_options = root_mount_options().unwrap_or_else(|e| {
+        error!("bad root mount options: {}", e);
+        MountOptions { read_only: true, ..Default::default() }
+    });
+    let root_fs_type = option_env!("ROOTFSTYPE").filter(|name| !name.is_empty());
+    let root_dev = root_device(root_fs_type);
 
-    // init filesystem list
     let mut fs_list = FileSystemList::new();
-    fs_list.add(xfs.clone());
+    let mounted_list = MountedFsList::new();
+    match fstype::open_fs(root_dev, root_fs_type, &root_options) {
+        Ok(fs) => {
+            info!("mount {} at /", fs.name());
+            fs_list.add(fs.clone());
+            mounted_list
+                .mount("/", fs, root_options, None)
+                .expect("failed to mount the root file system");
+        }
+        Err(e) => {
+            error!("failed to open the root file system: {:?}, using ramfs", e);
+            let fs: Arc<dyn VfsFileSystem> = Arc::new(RamFileSystem::new());
+            fs_list.add(fs.clone());
+            mounted_list
+                .mount("/", fs, MountOptions::default(), None)
+                .expect("failed to mount the root file system");
+        }
+    }
     FILESTSTEMS.init_by(fs_list);
+    MOUNTEDFS.init_by(mounted_list);
+    mount_procfs();
+}
 
-    // init mounted filesystem list
-    let mut mounted_list = MountedFsList::new();
-    mounted_list.mount("/", xfs.clone());
-    MOUNTEDFS.init_by(mounted_list)
+/// Mount procfs at `/proc`, creating the directory if the root lacks it.
+fn mount_procfs() {
+    if open("/proc").is_err() && mkdir("/proc").is_err() {
+        warn!("no /proc directory, procfs not mounted");
+        return;
+    }
+    if let Err(e) = mount_fs("none", "/proc", Some("procfs"), MountOptions::default()) {
+        warn!("failed to mount procfs: {:?}", e);
+    }
+}
 
+fn register_builtin_fs_types() {
+    let builtin = [
+        FsType { name: "xv6fs", probe: xv6fs_shim::probe, open: open_xv6fs },
+        FsType { name: "fat32", probe: fatfs_shim::probe, open: open_fat32 },
+        // Never detected on a disk, only mounted by name.
+        FsType { name: "ramfs", probe: |_| false, open: open_ramfs },
+        FsType { name: "procfs", probe: |_| false, open: |_, _| Ok(Arc::new(ProcFileSystem)) },
+    ];
+    for fs_type in builtin {
+        register_fs_type(fs_type).expect("duplicate built-in file system type");
+    }
 }
 
-fn init_block_dev(blk_devs: BlockDevices){
-    BLOCK_DEV.init_by(blk_devs);
+fn open_xv6fs(
+    dev: Option<Arc<dyn BlockDevice>>,
+    options: &MountOptions,
+) -> AxResult<Arc<dyn VfsFileSystem>> {
+    let Some(dev) = dev else {
+        return ax_err!(NotFound, "xv6fs needs a block device");
+    };
+    match VXV6FS::open(Arc::new(Xv6Device(dev)), xv6fs_options(options)) {
+        Ok(fs) => Ok(Arc::new(fs)),
+        Err(e) => {
+            error!("failed to mount xv6fs: {:?}", e);
+            Err(e)
+        }
+    }
 }
 
-pub struct DiskOps;
+fn open_fat32(
+    dev: Option<Arc<dyn BlockDevice>>,
+    options: &MountOptions,
+) -> AxResult<Arc<dyn VfsFileSystem>> {
+    let Some(dev) = dev else {
+        return ax_err!(NotFound, "fat32 needs a block device");
+    };
+    let fs = Fat32FileSystem::new(dev, axhal::time::wall_time, options)?;
+    Ok(Arc::new(fs))
+}
 
-impl DiskOperation for DiskOps {
-    fn read_block(index: usize, buf: &mut [u8]) {
-        block_devices()
-            .0
-            .read_block(index, buf)
-            .expect("can't read block");
-    }
+/// `size=` limits how much file data it may hold.
+fn open_ramfs(
+    _dev: Option<Arc<dyn BlockDevice>>,
+    options: &MountOptions,
+) -> AxResult<Arc<dyn VfsFileSystem>> {
+    let fs = match options.size {
+        Some(limit) => RamFileSystem::with_limit(limit),
+        None => RamFileSystem::new(),
+    };
+    Ok(Arc::new(fs))
+}
 
-    fn write_block(index: usize, data: &[u8]) {
-        block_devices()
-            .0
-            .write_block(index, data)
-            .expect("can't write block");
+/// Mount options of the root file system, taken from `ROOTFLAGS` at build time.
+fn root_mount_options() -> Result<MountOptions, &'static str> {
+    option_env!("ROOTFLAGS").unwrap_or("").parse()
+}
+
+/// xv6fs has no access times, so `noatime` needs no translation.
+fn xv6fs_options(options: &MountOptions) -> xv6fs::MountOptions {
+    let default = xv6fs::MountOptions::default();
+    xv6fs::MountOptions {
+        read_only: options.read_only,
+        sync: options.sync,
+        commit_interval: options.commit_interval.unwrap_or(default.commit_interval),
+        data_mode: match options.data_mode {
+            Some(DataMode::Journal) => xv6fs::DataMode::Journal,
+            Some(DataMode::Ordered) => xv6fs::DataMode::Ordered,
+            None => default.data_mode,
+        },
+        cache_blocks: options.cache_blocks.unwrap_or(default.cache_blocks),
     }
 }
 
-impl BlockDevice for DiskOps {
-    fn read_block(&self,index: usize, buf: &mut [u8]) {
-        //info!("read blockdevice");
-        BLOCK_DEV
-            .0
-            .read_block(index, buf)
-            .expect("can't read block");
+fn init_block_dev(blk_devs: BlockDevices) {
+    for dev in blk_devs.0 {
+        if let Err(e) = register_block_device(&dev.name, Arc::new(DriverDevice(dev.dev))) {
+            warn!("block device {} not registered: {:?}", dev.name, e);
+        }
     }
+}
 
-    fn write_block(&self,index: usize, data: &[u8]) {
-        BLOCK_DEV
-            .0
-            .write_block(index, data)
-            .expect("can't write block");
+/// The device to mount at `/`: the one named by `ROOTDEV` at build time,
+/// otherwise the first holding a file system of type `fs_type`, or of any
+/// known type if that is `None`.
+fn root_device(fs_type: Option<&str>) -> Option<Arc<dyn BlockDevice>> {
+    if let Some(name) = option_env!("ROOTDEV").filter(|name| !name.is_empty()) {
+        let dev = block_device(name);
+        if dev.is_none() {
+            error!("root device {} not found", name);
+        }
+        return dev;
     }
+    block_devices().into_iter().find_map(|name| {
+        let dev = block_device(&name)?;
+        let found = fstype::detect_fs_type(&*dev)?;
+        if fs_type.is_some_and(|t| t != found.name) {
+            return None;
+        }
+        info!("root device {}", name);
+        Some(dev)
+    })
 }
 
 pub fn filesystems() -> &'static FileSystemList {
@@ -145,8 +243,15 @@ impl AxFsInterface {
 }
 
 impl FsInterface for AxFsInterface{
+    /// The task's working directory if it is on xv6fs, otherwise the root
+    /// of xv6fs, so that relative xv6 paths start from there.
     fn get_cur_dir_inode(&self)->Option<xv6fs::inode::Inode> {
-        None
+        let icache=&xv6fs::inode::ICACHE;
+        let cwd=MOUNTEDFS.try_get().and_then(|mounted| mounted.lookup(&axtask::current().cwd()));
+        match cwd {
+            Some((mfs,rel)) if mfs.fs().name()==VXV6FS::NAME=>icache.namei(rel.as_bytes()).ok(),
+            _=>icache.namei(b"/").ok(),
+        }
     }
     fn new_sleep_lock(&self)->usize {
         self.fs_lock_list.write().new_lock()
@@ -171,4 +276,15 @@ pub fn test_sleep_lock(){
 
 pub fn test_link_unlink(){
     xv6fs::file::test_link_unlink();
-}
\ No newline at end of file
+}
+/// Start the scheduler for a unit test, once per test binary, and hold off
+/// other tests that use it until the returned guard is dropped: all test
+/// threads share one run queue and one current task.
+#[cfg(test)]
+pub(crate) fn test_scheduler() -> std::sync::MutexGuard<'static, ()> {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    static SERIAL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    INIT.call_once(axtask::init_scheduler);
+    guard
+}
@@ -0,0 +1,396 @@
+//! Advisory file locks.
+//!
+//! Locks belong to an inode, found by mount path and inode number, and come
+//! in two independent flavours, as on Linux:
+//!
+//! - Whole-file locks, as taken by `flock`. They belong to one open file,
+//!   a [`FileLocks`], and go away when it is dropped.
+//! - Byte-range locks, as taken by `fcntl(F_SETLK)`. They belong to a task;
+//!   a task never conflicts with itself, and a new lock replaces whatever
+//!   the task held on the same bytes and merges with its locks of the same
+//!   kind next to it.
+//!
+//! Nothing stops a task that ignores the locks from reading or writing.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use axerror::{ax_err, AxResult};
+use axtask::WaitQueue;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::mutex::Mutex;
+use spinlock::SpinNoIrq;
+
+/// Lock state of the inodes with locks or open files, keyed by mount path
+/// and inode number. Entries are dropped with the last open file.
+static INODES: Mutex<BTreeMap<(String, usize), Weak<InodeLocks>>> = Mutex::new(BTreeMap::new());
+
+/// Source of [`FileLocks`] ids.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockKind {
+    /// Many may hold it at once; also called a read lock.
+    Shared,
+    /// Only one may hold it; also called a write lock.
+    Exclusive,
+}
+
+impl LockKind {
+    fn conflicts(self, other: LockKind) -> bool {
+        self == LockKind::Exclusive || other == LockKind::Exclusive
+    }
+}
+
+/// A byte-range lock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeLock {
+    pub kind: LockKind,
+    pub start: u64,
+    /// One past the last byte locked, `u64::MAX` for up to any end of file.
+    pub end: u64,
+    /// Id of the task that holds it.
+    pub owner: u64,
+}
+
+impl RangeLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+}
+
+#[derive(Default)]
+struct LockState {
+    /// Whole-file locks by `FileLocks` id.
+    whole: Vec<(u64, LockKind)>,
+    ranges: Vec<RangeLock>,
+}
+
+impl LockState {
+    fn whole_conflict(&self, id: u64, kind: LockKind) -> bool {
+        self.whole
+            .iter()
+            .any(|&(holder, held)| holder != id && kind.conflicts(held))
+    }
+
+    fn range_conflict(&self, owner: u64, kind: LockKind, start: u64, end: u64) -> Option<RangeLock> {
+        self.ranges
+            .iter()
+            .find(|lock| lock.owner != owner && lock.overlaps(start, end) && kind.conflicts(lock.kind))
+            .copied()
+    }
+
+    /// Drop what `owner` holds of `start..end`, cutting its locks that
+    /// stick out of it.
+    fn clear_range(&mut self, owner: u64, start: u64, end: u64) {
+        let mut kept = Vec::with_capacity(self.ranges.len());
+        for lock in self.ranges.drain(..) {
+            if lock.owner != owner || !lock.overlaps(start, end) {
+                kept.push(lock);
+                continue;
+            }
+            if lock.start < start {
+                kept.push(RangeLock { end: start, ..lock });
+            }
+            if end < lock.end {
+                kept.push(RangeLock { start: end, ..lock });
+            }
+        }
+        self.ranges = kept;
+    }
+
+    /// Add `lock`, merged with the locks of its owner and kind that end
+    /// where it starts or start where it ends.
+    fn insert_range(&mut self, mut lock: RangeLock) {
+        self.ranges.retain(|other| {
+            let adjacent = other.end == lock.start || other.start == lock.end;
+            if other.owner != lock.owner || other.kind != lock.kind || !adjacent {
+                return true;
+            }
+            lock.start = lock.start.min(other.start);
+            lock.end = lock.end.max(other.end);
+            false
+        });
+        self.ranges.push(lock);
+    }
+}
+
+/// The locks of one inode.
+struct InodeLocks {
+    // `SpinNoIrq` so that it can be checked in `wait_until` conditions,
+    // which run with the run queue locked.
+    state: SpinNoIrq<LockState>,
+    wq: WaitQueue,
+}
+
+/// An open file's handle on the locks of its inode. Its whole-file lock is
+/// released when it is dropped.
+pub struct FileLocks {
+    inode: Arc<InodeLocks>,
+    id: u64,
+}
+
+impl FileLocks {
+    /// The handle of a newly opened file with inode `ino` on the mount at
+    /// `mount_path`.
+    pub(crate) fn new(mount_path: &str, ino: usize) -> Self {
+        let mut inodes = INODES.lock();
+        inodes.retain(|_, inode| inode.strong_count() > 0);
+        let key = (mount_path.to_string(), ino);
+        let inode = match inodes.get(&key).and_then(Weak::upgrade) {
+            Some(inode) => inode,
+            None => {
+                let inode = Arc::new(InodeLocks {
+                    state: SpinNoIrq::new(LockState::default()),
+                    wq: WaitQueue::new(),
+                });
+                inodes.insert(key, Arc::downgrade(&inode));
+                inode
+            }
+        };
+        Self {
+            inode,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Take or convert the whole-file lock. If another open file holds a
+    /// conflicting one, wait for it to go if `wait` is set, or fail with
+    /// `Again`.
+    pub fn lock(&self, kind: LockKind, wait: bool) -> AxResult {
+        loop {
+            {
+                let mut state = self.inode.state.lock();
+                if !state.whole_conflict(self.id, kind) {
+                    state.whole.retain(|&(holder, _)| holder != self.id);
+                    state.whole.push((self.id, kind));
+                    break;
+                }
+            }
+            if !wait {
+                return ax_err!(Again, "file is locked");
+            }
+            self.inode
+                .wq
+                .wait_until(|| !self.inode.state.lock().whole_conflict(self.id, kind));
+        }
+        // A conversion to shared may let others in.
+        self.inode.wq.notify_all(true);
+        Ok(())
+    }
+
+    /// Release the whole-file lock, if held.
+    pub fn unlock(&self) {
+        self.inode
+            .state
+            .lock()
+            .whole
+            .retain(|&(holder, _)| holder != self.id);
+        self.inode.wq.notify_all(true);
+    }
+
+    /// Lock `start..end` for task `owner`, replacing what it held there and
+    /// merging it with its locks of the same kind next to it.
+    /// If another task holds a conflicting lock, wait for it to go if
+    /// `wait` is set, or fail with `Again`.
+    pub fn lock_range(&self, owner: u64, kind: LockKind, start: u64, end: u64, wait: bool) -> AxResult {
+        if start >= end {
+            return ax_err!(InvalidParam, "empty lock range");
+        }
+        loop {
+            {
+                let mut state = self.inode.state.lock();
+                if state.range_conflict(owner, kind, start, end).is_none() {
+                    state.clear_range(owner, start, end);
+                    state.insert_range(RangeLock {
+                        kind,
+                        start,
+                        end,
+                        owner,
+                    });
+                    break;
+                }
+            }
+            if !wait {
+                return ax_err!(Again, "range is locked");
+            }
+            self.inode.wq.wait_until(|| {
+                let state = self.inode.state.lock();
+                state.range_conflict(owner, kind, start, end).is_none()
+            });
+        }
+        self.inode.wq.notify_all(true);
+        Ok(())
+    }
+
+    /// Release what task `owner` holds of `start..end`.
+    pub fn unlock_range(&self, owner: u64, start: u64, end: u64) {
+        self.inode.state.lock().clear_range(owner, start, end);
+        self.inode.wq.notify_all(true);
+    }
+
+    /// Release every byte-range lock task `owner` holds on the inode.
+    pub fn unlock_ranges(&self, owner: u64) {
+        self.unlock_range(owner, 0, u64::MAX);
+    }
+
+    /// A lock of another task that would keep `owner` from locking
+    /// `start..end` as `kind`.
+    pub fn test_range(&self, owner: u64, kind: LockKind, start: u64, end: u64) -> Option<RangeLock> {
+        self.inode
+            .state
+            .lock()
+            .range_conflict(owner, kind, start, end)
+    }
+}
+
+impl Drop for FileLocks {
+    fn drop(&mut self) {
+        self.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axerror::AxError;
+    use core::sync::atomic::AtomicBool;
+    use LockKind::{Exclusive, Shared};
+
+    /// The byte ranges `owner` holds, in order.
+    fn ranges(locks: &FileLocks, owner: u64) -> Vec<(LockKind, u64, u64)> {
+        let state = locks.inode.state.lock();
+        let mut ranges: Vec<_> = state
+            .ranges
+            .iter()
+            .filter(|lock| lock.owner == owner)
+            .map(|lock| (lock.kind, lock.start, lock.end))
+            .collect();
+        ranges.sort_by_key(|&(_, start, _)| start);
+        ranges
+    }
+
+    #[test]
+    fn test_split() {
+        let _scheduler = crate::test_scheduler();
+        let file = FileLocks::new("/split", 1);
+        file.lock_range(1, Exclusive, 0, 100, false).unwrap();
+        file.unlock_range(1, 40, 60);
+        assert_eq!(ranges(&file, 1), [(Exclusive, 0, 40), (Exclusive, 60, 100)]);
+        assert_eq!(file.test_range(2, Shared, 40, 60), None);
+        let lock = file.test_range(2, Shared, 30, 50).unwrap();
+        assert_eq!((lock.owner, lock.start, lock.end), (1, 0, 40));
+
+        // A lock of another kind replaces the bytes it covers.
+        file.lock_range(1, Shared, 20, 80, false).unwrap();
+        assert_eq!(
+            ranges(&file, 1),
+            [(Exclusive, 0, 20), (Shared, 20, 80), (Exclusive, 80, 100)]
+        );
+        file.unlock_ranges(1);
+        assert_eq!(ranges(&file, 1), []);
+    }
+
+    #[test]
+    fn test_merge() {
+        let _scheduler = crate::test_scheduler();
+        let file = FileLocks::new("/merge", 1);
+        file.lock_range(1, Shared, 0, 10, false).unwrap();
+        file.lock_range(1, Shared, 20, 30, false).unwrap();
+        file.lock_range(1, Exclusive, 30, 40, false).unwrap();
+        file.lock_range(1, Shared, 10, 20, false).unwrap();
+        assert_eq!(ranges(&file, 1), [(Shared, 0, 30), (Exclusive, 30, 40)]);
+        // Overlapping locks of the same kind end up as one.
+        file.lock_range(1, Exclusive, 25, 50, false).unwrap();
+        assert_eq!(ranges(&file, 1), [(Shared, 0, 25), (Exclusive, 25, 50)]);
+        // Another owner's locks are left alone.
+        file.lock_range(2, Shared, 50, 60, false).unwrap();
+        file.lock_range(1, Exclusive, 60, 70, false).unwrap();
+        assert_eq!(ranges(&file, 1), [(Shared, 0, 25), (Exclusive, 25, 50), (Exclusive, 60, 70)]);
+        assert_eq!(ranges(&file, 2), [(Shared, 50, 60)]);
+    }
+
+    #[test]
+    fn test_range_conflicts() {
+        let _scheduler = crate::test_scheduler();
+        let file = FileLocks::new("/ranges", 1);
+        file.lock_range(1, Shared, 0, 10, false).unwrap();
+        file.lock_range(2, Shared, 5, 15, false).unwrap();
+        let again = Err(AxError::Again);
+        assert_eq!(file.lock_range(3, Exclusive, 9, 20, false), again);
+        assert_eq!(file.lock_range(3, Exclusive, 15, 20, false), Ok(()));
+        assert_eq!(file.lock_range(1, Shared, 19, 21, false), again);
+        // A task does not conflict with itself, but does with the others.
+        assert_eq!(file.lock_range(1, Exclusive, 0, 5, false), Ok(()));
+        assert_eq!(file.lock_range(1, Exclusive, 0, 6, false), again);
+        assert_eq!(file.lock_range(1, Shared, 5, 5, false), Err(AxError::InvalidParam));
+        // Range and whole-file locks do not see each other.
+        assert_eq!(file.lock(Exclusive, false), Ok(()));
+    }
+
+    #[test]
+    fn test_whole_conflicts() {
+        let _scheduler = crate::test_scheduler();
+        let a = FileLocks::new("/whole", 1);
+        let b = FileLocks::new("/whole", 1);
+        let other = FileLocks::new("/whole", 2);
+        let again = Err(AxError::Again);
+        a.lock(Shared, false).unwrap();
+        b.lock(Shared, false).unwrap();
+        assert_eq!(b.lock(Exclusive, false), again);
+        assert_eq!(other.lock(Exclusive, false), Ok(()));
+        a.unlock();
+        // A conversion is not blocked by the file's own lock.
+        assert_eq!(b.lock(Exclusive, false), Ok(()));
+        assert_eq!(a.lock(Shared, false), again);
+    }
+
+    #[test]
+    fn test_release_on_close() {
+        let _scheduler = crate::test_scheduler();
+        let a = FileLocks::new("/close", 1);
+        let b = FileLocks::new("/close", 1);
+        a.lock(Exclusive, false).unwrap();
+        a.lock_range(1, Exclusive, 0, 10, false).unwrap();
+        assert_eq!(b.lock(Shared, false), Err(AxError::Again));
+        assert!(b.test_range(2, Shared, 0, 1).is_some());
+        // Closing a descriptor drops its task's range locks, whichever open
+        // file of the inode they were taken through.
+        b.unlock_ranges(1);
+        assert!(a.test_range(2, Shared, 0, 1).is_none());
+        drop(a);
+        assert_eq!(b.lock(Shared, false), Ok(()));
+        drop(b);
+        // Nothing is left behind once every open file is gone.
+        let c = FileLocks::new("/close", 1);
+        assert_eq!(c.lock(Exclusive, false), Ok(()));
+        assert!(c.test_range(2, Exclusive, 0, u64::MAX).is_none());
+    }
+
+    #[test]
+    fn test_wait() {
+        let _scheduler = crate::test_scheduler();
+        static DONE: AtomicBool = AtomicBool::new(false);
+        let a = FileLocks::new("/wait", 1);
+        let b = FileLocks::new("/wait", 1);
+        a.lock(Exclusive, false).unwrap();
+        a.lock_range(1, Shared, 0, 10, false).unwrap();
+        axtask::spawn(move || {
+            b.lock(Shared, true).unwrap();
+            b.lock_range(2, Exclusive, 5, 6, true).unwrap();
+            DONE.store(true, Ordering::Release);
+        });
+        axtask::yield_now();
+        assert!(!DONE.load(Ordering::Acquire));
+        a.unlock();
+        axtask::yield_now();
+        assert!(!DONE.load(Ordering::Acquire));
+        a.unlock_ranges(1);
+        while !DONE.load(Ordering::Acquire) {
+            axtask::yield_now();
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Loop devices: block devices backed by a file.
+//!
+//! [`attach_loop`] turns an image file into the block device `loop0`,
+//! `loop1` and so on, with `loop0p1`, … for the partitions on it, which
+//! can then be mounted with [`mount_fs`](crate::mount_fs) like a disk.
+//! The image is read and written through the page cache, so the loop
+//! device and the file agree, and its mount stays busy while attached.
+//!
+//! The size of the device is fixed when it is attached; a trailing part
+//! block of the file is left out.
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use axerror::{ax_err, AxError, AxResult};
+use driver_block::partition::read_partitions;
+use driver_block::{BlockDriverOps, Partition, BLOCK_SIZE};
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+use vfscore::VfsFile;
+
+use crate::blockdev::{register_block_device, unregister_block_device, DriverDevice};
+use crate::mount::MountedFileSystem;
+
+/// A block device whose blocks are those of a file.
+pub struct LoopDevice {
+    /// A sleeping lock, held across the file I/O. `None` once detached.
+    file: axsync::Mutex<Option<Box<dyn VfsFile>>>,
+    num_blocks: u64,
+}
+
+// SAFETY: file systems serialize access to their files with their own
+// locks, and the file is only used under `file`.
+unsafe impl Send for LoopDevice {}
+unsafe impl Sync for LoopDevice {}
+
+impl LoopDevice {
+    /// A loop device on the regular file `file`, read and written with its
+    /// `read_at` and `write_at`.
+    pub fn new(file: Box<dyn VfsFile>) -> AxResult<Self> {
+        let attr = file.getattr()?;
+        if attr.is_dir() {
+            return ax_err!(IsADirectory, "loop: backing file is a directory");
+        }
+        Ok(Self {
+            file: axsync::Mutex::new(Some(file)),
+            num_blocks: (attr.size / BLOCK_SIZE) as u64,
+        })
+    }
+
+    /// Close the backing file. Later I/O fails.
+    fn close(&self) {
+        self.file.lock().take();
+    }
+
+    /// Byte offset of block `block_id`, if `len` bytes from it are on the
+    /// device and `len` is a multiple of the block size.
+    fn offset(&self, block_id: usize, len: usize) -> DevResult<usize> {
+        let end = (block_id as u64).checked_add((len / BLOCK_SIZE) as u64);
+        if len % BLOCK_SIZE != 0 || !end.is_some_and(|end| end <= self.num_blocks) {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(block_id * BLOCK_SIZE)
+    }
+}
+
+impl BaseDriverOps for LoopDevice {
+    fn device_name(&self) -> &str {
+        "loop"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl BlockDriverOps for LoopDevice {
+    fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> DevResult {
+        self.read_blocks(block_id, buf)
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> DevResult {
+        self.write_blocks(block_id, buf)
+    }
+
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) -> DevResult {
+        let offset = self.offset(start_block, buf.len())?;
+        let file = self.file.lock();
+        let file = file.as_ref().ok_or(DevError::BadState)?;
+        let mut done = 0;
+        while done < buf.len() {
+            match file.read_at(offset + done, &mut buf[done..]) {
+                Ok(0) => break,
+                Ok(n) => done += n,
+                Err(_) => return Err(DevError::Io),
+            }
+        }
+        // The file was cut short after it was attached.
+        buf[done..].fill(0);
+        Ok(())
+    }
+
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) -> DevResult {
+        let offset = self.offset(start_block, buf.len())?;
+        let file = self.file.lock();
+        let file = file.as_ref().ok_or(DevError::BadState)?;
+        let mut done = 0;
+        while done < buf.len() {
+            match file.write_at(offset + done, &buf[done..]) {
+                Ok(0) | Err(_) => return Err(DevError::Io),
+                Ok(n) => done += n,
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> DevResult {
+        let file = self.file.lock();
+        let file = file.as_ref().ok_or(DevError::BadState)?;
+        file.fsync().map_err(|_| DevError::Io)
+    }
+}
+
+/// Attached loop devices, by number. A sleeping lock, held while the
+/// partition table of a new one is read.
+static LOOPS: axsync::Mutex<Vec<Option<LoopEntry>>> = axsync::Mutex::new(Vec::new());
+
+struct LoopEntry {
+    dev: Arc<LoopDevice>,
+    /// Names registered for it: the device and its partitions.
+    names: Vec<String>,
+}
+
+/// Register `dev` as `name`, and note the name in `names`.
+fn register(names: &mut Vec<String>, name: String, dev: Arc<dyn BlockDriverOps>) -> AxResult {
+    register_block_device(&name, Arc::new(DriverDevice(dev)))?;
+    names.push(name);
+    Ok(())
+}
+
+/// Attach the image file at `path` to the lowest free loop device, and
+/// return its name, such as `loop0`.
+pub fn attach_loop(path: &str) -> AxResult<String> {
+    let dev = Arc::new(LoopDevice::new(crate::open(path)?)?);
+    let mut loops = LOOPS.lock();
+    let number = loops.iter().position(Option::is_none).unwrap_or(loops.len());
+    let name = format!("loop{}", number);
+    let partitions = read_partitions(&*dev).unwrap_or_else(|e| {
+        warn!("failed to read the partition table of {}: {:?}", path, e);
+        Vec::new()
+    });
+
+    let mut names = Vec::new();
+    let result = register(&mut names, name.clone(), dev.clone()).and_then(|_| {
+        partitions.iter().try_for_each(|info| {
+            let part = Arc::new(Partition::new(dev.clone(), info));
+            register(&mut names, format!("{}p{}", name, info.number), part)
+        })
+    });
+    if let Err(e) = result {
+        for name in names {
+            unregister_block_device(&name);
+        }
+        return Err(e);
+    }
+
+    info!("{} attached to {}, {} blocks", name, path, dev.num_blocks());
+    let entry = Some(LoopEntry { dev, names });
+    if number == loops.len() {
+        loops.push(entry);
+    } else {
+        loops[number] = entry;
+    }
+    Ok(name)
+}
+
+/// Detach the loop device `name`, which may start with `/dev/`, closing its
+/// image file. Fails with `ResourceBusy` while it or a partition on it is
+/// mounted.
+pub fn detach_loop(name: &str) -> AxResult {
+    let name = name.strip_prefix("/dev/").unwrap_or(name);
+    let number = name
+        .strip_prefix("loop")
+        .and_then(|number| number.parse::<usize>().ok())
+        .ok_or(AxError::NotFound)?;
+    let mut loops = LOOPS.lock();
+    let names = match loops.get(number) {
+        Some(Some(entry)) => &entry.names,
+        _ => return ax_err!(NotFound),
+    };
+    let mounted =
+        |mfs: &MountedFileSystem| mfs.source().is_some_and(|s| names.iter().any(|n| n == s));
+    if crate::mounts().iter().any(mounted) {
+        return ax_err!(ResourceBusy, "loop: device is mounted");
+    }
+    let entry = loops[number].take().unwrap();
+    drop(loops);
+    for name in entry.names.iter() {
+        unregister_block_device(name);
+    }
+    entry.dev.close();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RamFileSystem;
+    use vfscore::{FileType, VfsFileSystem};
+
+    /// A loop device on `file` of ramfs `root`, which is made `len` bytes
+    /// long, each the low byte of its offset.
+    fn loop_on(root: &dyn VfsFile, len: usize) -> LoopDevice {
+        let file = root.create("img", FileType::File, 0o644).unwrap();
+        let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        file.write_at(0, &data).unwrap();
+        LoopDevice::new(file).unwrap()
+    }
+
+    fn is_invalid(result: DevResult) -> bool {
+        matches!(result, Err(DevError::InvalidParam))
+    }
+
+    #[test]
+    fn test_offsets() {
+        let _scheduler = crate::test_scheduler();
+        let root = Arc::new(RamFileSystem::new()).root();
+        let dev = loop_on(&*root, 3 * BLOCK_SIZE + 100);
+        // The trailing part block is left out.
+        assert_eq!(dev.num_blocks(), 3);
+        let mut buf = [0u8; 2 * BLOCK_SIZE];
+        dev.read_blocks(1, &mut buf).unwrap();
+        assert!((0..buf.len()).all(|i| buf[i] == (BLOCK_SIZE + i) as u8));
+
+        dev.write_block(1, &[0xab; BLOCK_SIZE]).unwrap();
+        let file = root.lookup("img").unwrap();
+        let mut bytes = [0u8; 3];
+        file.read_at(BLOCK_SIZE - 1, &mut bytes).unwrap();
+        assert_eq!(bytes, [0xff, 0xab, 0xab]);
+        file.read_at(2 * BLOCK_SIZE - 1, &mut bytes).unwrap();
+        assert_eq!(bytes, [0xab, 0, 1]);
+
+        // A file cut short after it was attached reads as zeros.
+        file.truncate(2 * BLOCK_SIZE + 3).unwrap();
+        dev.read_block(2, &mut buf[..BLOCK_SIZE]).unwrap();
+        assert_eq!(buf[..3], [0, 1, 2]);
+        assert!(buf[3..BLOCK_SIZE].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_alignment() {
+        let _scheduler = crate::test_scheduler();
+        let root = Arc::new(RamFileSystem::new()).root();
+        let dev = loop_on(&*root, 4 * BLOCK_SIZE);
+        let mut buf = [0u8; 2 * BLOCK_SIZE];
+        // Only whole blocks, all on the device.
+        assert!(is_invalid(dev.read_blocks(0, &mut buf[..100])));
+        assert!(is_invalid(dev.read_blocks(0, &mut buf[..BLOCK_SIZE + 1])));
+        assert!(is_invalid(dev.write_blocks(1, &buf[..BLOCK_SIZE - 1])));
+        assert!(is_invalid(dev.read_blocks(3, &mut buf)));
+        assert!(is_invalid(dev.write_blocks(3, &buf)));
+        assert!(is_invalid(dev.read_block(4, &mut buf[..BLOCK_SIZE])));
+        assert!(is_invalid(dev.read_blocks(usize::MAX, &mut buf)));
+        dev.read_blocks(2, &mut buf).unwrap();
+        assert_eq!(buf[0], (2 * BLOCK_SIZE) as u8);
+
+        // Detached, it fails.
+        dev.close();
+        let read = dev.read_block(0, &mut buf[..BLOCK_SIZE]);
+        assert!(matches!(read, Err(DevError::BadState)));
+    }
+}
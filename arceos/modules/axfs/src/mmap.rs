@@ -0,0 +1,181 @@
+//! Memory mapping of files.
+//!
+//! [`mmap`] maps pages of the [`cache`] into a window of kernel address
+//! space set aside for it, so a mapping sees the same data as `read` and
+//! `write`. What is written through a shared writable mapping reaches the
+//! file on [`msync`] and [`munmap`]. Only files the cache holds can be
+//! mapped, and a mapping keeps its file open.
+//!
+//! Pages wholly past the end of the file are left unmapped, so touching
+//! them faults, where Linux would raise `SIGBUS`. Private mappings must be
+//! read-only, as there is no copy-on-write, and mappings are only ever
+//! unmapped whole.
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use axconfig::{MMAP_BASE, MMAP_SIZE};
+use axerror::{ax_err, AxError, AxResult};
+use axhal::arch::flush_tlb;
+use axhal::mem::{virt_to_phys, VirtAddr};
+use axhal::paging::{kernel_page_table, MappingFlags};
+use spin::mutex::Mutex;
+
+use crate::cache::{self, Key, Page, PAGE_SIZE};
+use crate::fd::OpenFile;
+
+/// Pages may be read.
+pub const PROT_READ: u32 = 1;
+/// Pages may be written.
+pub const PROT_WRITE: u32 = 2;
+/// Pages may be executed.
+pub const PROT_EXEC: u32 = 4;
+
+/// Writes reach the file and other mappings of it.
+pub const MAP_SHARED: u32 = 0x01;
+/// Writes stay in the mapping. Only read-only ones are supported.
+pub const MAP_PRIVATE: u32 = 0x02;
+const MAP_TYPE: u32 = 0x0f;
+
+struct Mapping {
+    /// Keeps the file open, to write the pages back through.
+    _file: Arc<OpenFile>,
+    inode: Key,
+    /// Page of the file the mapping starts at.
+    first: usize,
+    /// One per page of the mapping, `None` past the end of the file.
+    pages: Vec<Option<Arc<Page>>>,
+    shared_writable: bool,
+}
+
+impl Mapping {
+    fn len(&self) -> usize {
+        self.pages.len() * PAGE_SIZE
+    }
+}
+
+/// Mappings by start address.
+static MAPPINGS: Mutex<BTreeMap<usize, Mapping>> = Mutex::new(BTreeMap::new());
+
+/// The lowest free `len` bytes of the window.
+fn find_free(mappings: &BTreeMap<usize, Mapping>, len: usize) -> Option<usize> {
+    let mut start = MMAP_BASE;
+    for (&addr, mapping) in mappings {
+        if addr - start >= len {
+            break;
+        }
+        start = addr + mapping.len();
+    }
+    (MMAP_BASE + MMAP_SIZE - start >= len).then_some(start)
+}
+
+fn unmap_pages(start: usize, pages: &[Option<Arc<Page>>]) {
+    let mut page_table = kernel_page_table().lock();
+    for (i, _) in pages.iter().enumerate().filter(|(_, page)| page.is_some()) {
+        let vaddr = VirtAddr::from(start + i * PAGE_SIZE);
+        if page_table.unmap(vaddr).is_ok() {
+            flush_tlb(Some(vaddr));
+        }
+    }
+}
+
+/// Map `len` bytes of the file behind `fd` from `offset`, which must be a
+/// multiple of the page size. `prot` is made of `PROT_*` bits and `flags`
+/// is `MAP_SHARED` or `MAP_PRIVATE`. Returns the address of the mapping.
+pub fn mmap(fd: usize, len: usize, prot: u32, flags: u32, offset: usize) -> AxResult<usize> {
+    if len == 0 || offset % PAGE_SIZE != 0 {
+        return ax_err!(InvalidParam, "mmap: bad length or offset");
+    }
+    let writable = prot & PROT_WRITE != 0;
+    let shared = match flags & MAP_TYPE {
+        MAP_SHARED => true,
+        MAP_PRIVATE if writable => return ax_err!(Unsupported, "mmap: private writable mapping"),
+        MAP_PRIVATE => false,
+        _ => return ax_err!(InvalidParam, "mmap: bad mapping type"),
+    };
+    if flags & !MAP_TYPE != 0 {
+        return ax_err!(Unsupported, "mmap: unsupported flags");
+    }
+    let file = crate::fd::file(fd)?;
+    if !file.readable || (shared && writable && !file.writable) {
+        return ax_err!(PermissionDenied, "mmap: file not open for it");
+    }
+    let count = len.div_ceil(PAGE_SIZE);
+    let first = offset / PAGE_SIZE;
+    let pages = cache::map_pages(&file.inode, first, count)?;
+
+    let mut map_flags = MappingFlags::empty();
+    if prot & PROT_READ != 0 {
+        map_flags |= MappingFlags::READ;
+    }
+    if writable {
+        map_flags |= MappingFlags::WRITE;
+    }
+    if prot & PROT_EXEC != 0 {
+        map_flags |= MappingFlags::EXECUTE;
+    }
+
+    let mut mappings = MAPPINGS.lock();
+    let start = find_free(&mappings, count * PAGE_SIZE).ok_or(AxError::NoMemory)?;
+    if !map_flags.is_empty() {
+        let mut page_table = kernel_page_table().lock();
+        for (i, page) in pages.iter().enumerate() {
+            let Some(page) = page else {
+                continue;
+            };
+            let vaddr = VirtAddr::from(start + i * PAGE_SIZE);
+            let paddr = virt_to_phys(page.vaddr().into());
+            if page_table.map_region(vaddr, paddr, PAGE_SIZE, map_flags, false).is_err() {
+                drop(page_table);
+                unmap_pages(start, &pages[..i]);
+                return ax_err!(NoMemory, "mmap: failed to map page");
+            }
+        }
+    }
+    mappings.insert(
+        start,
+        Mapping {
+            inode: file.inode.clone(),
+            _file: file,
+            first,
+            pages,
+            shared_writable: shared && writable,
+        },
+    );
+    Ok(start)
+}
+
+/// Unmap the mapping at `addr`, which must be all `len` bytes of it,
+/// writing back what was written through it.
+pub fn munmap(addr: usize, len: usize) -> AxResult {
+    let mapping = {
+        let mut mappings = MAPPINGS.lock();
+        match mappings.get(&addr) {
+            Some(mapping) if mapping.len() == len.div_ceil(PAGE_SIZE) * PAGE_SIZE => {}
+            _ => return ax_err!(InvalidParam, "munmap: not a whole mapping"),
+        }
+        mappings.remove(&addr).unwrap()
+    };
+    unmap_pages(addr, &mapping.pages);
+    if mapping.shared_writable {
+        cache::sync_pages(&mapping.inode, mapping.first, &mapping.pages)?;
+    }
+    Ok(())
+}
+
+/// Write back what was written through the mapping at `addr..addr + len`.
+pub fn msync(addr: usize, len: usize) -> AxResult {
+    let (inode, first, pages) = {
+        let mappings = MAPPINGS.lock();
+        let (&start, mapping) = mappings
+            .range(..=addr)
+            .next_back()
+            .filter(|(&start, mapping)| addr + len <= start + mapping.len())
+            .ok_or(AxError::NoMemory)?;
+        if !mapping.shared_writable {
+            return Ok(());
+        }
+        let from = (addr - start) / PAGE_SIZE;
+        let to = (addr + len - start).div_ceil(PAGE_SIZE);
+        (mapping.inode.clone(), mapping.first + from, mapping.pages[from..to].to_vec())
+    };
+    cache::sync_pages(&inode, first, &pages)
+}
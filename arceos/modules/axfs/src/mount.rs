@@ -1,23 +1,56 @@
 use alloc::{
+    boxed::Box,
     string::{String, ToString},
     sync::Arc,
     vec::Vec,
 };
+use core::sync::atomic::{AtomicUsize, Ordering};
+use axerror::{ax_err, AxResult};
 use lazy_init::LazyInit;
-use vfscore::VfsFileSystem;
+use spin::RwLock;
+use vfscore::{
+    DirEntry, FileType, Metadata, MountOptions, SetAttr, VfsFile, VfsFileSystem, VfsResult,
+};
+
+use crate::cache;
+use crate::notify::{
+    self, IN_ATTRIB, IN_CREATE, IN_DELETE, IN_DELETE_SELF, IN_ISDIR, IN_MODIFY, IN_MOVED_FROM,
+    IN_MOVED_TO, IN_MOVE_SELF,
+};
 
 pub(crate) static MOUNTEDFS: LazyInit<MountedFsList> = LazyInit::new();
 
 /// Mounted filesystem
 #[derive(Clone)]
 pub struct MountedFileSystem {
-    path: String,
+    path: Arc<str>,
     fs: Arc<dyn VfsFileSystem>,
+    options: MountOptions,
+    /// Block device it was mounted from, such as `vda1`.
+    source: Option<String>,
+    /// Files opened through this mount and not yet dropped.
+    open_files: Arc<AtomicUsize>,
+    /// Whether file data goes through the [`cache`].
+    cache_pages: bool,
 }
 
 impl MountedFileSystem {
-    pub const fn new(path: String, fs: Arc<dyn VfsFileSystem>) -> Self {
-        MountedFileSystem { path, fs }
+    pub fn new(
+        path: String,
+        fs: Arc<dyn VfsFileSystem>,
+        options: MountOptions,
+        source: Option<String>,
+    ) -> Self {
+        // A `sync` mount wants every write on the disk when it returns.
+        let cache_pages = fs.cache_pages() && !options.sync;
+        MountedFileSystem {
+            path: path.into(),
+            fs,
+            options,
+            source,
+            open_files: Arc::new(AtomicUsize::new(0)),
+            cache_pages,
+        }
     }
 
     pub fn path(&self) -> &str {
@@ -27,82 +60,449 @@ impl MountedFileSystem {
     pub fn fs(&self) -> &Arc<dyn VfsFileSystem> {
         &self.fs
     }
-}
 
-// TODO: mount or umount through the MountedFileSystem
-// pub trait Mount {
-//     fn mount(&mut self, path: &str) -> Result<(), String>;
-//     fn umount(&mut self) -> Result<(), String>;
-// }
-// impl Mount for MountedFileSystem {
-//     fn mount(&mut self, path: &str) -> Result<(), alloc::string::String> {
-//         todo!("mount a file system")
-//     }
-
-//     fn umount(&mut self) -> Result<(), String> {
-//         // MOUNTEDFS.0.drain_filter(filter)
-//         MOUNTEDFS.umount(self);
-//         Ok(())
-//     }
-// }
+    pub fn options(&self) -> &MountOptions {
+        &self.options
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// Whether a file opened through this mount is still alive.
+    pub fn is_busy(&self) -> bool {
+        self.open_files.load(Ordering::Acquire) > 0
+    }
+
+    /// Root directory of the file system.
+    pub(crate) fn root(&self) -> Box<dyn VfsFile> {
+        self.fs.clone().root()
+    }
+
+    /// Count `file` as open on this mount until it is dropped.
+    pub(crate) fn track(&self, file: Box<dyn VfsFile>) -> Box<dyn VfsFile> {
+        Box::new(MountedFile::new(
+            file,
+            self.path.clone(),
+            self.open_files.clone(),
+            self.cache_pages,
+            self.options.read_only,
+        ))
+    }
+}
 
 /// Mounted file system list
-pub struct MountedFsList(Vec<MountedFileSystem>);
+pub struct MountedFsList(RwLock<Vec<MountedFileSystem>>);
 
 impl MountedFsList {
     pub fn new() -> Self {
-        Self(vec![])
+        Self(RwLock::new(vec![]))
     }
 
-    /// umount a file system
-    pub fn umount(&mut self, mounted_fs: &MountedFileSystem) {
-        self.0
-            .retain(|fs| fs.path == mounted_fs.path && fs.fs.name() == mounted_fs.fs.name());
+    /// mount a file system at `path`, from the block device `source` if it
+    /// has one. Fails if something is already mounted there.
+    pub fn mount(
+        &self,
+        path: &str,
+        source_fs: Arc<dyn VfsFileSystem>,
+        options: MountOptions,
+        source: Option<String>,
+    ) -> AxResult {
+        let path = normalize(path);
+        let mut list = self.0.write();
+        if list.iter().any(|mfs| *mfs.path == *path) {
+            return ax_err!(AlreadyExists, "mount: mount point busy");
+        }
+        list.push(MountedFileSystem::new(path, source_fs, options, source));
+        Ok(())
     }
 
-    /// umount a file system by path
-    pub fn umount_by_path(&mut self, path: &str) {
-        self.0.retain(|fs| fs.path == path);
+    /// umount the file system at `path`. Fails while files are open on it
+    /// or another file system is mounted below it.
+    pub fn umount(&self, path: &str) -> AxResult {
+        let path = normalize(path);
+        let mut list = self.0.write();
+        let index = match list.iter().position(|mfs| *mfs.path == *path) {
+            Some(index) => index,
+            None => return ax_err!(NotFound, "umount: not mounted"),
+        };
+        let nested = list
+            .iter()
+            .any(|mfs| *mfs.path != *path && strip_mount_point(&mfs.path, &path).is_some());
+        if nested || list[index].is_busy() {
+            return ax_err!(ResourceBusy, "umount: target is busy");
+        }
+        // Another file system may be mounted here next. No file is open,
+        // so no page can get dirty once this has checked them.
+        cache::drop_mount(&path)?;
+        let mfs = list.remove(index);
+        mfs.fs().umount();
+        Ok(())
     }
 
-    /// umount a file system by fs
-    pub fn umount_by_fs(&mut self, fs: Arc<dyn VfsFileSystem>) {
-        self.0.retain(|mfs| mfs.fs.name() == fs.name());
+    /// all mounted file systems, in mount order
+    pub fn list(&self) -> Vec<MountedFileSystem> {
+        self.0.read().clone()
     }
 
-    /// mount a file system
-    pub fn mount(&mut self, path: &str, source_fs: Arc<dyn VfsFileSystem>) {
-        // find whether it was mounted.
-        let search = self
-            .0
+    /// Find the file system `path` lives on: the mount with the longest
+    /// path that is a prefix of `path` on a component boundary. Also returns
+    /// the path inside that file system, which is `"/"` for its root.
+    pub fn lookup(&self, path: &str) -> Option<(MountedFileSystem, String)> {
+        let path = normalize(path);
+        let list = self.0.read();
+        let mfs = list
             .iter()
-            .find(|fs| fs.path == path && fs.fs.name() == source_fs.name());
+            .filter(|mfs| strip_mount_point(&path, &mfs.path).is_some())
+            .max_by_key(|mfs| mfs.path.len())?;
+        let rel = strip_mount_point(&path, &mfs.path)?;
+        let rel = if rel.is_empty() {
+            "/".to_string()
+        } else {
+            rel.to_string()
+        };
+        Some((mfs.clone(), rel))
+    }
+
+    /// Whether a file system is mounted exactly at `path`.
+    pub fn is_mount_point(&self, path: &str) -> bool {
+        let path = normalize(path);
+        self.0.read().iter().any(|mfs| *mfs.path == *path)
+    }
+}
+
+/// Absolute form of `path` with empty and `.` components dropped and `..`
+/// applied. Anything from the first NUL byte on is ignored.
+pub(crate) fn normalize(path: &str) -> String {
+    let path = path.split('\0').next().unwrap_or("");
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    let mut normalized = String::new();
+    for part in parts {
+        normalized.push('/');
+        normalized.push_str(part);
+    }
+    if normalized.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// What is left of normalized `path` below `mount_point`, or `None` if it
+/// is not below it. The rest is empty or starts with `/`.
+fn strip_mount_point<'a>(path: &'a str, mount_point: &str) -> Option<&'a str> {
+    if mount_point == "/" {
+        return Some(if path == "/" { "" } else { path });
+    }
+    let rest = path.strip_prefix(mount_point)?;
+    if rest.is_empty() || rest.starts_with('/') {
+        Some(rest)
+    } else {
+        None
+    }
+}
 
-        // mount if it not been mounted.
-        if search.is_none() {
-            self.0
-                .push(MountedFileSystem::new(path.to_string(), source_fs.clone()))
+/// A file opened through a mount. Keeps the mount busy until dropped,
+/// sends the I/O of regular files through the [`cache`] if the mount has
+/// one, and tells [`notify`] watchers about the changes made through it.
+/// On a read-only mount it refuses every change, whatever the file system.
+struct MountedFile {
+    inner: Arc<dyn VfsFile>,
+    mount: Arc<str>,
+    open_files: Arc<AtomicUsize>,
+    cache_pages: bool,
+    /// Key of the file in the cache, if its data goes through it.
+    cached: Option<cache::Key>,
+    read_only: bool,
+}
+
+fn dir_flag(attr: &Metadata) -> u32 {
+    if attr.is_dir() {
+        IN_ISDIR
+    } else {
+        0
+    }
+}
+
+impl MountedFile {
+    fn new(
+        inner: Box<dyn VfsFile>,
+        mount: Arc<str>,
+        open_files: Arc<AtomicUsize>,
+        cache_pages: bool,
+        read_only: bool,
+    ) -> Self {
+        open_files.fetch_add(1, Ordering::AcqRel);
+        let inner: Arc<dyn VfsFile> = inner.into();
+        let cached = cache_pages
+            .then(|| inner.getattr().ok())
+            .flatten()
+            .filter(|attr| attr.is_file())
+            .map(|attr| (mount.clone(), attr.ino));
+        if let Some(key) = &cached {
+            cache::open(key, &inner);
+        }
+        Self {
+            inner,
+            mount,
+            open_files,
+            cache_pages,
+            cached,
+            read_only,
         }
     }
 
-    /// get a matched fiel system by path
-    pub fn get_matched_fs(&self, path: &str) -> Option<&MountedFileSystem> {
-        let (_, index) = self
-            .0
-            .iter()
-            .enumerate()
-            .fold((0, usize::MAX), |(len, index), (i, x)| {
-                if x.path.len() >= len && path.starts_with(&x.path) {
-                    (x.path.len(), i)
-                } else {
-                    (len, index)
-                }
-            });
-
-        // return matched fs
-        match index {
-            usize::MAX => None,
-            index => Some(self.0.get(index).unwrap()),
+    fn check_writable(&self) -> VfsResult {
+        if self.read_only {
+            return ax_err!(ReadOnlyFilesystem);
+        }
+        Ok(())
+    }
+
+    fn track(&self, file: VfsResult<Box<dyn VfsFile>>) -> VfsResult<Box<dyn VfsFile>> {
+        file.map(|file| {
+            let file = Self::new(
+                file,
+                self.mount.clone(),
+                self.open_files.clone(),
+                self.cache_pages,
+                self.read_only,
+            );
+            Box::new(file) as Box<dyn VfsFile>
+        })
+    }
+
+    /// Attributes of the file at `path` below this directory, if watchers
+    /// or the cache may want them.
+    fn watched_attr(&self, path: &str) -> Option<Metadata> {
+        if !notify::watching() && !self.cache_pages {
+            return None;
+        }
+        self.inner.lookup(path).ok()?.getattr().ok()
+    }
+
+    /// Drop the cached pages of the file with `attr`, which just lost a
+    /// name, if that was its last one.
+    fn uncache(&self, attr: &Metadata) {
+        if self.cache_pages && attr.is_file() && attr.nlink <= 1 {
+            cache::discard(&(self.mount.clone(), attr.ino));
+        }
+    }
+
+    /// Tell watchers of this file about `mask`.
+    fn notify_self(&self, mask: u32) {
+        if !notify::watching() {
+            return;
         }
+        if let Ok(attr) = self.inner.getattr() {
+            notify::notify(&self.mount, attr.ino, mask | dir_flag(&attr), 0, None);
+        }
+    }
+
+    /// Tell watchers of the directory `path` is in about `mask` on the
+    /// last name of `path`.
+    fn notify_name(&self, path: &str, mask: u32, cookie: u32) {
+        if !notify::watching() {
+            return;
+        }
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let dir = if parent.is_empty() {
+            self.inner.getattr()
+        } else {
+            self.inner.lookup(parent).and_then(|dir| dir.getattr())
+        };
+        if let Ok(dir) = dir {
+            notify::notify(&self.mount, dir.ino, mask, cookie, Some(name));
+        }
+    }
+
+    /// Tell watchers the file with `attr` lost a name: it is gone if that
+    /// was its last one.
+    fn notify_removed(&self, attr: &Metadata) {
+        let mask = if attr.is_dir() || attr.nlink <= 1 {
+            IN_DELETE_SELF
+        } else {
+            IN_ATTRIB
+        };
+        notify::notify(&self.mount, attr.ino, mask | dir_flag(attr), 0, None);
+    }
+
+    fn notify_created(&self, path: &str, file: &VfsResult<Box<dyn VfsFile>>) {
+        if let Ok(file) = file {
+            let flag = file.getattr().map_or(0, |attr| dir_flag(&attr));
+            self.notify_name(path, IN_CREATE | flag, 0);
+        }
+    }
+}
+
+impl Drop for MountedFile {
+    fn drop(&mut self) {
+        if let Some(key) = &self.cached {
+            cache::close(key, &self.inner);
+        }
+        self.open_files.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl VfsFile for MountedFile {
+    fn getattr(&self) -> VfsResult<Metadata> {
+        self.inner.getattr()
+    }
+    fn setattr(&self, attr: SetAttr) -> VfsResult {
+        self.check_writable()?;
+        match (&self.cached, attr.size) {
+            (Some(key), Some(size)) => cache::truncate(key, size, || self.inner.setattr(attr))?,
+            _ => self.inner.setattr(attr)?,
+        }
+        if attr.size.is_some() {
+            self.notify_self(IN_MODIFY);
+        }
+        if attr.mode.is_some() || attr.mtime.is_some() {
+            self.notify_self(IN_ATTRIB);
+        }
+        Ok(())
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> VfsResult<usize> {
+        match &self.cached {
+            Some(key) => cache::read(key, &*self.inner, offset, buf),
+            None => self.inner.read_at(offset, buf),
+        }
+    }
+    fn write_at(&self, offset: usize, data: &[u8]) -> VfsResult<usize> {
+        self.check_writable()?;
+        let written = match &self.cached {
+            Some(key) => cache::write(key, &*self.inner, offset, data)?,
+            None => self.inner.write_at(offset, data)?,
+        };
+        if written > 0 {
+            self.notify_self(IN_MODIFY);
+        }
+        Ok(written)
+    }
+    fn truncate(&self, size: usize) -> VfsResult {
+        self.check_writable()?;
+        match &self.cached {
+            Some(key) => cache::truncate(key, size, || self.inner.truncate(size))?,
+            None => self.inner.truncate(size)?,
+        }
+        self.notify_self(IN_MODIFY);
+        Ok(())
+    }
+    fn fsync(&self) -> VfsResult {
+        if let Some(key) = &self.cached {
+            cache::flush(key, &*self.inner)?;
+        }
+        self.inner.fsync()
+    }
+    fn sync_all(&self) -> VfsResult {
+        if let Some(key) = &self.cached {
+            cache::flush(key, &*self.inner)?;
+        }
+        self.inner.sync_all()
+    }
+    fn ioctl(&self, cmd: usize, arg: &mut [u8]) -> VfsResult<usize> {
+        self.inner.ioctl(cmd, arg)
+    }
+    fn lookup(&self, path: &str) -> VfsResult<Box<dyn VfsFile>> {
+        self.track(self.inner.lookup(path))
+    }
+    fn create(&self, path: &str, ty: FileType, mode: u16) -> VfsResult<Box<dyn VfsFile>> {
+        self.check_writable()?;
+        let file = self.inner.create(path, ty, mode);
+        self.notify_created(path, &file);
+        self.track(file)
+    }
+    fn mknod(&self, path: &str, major: u16, minor: u16) -> VfsResult<Box<dyn VfsFile>> {
+        self.check_writable()?;
+        let file = self.inner.mknod(path, major, minor);
+        self.notify_created(path, &file);
+        self.track(file)
+    }
+    fn unlink(&self, path: &str) -> VfsResult {
+        self.check_writable()?;
+        let attr = self.watched_attr(path);
+        self.inner.unlink(path)?;
+        if let Some(attr) = attr {
+            self.uncache(&attr);
+            self.notify_removed(&attr);
+            self.notify_name(path, IN_DELETE, 0);
+        }
+        Ok(())
+    }
+    fn rmdir(&self, path: &str) -> VfsResult {
+        self.check_writable()?;
+        let attr = self.watched_attr(path);
+        self.inner.rmdir(path)?;
+        if let Some(attr) = attr {
+            self.notify_removed(&attr);
+            self.notify_name(path, IN_DELETE | IN_ISDIR, 0);
+        }
+        Ok(())
+    }
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.check_writable()?;
+        // A file the rename replaces loses its name.
+        let replaced = self.watched_attr(dst_path);
+        self.inner.rename(src_path, dst_path)?;
+        if let Some(attr) = self.watched_attr(dst_path) {
+            if let Some(replaced) = replaced.filter(|old| old.ino != attr.ino) {
+                self.uncache(&replaced);
+                self.notify_removed(&replaced);
+            }
+            let cookie = notify::next_cookie();
+            let flag = dir_flag(&attr);
+            self.notify_name(src_path, IN_MOVED_FROM | flag, cookie);
+            self.notify_name(dst_path, IN_MOVED_TO | flag, cookie);
+            notify::notify(&self.mount, attr.ino, IN_MOVE_SELF | flag, 0, None);
+        }
+        Ok(())
+    }
+    fn link(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.check_writable()?;
+        self.inner.link(src_path, dst_path)?;
+        if let Some(attr) = self.watched_attr(dst_path) {
+            self.notify_name(dst_path, IN_CREATE, 0);
+            notify::notify(&self.mount, attr.ino, IN_ATTRIB, 0, None);
+        }
+        Ok(())
+    }
+    fn symlink(&self, target: &str, path: &str) -> VfsResult {
+        self.check_writable()?;
+        self.inner.symlink(target, path)?;
+        self.notify_name(path, IN_CREATE, 0);
+        Ok(())
+    }
+    fn readlink(&self) -> VfsResult<String> {
+        self.inner.readlink()
+    }
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>> {
+        self.inner.readdir()
+    }
+    fn get_xattr(&self, name: &str) -> VfsResult<Vec<u8>> {
+        self.inner.get_xattr(name)
+    }
+    fn set_xattr(&self, name: &str, value: &[u8]) -> VfsResult {
+        self.check_writable()?;
+        self.inner.set_xattr(name, value)?;
+        self.notify_self(IN_ATTRIB);
+        Ok(())
+    }
+    fn list_xattr(&self) -> VfsResult<Vec<String>> {
+        self.inner.list_xattr()
+    }
+    fn remove_xattr(&self, name: &str) -> VfsResult {
+        self.check_writable()?;
+        self.inner.remove_xattr(name)?;
+        self.notify_self(IN_ATTRIB);
+        Ok(())
     }
 }
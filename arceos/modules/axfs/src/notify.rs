@@ -0,0 +1,400 @@
+//! File change notification, in the manner of Linux inotify.
+//!
+//! A [`Watcher`] watches inodes, found by path or by mount path and inode
+//! number, and queues an [`Event`] for every change it asked for. Events
+//! come from the mount layer, so every file system sends them.
+//!
+//! A watch on a directory also reports changes to the names in it:
+//! `IN_CREATE`, `IN_DELETE`, `IN_MOVED_FROM` and `IN_MOVED_TO` carry the
+//! name. The two halves of a rename share a cookie.
+
+use alloc::{
+    collections::VecDeque,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use axerror::{ax_err, AxResult};
+use axtask::WaitQueue;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use spin::mutex::Mutex;
+use spinlock::SpinNoIrq;
+
+/// The file was written or truncated.
+pub const IN_MODIFY: u32 = 0x2;
+/// Permissions, times, extended attributes or the link count changed.
+pub const IN_ATTRIB: u32 = 0x4;
+/// A name was moved out of the watched directory.
+pub const IN_MOVED_FROM: u32 = 0x40;
+/// A name was moved into the watched directory.
+pub const IN_MOVED_TO: u32 = 0x80;
+/// A name was created in the watched directory.
+pub const IN_CREATE: u32 = 0x100;
+/// A name was removed from the watched directory.
+pub const IN_DELETE: u32 = 0x200;
+/// The watched file was removed.
+pub const IN_DELETE_SELF: u32 = 0x400;
+/// The watched file was renamed.
+pub const IN_MOVE_SELF: u32 = 0x800;
+/// Every event above.
+pub const IN_ALL_EVENTS: u32 = IN_MODIFY
+    | IN_ATTRIB
+    | IN_MOVED_FROM
+    | IN_MOVED_TO
+    | IN_CREATE
+    | IN_DELETE
+    | IN_DELETE_SELF
+    | IN_MOVE_SELF;
+/// The queue filled up and events were lost. Not tied to a watch.
+pub const IN_Q_OVERFLOW: u32 = 0x4000;
+/// Set along with the event when its subject is a directory.
+pub const IN_ISDIR: u32 = 0x4000_0000;
+
+/// Most events a watcher queues before dropping them.
+const MAX_QUEUED_EVENTS: usize = 1024;
+
+/// A change to a watched inode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// The watch descriptor [`Watcher::add_watch`] returned.
+    pub wd: usize,
+    /// One `IN_*` event, maybe with `IN_ISDIR`.
+    pub mask: u32,
+    /// Ties `IN_MOVED_FROM` to its `IN_MOVED_TO`; otherwise 0.
+    pub cookie: u32,
+    /// The name in the watched directory the event is about.
+    pub name: Option<String>,
+}
+
+struct Watch {
+    watcher: Weak<WatcherInner>,
+    wd: usize,
+    /// Mount path and inode number of what is watched.
+    key: (String, usize),
+    mask: u32,
+}
+
+/// Every watch of every watcher.
+static WATCHES: Mutex<Vec<Watch>> = Mutex::new(Vec::new());
+
+/// Number of entries in `WATCHES`, so that changes need not lock it when
+/// nobody watches.
+static WATCH_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+struct WatcherInner {
+    // `SpinNoIrq` so that it can be checked in `wait_until` conditions,
+    // which run with the run queue locked.
+    queue: SpinNoIrq<VecDeque<Event>>,
+    wq: WaitQueue,
+    next_wd: AtomicUsize,
+}
+
+impl WatcherInner {
+    fn push(&self, event: Event) {
+        let mut queue = self.queue.lock();
+        // Repeats of the last event, like a run of writes, are merged.
+        if queue.back() == Some(&event) {
+            return;
+        }
+        match queue.len() {
+            n if n < MAX_QUEUED_EVENTS => queue.push_back(event),
+            MAX_QUEUED_EVENTS => queue.push_back(Event {
+                wd: 0,
+                mask: IN_Q_OVERFLOW,
+                cookie: 0,
+                name: None,
+            }),
+            _ => {}
+        }
+    }
+}
+
+/// A queue of events from a set of watches. The watches go away when it is
+/// dropped.
+pub struct Watcher(Arc<WatcherInner>);
+
+impl Watcher {
+    pub fn new() -> Self {
+        Self(Arc::new(WatcherInner {
+            queue: SpinNoIrq::new(VecDeque::new()),
+            wq: WaitQueue::new(),
+            next_wd: AtomicUsize::new(1),
+        }))
+    }
+
+    /// Watch the file at `path`, following symlinks, for the `IN_*` events
+    /// in `mask`. Returns the watch descriptor its events carry. Watching
+    /// the same file again replaces the mask and keeps the descriptor.
+    pub fn add_watch(&self, path: &str, mask: u32) -> AxResult<usize> {
+        let (mount, ino) = crate::ops::inode_of(path)?;
+        self.add_watch_inode(&mount, ino, mask)
+    }
+
+    /// Watch inode `ino` of the file system mounted at `mount_path`.
+    pub fn add_watch_inode(&self, mount_path: &str, ino: usize, mask: u32) -> AxResult<usize> {
+        if mask & IN_ALL_EVENTS == 0 {
+            return ax_err!(InvalidParam, "add_watch: no events in mask");
+        }
+        let key = (mount_path.to_string(), ino);
+        let mut watches = WATCHES.lock();
+        let mine = |watch: &&mut Watch| Weak::as_ptr(&watch.watcher) == Arc::as_ptr(&self.0);
+        if let Some(watch) = watches.iter_mut().filter(mine).find(|w| w.key == key) {
+            watch.mask = mask & IN_ALL_EVENTS;
+            return Ok(watch.wd);
+        }
+        let wd = self.0.next_wd.fetch_add(1, Ordering::Relaxed);
+        watches.push(Watch {
+            watcher: Arc::downgrade(&self.0),
+            wd,
+            key,
+            mask: mask & IN_ALL_EVENTS,
+        });
+        WATCH_COUNT.store(watches.len(), Ordering::Release);
+        Ok(wd)
+    }
+
+    /// Stop watching what `wd` watches.
+    pub fn remove_watch(&self, wd: usize) -> AxResult {
+        let mut watches = WATCHES.lock();
+        let index = watches
+            .iter()
+            .position(|w| w.wd == wd && Weak::as_ptr(&w.watcher) == Arc::as_ptr(&self.0))
+            .ok_or(axerror::AxError::InvalidParam)?;
+        watches.remove(index);
+        WATCH_COUNT.store(watches.len(), Ordering::Release);
+        Ok(())
+    }
+
+    /// Take the oldest event, waiting for one if the queue is empty.
+    pub fn read(&self) -> Event {
+        loop {
+            if let Some(event) = self.try_read() {
+                return event;
+            }
+            self.0.wq.wait_until(|| !self.0.queue.lock().is_empty());
+        }
+    }
+
+    /// Take the oldest event, if any.
+    pub fn try_read(&self) -> Option<Event> {
+        self.0.queue.lock().pop_front()
+    }
+
+    /// Number of events waiting.
+    pub fn pending(&self) -> usize {
+        self.0.queue.lock().len()
+    }
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        let mut watches = WATCHES.lock();
+        watches.retain(|w| Weak::as_ptr(&w.watcher) != Arc::as_ptr(&self.0));
+        WATCH_COUNT.store(watches.len(), Ordering::Release);
+    }
+}
+
+/// Whether anything is watched at all. Lets callers skip the work of
+/// finding what changed.
+pub(crate) fn watching() -> bool {
+    WATCH_COUNT.load(Ordering::Acquire) > 0
+}
+
+/// A cookie for the two halves of a rename.
+pub(crate) fn next_cookie() -> u32 {
+    NEXT_COOKIE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Queue an event for every watch on inode `ino` of the mount at
+/// `mount_path` that asked for it.
+pub(crate) fn notify(mount_path: &str, ino: usize, mask: u32, cookie: u32, name: Option<&str>) {
+    if !watching() {
+        return;
+    }
+    let targets: Vec<(Arc<WatcherInner>, usize)> = WATCHES
+        .lock()
+        .iter()
+        .filter(|w| w.key.1 == ino && w.key.0 == mount_path && w.mask & mask != 0)
+        .filter_map(|w| Some((w.watcher.upgrade()?, w.wd)))
+        .collect();
+    // Waking a reader may reschedule, so not under the lock.
+    for (watcher, wd) in targets {
+        watcher.push(Event {
+            wd,
+            mask,
+            cookie,
+            name: name.map(|name| name.to_string()),
+        });
+        watcher.wq.notify_all(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mount::MountedFileSystem;
+    use crate::RamFileSystem;
+    use alloc::boxed::Box;
+    use core::sync::atomic::AtomicBool;
+    use vfscore::{FileType, MountOptions, SetAttr, VfsFile};
+
+    /// The root of a new ramfs mounted at `path`, opened through the mount
+    /// so that its changes are sent.
+    fn mount_ramfs(path: &str) -> Box<dyn VfsFile> {
+        let fs = Arc::new(RamFileSystem::new());
+        let mfs = MountedFileSystem::new(path.to_string(), fs, MountOptions::default(), None);
+        mfs.track(mfs.root())
+    }
+
+    fn watch(watcher: &Watcher, mount_path: &str, file: &dyn VfsFile, mask: u32) -> usize {
+        let ino = file.getattr().unwrap().ino;
+        watcher.add_watch_inode(mount_path, ino, mask).unwrap()
+    }
+
+    /// The queued events as watch descriptor, mask and name.
+    fn events(watcher: &Watcher) -> Vec<(usize, u32, Option<String>)> {
+        core::iter::from_fn(|| watcher.try_read())
+            .map(|event| (event.wd, event.mask, event.name))
+            .collect()
+    }
+
+    fn named(wd: usize, mask: u32, name: &str) -> (usize, u32, Option<String>) {
+        (wd, mask, Some(name.to_string()))
+    }
+
+    #[test]
+    fn test_create_delete() {
+        let _scheduler = crate::test_scheduler();
+        let root = mount_ramfs("/create");
+        let watcher = Watcher::new();
+        let wd = watch(&watcher, "/create", &*root, IN_ALL_EVENTS);
+        let file = root.create("a", FileType::File, 0o644).unwrap();
+        let dir = root.create("d", FileType::Dir, 0o755).unwrap();
+        let file_wd = watch(&watcher, "/create", &*file, IN_ALL_EVENTS);
+        let dir_wd = watch(&watcher, "/create", &*dir, IN_ALL_EVENTS);
+        root.unlink("a").unwrap();
+        root.rmdir("d").unwrap();
+        assert_eq!(
+            events(&watcher),
+            [
+                named(wd, IN_CREATE, "a"),
+                named(wd, IN_CREATE | IN_ISDIR, "d"),
+                (file_wd, IN_DELETE_SELF, None),
+                named(wd, IN_DELETE, "a"),
+                (dir_wd, IN_DELETE_SELF | IN_ISDIR, None),
+                named(wd, IN_DELETE | IN_ISDIR, "d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_modify() {
+        let _scheduler = crate::test_scheduler();
+        let root = mount_ramfs("/modify");
+        let file = root.create("a", FileType::File, 0o644).unwrap();
+        let watcher = Watcher::new();
+        let wd = watch(&watcher, "/modify", &*file, IN_ALL_EVENTS);
+        file.write_at(0, b"abc").unwrap();
+        // A run of writes is one event.
+        file.write_at(3, b"def").unwrap();
+        let attr = SetAttr {
+            mode: Some(0o600),
+            ..Default::default()
+        };
+        file.setattr(attr).unwrap();
+        file.truncate(1).unwrap();
+        assert_eq!(
+            events(&watcher),
+            [(wd, IN_MODIFY, None), (wd, IN_ATTRIB, None), (wd, IN_MODIFY, None)]
+        );
+        // Reads change nothing.
+        file.read_at(0, &mut [0; 4]).unwrap();
+        assert_eq!(watcher.pending(), 0);
+    }
+
+    #[test]
+    fn test_move() {
+        let _scheduler = crate::test_scheduler();
+        let root = mount_ramfs("/move");
+        let file = root.create("a", FileType::File, 0o644).unwrap();
+        let dir = root.create("d", FileType::Dir, 0o755).unwrap();
+        let watcher = Watcher::new();
+        let wd = watch(&watcher, "/move", &*root, IN_ALL_EVENTS);
+        let dir_wd = watch(&watcher, "/move", &*dir, IN_ALL_EVENTS);
+        let file_wd = watch(&watcher, "/move", &*file, IN_ALL_EVENTS);
+        root.rename("a", "d/b").unwrap();
+        let from = watcher.try_read().unwrap();
+        let to = watcher.try_read().unwrap();
+        assert_eq!((from.wd, from.mask, from.name.as_deref()), (wd, IN_MOVED_FROM, Some("a")));
+        assert_eq!((to.wd, to.mask, to.name.as_deref()), (dir_wd, IN_MOVED_TO, Some("b")));
+        assert_ne!(from.cookie, 0);
+        assert_eq!(from.cookie, to.cookie);
+        assert_eq!(events(&watcher), [(file_wd, IN_MOVE_SELF, None)]);
+    }
+
+    #[test]
+    fn test_mask() {
+        let _scheduler = crate::test_scheduler();
+        let root = mount_ramfs("/mask");
+        let watcher = Watcher::new();
+        let wd = watch(&watcher, "/mask", &*root, IN_DELETE);
+        root.create("a", FileType::File, 0o644).unwrap();
+        root.unlink("a").unwrap();
+        assert_eq!(events(&watcher), [named(wd, IN_DELETE, "a")]);
+        // Watching again replaces the mask and keeps the descriptor.
+        assert_eq!(watch(&watcher, "/mask", &*root, IN_CREATE), wd);
+        root.create("b", FileType::File, 0o644).unwrap();
+        root.unlink("b").unwrap();
+        assert_eq!(events(&watcher), [named(wd, IN_CREATE, "b")]);
+        assert_eq!(
+            watcher.add_watch_inode("/mask", 1, IN_Q_OVERFLOW),
+            Err(axerror::AxError::InvalidParam)
+        );
+    }
+
+    #[test]
+    fn test_remove_watch() {
+        let _scheduler = crate::test_scheduler();
+        let root = mount_ramfs("/remove");
+        let watcher = Watcher::new();
+        let other = Watcher::new();
+        let wd = watch(&watcher, "/remove", &*root, IN_ALL_EVENTS);
+        let other_wd = watch(&other, "/remove", &*root, IN_ALL_EVENTS);
+        watcher.remove_watch(wd).unwrap();
+        assert_eq!(watcher.remove_watch(wd), Err(axerror::AxError::InvalidParam));
+        root.create("a", FileType::File, 0o644).unwrap();
+        assert_eq!(watcher.pending(), 0);
+        assert_eq!(events(&other), [named(other_wd, IN_CREATE, "a")]);
+        drop(other);
+        assert!(!watching());
+        root.unlink("a").unwrap();
+        assert_eq!(watcher.pending(), 0);
+    }
+
+    #[test]
+    fn test_read_waits() {
+        let _scheduler = crate::test_scheduler();
+        static DONE: AtomicBool = AtomicBool::new(false);
+        let root = mount_ramfs("/wait");
+        let watcher = Watcher::new();
+        let ino = root.getattr().unwrap().ino;
+        let wd = watch(&watcher, "/wait", &*root, IN_CREATE);
+        axtask::spawn(move || {
+            notify("/wait", ino, IN_CREATE, 0, Some("a"));
+            DONE.store(true, Ordering::Release);
+        });
+        let event = watcher.read();
+        assert_eq!((event.wd, event.mask, event.name.as_deref()), (wd, IN_CREATE, Some("a")));
+        while !DONE.load(Ordering::Acquire) {
+            axtask::yield_now();
+        }
+    }
+}
@@ -1,50 +1,378 @@
-use alloc::boxed::Box;
-use vfscore::VfsFile;
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use axerror::{ax_err, AxError, AxResult};
+use vfscore::{DirEntry, FileType, Metadata, MountOptions, StatFs, VfsFile, VfsFileSystem};
 
-use crate::mount::MOUNTEDFS;
+use crate::dev::DeviceFile;
+use crate::lock::FileLocks;
+use crate::mount::{normalize, MountedFileSystem, MOUNTEDFS};
+use crate::pipe::{fifo_pipe, Pipe, PipeReader, PipeWriter};
+
+/// Most symlinks followed while resolving one path, as on Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Look up normalized `path` on the mount it lives on, without following
+/// any symlink.
+fn lookup_raw(path: &str) -> AxResult<(MountedFileSystem, Box<dyn VfsFile>)> {
+    let (mfs, rel) = MOUNTEDFS.lookup(path).ok_or(AxError::NotFound)?;
+    let file = if rel == "/" {
+        mfs.root()
+    } else {
+        mfs.root().lookup(&rel)?
+    };
+    Ok((mfs, file))
+}
+
+/// `path` made absolute against the current working directory.
+fn absolute(path: &str) -> String {
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", axtask::current().cwd(), path)
+    }
+}
+
+/// `path` made absolute, with every symlink in it replaced by its target.
+/// The last component is only followed if `follow_last` is set, and may be
+/// missing.
+fn resolve(path: &str, follow_last: bool) -> AxResult<String> {
+    if path.split('\0').next().unwrap_or("").is_empty() {
+        return ax_err!(NotFound, "empty path");
+    }
+    let mut pending: Vec<String> = components(&absolute(path)).rev().collect();
+    let mut resolved = String::new();
+    let mut links = 0;
+    while let Some(name) = pending.pop() {
+        match name.as_str() {
+            "." => continue,
+            ".." => {
+                let parent = resolved.rfind('/').unwrap_or(0);
+                resolved.truncate(parent);
+                continue;
+            }
+            _ => {}
+        }
+        let candidate = format!("{}/{}", resolved, name);
+        let last = pending.is_empty();
+        if last && !follow_last {
+            resolved = candidate;
+            break;
+        }
+        let file = match lookup_raw(&candidate) {
+            Ok((_, file)) => file,
+            Err(AxError::NotFound) if last => {
+                resolved = candidate;
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if file.getattr()?.file_type != FileType::Symlink {
+            resolved = candidate;
+            continue;
+        }
+        links += 1;
+        if links > MAX_SYMLINKS {
+            return ax_err!(FilesystemLoop, "too many levels of symbolic links");
+        }
+        let target = file.readlink()?;
+        if target.starts_with('/') {
+            resolved.clear();
+        }
+        pending.extend(components(&target).rev());
+    }
+    if resolved.is_empty() {
+        resolved.push('/');
+    }
+    Ok(resolved)
+}
+
+fn components(path: &str) -> impl DoubleEndedIterator<Item = String> + '_ {
+    path.split('\0')
+        .next()
+        .unwrap_or("")
+        .split('/')
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+}
+
+/// Look up `path`, following symlinks.
+fn lookup(path: &str) -> AxResult<(MountedFileSystem, Box<dyn VfsFile>)> {
+    lookup_raw(&resolve(path, true)?)
+}
+
+/// The current working directory of the calling task.
+pub fn getcwd() -> String {
+    axtask::current().cwd()
+}
+
+/// Change the current working directory of the calling task.
+pub fn chdir(path: &str) -> AxResult {
+    let path = resolve(path, true)?;
+    if !lookup_raw(&path)?.1.getattr()?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    axtask::current().set_cwd(&path);
+    Ok(())
+}
 
 /// open file with given path
-pub fn open(path: &str) -> Option<Box<dyn VfsFile>> {
+pub fn open(path: &str) -> AxResult<Box<dyn VfsFile>> {
     info!("open: path is {}",path);
-    let fs = MOUNTEDFS.get_matched_fs(path)?;
-    //let path = &path[fs.path().len()..];
-    let path = &path[0..];
-    if path.len() > 1 {
-        fs.fs().root().open(path)
-    } else {
-        Some(fs.fs().root())
+    let (mfs, file) = lookup(path)?;
+    match file.getattr()?.rdev {
+        Some((major, minor)) => Ok(Box::new(DeviceFile::open(major, minor)?)),
+        None => Ok(mfs.track(file)),
     }
 }
 
+/// Whether the file at `path` is on a read-only mount, following symlinks.
+pub fn read_only(path: &str) -> AxResult<bool> {
+    let (mfs, _) = lookup(path)?;
+    Ok(mfs.options().read_only)
+}
+
+/// A handle on the advisory locks of the file at `path`, for a file just
+/// opened there. See [`lock`](crate::lock).
+pub fn file_locks(path: &str) -> AxResult<FileLocks> {
+    let (mount, ino) = inode_of(path)?;
+    Ok(FileLocks::new(&mount, ino))
+}
+
+/// Mount path and inode number of the file at `path`, following symlinks.
+pub(crate) fn inode_of(path: &str) -> AxResult<(String, usize)> {
+    let (mfs, file) = lookup(path)?;
+    Ok((mfs.path().to_string(), file.getattr()?.ino))
+}
+
+/// The mount `path` lives on and the path inside it, if the mount is
+/// writable. A symlink at the end of `path` is not followed.
+fn writable(path: &str) -> AxResult<(MountedFileSystem, String)> {
+    let (mfs, rel) = MOUNTEDFS
+        .lookup(&resolve(path, false)?)
+        .ok_or(AxError::NotFound)?;
+    if mfs.options().read_only {
+        return ax_err!(ReadOnlyFilesystem);
+    }
+    Ok((mfs, rel))
+}
+
+/// Open the directory `path` will be created in, on a writable mount.
+/// Returns the mount, the directory and the name to create. Changes made
+/// through the directory reach [`notify`](crate::notify) watchers.
+fn parent_dir(path: &str) -> AxResult<(MountedFileSystem, Box<dyn VfsFile>, String)> {
+    let (mfs, rel) = writable(path)?;
+    // `rel` is "/" for the root of the mount, which exists already.
+    let (parent, name) = match rel.rsplit_once('/') {
+        Some((_, "")) | None => return ax_err!(AlreadyExists),
+        Some(split) => split,
+    };
+    let root = mfs.track(mfs.root());
+    let dir = if parent.is_empty() {
+        root
+    } else {
+        root.lookup(parent)?
+    };
+    Ok((mfs, dir, name.to_string()))
+}
+
+fn create_node(path: &str, ty: FileType, mode: u16) -> AxResult<Box<dyn VfsFile>> {
+    let (_, dir, name) = parent_dir(path)?;
+    dir.create(&name, ty, mode)
+}
+
 /// create a new file by given path
-pub fn create(path: &str) -> Option<Box<dyn VfsFile>> {
+pub fn create(path: &str) -> AxResult<Box<dyn VfsFile>> {
     info!("create: path is {}",path);
-    let fs = MOUNTEDFS.get_matched_fs(path)?;
-    let path = &path[fs.path().len()..];
-    if path.len() > 0 {
-        fs.fs().root().create(path)
-    } else {
-        None
-    }
+    create_node(path, FileType::File, 0o644)
 }
 
 /// create a new directory by given path
-pub fn mkdir(path: &str) -> Option<Box<dyn VfsFile>> {
+pub fn mkdir(path: &str) -> AxResult<Box<dyn VfsFile>> {
     info!("mkdir: path is {}",path);
-    let fs = MOUNTEDFS.get_matched_fs(path)?;
-    let path = &path[fs.path().len()..];
-    if path.len() > 0 {
-        fs.fs().root().mkdir(path)
-    } else {
-        None
+    create_node(path, FileType::Dir, 0o755)
+}
+
+/// create a named pipe by given path
+pub fn mkfifo(path: &str) -> AxResult<Box<dyn VfsFile>> {
+    info!("mkfifo: path is {}",path);
+    create_node(path, FileType::Fifo, 0o644)
+}
+
+/// create a device node by given path
+pub fn mknod(path: &str, major: u16, minor: u16) -> AxResult<Box<dyn VfsFile>> {
+    info!("mknod: path is {}",path);
+    let (_, dir, name) = parent_dir(path)?;
+    dir.mknod(&name, major, minor)
+}
+
+/// create a symlink at `path` pointing to `target`
+pub fn symlink(target: &str, path: &str) -> AxResult {
+    info!("symlink: {} to {}", path, target);
+    let (_, dir, name) = parent_dir(path)?;
+    dir.symlink(target, &name)
+}
+
+/// the target of the symlink at given path
+pub fn readlink(path: &str) -> AxResult<String> {
+    let (_, file) = lookup_raw(&resolve(path, false)?)?;
+    file.readlink()
+}
+
+/// open the read end of the named pipe at given path
+pub fn open_fifo_reader(path: &str) -> AxResult<PipeReader> {
+    open_fifo(path).map(PipeReader::new)
+}
+
+/// open the write end of the named pipe at given path
+pub fn open_fifo_writer(path: &str) -> AxResult<PipeWriter> {
+    open_fifo(path).map(PipeWriter::new)
+}
+
+fn open_fifo(path: &str) -> AxResult<Arc<Pipe>> {
+    let (mfs, file) = lookup(path)?;
+    let attr = file.getattr()?;
+    if attr.file_type != FileType::Fifo {
+        return ax_err!(InvalidParam, "open_fifo: not a fifo");
     }
+    Ok(fifo_pipe(mfs.path(), attr.ino))
+}
+
+/// metadata of the file at given path, following symlinks
+pub fn metadata(path: &str) -> AxResult<Metadata> {
+    lookup(path)?.1.getattr()
 }
 
-/// remove a file or directory
-pub fn remove(path: &str) -> Option<()> {
+/// metadata of the file at given path, or of the symlink itself if it is one
+pub fn symlink_metadata(path: &str) -> AxResult<Metadata> {
+    lookup_raw(&resolve(path, false)?)?.1.getattr()
+}
+
+/// absolute form of given path, with `.`, `..` and symlinks resolved.
+/// The file must exist.
+pub fn canonicalize(path: &str) -> AxResult<String> {
+    let path = resolve(path, true)?;
+    lookup_raw(&path)?;
+    Ok(path)
+}
+
+/// statistics of the file system given path lives on
+pub fn statfs(path: &str) -> AxResult<StatFs> {
+    let (mfs, _) = lookup(path)?;
+    mfs.fs().statfs()
+}
+
+/// entries of the directory at given path, without `.` and `..`
+pub fn read_dir(path: &str) -> AxResult<Vec<DirEntry>> {
+    lookup(path)?.1.readdir()
+}
+
+/// flush the file at given path to stable storage
+pub fn fsync(path: &str) -> AxResult {
+    let (mfs, file) = lookup(path)?;
+    mfs.track(file).fsync()
+}
+
+/// flush every mounted file system to stable storage
+pub fn sync_all() -> AxResult {
+    for mounted in MOUNTEDFS.list() {
+        crate::cache::flush_mount(mounted.path())?;
+        mounted.root().sync_all()?;
+    }
+    Ok(())
+}
+
+/// remove a file or an empty directory
+pub fn remove(path: &str) -> AxResult {
     info!("remove: path is {}",path);
-    let fs = MOUNTEDFS.get_matched_fs(path)?;
-    //let path = &path[fs.path().len()..];
-    //info!("path is {}",path);
-    Some(fs.fs().root().remove(path))
+    // A mount point stays until it is unmounted.
+    if MOUNTEDFS.is_mount_point(&resolve(path, false)?) {
+        return ax_err!(ResourceBusy, "remove: mount point");
+    }
+    let (_, dir, name) = parent_dir(path)?;
+    if dir.lookup(&name)?.getattr()?.is_dir() {
+        dir.rmdir(&name)
+    } else {
+        dir.unlink(&name)
+    }
+}
+
+/// The mount both `src` and `dst` live on, with their paths inside it.
+fn same_mount(src: &str, dst: &str) -> AxResult<(MountedFileSystem, String, String)> {
+    let (mfs, src_rel) = writable(src)?;
+    let (dst_mfs, dst_rel) = writable(dst)?;
+    if mfs.path() != dst_mfs.path() {
+        return ax_err!(CrossesDevices, "paths are on different mounts");
+    }
+    if src_rel == "/" || dst_rel == "/" {
+        return ax_err!(ResourceBusy, "mount point");
+    }
+    Ok((mfs, src_rel, dst_rel))
+}
+
+/// rename `src` to `dst`, replacing `dst` if it exists
+pub fn rename(src: &str, dst: &str) -> AxResult {
+    info!("rename: {} to {}", src, dst);
+    let (mfs, src_rel, dst_rel) = same_mount(src, dst)?;
+    mfs.track(mfs.root()).rename(&src_rel, &dst_rel)
+}
+
+/// make `dst` another name for the file at `src`
+pub fn link(src: &str, dst: &str) -> AxResult {
+    info!("link: {} to {}", src, dst);
+    let (mfs, src_rel, dst_rel) = same_mount(src, dst)?;
+    mfs.track(mfs.root()).link(&src_rel, &dst_rel)
+}
+
+/// cut or zero-extend the file at `path` to `size` bytes
+pub fn truncate(path: &str, size: usize) -> AxResult {
+    let (mfs, file) = lookup(path)?;
+    if mfs.options().read_only {
+        return ax_err!(ReadOnlyFilesystem);
+    }
+    mfs.track(file).truncate(size)
+}
+
+/// Mount `fs` at `path`, which must be an existing directory unless it is
+/// the first mount at `/`.
+pub fn mount(path: &str, fs: Arc<dyn VfsFileSystem>, options: MountOptions) -> AxResult {
+    mount_from(None, path, fs, options)
+}
+
+/// [`mount`], noting the block device `fs` was opened on.
+pub(crate) fn mount_from(
+    source: Option<String>,
+    path: &str,
+    fs: Arc<dyn VfsFileSystem>,
+    options: MountOptions,
+) -> AxResult {
+    info!("mount: {} at {}", fs.name(), path);
+    let path = absolute(path);
+    if normalize(&path) != "/" && !metadata(&path)?.is_dir() {
+        return ax_err!(NotADirectory, "mount: mount point is not a directory");
+    }
+    MOUNTEDFS.mount(&path, fs, options, source)
+}
+
+/// Unmount the file system at `path`. Fails with `ResourceBusy` while files
+/// are open on it or other file systems are mounted below it, and with the
+/// error of writing its cached pages back if that fails.
+pub fn umount(path: &str) -> AxResult {
+    info!("umount: path is {}", path);
+    let path = normalize(&absolute(path));
+    let mounted = MOUNTEDFS.list().into_iter().find(|mfs| mfs.path() == path);
+    if let Some(mounted) = mounted {
+        crate::cache::flush_mount(mounted.path())?;
+        mounted.root().sync_all()?;
+    }
+    MOUNTEDFS.umount(&path)
+}
+
+/// All mounted file systems, in mount order.
+pub fn mounts() -> Vec<MountedFileSystem> {
+    MOUNTEDFS.list()
 }
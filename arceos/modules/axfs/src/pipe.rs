@@ -0,0 +1,390 @@
+//! In-kernel pipes and named FIFOs.
+//!
+//! A [`Pipe`] is a bounded ring buffer shared by any number of [`PipeReader`]s
+//! and [`PipeWriter`]s. Reads block until data arrives, writes block until
+//! there is room. A read returns 0 (EOF) once every writer is closed, and a
+//! write fails with [`AxError::BrokenPipe`](axerror::AxError::BrokenPipe) once
+//! every reader is closed.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+};
+use axerror::{ax_err, AxResult};
+use axtask::WaitQueue;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use spin::mutex::Mutex;
+use spinlock::SpinNoIrq;
+use vfscore::{FileType, Metadata, VfsFile, VfsResult};
+
+/// Capacity of the pipe buffer in bytes.
+pub const PIPE_BUF_SIZE: usize = 4096;
+
+/// Named pipes currently opened, keyed by mount path and inode number.
+/// Entries are dropped together with the last end of the pipe.
+static FIFOS: Mutex<BTreeMap<(String, usize), Weak<Pipe>>> = Mutex::new(BTreeMap::new());
+
+struct PipeRing {
+    data: [u8; PIPE_BUF_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl PipeRing {
+    const fn new() -> Self {
+        Self {
+            data: [0; PIPE_BUF_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn pop(&mut self, buf: &mut [u8]) -> usize {
+        let n = buf.len().min(self.len);
+        for b in buf[..n].iter_mut() {
+            *b = self.data[self.head];
+            self.head = (self.head + 1) % PIPE_BUF_SIZE;
+        }
+        self.len -= n;
+        n
+    }
+
+    fn push(&mut self, data: &[u8]) -> usize {
+        let n = data.len().min(PIPE_BUF_SIZE - self.len);
+        let mut tail = (self.head + self.len) % PIPE_BUF_SIZE;
+        for &b in &data[..n] {
+            self.data[tail] = b;
+            tail = (tail + 1) % PIPE_BUF_SIZE;
+        }
+        self.len += n;
+        n
+    }
+}
+
+/// The buffer and bookkeeping shared by both ends of a pipe.
+pub struct Pipe {
+    // `SpinNoIrq` so that it can be checked in `wait_until` conditions,
+    // which run with the run queue locked.
+    ring: SpinNoIrq<PipeRing>,
+    readers: AtomicUsize,
+    writers: AtomicUsize,
+    // A FIFO may be opened by one side before the other,
+    // so EOF and EPIPE only count once that side has shown up.
+    had_reader: AtomicBool,
+    had_writer: AtomicBool,
+    read_wq: WaitQueue,
+    write_wq: WaitQueue,
+}
+
+impl Pipe {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            ring: SpinNoIrq::new(PipeRing::new()),
+            readers: AtomicUsize::new(0),
+            writers: AtomicUsize::new(0),
+            had_reader: AtomicBool::new(false),
+            had_writer: AtomicBool::new(false),
+            read_wq: WaitQueue::new(),
+            write_wq: WaitQueue::new(),
+        })
+    }
+
+    fn write_end_closed(&self) -> bool {
+        self.had_writer.load(Ordering::Acquire) && self.writers.load(Ordering::Acquire) == 0
+    }
+
+    fn read_end_closed(&self) -> bool {
+        self.had_reader.load(Ordering::Acquire) && self.readers.load(Ordering::Acquire) == 0
+    }
+
+    fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = self.ring.lock().pop(buf);
+            if n > 0 {
+                self.write_wq.notify_all(true);
+                return Ok(n);
+            }
+            if self.write_end_closed() {
+                return Ok(0);
+            }
+            self.read_wq
+                .wait_until(|| self.ring.lock().len > 0 || self.write_end_closed());
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> AxResult<usize> {
+        let mut written = 0;
+        while written < data.len() {
+            if self.read_end_closed() {
+                return if written > 0 {
+                    Ok(written)
+                } else {
+                    ax_err!(BrokenPipe)
+                };
+            }
+            let n = self.ring.lock().push(&data[written..]);
+            if n > 0 {
+                written += n;
+                self.read_wq.notify_all(true);
+                continue;
+            }
+            self.write_wq.wait_until(|| {
+                self.ring.lock().len < PIPE_BUF_SIZE || self.read_end_closed()
+            });
+        }
+        Ok(written)
+    }
+
+    fn buffered(&self) -> usize {
+        self.ring.lock().len
+    }
+}
+
+/// The read end of a pipe.
+pub struct PipeReader(Arc<Pipe>);
+
+/// The write end of a pipe.
+pub struct PipeWriter(Arc<Pipe>);
+
+impl PipeReader {
+    pub(crate) fn new(pipe: Arc<Pipe>) -> Self {
+        pipe.readers.fetch_add(1, Ordering::AcqRel);
+        pipe.had_reader.store(true, Ordering::Release);
+        pipe.write_wq.notify_all(false);
+        Self(pipe)
+    }
+
+    /// Read some bytes, blocking until at least one is available.
+    /// Returns `Ok(0)` once all writers are closed and the buffer is drained.
+    pub fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl PipeWriter {
+    pub(crate) fn new(pipe: Arc<Pipe>) -> Self {
+        pipe.writers.fetch_add(1, Ordering::AcqRel);
+        pipe.had_writer.store(true, Ordering::Release);
+        pipe.read_wq.notify_all(false);
+        Self(pipe)
+    }
+
+    /// Write all of `data`, blocking while the buffer is full.
+    /// Fails with [`AxError::BrokenPipe`](axerror::AxError::BrokenPipe)
+    /// if all readers are closed before anything is written.
+    pub fn write(&self, data: &[u8]) -> AxResult<usize> {
+        self.0.write(data)
+    }
+}
+
+impl Clone for PipeReader {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Clone for PipeWriter {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.0.readers.fetch_sub(1, Ordering::AcqRel);
+        self.0.write_wq.notify_all(true);
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.0.writers.fetch_sub(1, Ordering::AcqRel);
+        self.0.read_wq.notify_all(true);
+    }
+}
+
+/// Attributes of either end of `pipe`. The size is what is buffered.
+fn pipe_attr(pipe: &Pipe) -> Metadata {
+    Metadata {
+        ino: 0,
+        file_type: FileType::Fifo,
+        mode: 0o600,
+        size: pipe.buffered(),
+        nlink: 1,
+        rdev: None,
+        mtime: None,
+    }
+}
+
+impl VfsFile for PipeReader {
+    fn getattr(&self) -> VfsResult<Metadata> {
+        Ok(pipe_attr(&self.0))
+    }
+    /// Pipes are streams, so `offset` is ignored.
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> VfsResult<usize> {
+        self.0.read(buf)
+    }
+    fn write_at(&self, _offset: usize, _data: &[u8]) -> VfsResult<usize> {
+        ax_err!(PermissionDenied, "write to the read end of a pipe")
+    }
+}
+
+impl VfsFile for PipeWriter {
+    fn getattr(&self) -> VfsResult<Metadata> {
+        Ok(pipe_attr(&self.0))
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> VfsResult<usize> {
+        ax_err!(PermissionDenied, "read from the write end of a pipe")
+    }
+    /// Pipes are streams, so `offset` is ignored.
+    fn write_at(&self, _offset: usize, data: &[u8]) -> VfsResult<usize> {
+        self.0.write(data)
+    }
+}
+
+/// Create an anonymous pipe, returning its read and write ends.
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    let pipe = Pipe::new();
+    (PipeReader::new(pipe.clone()), PipeWriter::new(pipe))
+}
+
+/// Find the in-memory pipe behind a FIFO inode, creating it on first open.
+pub(crate) fn fifo_pipe(mount_path: &str, ino: usize) -> Arc<Pipe> {
+    let mut fifos = FIFOS.lock();
+    fifos.retain(|_, p| p.strong_count() > 0);
+    let key = (mount_path.to_string(), ino);
+    if let Some(pipe) = fifos.get(&key).and_then(Weak::upgrade) {
+        return pipe;
+    }
+    let pipe = Pipe::new();
+    fifos.insert(key, Arc::downgrade(&pipe));
+    pipe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axerror::AxError;
+
+    fn wait_for(flag: &AtomicBool) {
+        while !flag.load(Ordering::Acquire) {
+            axtask::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let _scheduler = crate::test_scheduler();
+        let (reader, writer) = pipe();
+        let mut buf = [0u8; PIPE_BUF_SIZE];
+        assert_eq!(writer.write(&[0xff; 3000]), Ok(3000));
+        assert_eq!(reader.read(&mut buf[..3000]), Ok(3000));
+        // Starts 3000 bytes in, so it goes past the end of the ring.
+        let data: Vec<u8> = (0..PIPE_BUF_SIZE).map(|i| i as u8).collect();
+        assert_eq!(writer.write(&data), Ok(PIPE_BUF_SIZE));
+        assert_eq!(reader.getattr().unwrap().size, PIPE_BUF_SIZE);
+        assert_eq!(reader.read(&mut buf), Ok(PIPE_BUF_SIZE));
+        assert_eq!(buf[..], data[..]);
+    }
+
+    #[test]
+    fn test_eof() {
+        let _scheduler = crate::test_scheduler();
+        let (reader, writer) = pipe();
+        let other = writer.clone();
+        assert_eq!(writer.write(b"abc"), Ok(3));
+        drop(writer);
+        assert_eq!(other.write(b"de"), Ok(2));
+        drop(other);
+        // What is buffered is still read before EOF.
+        let mut buf = [0u8; 8];
+        assert_eq!(reader.read(&mut buf), Ok(5));
+        assert_eq!(&buf[..5], b"abcde");
+        assert_eq!(reader.read(&mut buf), Ok(0));
+    }
+
+    #[test]
+    fn test_broken_pipe() {
+        let _scheduler = crate::test_scheduler();
+        let (reader, writer) = pipe();
+        let other = reader.clone();
+        drop(reader);
+        assert_eq!(writer.write(b"abc"), Ok(3));
+        drop(other);
+        assert_eq!(writer.write(b"abc"), Err(AxError::BrokenPipe));
+    }
+
+    #[test]
+    fn test_blocking_read() {
+        let _scheduler = crate::test_scheduler();
+        static DONE: AtomicBool = AtomicBool::new(false);
+        let (reader, writer) = pipe();
+        axtask::spawn(move || {
+            let mut buf = [0u8; 8];
+            assert_eq!(reader.read(&mut buf), Ok(4));
+            assert_eq!(&buf[..4], b"ping");
+            DONE.store(true, Ordering::Release);
+        });
+        axtask::yield_now();
+        assert!(!DONE.load(Ordering::Acquire));
+        assert_eq!(writer.write(b"ping"), Ok(4));
+        wait_for(&DONE);
+    }
+
+    #[test]
+    fn test_blocking_write() {
+        let _scheduler = crate::test_scheduler();
+        static DONE: AtomicBool = AtomicBool::new(false);
+        let (reader, writer) = pipe();
+        assert_eq!(writer.write(&[1; PIPE_BUF_SIZE]), Ok(PIPE_BUF_SIZE));
+        axtask::spawn(move || {
+            assert_eq!(writer.write(&[2; 100]), Ok(100));
+            DONE.store(true, Ordering::Release);
+        });
+        axtask::yield_now();
+        assert!(!DONE.load(Ordering::Acquire));
+        let mut buf = [0u8; 100];
+        assert_eq!(reader.read(&mut buf), Ok(100));
+        wait_for(&DONE);
+        let mut buf = [0u8; PIPE_BUF_SIZE];
+        assert_eq!(reader.read(&mut buf), Ok(PIPE_BUF_SIZE));
+        assert!(buf[..PIPE_BUF_SIZE - 100].iter().all(|&b| b == 1));
+        assert!(buf[PIPE_BUF_SIZE - 100..].iter().all(|&b| b == 2));
+    }
+
+    #[test]
+    fn test_wakeup_on_close() {
+        let _scheduler = crate::test_scheduler();
+        static DONE: AtomicBool = AtomicBool::new(false);
+        let (reader, writer) = pipe();
+        axtask::spawn(move || {
+            let mut buf = [0u8; 8];
+            assert_eq!(reader.read(&mut buf), Ok(0));
+            DONE.store(true, Ordering::Release);
+        });
+        axtask::yield_now();
+        assert!(!DONE.load(Ordering::Acquire));
+        drop(writer);
+        wait_for(&DONE);
+    }
+
+    #[test]
+    fn test_fifo() {
+        let _scheduler = crate::test_scheduler();
+        let pipe = fifo_pipe("/tmp", 1);
+        let writer = PipeWriter::new(pipe.clone());
+        // No reader has opened it yet, so this is buffered, not EPIPE.
+        assert_eq!(writer.write(b"abc"), Ok(3));
+        let reader = PipeReader::new(fifo_pipe("/tmp", 1));
+        assert!(Arc::ptr_eq(&pipe, &reader.0));
+        assert!(!Arc::ptr_eq(&pipe, &fifo_pipe("/tmp", 2)));
+        let mut buf = [0u8; 8];
+        assert_eq!(reader.read(&mut buf), Ok(3));
+        drop((pipe, reader, writer));
+        assert_eq!(fifo_pipe("/tmp", 1).buffered(), 0);
+    }
+}
@@ -0,0 +1,237 @@
+//! A read-only file system whose files show kernel state.
+//!
+//! File contents are generated when the file is opened, so a reader sees
+//! one consistent snapshot however many reads it takes.
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::fmt::Write;
+use axerror::AxError;
+use vfscore::{DirEntry, FileType, Metadata, VfsFile, VfsFileSystem, VfsResult};
+
+use crate::mount::MOUNTEDFS;
+
+enum Entry {
+    File(fn() -> String),
+    Dir(&'static [(&'static str, Entry)]),
+}
+
+static ROOT: Entry = Entry::Dir(&[
+    ("meminfo", Entry::File(meminfo)),
+    ("mounts", Entry::File(mounts)),
+    ("tasks", Entry::File(tasks)),
+    ("uptime", Entry::File(uptime)),
+    (
+        "xv6fs",
+        Entry::Dir(&[
+            ("bcache", Entry::File(xv6fs_bcache)),
+            ("icache", Entry::File(xv6fs_icache)),
+            ("log", Entry::File(xv6fs_log)),
+        ]),
+    ),
+]);
+
+fn meminfo() -> String {
+    let allocator = axalloc::global_allocator();
+    format!(
+        "used_bytes: {}\navailable_bytes: {}\nused_pages: {}\navailable_pages: {}\n\
+         cached_pages: {}\ndirty_pages: {}\n",
+        allocator.used_bytes(),
+        allocator.available_bytes(),
+        allocator.used_pages(),
+        allocator.available_pages(),
+        crate::cache::cached_pages(),
+        crate::cache::dirty_pages(),
+    )
+}
+
+/// One line per mount: file system, mount point, options.
+fn mounts() -> String {
+    let mut out = String::new();
+    for mounted in MOUNTEDFS.list() {
+        let _ = writeln!(
+            out,
+            "{} {} {}",
+            mounted.fs().name(),
+            mounted.path(),
+            mounted.options()
+        );
+    }
+    out
+}
+
+fn tasks() -> String {
+    let mut out = String::from("ID NAME STATE\n");
+    for task in axtask::tasks() {
+        let name = if task.name.is_empty() { "-" } else { task.name };
+        let _ = writeln!(out, "{} {} {:?}", task.id.as_u64(), name, task.state);
+    }
+    out
+}
+
+/// Seconds since boot.
+fn uptime() -> String {
+    let now = axhal::time::current_time();
+    format!("{}.{:02}\n", now.as_secs(), now.subsec_millis() / 10)
+}
+
+fn xv6fs_bcache() -> String {
+    if !xv6fs_shim::is_mounted() {
+        return "xv6fs is not mounted\n".to_string();
+    }
+    let stats = xv6fs::buffer_cache::BLOCK_CACHE_MANAGER.stats();
+    format!(
+        "capacity: {}\nin_use: {}\nhits: {}\nmisses: {}\n",
+        stats.capacity, stats.in_use, stats.hits, stats.misses
+    )
+}
+
+fn xv6fs_icache() -> String {
+    if !xv6fs_shim::is_mounted() {
+        return "xv6fs is not mounted\n".to_string();
+    }
+    let stats = xv6fs::inode::ICACHE.stats();
+    format!("capacity: {}\nin_use: {}\n", stats.capacity, stats.in_use)
+}
+
+fn xv6fs_log() -> String {
+    if !xv6fs_shim::is_mounted() {
+        return "xv6fs is not mounted\n".to_string();
+    }
+    let stats = xv6fs::log::LOG_MANAGER.stats();
+    format!(
+        "size: {}\nused: {}\npending_ops: {}\nseq: {}\n",
+        stats.size, stats.used, stats.pending_ops, stats.seq
+    )
+}
+
+/// The file system. Mount it at `/proc`.
+pub struct ProcFileSystem;
+
+impl VfsFileSystem for ProcFileSystem {
+    fn name(&self) -> &str {
+        "procfs"
+    }
+
+    fn root(self: Arc<Self>) -> Box<dyn VfsFile> {
+        ProcFile::open_entry(&ROOT)
+    }
+}
+
+/// An open procfs file or directory.
+struct ProcFile {
+    entry: &'static Entry,
+    /// What the file showed when it was opened.
+    content: Vec<u8>,
+}
+
+impl ProcFile {
+    fn open_entry(entry: &'static Entry) -> Box<dyn VfsFile> {
+        let content = match entry {
+            Entry::File(generate) => generate().into_bytes(),
+            Entry::Dir(_) => Vec::new(),
+        };
+        Box::new(Self { entry, content })
+    }
+}
+
+/// Inode number of `entry`: its place in the tree, counting from 1 for the
+/// root in depth-first order.
+fn entry_ino(entry: &Entry) -> usize {
+    fn walk(dir: &Entry, target: &Entry, next: &mut usize) -> Option<usize> {
+        let ino = *next;
+        *next += 1;
+        if core::ptr::eq(dir, target) {
+            return Some(ino);
+        }
+        match dir {
+            Entry::Dir(entries) => entries.iter().find_map(|(_, e)| walk(e, target, next)),
+            Entry::File(_) => None,
+        }
+    }
+    walk(&ROOT, entry, &mut 1).expect("procfs entry outside the tree")
+}
+
+fn entry_type(entry: &Entry) -> FileType {
+    match entry {
+        Entry::File(_) => FileType::File,
+        Entry::Dir(_) => FileType::Dir,
+    }
+}
+
+impl VfsFile for ProcFile {
+    fn getattr(&self) -> VfsResult<Metadata> {
+        let file_type = entry_type(self.entry);
+        Ok(Metadata {
+            ino: entry_ino(self.entry),
+            file_type,
+            mode: if file_type == FileType::Dir { 0o555 } else { 0o444 },
+            size: self.content.len(),
+            nlink: 1,
+            rdev: None,
+            mtime: None,
+        })
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> VfsResult<usize> {
+        if let Entry::Dir(_) = self.entry {
+            return Err(AxError::IsADirectory);
+        }
+        let start = offset.min(self.content.len());
+        let len = (self.content.len() - start).min(buf.len());
+        buf[..len].copy_from_slice(&self.content[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: usize, _data: &[u8]) -> VfsResult<usize> {
+        Err(AxError::ReadOnlyFilesystem)
+    }
+
+    fn lookup(&self, path: &str) -> VfsResult<Box<dyn VfsFile>> {
+        let mut entry = self.entry;
+        for name in path.split('/').filter(|name| !name.is_empty() && *name != ".") {
+            entry = match entry {
+                Entry::Dir(entries) => {
+                    &entries
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .ok_or(AxError::NotFound)?
+                        .1
+                }
+                Entry::File(_) => return Err(AxError::NotADirectory),
+            };
+        }
+        Ok(ProcFile::open_entry(entry))
+    }
+
+    fn create(&self, _path: &str, _ty: FileType, _mode: u16) -> VfsResult<Box<dyn VfsFile>> {
+        Err(AxError::ReadOnlyFilesystem)
+    }
+
+    fn unlink(&self, _path: &str) -> VfsResult {
+        Err(AxError::ReadOnlyFilesystem)
+    }
+
+    fn rmdir(&self, _path: &str) -> VfsResult {
+        Err(AxError::ReadOnlyFilesystem)
+    }
+
+    fn readdir(&self) -> VfsResult<Vec<DirEntry>> {
+        match self.entry {
+            Entry::Dir(entries) => Ok(entries
+                .iter()
+                .map(|(name, entry)| DirEntry {
+                    name: name.to_string(),
+                    ino: entry_ino(entry),
+                    file_type: entry_type(entry),
+                })
+                .collect()),
+            Entry::File(_) => Err(AxError::NotADirectory),
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use axalloc::global_allocator;
+use lazy_init::LazyInit;
 use page_table::PagingIf;
+use spinlock::SpinNoIrq;
 
 use crate::mem::{phys_to_virt, virt_to_phys, MemRegionFlags, PhysAddr, VirtAddr, PAGE_SIZE_4K};
 
@@ -53,3 +55,19 @@ cfg_if::cfg_if! {
         pub type PageTable = page_table::aarch64::A64PageTable<PagingIfImpl>;
     }
 }
+
+static KERNEL_PAGE_TABLE: LazyInit<SpinNoIrq<PageTable>> = LazyInit::new();
+
+/// Make `page_table` the kernel page table. Called once, by the primary
+/// CPU; every CPU then installs it with [`write_page_table_root`].
+///
+/// [`write_page_table_root`]: crate::arch::write_page_table_root
+pub fn init_kernel_page_table(page_table: PageTable) {
+    KERNEL_PAGE_TABLE.init_by(SpinNoIrq::new(page_table));
+}
+
+/// The page table all CPUs run on, for mapping memory into kernel space
+/// after boot. Flush the TLB after changing existing mappings.
+pub fn kernel_page_table() -> &'static SpinNoIrq<PageTable> {
+    &KERNEL_PAGE_TABLE
+}
@@ -45,6 +45,10 @@ pub mod time {
     }
 
     pub fn set_oneshot_timer(deadline_ns: u64) {}
+
+    pub fn epochoffset_nanos() -> u64 {
+        0
+    }
 }
 
 pub mod irq {
@@ -1,6 +1,7 @@
 mod boot;
 mod generic_timer;
 mod pl011;
+mod pl031;
 mod psci;
 
 pub mod console;
@@ -12,6 +13,7 @@ pub mod mp;
 
 pub mod time {
     pub use super::generic_timer::*;
+    pub use super::pl031::epochoffset_nanos;
 }
 
 pub mod misc {
@@ -30,6 +32,7 @@ pub(crate) fn platform_init(cpu_id: usize, _dtb: *const u8) {
     self::irq::init_percpu(cpu_id);
     self::pl011::init();
     self::generic_timer::init();
+    self::pl031::init();
 }
 
 #[cfg(feature = "smp")]
@@ -0,0 +1,26 @@
+//! PL031 real-time clock.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use memory_addr::PhysAddr;
+
+use crate::mem::phys_to_virt;
+
+const RTC_BASE: PhysAddr = PhysAddr::from(0x0901_0000);
+
+/// Data register, counting seconds since the epoch.
+const RTC_DR: usize = 0x00;
+
+/// Wall-clock time at boot, in nanoseconds since the epoch.
+static EPOCHOFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Nanoseconds from the epoch to the time the monotonic clock counts from.
+pub fn epochoffset_nanos() -> u64 {
+    EPOCHOFFSET_NANOS.load(Ordering::Relaxed)
+}
+
+pub(super) fn init() {
+    let dr = (phys_to_virt(RTC_BASE).as_usize() + RTC_DR) as *const u32;
+    let secs = unsafe { dr.read_volatile() } as u64;
+    let now = secs * crate::time::NANOS_PER_SEC;
+    EPOCHOFFSET_NANOS.store(now.saturating_sub(crate::time::current_time_nanos()), Ordering::Relaxed);
+}
@@ -19,6 +19,7 @@ pub(crate) fn platform_init(cpu_id: usize, _dtb: usize) {
     crate::cpu::init_percpu(cpu_id, true);
     self::irq::init();
     self::time::init();
+    self::time::init_rtc();
 }
 
 #[cfg(feature = "smp")]
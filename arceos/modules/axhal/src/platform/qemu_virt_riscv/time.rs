@@ -1,9 +1,21 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use memory_addr::PhysAddr;
 use riscv::register::{sie, time};
 
+use crate::mem::phys_to_virt;
+
 const NANOS_PER_TICK: u64 = crate::time::NANOS_PER_SEC / axconfig::TIMER_FREQUENCY as u64;
 
 pub const TIMER_IRQ_NUM: usize = super::irq::S_TIMER;
 
+/// Goldfish real-time clock, counting nanoseconds since the epoch.
+const RTC_BASE: PhysAddr = PhysAddr::from(0x0010_1000);
+const RTC_TIME_LOW: usize = 0x00;
+const RTC_TIME_HIGH: usize = 0x04;
+
+/// Wall-clock time at boot, in nanoseconds since the epoch.
+static EPOCHOFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
+
 #[inline]
 pub fn current_ticks() -> u64 {
     time::read() as u64
@@ -23,6 +35,22 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
     sbi_rt::set_timer(nanos_to_ticks(deadline_ns));
 }
 
+/// Nanoseconds from the epoch to the time the monotonic clock counts from.
+pub fn epochoffset_nanos() -> u64 {
+    EPOCHOFFSET_NANOS.load(Ordering::Relaxed)
+}
+
+pub(super) fn init_rtc() {
+    let base = phys_to_virt(RTC_BASE).as_usize();
+    // Reading the low half latches the high half.
+    let now = unsafe {
+        let low = ((base + RTC_TIME_LOW) as *const u32).read_volatile() as u64;
+        let high = ((base + RTC_TIME_HIGH) as *const u32).read_volatile() as u64;
+        (high << 32) | low
+    };
+    EPOCHOFFSET_NANOS.store(now.saturating_sub(crate::time::current_time_nanos()), Ordering::Relaxed);
+}
+
 pub fn init() {
     unsafe {
         sie::set_ssoft();
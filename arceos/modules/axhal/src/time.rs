@@ -1,7 +1,8 @@
 pub type TimeValue = core::time::Duration;
 
 pub use crate::platform::time::{
-    current_ticks, nanos_to_ticks, set_oneshot_timer, ticks_to_nanos, TIMER_IRQ_NUM,
+    current_ticks, epochoffset_nanos, nanos_to_ticks, set_oneshot_timer, ticks_to_nanos,
+    TIMER_IRQ_NUM,
 };
 
 pub const MILLIS_PER_SEC: u64 = 1_000;
@@ -17,3 +18,13 @@ pub fn current_time_nanos() -> u64 {
 pub fn current_time() -> TimeValue {
     TimeValue::from_nanos(current_time_nanos())
 }
+
+/// Time since the Unix epoch, from the real-time clock read at boot. Zero
+/// at boot on platforms without one.
+pub fn wall_time_nanos() -> u64 {
+    current_time_nanos() + epochoffset_nanos()
+}
+
+pub fn wall_time() -> TimeValue {
+    TimeValue::from_nanos(wall_time_nanos())
+}
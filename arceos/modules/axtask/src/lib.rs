@@ -36,12 +36,13 @@ mod wait_queue;
 #[cfg(test)]
 mod tests;
 
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
+use spinlock::SpinNoIrq;
 
 use self::run_queue::{AxRunQueue, RUN_QUEUE};
 use self::task::{CurrentTask, TaskInner};
 
-pub use self::task::TaskId;
+pub use self::task::{tasks, TaskId, TaskInfo, TaskState};
 pub use self::wait_queue::WaitQueue;
 
 cfg_if::cfg_if! {
@@ -109,7 +110,21 @@ pub fn sleep_until(deadline: axhal::time::TimeValue) {
     RUN_QUEUE.lock().sleep_until(deadline);
 }
 
+/// Run by every task as it exits.
+static EXIT_HOOKS: SpinNoIrq<Vec<fn(TaskId)>> = SpinNoIrq::new(Vec::new());
+
+/// Call `hook` with the id of every task that exits, to release what it
+/// held. It runs in the exiting task, which may still block.
+pub fn on_exit(hook: fn(TaskId)) {
+    EXIT_HOOKS.lock().push(hook);
+}
+
 pub fn exit(exit_code: i32) -> ! {
+    let id = current().id();
+    let hooks = EXIT_HOOKS.lock().clone();
+    for hook in hooks {
+        hook(id);
+    }
     RUN_QUEUE.lock().exit_current(exit_code)
 }
 
@@ -1,4 +1,4 @@
-use alloc::{boxed::Box, sync::Arc};
+use alloc::{boxed::Box, string::String, sync::{Arc, Weak}, vec::Vec};
 use core::ops::Deref;
 use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering, AtomicU32};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
@@ -8,6 +8,7 @@ use core::sync::atomic::AtomicUsize;
 
 use axhal::arch::TaskContext;
 use memory_addr::{align_up_4k, VirtAddr};
+use spinlock::SpinNoIrq;
 
 use crate::{AxTask, AxTaskRef};
 
@@ -16,7 +17,7 @@ pub struct TaskId(u64);
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
     Running = 1,
     Ready = 2,
     Blocked = 3,
@@ -43,6 +44,10 @@ pub struct TaskInner {
     kstack: Option<TaskStack>,
     ctx: UnsafeCell<TaskContext>,
     channel: AtomicU32,
+
+    /// Current working directory, an absolute path. A spawned task starts
+    /// in the directory of the task that spawned it.
+    cwd: SpinNoIrq<String>,
 }
 
 impl TaskId {
@@ -68,6 +73,38 @@ impl const From<u8> for TaskState {
     }
 }
 
+/// Every task not yet dropped, for [`tasks`].
+static ALL_TASKS: SpinNoIrq<Vec<Weak<AxTask>>> = SpinNoIrq::new(Vec::new());
+
+/// A snapshot of a task, as listed by [`tasks`].
+#[derive(Debug, Clone, Copy)]
+pub struct TaskInfo {
+    pub id: TaskId,
+    pub name: &'static str,
+    pub state: TaskState,
+}
+
+/// All live tasks, in creation order.
+pub fn tasks() -> Vec<TaskInfo> {
+    ALL_TASKS
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|task| TaskInfo {
+            id: task.id(),
+            name: task.name,
+            state: task.state(),
+        })
+        .collect()
+}
+
+fn register(task: AxTaskRef) -> AxTaskRef {
+    let mut all = ALL_TASKS.lock();
+    all.retain(|t| t.strong_count() > 0);
+    all.push(Arc::downgrade(&task));
+    task
+}
+
 unsafe impl Send for TaskInner {}
 unsafe impl Sync for TaskInner {}
 
@@ -83,11 +120,26 @@ impl TaskInner {
     pub fn id_name(&self) -> alloc::string::String {
         alloc::format!("Task({}, {:?})", self.id.as_u64(), self.name)
     }
+
+    /// The current working directory.
+    pub fn cwd(&self) -> String {
+        self.cwd.lock().clone()
+    }
+
+    /// Change the current working directory. `cwd` must be absolute; the
+    /// file system layer checks that it exists.
+    pub fn set_cwd(&self, cwd: &str) {
+        *self.cwd.lock() = String::from(cwd);
+    }
 }
 
 // private methods
 impl TaskInner {
-    const fn new_common(id: TaskId, name: &'static str) -> Self {
+    fn new_common(id: TaskId, name: &'static str) -> Self {
+        let cwd = match crate::current_may_uninit() {
+            Some(curr) => curr.cwd(),
+            None => String::from("/"),
+        };
         Self {
             id,
             name,
@@ -104,6 +156,7 @@ impl TaskInner {
             kstack: None,
             ctx: UnsafeCell::new(TaskContext::new()),
             channel:AtomicU32::new(0),
+            cwd: SpinNoIrq::new(cwd),
         }
     }
 
@@ -120,7 +173,7 @@ impl TaskInner {
         if name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(AxTask::new(t))
+        register(Arc::new(AxTask::new(t)))
     }
 
     pub(crate) fn new_init(name: &'static str) -> AxTaskRef {
@@ -130,7 +183,7 @@ impl TaskInner {
         if name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(AxTask::new(t))
+        register(Arc::new(AxTask::new(t)))
     }
 
     #[inline]
@@ -1,4 +1,4 @@
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Mutex, Once};
 
 use crate::{self as axtask, current, WaitQueue};
@@ -101,3 +101,23 @@ fn test_wait_queue() {
     assert!(!current().in_timer_list());
     assert!(!current().in_wait_queue());
 }
+
+#[test]
+fn test_exit_hook() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(|| axtask::init_scheduler());
+
+    static SPAWNED: AtomicU64 = AtomicU64::new(0);
+    static EXITED: AtomicU64 = AtomicU64::new(0);
+
+    axtask::on_exit(|id| {
+        if id.as_u64() == SPAWNED.load(Ordering::Relaxed) {
+            EXITED.store(id.as_u64(), Ordering::Relaxed);
+        }
+    });
+    axtask::spawn(|| SPAWNED.store(current().id().as_u64(), Ordering::Relaxed));
+    while EXITED.load(Ordering::Relaxed) == 0 {
+        axtask::yield_now();
+    }
+    assert_eq!(EXITED.load(Ordering::Relaxed), SPAWNED.load(Ordering::Relaxed));
+}
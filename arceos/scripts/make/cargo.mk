@@ -13,6 +13,10 @@ else
 endif
 
 features-$(FS) += libax/fs
+features-$(RAMDISK) += libax/ramdisk
+ifneq ($(RAMDISK_IMAGE),)
+  features-y += libax/ramdisk-image
+endif
 features-$(NET) += libax/net
 features-$(GRAPHIC) += libax/display
 
@@ -0,0 +1,29 @@
+#ifndef __DIRENT_H__
+#define __DIRENT_H__
+
+#include <sys/types.h>
+
+#define DT_UNKNOWN 0
+#define DT_FIFO    1
+#define DT_CHR     2
+#define DT_DIR     4
+#define DT_BLK     6
+#define DT_REG     8
+#define DT_LNK     10
+#define DT_SOCK    12
+
+struct dirent {
+    ino_t d_ino;             /* inode number */
+    off_t d_off;             /* not used */
+    unsigned short d_reclen; /* length of this record */
+    unsigned char d_type;    /* type of file, one of DT_* */
+    char d_name[256];        /* NUL-terminated file name */
+};
+
+typedef struct __dirstream DIR;
+
+DIR *opendir(const char *name);
+struct dirent *readdir(DIR *dirp);
+int closedir(DIR *dirp);
+
+#endif // __DIRENT_H__
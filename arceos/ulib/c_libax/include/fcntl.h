@@ -185,9 +185,9 @@
 
 #define F_LINUX_SPECIFIC_BASE 1024
 
-typedef unsigned int __kernel_off_t;
+typedef long long __kernel_off_t;
 
-typedef unsigned int __kernel_pid_t;
+typedef int __kernel_pid_t;
 
 #ifndef HAVE_ARCH_STRUCT_FLOCK
 struct flock {
@@ -0,0 +1,8 @@
+#ifndef __SYS_FILE_H__
+#define __SYS_FILE_H__
+
+#include <fcntl.h>
+
+int flock(int fd, int operation);
+
+#endif // __SYS_FILE_H__
@@ -4,10 +4,6 @@
 #include <sys/types.h>
 #include <time.h>
 
-#define O_EXCL   1 // TODO;
-#define O_CREAT  2 // TODO;
-#define O_RDONLY 3 // TODO;
-
 struct stat {
     dev_t st_dev;         /* ID of device containing file*/
     ino_t st_ino;         /* inode number*/
@@ -24,6 +20,9 @@ struct stat {
     time_t st_ctime;      /* time of last status change*/
 };
 
+int stat(const char *path, struct stat *buf);
+int lstat(const char *path, struct stat *buf);
+int fstat(int fd, struct stat *buf);
 int fchmod(int fd, mode_t mode);
 int mkdir(const char *pathname, mode_t mode);
 
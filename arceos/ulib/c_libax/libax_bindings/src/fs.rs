@@ -0,0 +1,424 @@
+//! File system calls for the C library. They return a negated `errno` on
+//! failure, which the C side stores in `errno` before returning -1.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use core::ffi::{c_char, c_int, CStr};
+use core::ops::RangeInclusive;
+
+use axerror::LinuxError;
+use libax::fs::{self, fd, Error, Metadata, ReadDir};
+use libax::io::{self, Read, Write};
+
+const F_GETFD: c_int = 1;
+const F_SETFD: c_int = 2;
+const F_GETLK: c_int = 5;
+const F_SETLK: c_int = 6;
+const F_SETLKW: c_int = 7;
+
+const DT_FIFO: u8 = 1;
+const DT_CHR: u8 = 2;
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+const DT_LNK: u8 = 10;
+
+/// Run `f` and return what it gives, or the negated `errno` of its error.
+fn ax_call(f: impl FnOnce() -> Result<isize, LinuxError>) -> isize {
+    match f() {
+        Ok(ret) => ret,
+        Err(e) => -(e as isize),
+    }
+}
+
+/// Whether `fd` is one of `std` and still the console, not redirected by
+/// `dup2`.
+fn is_console(fd: c_int, std: RangeInclusive<c_int>) -> bool {
+    std.contains(&fd) && fd::get_flags(fd as usize).is_err()
+}
+
+/// Borrow a NUL-terminated C path as a `str`.
+unsafe fn c_path<'a>(path: *const c_char) -> Result<&'a str, LinuxError> {
+    if path.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    CStr::from_ptr(path)
+        .to_str()
+        .map_err(|_| LinuxError::EINVAL)
+}
+
+/// File metadata, copied into `struct stat` by the C side.
+#[repr(C)]
+pub struct AxStat {
+    pub st_ino: u64,
+    /// File type and permission bits, as in `st_mode`.
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_rdev: u32,
+    pub st_size: u64,
+    /// Size in 512-byte blocks.
+    pub st_blocks: u64,
+    /// Time of the last change to the data in seconds since the epoch, 0
+    /// if the file system keeps none.
+    pub st_mtime: i64,
+}
+
+impl From<Metadata> for AxStat {
+    fn from(attr: Metadata) -> Self {
+        let ty = attr.file_type();
+        let type_bits = if ty.is_dir() {
+            0o040000
+        } else if ty.is_symlink() {
+            0o120000
+        } else if ty.is_fifo() {
+            0o010000
+        } else if ty.is_device() {
+            0o020000
+        } else {
+            0o100000
+        };
+        let rdev = attr.rdev().map_or(0, |(major, minor)| ((major as u32) << 8) | minor as u32);
+        Self {
+            st_ino: attr.ino(),
+            st_mode: type_bits | attr.permissions().mode() as u32,
+            st_nlink: attr.nlink() as u32,
+            st_rdev: rdev,
+            st_size: attr.len(),
+            st_blocks: attr.len().div_ceil(512),
+            st_mtime: attr.modified().map_or(0, |mtime| mtime.as_secs() as i64),
+        }
+    }
+}
+
+unsafe fn write_stat(attr: Result<Metadata, Error>, buf: *mut AxStat) -> Result<isize, LinuxError> {
+    if buf.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    *buf = attr?.into();
+    Ok(0)
+}
+
+/// Open the file at `path` with `O_*` flags and return its descriptor.
+#[no_mangle]
+pub unsafe extern "C" fn ax_open(path: *const c_char, flags: c_int) -> c_int {
+    ax_call(|| Ok(fd::open(c_path(path)?, flags as u32)? as isize)) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ax_read(fd: c_int, buf: *mut u8, count: usize) -> isize {
+    ax_call(|| {
+        let buf = core::slice::from_raw_parts_mut(buf, count);
+        if is_console(fd, 0..=0) {
+            return Ok(io::stdin().read(buf)? as isize);
+        }
+        Ok(fd::read(fd as usize, buf)? as isize)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ax_write(fd: c_int, buf: *const u8, count: usize) -> isize {
+    ax_call(|| {
+        let buf = core::slice::from_raw_parts(buf, count);
+        if is_console(fd, 1..=2) {
+            return Ok(io::stdout().write(buf)? as isize);
+        }
+        Ok(fd::write(fd as usize, buf)? as isize)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn ax_close(fd: c_int) -> c_int {
+    ax_call(|| {
+        fd::close(fd as usize)?;
+        Ok(0)
+    }) as c_int
+}
+
+/// Move the offset of `fd` and return the new one.
+#[no_mangle]
+pub extern "C" fn ax_lseek(fd: c_int, offset: i64, whence: c_int) -> i64 {
+    ax_call(|| Ok(fd::lseek(fd as usize, offset as isize, whence as u32)? as isize)) as i64
+}
+
+#[no_mangle]
+pub extern "C" fn ax_ftruncate(fd: c_int, length: i64) -> c_int {
+    ax_call(|| {
+        let length = usize::try_from(length).map_err(|_| LinuxError::EINVAL)?;
+        fd::ftruncate(fd as usize, length)?;
+        Ok(0)
+    }) as c_int
+}
+
+/// Flush the file behind `fd` to stable storage.
+#[no_mangle]
+pub extern "C" fn ax_fsync(fd: c_int) -> c_int {
+    ax_call(|| {
+        fd::fsync(fd as usize)?;
+        Ok(0)
+    }) as c_int
+}
+
+/// `F_GETFD` and `F_SETFD`; other commands are left to the C side.
+#[no_mangle]
+pub extern "C" fn ax_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
+    ax_call(|| match cmd {
+        F_GETFD => Ok(fd::get_flags(fd as usize)? as isize),
+        F_SETFD => {
+            fd::set_flags(fd as usize, arg as u32)?;
+            Ok(0)
+        }
+        _ => Err(LinuxError::EINVAL),
+    }) as c_int
+}
+
+/// Take, convert or release the whole-file lock of `fd`, as `flock` does.
+#[no_mangle]
+pub extern "C" fn ax_flock(fd: c_int, op: c_int) -> c_int {
+    ax_call(|| {
+        fd::flock(fd as usize, op as u32)?;
+        Ok(0)
+    }) as c_int
+}
+
+/// A byte-range lock, copied to and from `struct flock` by the C side.
+#[repr(C)]
+pub struct AxFlock {
+    pub l_type: c_int,
+    pub l_whence: c_int,
+    pub l_start: i64,
+    pub l_len: i64,
+    /// Id of the task holding the lock `F_GETLK` found.
+    pub l_pid: u64,
+}
+
+/// `F_GETLK`, `F_SETLK` and `F_SETLKW`.
+#[no_mangle]
+pub unsafe extern "C" fn ax_fcntl_lock(fd: c_int, cmd: c_int, lock: *mut AxFlock) -> c_int {
+    ax_call(|| {
+        if lock.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let lock = &mut *lock;
+        let mut flock = fd::Flock {
+            kind: lock.l_type as u32,
+            whence: lock.l_whence as u32,
+            start: lock.l_start,
+            len: lock.l_len,
+            owner: 0,
+        };
+        match cmd {
+            F_GETLK => {
+                fd::get_lock(fd as usize, &mut flock)?;
+                lock.l_type = flock.kind as c_int;
+                lock.l_whence = flock.whence as c_int;
+                lock.l_start = flock.start;
+                lock.l_len = flock.len;
+                lock.l_pid = flock.owner;
+            }
+            F_SETLK | F_SETLKW => fd::set_lock(fd as usize, &flock, cmd == F_SETLKW)?,
+            _ => return Err(LinuxError::EINVAL),
+        }
+        Ok(0)
+    }) as c_int
+}
+
+/// Map `len` bytes of the file behind `fd` from `offset` and store the
+/// address in `addr`. The address does not fit the negated `errno`
+/// convention, as kernel addresses are negative.
+#[cfg(feature = "paging")]
+#[no_mangle]
+pub unsafe extern "C" fn ax_mmap(
+    fd: c_int,
+    len: usize,
+    prot: c_int,
+    flags: c_int,
+    offset: i64,
+    addr: *mut *mut core::ffi::c_void,
+) -> c_int {
+    ax_call(|| {
+        let offset = usize::try_from(offset).map_err(|_| LinuxError::EINVAL)?;
+        let start = fd::mmap(fd as usize, len, prot as u32, flags as u32, offset)?;
+        *addr = start as *mut core::ffi::c_void;
+        Ok(0)
+    }) as c_int
+}
+
+#[cfg(feature = "paging")]
+#[no_mangle]
+pub extern "C" fn ax_munmap(addr: *mut core::ffi::c_void, len: usize) -> c_int {
+    ax_call(|| {
+        fd::munmap(addr as usize, len)?;
+        Ok(0)
+    }) as c_int
+}
+
+#[cfg(feature = "paging")]
+#[no_mangle]
+pub extern "C" fn ax_msync(addr: *mut core::ffi::c_void, len: usize) -> c_int {
+    ax_call(|| {
+        fd::msync(addr as usize, len)?;
+        Ok(0)
+    }) as c_int
+}
+
+/// Metadata of the file at `path`, following symlinks.
+#[no_mangle]
+pub unsafe extern "C" fn ax_stat(path: *const c_char, buf: *mut AxStat) -> c_int {
+    ax_call(|| write_stat(fs::metadata(c_path(path)?), buf)) as c_int
+}
+
+/// Metadata of the file at `path`, or of the symlink itself if it is one.
+#[no_mangle]
+pub unsafe extern "C" fn ax_lstat(path: *const c_char, buf: *mut AxStat) -> c_int {
+    ax_call(|| write_stat(fs::symlink_metadata(c_path(path)?), buf)) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ax_fstat(fd: c_int, buf: *mut AxStat) -> c_int {
+    ax_call(|| write_stat(fd::fstat(fd as usize), buf)) as c_int
+}
+
+/// Whether the file at `path` exists. There is only one user, who may do
+/// anything, so `mode` is not checked.
+#[no_mangle]
+pub unsafe extern "C" fn ax_access(path: *const c_char, _mode: c_int) -> c_int {
+    ax_call(|| {
+        fs::metadata(c_path(path)?)?;
+        Ok(0)
+    }) as c_int
+}
+
+/// Set the permission bits of the file behind `fd`. Fails with `ENOSYS`
+/// where the file system keeps none.
+#[no_mangle]
+pub extern "C" fn ax_fchmod(fd: c_int, mode: u32) -> c_int {
+    ax_call(|| {
+        fd::fchmod(fd as usize, mode as u16 & 0o7777)?;
+        Ok(0)
+    }) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ax_mkdir(path: *const c_char, mode: u32) -> c_int {
+    ax_call(|| {
+        let path = c_path(path)?;
+        fs::create_dir(path)?;
+        // Not every file system keeps permission bits.
+        match fs::set_permissions(path, fs::Permissions::from_mode(mode as u16 & 0o7777)) {
+            Ok(()) | Err(Error::Unsupported) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ax_unlink(path: *const c_char) -> c_int {
+    ax_call(|| {
+        fs::remove_file(c_path(path)?)?;
+        Ok(0)
+    }) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ax_rmdir(path: *const c_char) -> c_int {
+    ax_call(|| {
+        fs::remove_dir(c_path(path)?)?;
+        Ok(0)
+    }) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ax_rename(old_path: *const c_char, new_path: *const c_char) -> c_int {
+    ax_call(|| {
+        fs::rename(c_path(old_path)?, c_path(new_path)?)?;
+        Ok(0)
+    }) as c_int
+}
+
+/// Copy the target of the symlink at `path` into `buf`, without a NUL.
+/// Returns the number of bytes copied; a long target is cut short.
+#[no_mangle]
+pub unsafe extern "C" fn ax_readlink(path: *const c_char, buf: *mut c_char, size: usize) -> isize {
+    ax_call(|| {
+        let target = fs::read_link(c_path(path)?)?;
+        let len = target.len().min(size);
+        core::ptr::copy_nonoverlapping(target.as_ptr(), buf as *mut u8, len);
+        Ok(len as isize)
+    })
+}
+
+/// Copy the current working directory, NUL-terminated, into `buf`.
+#[no_mangle]
+pub unsafe extern "C" fn ax_getcwd(buf: *mut c_char, size: usize) -> c_int {
+    ax_call(|| {
+        let cwd = fs::getcwd().into_string();
+        if buf.is_null() || cwd.len() >= size {
+            return Err(LinuxError::ERANGE);
+        }
+        let buf = core::slice::from_raw_parts_mut(buf as *mut u8, size);
+        buf[..cwd.len()].copy_from_slice(cwd.as_bytes());
+        buf[cwd.len()] = 0;
+        Ok(0)
+    }) as c_int
+}
+
+/// An open directory stream, behind `DIR`.
+pub struct AxDir(ReadDir);
+
+/// A directory entry, copied into `struct dirent` by the C side.
+#[repr(C)]
+pub struct AxDirent {
+    pub d_ino: u64,
+    /// One of the `DT_*` types.
+    pub d_type: u8,
+    /// NUL-terminated, cut short if longer than 255 bytes.
+    pub d_name: [c_char; 256],
+}
+
+/// Open the directory at `path` and store its stream in `dir`.
+#[no_mangle]
+pub unsafe extern "C" fn ax_opendir(path: *const c_char, dir: *mut *mut AxDir) -> c_int {
+    ax_call(|| {
+        let path = c_path(path)?;
+        if !fs::metadata(path)?.is_dir() {
+            return Err(LinuxError::ENOTDIR);
+        }
+        *dir = Box::into_raw(Box::new(AxDir(fs::read_dir(path)?)));
+        Ok(0)
+    }) as c_int
+}
+
+/// Store the next entry of `dir` in `entry`. Returns 1, or 0 at the end.
+#[no_mangle]
+pub unsafe extern "C" fn ax_readdir(dir: *mut AxDir, entry: *mut AxDirent) -> c_int {
+    ax_call(|| {
+        let next = match (*dir).0.next() {
+            Some(next) => next?,
+            None => return Ok(0),
+        };
+        let ty = next.file_type()?;
+        let entry = &mut *entry;
+        entry.d_ino = next.ino();
+        entry.d_type = if ty.is_dir() {
+            DT_DIR
+        } else if ty.is_symlink() {
+            DT_LNK
+        } else if ty.is_fifo() {
+            DT_FIFO
+        } else if ty.is_device() {
+            DT_CHR
+        } else {
+            DT_REG
+        };
+        let name = next.file_name();
+        let len = name.len().min(entry.d_name.len() - 1);
+        core::ptr::copy_nonoverlapping(name.as_ptr(), entry.d_name.as_mut_ptr() as *mut u8, len);
+        entry.d_name[len] = 0;
+        Ok(1)
+    }) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ax_closedir(dir: *mut AxDir) {
+    drop(Box::from_raw(dir));
+}
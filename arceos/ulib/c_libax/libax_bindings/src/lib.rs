@@ -4,6 +4,9 @@
 #[cfg(feature = "alloc")]
 mod malloc;
 
+#[cfg(feature = "fs")]
+mod fs;
+
 use core::ffi::{c_char, c_int};
 
 #[macro_use]
@@ -33,3 +36,6 @@ pub extern "C" fn ax_panic() -> ! {
 
 #[cfg(feature = "alloc")]
 pub use malloc::{ax_free, ax_malloc};
+
+#[cfg(feature = "fs")]
+pub use fs::*;
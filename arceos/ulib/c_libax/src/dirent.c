@@ -0,0 +1,49 @@
+#include <dirent.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include <libax.h>
+
+#include "syscall.h"
+
+#if defined(AX_CONFIG_FS) && defined(AX_CONFIG_ALLOC)
+
+struct __dirstream {
+    struct AxDir *dir;
+    struct dirent entry;
+};
+
+DIR *opendir(const char *name)
+{
+    DIR *dirp = malloc(sizeof(DIR));
+    if (!dirp)
+        return NULL;
+    if (__syscall_ret(ax_opendir(name, &dirp->dir)) < 0) {
+        free(dirp);
+        return NULL;
+    }
+    return dirp;
+}
+
+// `.` and `..` are not listed.
+struct dirent *readdir(DIR *dirp)
+{
+    struct AxDirent entry;
+    if (__syscall_ret(ax_readdir(dirp->dir, &entry)) <= 0)
+        return NULL;
+    dirp->entry.d_ino = entry.d_ino;
+    dirp->entry.d_off = 0;
+    dirp->entry.d_reclen = sizeof(struct dirent);
+    dirp->entry.d_type = entry.d_type;
+    strncpy(dirp->entry.d_name, entry.d_name, sizeof(dirp->entry.d_name));
+    return &dirp->entry;
+}
+
+int closedir(DIR *dirp)
+{
+    ax_closedir(dirp->dir);
+    free(dirp);
+    return 0;
+}
+
+#endif // AX_CONFIG_FS && AX_CONFIG_ALLOC
@@ -0,0 +1,14 @@
+#include <errno.h>
+
+#include "syscall.h"
+
+int errno;
+
+long __syscall_ret(long ret)
+{
+    if (ret < 0) {
+        errno = -ret;
+        return -1;
+    }
+    return ret;
+}
@@ -1,17 +1,60 @@
+#include <errno.h>
 #include <fcntl.h>
-#include <stdio.h>
+#include <stdarg.h>
+#include <sys/file.h>
+
+#include <libax.h>
+
+#include "syscall.h"
+
+#ifdef AX_CONFIG_FS
 
-// TODO:
 int fcntl(int fd, int cmd, ... /* arg */)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    va_list ap;
+    va_start(ap, cmd);
+    unsigned long arg = va_arg(ap, unsigned long);
+    va_end(ap);
+
+    switch (cmd) {
+    case F_GETFD:
+    case F_SETFD:
+        return __syscall_ret(ax_fcntl(fd, cmd, arg));
+    case F_GETLK:
+    case F_SETLK:
+    case F_SETLKW: {
+        struct flock *fl = (struct flock *)arg;
+        struct AxFlock lock = {
+            .l_type = fl->l_type,
+            .l_whence = fl->l_whence,
+            .l_start = fl->l_start,
+            .l_len = fl->l_len,
+        };
+        int ret = __syscall_ret(ax_fcntl_lock(fd, cmd, &lock));
+        if (ret == 0 && cmd == F_GETLK) {
+            fl->l_type = lock.l_type;
+            fl->l_whence = lock.l_whence;
+            fl->l_start = lock.l_start;
+            fl->l_len = lock.l_len;
+            fl->l_pid = lock.l_pid;
+        }
+        return ret;
+    }
+    default:
+        errno = EINVAL;
+        return -1;
+    }
+}
+
+int flock(int fd, int operation)
+{
+    return __syscall_ret(ax_flock(fd, operation));
 }
 
-// TODO:
+// New files get mode 0644, whatever the `mode` argument says.
 int open(const char *filename, int flags, ...)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    printf("open file: %s\n", filename);
-    return -1;
+    return __syscall_ret(ax_open(filename, flags));
 }
+
+#endif // AX_CONFIG_FS
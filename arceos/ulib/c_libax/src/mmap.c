@@ -1,7 +1,38 @@
 #include <stddef.h>
 #include <stdio.h>
+#include <sys/mman.h>
 #include <sys/types.h>
 
+#include <libax.h>
+
+#include "syscall.h"
+
+#if defined(AX_CONFIG_FS) && defined(AX_CONFIG_PAGING)
+
+// Only files can be mapped, and `addr` is never used, as with no
+// `MAP_FIXED` it is only a hint.
+void *mmap(void *addr, size_t len, int prot, int flags, int fildes, off_t off)
+{
+    void *start;
+    if (__syscall_ret(ax_mmap(fildes, len, prot, flags, off, &start)) < 0)
+        return MAP_FAILED;
+    return start;
+}
+
+// Only whole mappings can be unmapped.
+int munmap(void *addr, size_t length)
+{
+    return __syscall_ret(ax_munmap(addr, length));
+}
+
+// Always synchronous.
+int msync(void *addr, size_t length, int flags)
+{
+    return __syscall_ret(ax_msync(addr, length));
+}
+
+#else
+
 // TODO:
 void *mmap(void *addr, size_t len, int prot, int flags, int fildes, off_t off)
 {
@@ -17,6 +48,8 @@ int munmap(void *addr, size_t length)
     return 0;
 }
 
+#endif // AX_CONFIG_FS && AX_CONFIG_PAGING
+
 // TODO:
 void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
              ... /* void *new_address */)
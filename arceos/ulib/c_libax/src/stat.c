@@ -1,16 +1,21 @@
 #include <stdio.h>
+#include <sys/stat.h>
 #include <sys/types.h>
 
-// TODO:
+#include <libax.h>
+
+#include "syscall.h"
+
+#ifdef AX_CONFIG_FS
+
 int fchmod(int fd, mode_t mode)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    return __syscall_ret(ax_fchmod(fd, mode));
 }
 
-// TODO:
 int mkdir(const char *pathname, mode_t mode)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    return __syscall_ret(ax_mkdir(pathname, mode));
 }
+
+#endif // AX_CONFIG_FS
@@ -0,0 +1,8 @@
+#ifndef __SYSCALL_H__
+#define __SYSCALL_H__
+
+// Turn the negated `errno` an `ax_*` call returns on failure into -1 with
+// `errno` set. Other values are passed through.
+long __syscall_ret(long ret);
+
+#endif // __SYSCALL_H__
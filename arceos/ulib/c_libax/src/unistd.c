@@ -1,16 +1,14 @@
+#include <errno.h>
 #include <stdio.h>
 #include <sys/types.h>
 #include <unistd.h>
 
-// TODO:
-long int sysconf(int name)
-{
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
-}
+#include <libax.h>
+
+#include "syscall.h"
 
 // TODO:
-off_t lseek(int fd, off_t offset, int whence)
+long int sysconf(int name)
 {
     printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
     return 0;
@@ -32,106 +30,111 @@ pid_t getpid(void)
 }
 
 // TODO:
-int fsync(int fd)
+int fchown(int fd, uid_t owner, gid_t group)
 {
     printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
     return 0;
 }
 
 // TODO:
-int close(int fd)
+uid_t geteuid(void)
 {
     printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return -1;
+    return 0;
+}
+
+#ifdef AX_CONFIG_FS
+
+off_t lseek(int fd, off_t offset, int whence)
+{
+    return __syscall_ret(ax_lseek(fd, offset, whence));
+}
+
+int fsync(int fd)
+{
+    return __syscall_ret(ax_fsync(fd));
+}
+
+int close(int fd)
+{
+    return __syscall_ret(ax_close(fd));
 }
 
-// TODO:
 int access(const char *pathname, int mode)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    return __syscall_ret(ax_access(pathname, mode));
 }
 
-// TODO:
 char *getcwd(char *buf, size_t size)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
+    if (__syscall_ret(ax_getcwd(buf, size)) < 0)
+        return 0;
+    return buf;
+}
+
+static int __fill_stat(int ret, const struct AxStat *st, struct stat *buf)
+{
+    if (__syscall_ret(ret) < 0)
+        return -1;
+    *buf = (struct stat){
+        .st_ino = st->st_ino,
+        .st_mode = st->st_mode,
+        .st_nlink = st->st_nlink,
+        .st_rdev = st->st_rdev,
+        .st_size = st->st_size,
+        .st_blksize = 512,
+        .st_blocks = st->st_blocks,
+        .st_mtime = st->st_mtime,
+    };
     return 0;
 }
 
-// TODO:
 int lstat(const char *path, struct stat *buf)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    struct AxStat st;
+    return __fill_stat(ax_lstat(path, &st), &st, buf);
 }
 
-// TODO:
 int stat(const char *path, struct stat *buf)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    struct AxStat st;
+    return __fill_stat(ax_stat(path, &st), &st, buf);
 }
 
-// TODO:
 int fstat(int fd, struct stat *buf)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    struct AxStat st;
+    return __fill_stat(ax_fstat(fd, &st), &st, buf);
 }
 
-// TODO:
 int ftruncate(int fd, off_t length)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    return __syscall_ret(ax_ftruncate(fd, length));
 }
 
-// TODO:
 ssize_t read(int fd, void *buf, size_t count)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    return __syscall_ret(ax_read(fd, buf, count));
 }
 
-// TODO:
 ssize_t write(int fd, const void *buf, size_t count)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    return __syscall_ret(ax_write(fd, buf, count));
 }
 
-// TODO:
 int unlink(const char *pathname)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    return __syscall_ret(ax_unlink(pathname));
 }
 
-// TODO:
 int rmdir(const char *pathname)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
-}
-
-// TODO:
-int fchown(int fd, uid_t owner, gid_t group)
-{
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    return __syscall_ret(ax_rmdir(pathname));
 }
 
-// TODO:
-uid_t geteuid(void)
-{
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
-}
-
-// TODO:
 ssize_t readlink(const char *path, char *buf, size_t bufsiz)
 {
-    printf("%s%s\n", "Error: no ax_call implementation for ", __func__);
-    return 0;
+    return __syscall_ret(ax_readlink(path, buf, bufsiz));
 }
+
+#endif // AX_CONFIG_FS
@@ -0,0 +1,71 @@
+//! Directory listings, as in `std::fs`.
+
+use super::{FileType, Metadata, Path, PathBuf, Result};
+use alloc::string::String;
+use alloc::vec::IntoIter;
+
+/// Iterator over the entries of a directory, from [`read_dir`](super::read_dir).
+/// `.` and `..` are left out.
+pub struct ReadDir {
+    dir: PathBuf,
+    entries: IntoIter<axfs::DirEntry>,
+}
+
+impl ReadDir {
+    pub(crate) fn new(dir: &Path) -> Result<Self> {
+        let entries = axfs::read_dir(dir.as_str())?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            entries: entries.into_iter(),
+        })
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        Some(Ok(DirEntry {
+            path: self.dir.join(&entry.name),
+            name: entry.name,
+            ino: entry.ino as u64,
+            file_type: FileType(entry.file_type),
+        }))
+    }
+}
+
+/// An entry of a directory.
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    path: PathBuf,
+    name: String,
+    ino: u64,
+    file_type: FileType,
+}
+
+impl DirEntry {
+    /// The directory joined with the entry name.
+    pub fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    pub fn file_name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Type of the entry itself; a symlink is not followed.
+    pub fn file_type(&self) -> Result<FileType> {
+        Ok(self.file_type)
+    }
+
+    /// Metadata of the entry itself; a symlink is not followed.
+    pub fn metadata(&self) -> Result<Metadata> {
+        super::symlink_metadata(&self.path)
+    }
+
+    /// Inode number.
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+}
@@ -0,0 +1,20 @@
+//! POSIX-style file descriptors, shared with the C library. See
+//! [`axfs::fd`] for how they behave.
+
+use super::{Metadata, Result};
+
+pub use axfs::fd::{
+    close, close_on_exec, dup, dup2, fchmod, flock, fsync, ftruncate, get_flags, get_lock, lseek,
+    open, read, set_flags, set_lock, write, Flock, FD_CLOEXEC, F_RDLCK, F_UNLCK, F_WRLCK, LOCK_EX,
+    LOCK_NB, LOCK_SH, LOCK_UN, O_APPEND, O_CLOEXEC, O_CREAT, O_EXCL, O_RDONLY, O_RDWR, O_TRUNC,
+    O_WRONLY, SEEK_CUR, SEEK_END, SEEK_SET,
+};
+#[cfg(feature = "paging")]
+pub use axfs::mmap::{
+    mmap, msync, munmap, MAP_PRIVATE, MAP_SHARED, PROT_EXEC, PROT_READ, PROT_WRITE,
+};
+
+/// Metadata of the file behind `fd`.
+pub fn fstat(fd: usize) -> Result<Metadata> {
+    axfs::fd::fstat(fd).map(Metadata)
+}
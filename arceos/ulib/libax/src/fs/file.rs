@@ -0,0 +1,238 @@
+//! Open files, as in `std::fs`.
+
+use super::{Metadata, Path, Permissions, Result};
+use crate::io::{Read, Seek, SeekFrom, Write};
+use alloc::boxed::Box;
+use axerror::{ax_err, AxError};
+use axfs::lock::{FileLocks, LockKind};
+use axfs::{SetAttr, VfsFile};
+
+/// An open file. Reads and writes start at its own position, which
+/// [`Seek`] moves.
+///
+/// The lock methods take advisory whole-file locks, as `flock` does. They
+/// keep out other open files, including other `File`s of the same task, and
+/// are released when the file is dropped.
+pub struct File {
+    inner: Box<dyn VfsFile>,
+    locks: FileLocks,
+    offset: u64,
+    readable: bool,
+    writable: bool,
+    append: bool,
+}
+
+impl File {
+    /// Open an existing file for reading.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<File> {
+        OpenOptions::new().read(true).open(path)
+    }
+
+    /// Open a file for writing, creating it or cutting it to 0 bytes.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<File> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    pub fn options() -> OpenOptions {
+        OpenOptions::new()
+    }
+
+    pub fn metadata(&self) -> Result<Metadata> {
+        self.inner.getattr().map(Metadata)
+    }
+
+    /// Cut or zero-extend the file to `size` bytes. The position stays.
+    pub fn set_len(&self, size: u64) -> Result<()> {
+        if !self.writable {
+            return ax_err!(PermissionDenied, "file not opened for writing");
+        }
+        self.inner.truncate(size as usize)
+    }
+
+    pub fn set_permissions(&self, perm: Permissions) -> Result<()> {
+        self.inner.setattr(SetAttr {
+            mode: Some(perm.mode()),
+            ..Default::default()
+        })
+    }
+
+    /// Flush data and metadata to stable storage.
+    pub fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+
+    /// Flush data to stable storage.
+    pub fn sync_data(&self) -> Result<()> {
+        self.inner.fsync()
+    }
+
+    /// Take an exclusive lock, waiting for other holders to release theirs.
+    /// A shared lock this file holds is converted.
+    pub fn lock(&self) -> Result<()> {
+        self.locks.lock(LockKind::Exclusive, true)
+    }
+
+    /// Take a shared lock, waiting while someone holds an exclusive one.
+    pub fn lock_shared(&self) -> Result<()> {
+        self.locks.lock(LockKind::Shared, true)
+    }
+
+    /// Take an exclusive lock if nobody else holds one. Returns `false`
+    /// instead of waiting.
+    pub fn try_lock(&self) -> Result<bool> {
+        try_lock(self.locks.lock(LockKind::Exclusive, false))
+    }
+
+    /// Take a shared lock if nobody holds an exclusive one. Returns `false`
+    /// instead of waiting.
+    pub fn try_lock_shared(&self) -> Result<bool> {
+        try_lock(self.locks.lock(LockKind::Shared, false))
+    }
+
+    /// Release the lock this file holds, if any.
+    pub fn unlock(&self) -> Result<()> {
+        self.locks.unlock();
+        Ok(())
+    }
+}
+
+fn try_lock(result: Result<()>) -> Result<bool> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(AxError::Again) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if !self.readable {
+            return ax_err!(PermissionDenied, "file not opened for reading");
+        }
+        let n = self.inner.read_at(self.offset as usize, buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if !self.writable {
+            return ax_err!(PermissionDenied, "file not opened for writing");
+        }
+        if self.append {
+            self.offset = self.inner.getattr()?.size as u64;
+        }
+        let n = self.inner.write_at(self.offset as usize, buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => (self.inner.getattr()?.size as u64).checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+        };
+        self.offset = offset.ok_or(AxError::InvalidParam)?;
+        Ok(self.offset)
+    }
+}
+
+/// How to open a file, as in `std::fs::OpenOptions`.
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+}
+
+impl OpenOptions {
+    /// All options off.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// Write at the end of the file, whatever the position.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Cut the file to 0 bytes. Needs `write`.
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Create the file if it is missing. Needs `write` or `append`.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Create the file, failing if it exists. Needs `write` or `append`.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<File> {
+        let path = path.as_ref().as_str();
+        let writable = self.write || self.append;
+        if !self.read && !writable {
+            return ax_err!(InvalidParam, "open: neither read nor write");
+        }
+        if (self.truncate && !self.write) || ((self.create || self.create_new) && !writable) {
+            return ax_err!(InvalidParam, "open: option needs write access");
+        }
+        let inner = match axfs::open(path) {
+            Ok(_) if self.create_new => return ax_err!(AlreadyExists),
+            Ok(file) => file,
+            Err(AxError::NotFound) if self.create || self.create_new => axfs::create(path)?,
+            Err(e) => return Err(e),
+        };
+        let attr = inner.getattr()?;
+        if writable && attr.is_dir() {
+            return ax_err!(IsADirectory);
+        }
+        if writable && attr.is_file() && axfs::read_only(path)? {
+            return ax_err!(ReadOnlyFilesystem);
+        }
+        // Devices and pipes have nothing to cut.
+        if self.truncate && attr.is_file() {
+            inner.truncate(0)?;
+        }
+        Ok(File {
+            inner,
+            locks: axfs::file_locks(path)?,
+            offset: 0,
+            readable: self.read,
+            writable,
+            append: self.append,
+        })
+    }
+}
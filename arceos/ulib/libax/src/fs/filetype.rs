@@ -1,12 +0,0 @@
-#[derive(Clone, Copy, Debug)]
-pub struct FileType();
-
-impl FileType {
-    pub fn is_file(&self) -> bool {
-        todo!("is_file")
-    }
-
-    pub fn is_dir(&self) -> bool {
-        todo!("is_dir")
-    }
-}
@@ -0,0 +1,112 @@
+//! File metadata, as in `std::fs`.
+
+use core::time::Duration;
+
+/// Metadata of a file, from [`metadata`](super::metadata) or
+/// [`File::metadata`](super::File::metadata).
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata(pub(crate) axfs::Metadata);
+
+impl Metadata {
+    pub fn file_type(&self) -> FileType {
+        FileType(self.0.file_type)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type().is_symlink()
+    }
+
+    /// Size of the file in bytes.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.0.size as u64
+    }
+
+    pub fn permissions(&self) -> Permissions {
+        Permissions(self.0.mode)
+    }
+
+    /// Inode number.
+    pub fn ino(&self) -> u64 {
+        self.0.ino as u64
+    }
+
+    /// Number of hard links.
+    pub fn nlink(&self) -> u64 {
+        self.0.nlink as u64
+    }
+
+    /// Device number (major, minor) of a device node.
+    pub fn rdev(&self) -> Option<(u16, u16)> {
+        self.0.rdev
+    }
+
+    /// Time of the last change to the data, since the Unix epoch, if the
+    /// file system keeps one.
+    pub fn modified(&self) -> Option<Duration> {
+        self.0.mtime
+    }
+}
+
+/// Type of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileType(pub(crate) axfs::FileType);
+
+impl FileType {
+    pub fn is_file(&self) -> bool {
+        self.0 == axfs::FileType::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.0 == axfs::FileType::Dir
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.0 == axfs::FileType::Symlink
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        self.0 == axfs::FileType::Fifo
+    }
+
+    pub fn is_device(&self) -> bool {
+        self.0 == axfs::FileType::Device
+    }
+}
+
+/// Permission bits of a file, as the low 12 bits of a Unix mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Permissions(u16);
+
+impl Permissions {
+    pub fn from_mode(mode: u16) -> Self {
+        Self(mode & 0o7777)
+    }
+
+    pub fn mode(&self) -> u16 {
+        self.0
+    }
+
+    /// Whether no one may write the file.
+    pub fn readonly(&self) -> bool {
+        self.0 & 0o222 == 0
+    }
+
+    /// Clear or set the write bits. Making the file writable sets only the
+    /// owner's write bit.
+    pub fn set_readonly(&mut self, readonly: bool) {
+        if readonly {
+            self.0 &= !0o222;
+        } else {
+            self.0 |= 0o200;
+        }
+    }
+}
@@ -1,72 +1,216 @@
+mod dir;
 pub mod error;
-pub mod filetype;
+pub mod fd;
+mod file;
+mod metadata;
+mod mount;
 mod path;
+mod pipe;
+mod xattr;
 extern crate alloc;
 pub use self::error::{Error, Result};
 pub use alloc::{string::String};
 pub use alloc::vec::Vec;
 pub use alloc::vec;
-pub use path::Path;
+pub use dir::{DirEntry, ReadDir};
+pub use file::{File, OpenOptions};
+pub use metadata::{FileType, Metadata, Permissions};
+pub use mount::{
+    attach_loop, block_devices, detach_loop, fs_types, mount, mount_fs, mounts, umount,
+    MountOptions, MountedFileSystem, RamFileSystem, VfsFileSystem,
+};
+pub use axfs::notify;
+pub use axfs::StatFs;
+pub use path::{Component, Components, Path, PathBuf};
+pub use pipe::{mkfifo, open_fifo_reader, open_fifo_writer, pipe, PipeReader, PipeWriter};
+pub use xattr::{get_xattr, list_xattr, remove_xattr, set_xattr};
 
-use axfs::open;
+use crate::io::{Read, Write};
+use axerror::{ax_err, AxError};
 
 /// Read a file from the given path.
-pub fn read(path: Path) -> Result<Vec<u8>> {
-    match open(path.as_path()) {
-        Some(file) => {
-            let file_size = file.size();
-            let mut buffer = vec![0u8; file_size];
-            file.read(&mut buffer);
-            Ok(buffer)
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut buffer = Vec::with_capacity(file.metadata()?.len() as usize);
+    file.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Read a UTF-8 file from the given path.
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String> {
+    String::from_utf8(read(path)?).map_err(|_| AxError::InvalidParam)
+}
+
+/// Iterate over the entries of the directory at `path`.
+pub fn read_dir<P: AsRef<Path>>(path: P) -> Result<ReadDir> {
+    ReadDir::new(path.as_ref())
+}
+
+/// Write `data` to the file at `path`, creating it or replacing what it held.
+pub fn write<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<()> {
+    File::create(path)?.write_all(data)
+}
+
+/// Copy the contents and permissions of the file `from` to `to`, which is
+/// created or replaced. Returns the number of bytes copied.
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<u64> {
+    let mut src = File::open(from)?;
+    let attr = src.metadata()?;
+    if !attr.is_file() {
+        return ax_err!(InvalidParam, "copy: source is not a regular file");
+    }
+    let mut dst = File::create(to)?;
+    let mut buf = [0u8; 512];
+    let mut copied = 0;
+    loop {
+        let n = src.read(&mut buf)?;
+        if n == 0 {
+            break;
         }
-        None => Err(axerror::AxError::NotFound),
+        dst.write_all(&buf[..n])?;
+        copied += n as u64;
+    }
+    // Not every file system keeps permission bits.
+    match dst.set_permissions(attr.permissions()) {
+        Ok(()) | Err(AxError::Unsupported) => Ok(copied),
+        Err(e) => Err(e),
     }
 }
 
-// TODO: result to Result<ReadDirIterator>.
-pub fn read_dir(path: Path) -> Result<Vec<String>> {
-    match open(path.as_path()) {
-        Some(file) => Ok(file.read_dir()),
-        None => todo!(),
+/// Remove a file or symlink.
+pub fn remove_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref().as_str();
+    if axfs::symlink_metadata(path)?.is_dir() {
+        return ax_err!(IsADirectory);
     }
+    axfs::remove(path)
 }
 
-pub fn read_to_string(path: Path) -> Result<String> {
-    todo!()
+/// Remove an empty directory.
+pub fn remove_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref().as_str();
+    if !axfs::symlink_metadata(path)?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    axfs::remove(path)
 }
 
-pub fn write(path: Path, data: &[u8]) -> Result<()> {
-    //info!("begin write");
-    match open(path.as_path()) {
-        Some(file) => {
-            file.write(data);
-            Ok(())
+/// Remove a directory after removing everything in it. Symlinks are
+/// removed, not followed.
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    if !symlink_metadata(path)?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    for entry in read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_dir_all(entry.path())?;
+        } else {
+            remove_file(entry.path())?;
         }
-        None => axfs::create(path.as_path()).map_or(Err(axerror::AxError::Unsupported), |f| {
-            f.write(data);
-            Ok(())
-        }),
     }
+    remove_dir(path)
+}
+
+/// Create a directory. Its parent must exist.
+pub fn create_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    axfs::mkdir(path.as_ref().as_str()).map(|_| ())
+}
+
+/// Create a directory and any missing parents. An existing directory at
+/// `path` is fine.
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    match create_dir(path) {
+        Ok(()) => return Ok(()),
+        Err(AxError::NotFound) => {}
+        Err(_) if metadata(path).is_ok_and(|attr| attr.is_dir()) => return Ok(()),
+        Err(e) => return Err(e),
+    }
+    match path.parent() {
+        Some(parent) if !parent.as_str().is_empty() => create_dir_all(parent)?,
+        _ => return ax_err!(NotFound),
+    }
+    match create_dir(path) {
+        Err(_) if metadata(path).is_ok_and(|attr| attr.is_dir()) => Ok(()),
+        result => result,
+    }
+}
+
+/// Create a device node with the given device number.
+pub fn mknod<P: AsRef<Path>>(path: P, major: u16, minor: u16) -> Result<()> {
+    axfs::mknod(path.as_ref().as_str(), major, minor).map(|_| ())
+}
+
+/// Metadata of the file at `path`, following symlinks.
+pub fn metadata<P: AsRef<Path>>(path: P) -> Result<Metadata> {
+    axfs::metadata(path.as_ref().as_str()).map(Metadata)
+}
+
+/// Metadata of the file at `path`, or of the symlink itself if it is one.
+pub fn symlink_metadata<P: AsRef<Path>>(path: P) -> Result<Metadata> {
+    axfs::symlink_metadata(path.as_ref().as_str()).map(Metadata)
+}
+
+/// Change the permission bits of the file at `path`.
+pub fn set_permissions<P: AsRef<Path>>(path: P, perm: Permissions) -> Result<()> {
+    axfs::open(path.as_ref().as_str())?.setattr(axfs::SetAttr {
+        mode: Some(perm.mode()),
+        ..Default::default()
+    })
+}
+
+/// Absolute form of `path` with `.`, `..` and symlinks resolved. The file
+/// must exist.
+pub fn canonicalize<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+    axfs::canonicalize(path.as_ref().as_str()).map(PathBuf::from)
+}
+
+/// Create a symlink at `path` pointing to `target`.
+pub fn symlink<P: AsRef<Path>>(target: &str, path: P) -> Result<()> {
+    axfs::symlink(target, path.as_ref().as_str())
+}
+
+/// The target of the symlink at `path`.
+pub fn read_link<P: AsRef<Path>>(path: P) -> Result<String> {
+    axfs::readlink(path.as_ref().as_str())
+}
+
+/// Rename `from` to `to`, replacing `to` if it exists.
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    axfs::rename(from.as_ref().as_str(), to.as_ref().as_str())
+}
+
+/// Make `dst` another name for the file at `src`.
+pub fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<()> {
+    axfs::link(src.as_ref().as_str(), dst.as_ref().as_str())
+}
+
+/// Statistics of the file system `path` lives on.
+pub fn statfs<P: AsRef<Path>>(path: P) -> Result<StatFs> {
+    axfs::statfs(path.as_ref().as_str())
 }
 
-pub fn remove_file(path: Path) -> Result<()> {
-    axfs::remove(path.as_path());
-    Ok(())
+/// Change the current working directory of the calling task. Tasks it
+/// spawns afterwards start there too.
+pub fn chdir<P: AsRef<Path>>(path: P) -> Result<()> {
+    axfs::chdir(path.as_ref().as_str())
 }
 
-pub fn remove_dir(path: Path) -> Result<()> {
-    // TODO: judge the directory whether it is empty or not
-    axfs::remove(path.as_path());
-    Ok(())
+/// The current working directory of the calling task.
+pub fn getcwd() -> PathBuf {
+    PathBuf::from(axfs::getcwd())
 }
 
-pub fn remove_dir_all(path: Path) -> Result<()> {
-    axfs::remove(path.as_path());
-    Ok(())
+/// Flush the data and metadata of the file at `path` to stable storage.
+pub fn fsync<P: AsRef<Path>>(path: P) -> Result<()> {
+    axfs::fsync(path.as_ref().as_str())
 }
 
-pub fn create_dir(path: Path) -> Result<()> {
-    axfs::mkdir(path.as_path()).map_or(Err(axerror::AxError::NotFound), |_| Ok(()))
+/// Flush every mounted file system to stable storage.
+pub fn sync_all() -> Result<()> {
+    axfs::sync_all()
 }
 
 pub fn test_sleep_lock(){
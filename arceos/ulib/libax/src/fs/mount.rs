@@ -0,0 +1,62 @@
+//! Mounting file systems.
+
+use super::{Path, Result, String, Vec};
+use alloc::sync::Arc;
+use axerror::AxError;
+
+pub use axfs::mount::MountedFileSystem;
+pub use axfs::{MountOptions, RamFileSystem, VfsFileSystem};
+
+/// Mount `fs` on the existing directory `path`.
+///
+/// `options` is a comma separated list such as `"ro,noatime"`, see
+/// [`MountOptions`].
+pub fn mount<P: AsRef<Path>>(path: P, fs: Arc<dyn VfsFileSystem>, options: &str) -> Result<()> {
+    let options: MountOptions = options.parse().map_err(|_| AxError::InvalidParam)?;
+    axfs::mount(path.as_ref().as_str(), fs, options)
+}
+
+/// Mount the file system on the block device `source`, such as `vda1`,
+/// at `path`, as `mount -t`. With `fs_type` of `None` the type is detected
+/// from the device.
+pub fn mount_fs<P: AsRef<Path>>(
+    source: &str,
+    path: P,
+    fs_type: Option<&str>,
+    options: &str,
+) -> Result<()> {
+    let options: MountOptions = options.parse().map_err(|_| AxError::InvalidParam)?;
+    axfs::mount_fs(source, path.as_ref().as_str(), fs_type, options)
+}
+
+/// Attach the image file at `path` to a loop device, as `losetup -f`, and
+/// return the device name to mount it by, such as `loop0`.
+pub fn attach_loop<P: AsRef<Path>>(path: P) -> Result<String> {
+    axfs::attach_loop(path.as_ref().as_str())
+}
+
+/// Detach the loop device `name`, as `losetup -d`. Fails with
+/// `ResourceBusy` until what was mounted from it is unmounted.
+pub fn detach_loop(name: &str) -> Result<()> {
+    axfs::detach_loop(name)
+}
+
+/// Names of the block devices file systems can be mounted from.
+pub fn block_devices() -> Vec<String> {
+    axfs::block_devices()
+}
+
+/// Names of the file system types that can be mounted.
+pub fn fs_types() -> Vec<&'static str> {
+    axfs::fstype::fs_types()
+}
+
+/// Unmount the file system mounted at `path`.
+pub fn umount<P: AsRef<Path>>(path: P) -> Result<()> {
+    axfs::umount(path.as_ref().as_str())
+}
+
+/// All mounted file systems, in mount order.
+pub fn mounts() -> Vec<MountedFileSystem> {
+    axfs::mounts()
+}
@@ -1,24 +1,400 @@
+//! Paths, in the manner of `std::path`.
+//!
+//! Only `/` separates components. Paths need no trailing NUL.
+
 extern crate alloc;
-use alloc::string::ToString;
+use alloc::borrow::{Borrow, ToOwned};
 use alloc::string::String;
+use core::fmt;
+use core::ops::Deref;
+
+/// A component of a path, as yielded by [`Path::components`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Component<'a> {
+    /// The leading `/` of an absolute path.
+    RootDir,
+    /// `.`
+    CurDir,
+    /// `..`
+    ParentDir,
+    /// A file or directory name.
+    Normal(&'a str),
+}
+
+impl<'a> Component<'a> {
+    pub fn as_str(self) -> &'a str {
+        match self {
+            Component::RootDir => "/",
+            Component::CurDir => ".",
+            Component::ParentDir => "..",
+            Component::Normal(name) => name,
+        }
+    }
+}
+
+/// Iterator over the [`Component`]s of a path. Repeated `/`s are skipped,
+/// and so is `.` anywhere but at the start of a relative path.
+pub struct Components<'a> {
+    path: &'a str,
+    /// `RootDir` or the leading `.` is still to be yielded.
+    has_prefix: bool,
+}
+
+impl<'a> Iterator for Components<'a> {
+    type Item = Component<'a>;
 
-/// The struct contains the file path.
-pub struct Path(String);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.has_prefix {
+            self.has_prefix = false;
+            return Some(if self.path.starts_with('/') {
+                self.path = &self.path[1..];
+                Component::RootDir
+            } else {
+                self.path = self.path.strip_prefix('.').unwrap_or("");
+                Component::CurDir
+            });
+        }
+        loop {
+            self.path = self.path.trim_start_matches('/');
+            if self.path.is_empty() {
+                return None;
+            }
+            let (name, rest) = self.path.split_once('/').unwrap_or((self.path, ""));
+            self.path = rest;
+            return Some(match name {
+                "." => continue,
+                ".." => Component::ParentDir,
+                name => Component::Normal(name),
+            });
+        }
+    }
+}
+
+/// A borrowed path, like `str` is to `String`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Path(str);
 
 impl Path {
-    /// Create a new path from a string.
-    #[inline]
-    pub fn new(path: &str) -> Self {
-        Self(path.to_string())
+    /// Wrap a string slice as a path.
+    pub fn new<S: AsRef<str> + ?Sized>(s: &S) -> &Path {
+        // SAFETY: `Path` is a transparent wrapper of `str`.
+        unsafe { &*(s.as_ref() as *const str as *const Path) }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn to_path_buf(&self) -> PathBuf {
+        PathBuf(String::from(&self.0))
+    }
+
+    pub fn is_absolute(&self) -> bool {
+        self.0.starts_with('/')
+    }
+
+    pub fn is_relative(&self) -> bool {
+        !self.is_absolute()
+    }
+
+    pub fn components(&self) -> Components<'_> {
+        let has_prefix = self.is_absolute() || &self.0 == "." || self.0.starts_with("./");
+        Components {
+            path: &self.0,
+            has_prefix,
+        }
+    }
+
+    /// The path without its last component, or `None` if it ends in the
+    /// root or is empty.
+    pub fn parent(&self) -> Option<&Path> {
+        let path = self.0.trim_end_matches('/');
+        if path.is_empty() {
+            return None;
+        }
+        Some(match path.rfind('/') {
+            Some(index) => {
+                let parent = path[..index].trim_end_matches('/');
+                Path::new(if parent.is_empty() { "/" } else { parent })
+            }
+            None => Path::new(""),
+        })
+    }
+
+    /// The last component if it is a name, not the root, `.` or `..`.
+    pub fn file_name(&self) -> Option<&str> {
+        match self.components().last()? {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The file name without its extension.
+    pub fn file_stem(&self) -> Option<&str> {
+        let name = self.file_name()?;
+        Some(match split_extension(name) {
+            Some((stem, _)) => stem,
+            None => name,
+        })
+    }
+
+    /// The part of the file name after its last `.`, unless that `.` starts
+    /// the name.
+    pub fn extension(&self) -> Option<&str> {
+        split_extension(self.file_name()?).map(|(_, extension)| extension)
+    }
+
+    /// `path` appended to this path, or `path` itself if it is absolute.
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let mut buf = self.to_path_buf();
+        buf.push(path);
+        buf
+    }
+
+    /// The path with `.` and repeated `/`s dropped and `..` applied, without
+    /// looking at the file system. `..` at the root stays at the root; a
+    /// relative path keeps the `..`s it cannot apply.
+    pub fn normalize(&self) -> PathBuf {
+        let mut buf = PathBuf::new();
+        for component in self.components() {
+            match component {
+                Component::RootDir => buf.0.push('/'),
+                Component::CurDir => {}
+                Component::ParentDir => match buf.components().last() {
+                    Some(Component::Normal(_)) => {
+                        buf.pop();
+                    }
+                    Some(Component::RootDir) => {}
+                    _ => buf.push(".."),
+                },
+                Component::Normal(name) => buf.push(name),
+            }
+        }
+        if buf.0.is_empty() {
+            buf.0.push('.');
+        }
+        buf
+    }
+
+    /// This path made absolute against the directory `base` and normalized.
+    pub fn resolve<P: AsRef<Path>>(&self, base: P) -> PathBuf {
+        base.as_ref().join(self).normalize()
+    }
+
+    pub fn starts_with<P: AsRef<Path>>(&self, base: P) -> bool {
+        let mut components = self.components();
+        base.as_ref()
+            .components()
+            .all(|c| components.next() == Some(c))
+    }
+}
+
+fn split_extension(name: &str) -> Option<(&str, &str)> {
+    match name.rfind('.') {
+        Some(0) | None => None,
+        Some(index) => Some((&name[..index], &name[index + 1..])),
+    }
+}
+
+impl fmt::Debug for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ToOwned for Path {
+    type Owned = PathBuf;
+
+    fn to_owned(&self) -> PathBuf {
+        self.to_path_buf()
+    }
+}
+
+impl AsRef<Path> for Path {
+    fn as_ref(&self) -> &Path {
+        self
     }
+}
+
+impl AsRef<Path> for str {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<Path> for String {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
 
-    pub fn as_path(&self) -> &str {
+impl AsRef<str> for Path {
+    fn as_ref(&self) -> &str {
         &self.0
     }
 }
 
-impl From<&str> for Path {
+/// An owned, growable path.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PathBuf(String);
+
+impl PathBuf {
+    pub fn new() -> Self {
+        Self(String::new())
+    }
+
+    pub fn as_path(&self) -> &Path {
+        self
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// Append `path`, replacing the whole path if `path` is absolute.
+    pub fn push<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref().as_str();
+        if path.starts_with('/') {
+            self.0.clear();
+        } else if !self.0.is_empty() && !self.0.ends_with('/') {
+            self.0.push('/');
+        }
+        self.0.push_str(path);
+    }
+
+    /// Drop the last component. Returns `false` if there is no parent.
+    pub fn pop(&mut self) -> bool {
+        match self.parent().map(|parent| parent.as_str().len()) {
+            Some(len) => {
+                self.0.truncate(len);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace the last component with `name`.
+    pub fn set_file_name(&mut self, name: &str) {
+        if self.file_name().is_some() {
+            self.pop();
+        }
+        self.push(name);
+    }
+}
+
+impl Deref for PathBuf {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+impl Borrow<Path> for PathBuf {
+    fn borrow(&self) -> &Path {
+        self
+    }
+}
+
+impl AsRef<Path> for PathBuf {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+
+impl fmt::Debug for PathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for PathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for PathBuf {
     fn from(value: &str) -> Self {
-        Self(value.to_string())
+        Self(String::from(value))
+    }
+}
+
+impl From<String> for PathBuf {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&Path> for PathBuf {
+    fn from(value: &Path) -> Self {
+        value.to_path_buf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn components(path: &str) -> Vec<Component<'_>> {
+        Path::new(path).components().collect()
+    }
+
+    #[test]
+    fn test_components() {
+        use Component::*;
+        assert_eq!(components("/a//b/"), [RootDir, Normal("a"), Normal("b")]);
+        assert_eq!(components("a/./b/."), [Normal("a"), Normal("b")]);
+        assert_eq!(components("./a"), [CurDir, Normal("a")]);
+        assert_eq!(components("."), [CurDir]);
+        assert_eq!(components("a/../b/"), [Normal("a"), ParentDir, Normal("b")]);
+        assert_eq!(components("/.."), [RootDir, ParentDir]);
+        assert_eq!(components(""), []);
+    }
+
+    #[test]
+    fn test_parent() {
+        let parent = |path| Path::new(path).parent().map(Path::as_str);
+        assert_eq!(parent("/a/b"), Some("/a"));
+        assert_eq!(parent("/a/b/"), Some("/a"));
+        assert_eq!(parent("/a//b"), Some("/a"));
+        assert_eq!(parent("/a"), Some("/"));
+        assert_eq!(parent("a"), Some(""));
+        assert_eq!(parent("a/.."), Some("a"));
+        assert_eq!(parent("/"), None);
+        assert_eq!(parent(""), None);
+    }
+
+    #[test]
+    fn test_file_name() {
+        let file_name = |path| Path::new(path).file_name();
+        assert_eq!(file_name("/a/b.txt"), Some("b.txt"));
+        assert_eq!(file_name("a/b/"), Some("b"));
+        assert_eq!(file_name("a/b/."), Some("b"));
+        assert_eq!(file_name("a/.."), None);
+        assert_eq!(file_name("/"), None);
+        assert_eq!(file_name("."), None);
+    }
+
+    #[test]
+    fn test_join() {
+        let join = |base, path| Path::new(base).join(path).into_string();
+        assert_eq!(join("a", "b"), "a/b");
+        assert_eq!(join("a/", "b"), "a/b");
+        assert_eq!(join("/", "b"), "/b");
+        assert_eq!(join("", "b"), "b");
+        assert_eq!(join("a", "/b"), "/b");
+        assert_eq!(join("a", "../b"), "a/../b");
+        assert_eq!(Path::new("/a/b").join("../c").normalize().as_str(), "/a/c");
+        assert_eq!(Path::new("/").join("../..").normalize().as_str(), "/");
+        assert_eq!(Path::new("a").join("../..").normalize().as_str(), "..");
     }
 }
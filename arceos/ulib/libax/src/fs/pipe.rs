@@ -0,0 +1,42 @@
+//! Pipes and named pipes (FIFOs).
+
+pub use axfs::{PipeReader, PipeWriter};
+
+use super::{Path, Result};
+use crate::io::{Read, Write};
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        PipeReader::read(self, buf)
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        PipeWriter::write(self, buf)
+    }
+
+    fn flush(&mut self) -> Result {
+        Ok(())
+    }
+}
+
+/// Create an anonymous pipe, returning its read and write ends.
+pub fn pipe() -> Result<(PipeReader, PipeWriter)> {
+    Ok(axfs::pipe())
+}
+
+/// Create a named pipe at the given path.
+pub fn mkfifo<P: AsRef<Path>>(path: P) -> Result<()> {
+    axfs::mkfifo(path.as_ref().as_str()).map(|_| ())
+}
+
+/// Open the read end of the named pipe at the given path.
+pub fn open_fifo_reader<P: AsRef<Path>>(path: P) -> Result<PipeReader> {
+    axfs::open_fifo_reader(path.as_ref().as_str())
+}
+
+/// Open the write end of the named pipe at the given path.
+pub fn open_fifo_writer<P: AsRef<Path>>(path: P) -> Result<PipeWriter> {
+    axfs::open_fifo_writer(path.as_ref().as_str())
+}
@@ -0,0 +1,40 @@
+//! Extended attributes.
+
+use super::{Path, Result, String, Vec};
+use axerror::ax_err;
+
+/// Supported attribute namespaces.
+const NAMESPACES: [&str; 2] = ["user.", "system."];
+
+fn check_name(name: &str) -> Result<()> {
+    match NAMESPACES.iter().find(|ns| name.starts_with(*ns)) {
+        Some(ns) if name.len() > ns.len() => Ok(()),
+        _ => ax_err!(
+            InvalidParam,
+            "xattr name must start with \"user.\" or \"system.\""
+        ),
+    }
+}
+
+/// Get the value of an extended attribute of the file at `path`.
+pub fn get_xattr<P: AsRef<Path>>(path: P, name: &str) -> Result<Vec<u8>> {
+    check_name(name)?;
+    axfs::open(path.as_ref().as_str())?.get_xattr(name)
+}
+
+/// Create or replace an extended attribute of the file at `path`.
+pub fn set_xattr<P: AsRef<Path>>(path: P, name: &str, value: &[u8]) -> Result<()> {
+    check_name(name)?;
+    axfs::open(path.as_ref().as_str())?.set_xattr(name, value)
+}
+
+/// List the names of all extended attributes of the file at `path`.
+pub fn list_xattr<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+    axfs::open(path.as_ref().as_str())?.list_xattr()
+}
+
+/// Remove an extended attribute of the file at `path`.
+pub fn remove_xattr<P: AsRef<Path>>(path: P, name: &str) -> Result<()> {
+    check_name(name)?;
+    axfs::open(path.as_ref().as_str())?.remove_xattr(name)
+}
@@ -1,7 +1,9 @@
 mod error;
 pub mod prelude;
+mod stdio;
 
 pub use self::error::{Error, Result};
+pub use self::stdio::{stdin, stdout, Stdin, Stdout};
 
 #[cfg(feature = "alloc")]
 use alloc::{string::String, vec::Vec};
@@ -70,3 +72,27 @@ pub trait Write {
         Ok(())
     }
 }
+
+/// A position to [`Seek`] to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// Bytes from the start.
+    Start(u64),
+    /// Bytes from the end, may be negative.
+    End(i64),
+    /// Bytes from the current position, may be negative.
+    Current(i64),
+}
+
+pub trait Seek {
+    /// Move to `pos`, returning the new position from the start.
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+    fn rewind(&mut self) -> Result {
+        self.seek(SeekFrom::Start(0)).map(|_| ())
+    }
+
+    fn stream_position(&mut self) -> Result<u64> {
+        self.seek(SeekFrom::Current(0))
+    }
+}
@@ -1 +1 @@
-pub use super::{Read, Write};
+pub use super::{Read, Seek, Write};
@@ -0,0 +1,57 @@
+//! The console as standard input and output.
+
+use super::{Read, Result, Write};
+
+/// Standard input, read from the console.
+pub struct Stdin;
+
+/// Standard output, written to the console.
+pub struct Stdout;
+
+pub fn stdin() -> Stdin {
+    Stdin
+}
+
+pub fn stdout() -> Stdout {
+    Stdout
+}
+
+impl Read for Stdin {
+    /// Wait for at least one byte, then take what else has arrived.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut read = 0;
+        while read < buf.len() {
+            match axhal::console::getchar() {
+                Some(c) => {
+                    buf[read] = c;
+                    read += 1;
+                }
+                None if read > 0 => break,
+                None => {
+                    #[cfg(feature = "multitask")]
+                    crate::task::yield_now();
+                }
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for &c in buf {
+            if c == b'\n' {
+                axhal::console::putchar(b'\r');
+            }
+            axhal::console::putchar(c);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result {
+        Ok(())
+    }
+}
@@ -11,3 +11,15 @@ pub fn rand_u32() -> u32 {
     SEED.store(new_seed, SeqCst);
     (new_seed >> 33) as u32
 }
+
+/// `/dev/random` draws from the same generator.
+#[cfg(feature = "fs")]
+struct RandomIfImpl;
+
+#[cfg(feature = "fs")]
+#[crate_interface::impl_interface]
+impl axdevfs::RandomIf for RandomIfImpl {
+    fn rand_u32() -> u32 {
+        rand_u32()
+    }
+}
@@ -9,36 +9,42 @@ use xv6fs::fs_const::BSIZE;
 use xv6fs::inode::ICACHE;
 use xv6fs::log::LOG_MANAGER;
 use xv6fs::{BlockDevice,xv6fs::Xv6FileSystem,disk_inode::DiskInode,log::LogHeader,buffer_cache::BLOCK_CACHE_MANAGER};
-use std::fs::{read_dir, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use driver_block::{BlockDriverOps, FileDisk};
+use std::fs::read_dir;
 use std::mem::size_of;
 use std::sync::Arc;
-use std::sync::Mutex;
 
-/// Use a block size of 1024 bytes
-const BLOCK_SZ: usize = 1024;
-const BLOCK_NUM: usize = 131072; //64*2048
+const BLOCK_NUM: u64 = 131072; //64*2048 blocks of 512 bytes
 
-struct BlockFile(Mutex<File>);
+/// A disk image, in the block driver shared with the kernel.
+struct BlockFile(FileDisk);
 
 impl BlockDevice for BlockFile {
     /// Read a block from file
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
         info!("read block {}",block_id);
-        let mut file = self.0.lock().unwrap();
-        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
-            .expect("Error when seeking!");
-        assert_eq!(file.read(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
-        //info!("read block {} buf {:?}",block_id,buf);
+        self.0.read_block(block_id, buf).expect("can't read block");
     }
     /// Write a block into file
     fn write_block(&self, block_id: usize, buf: &[u8]) {
-        let mut file = self.0.lock().unwrap();
-        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
-            .expect("Error when seeking!");
-        assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
-        //info!("write block {} with buf {:?}",block_id,buf);
+        self.0.write_block(block_id, buf).expect("can't write block");
     }
+
+    fn flush(&self) {
+        self.0.flush().expect("can't flush image");
+    }
+}
+
+/// The image the tests run on, made if missing, of `BLOCK_NUM` blocks.
+#[cfg(test)]
+fn open_image() -> std::io::Result<Arc<BlockFile>> {
+    let f = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open("target/fs.img")?;
+    f.set_len(BLOCK_NUM * driver_block::BLOCK_SIZE as u64)?;
+    Ok(Arc::new(BlockFile(FileDisk::new(f)?)))
 }
 
 fn main(){
@@ -47,20 +53,12 @@ fn main(){
 
 #[test]
 fn xv6fs_test_create() -> std::io::Result<()> {
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/fs.img")?;
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
-        f
-    })));
+    let block_file = open_image()?;
     info!("block size:{}, disk inode size:{}, log header size:{}",BSIZE,size_of::<DiskInode>(),size_of::<LogHeader>());
     let mut xfs=Xv6FileSystem::new();
     //xfs.create(block_file.clone());
-    unsafe{xv6fs::init(block_file.clone(), 1);}
-    let root_inode=xfs.get_root_inode();
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
+    let root_inode=xfs.get_root_inode().unwrap();
     info!("root inode is {:?}",root_inode);
     let mut root_data=root_inode.lock();
     info!("root get locked");
@@ -90,20 +88,12 @@ fn xv6fs_test_create() -> std::io::Result<()> {
 
 #[test]
 fn xv6fs_log_delete() -> std::io::Result<()> {
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/fs.img")?;
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
-        f
-    })));
+    let block_file = open_image()?;
     info!("block size:{}, disk inode size:{}, log header size:{}",BSIZE,size_of::<DiskInode>(),size_of::<LogHeader>());
     let mut xfs=Xv6FileSystem::new();
     //xfs.create(block_file.clone());
-    unsafe{xv6fs::init(block_file.clone(), 1);}
-    let root_inode=xfs.get_root_inode();
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
+    let root_inode=xfs.get_root_inode().unwrap();
     //info!("root inode is {:?}",root_inode);
     let mut root_data=root_inode.lock();
     let dir_list=root_data.ls().unwrap();
@@ -117,17 +107,9 @@ fn xv6fs_log_delete() -> std::io::Result<()> {
 
 #[test]
 fn xv6fs_test_write() -> std::io::Result<()> {
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/fs.img")?;
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
-        f
-    })));
+    let block_file = open_image()?;
     let mut xfs=Xv6FileSystem::new();
-    unsafe{xv6fs::init(block_file.clone(), 1);}
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
     let path:&[u8]=b"/test\0\0\0";
     let mut inode=ICACHE.create(path, xv6fs::disk_inode::InodeType::File, 2, 1).unwrap();
     let mut inode_data=inode.lock();
@@ -141,18 +123,10 @@ fn xv6fs_test_write() -> std::io::Result<()> {
 
 #[test]
 fn xv6fs_ls_root() -> std::io::Result<()> {
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/fs.img")?;
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
-        f
-    })));
+    let block_file = open_image()?;
     let mut xfs=Xv6FileSystem::new();
-    unsafe{xv6fs::init(block_file.clone(), 1);}
-    let root_inode=xfs.get_root_inode();
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
+    let root_inode=xfs.get_root_inode().unwrap();
     //info!("root inode is {:?}",root_inode);
     let mut root_data=root_inode.lock();
     let dir_list=root_data.ls().unwrap();
@@ -163,19 +137,11 @@ fn xv6fs_ls_root() -> std::io::Result<()> {
 
 #[test]
 fn xv6fs_test_read() -> std::io::Result<()> {
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/fs.img")?;
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
-        f
-    })));
+    let block_file = open_image()?;
     info!("block size:{}, disk inode size:{}, log header size:{}",BSIZE,size_of::<DiskInode>(),size_of::<LogHeader>());
     let mut xfs=Xv6FileSystem::new();
     //xfs.create(block_file.clone());
-    unsafe{xv6fs::init(block_file.clone(), 1);}
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
     let path:&[u8]=b"/test\0\0\0";
     let mut inode=ICACHE.create(path, xv6fs::disk_inode::InodeType::File, 2, 1).unwrap();
     let mut inode_data=inode.lock();
@@ -195,17 +161,9 @@ fn xv6fs_test_read() -> std::io::Result<()> {
 
 #[test]
 fn xv6fs_test_bdealloc() -> std::io::Result<()> {
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/fs.img")?;
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
-        f
-    })));
+    let block_file = open_image()?;
     let mut xfs=Xv6FileSystem::new();
-    unsafe{xv6fs::init(block_file.clone(), 1);}
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
     bfree(0,47);
     LOG_MANAGER.commit_log();
     Ok(())
@@ -219,19 +177,11 @@ fn xv6fs_test_bdealloc() -> std::io::Result<()> {
 
 #[test]
 fn xv6fs_test_remove()->std::io::Result<()> {
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/fs.img")?;
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
-        f
-    })));
+    let block_file = open_image()?;
     let mut xfs=Xv6FileSystem::new();
-    unsafe{xv6fs::init(block_file.clone(), 1);}
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
     let path:&[u8]=b"/test\0\0\0";
-    let rinode=ICACHE.get_root_dir();
+    let rinode=ICACHE.get_root_dir().unwrap();
     ICACHE.remove(path);
     let mut rdata=rinode.lock();
     //rdata.dir_unlink(path);
@@ -246,19 +196,11 @@ fn xv6fs_test_remove()->std::io::Result<()> {
 
 #[test]
 fn xv6fs_test_remove_dir()->std::io::Result<()> {
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/fs.img")?;
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
-        f
-    })));
+    let block_file = open_image()?;
     let mut xfs=Xv6FileSystem::new();
-    unsafe{xv6fs::init(block_file.clone(), 1);}
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
     let path:&[u8]=b"/testdir\0\0\0";
-    let rinode=ICACHE.get_root_dir();
+    let rinode=ICACHE.get_root_dir().unwrap();
     ICACHE.remove(path);
     let mut rdata=rinode.lock();
     //rdata.dir_unlink(path);
@@ -269,4 +211,110 @@ fn xv6fs_test_remove_dir()->std::io::Result<()> {
     LOG_MANAGER.commit_log();
     //目录的nlink还没有处理
     Ok(())
-}
\ No newline at end of file
+}
+#[test]
+fn xv6fs_test_inline_grow_truncate() -> std::io::Result<()> {
+    let block_file = open_image()?;
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
+    let path:&[u8]=b"/inline\0";
+    let inode=ICACHE.create(path, xv6fs::disk_inode::InodeType::File, 2, 1).unwrap();
+    let mut inode_data=inode.lock();
+    inode_data.truncate(&inode).unwrap();
+    let small=[7u8;20];
+    inode_data.write(small.as_ptr() as usize, 0, small.len() as u32).unwrap();
+    assert!(inode_data.dinode.is_inline());
+    // Growing past the inode moves the data out to a block.
+    let big=[9u8;600];
+    inode_data.write(big.as_ptr() as usize, small.len() as u32, big.len() as u32).unwrap();
+    assert!(!inode_data.dinode.is_inline());
+    let mut buf=[0u8;620];
+    assert_eq!(inode_data.read(buf.as_mut_ptr() as usize, 0, 620), Ok(620));
+    assert_eq!(buf[..20], small);
+    assert_eq!(buf[20..], big);
+    // An emptied file keeps its data inline again.
+    inode_data.truncate(&inode).unwrap();
+    assert!(inode_data.dinode.is_inline());
+    assert_eq!(inode_data.dinode.size, 0);
+    inode_data.write(small.as_ptr() as usize, 0, small.len() as u32).unwrap();
+    assert!(inode_data.dinode.is_inline());
+    let mut buf=[0u8;20];
+    assert_eq!(inode_data.read(buf.as_mut_ptr() as usize, 0, 20), Ok(20));
+    assert_eq!(buf, small);
+    drop(inode_data);
+    drop(inode);
+    ICACHE.remove(path).unwrap();
+    LOG_MANAGER.commit_log();
+    Ok(())
+}
+
+#[test]
+fn xv6fs_test_remount() -> std::io::Result<()> {
+    let block_file = open_image()?;
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
+    let path:&[u8]=b"/remount\0";
+    let data=[5u8;700];
+    let inode=ICACHE.create(path, xv6fs::disk_inode::InodeType::File, 2, 1).unwrap();
+    let mut inode_data=inode.lock();
+    inode_data.truncate(&inode).unwrap();
+    inode_data.write(data.as_ptr() as usize, 0, data.len() as u32).unwrap();
+    drop(inode_data);
+    drop(inode);
+    LOG_MANAGER.commit_log();
+    // Unmounting writes everything back, so the file is there on the next mount.
+    unsafe{xv6fs::shutdown();}
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
+    let inode=ICACHE.namei(path).unwrap();
+    let mut inode_data=inode.lock();
+    let mut buf=[0u8;700];
+    assert_eq!(inode_data.read(buf.as_mut_ptr() as usize, 0, 700), Ok(700));
+    assert_eq!(buf, data);
+    drop(inode_data);
+    drop(inode);
+    ICACHE.remove(path).unwrap();
+    LOG_MANAGER.commit_log();
+    unsafe{xv6fs::shutdown();}
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
+    assert!(ICACHE.namei(path).is_err());
+    unsafe{xv6fs::shutdown();}
+    Ok(())
+}
+
+#[test]
+fn xv6fs_test_shrink() -> std::io::Result<()> {
+    let block_file = open_image()?;
+    unsafe{xv6fs::init(block_file.clone(), 1).unwrap();}
+    let path:&[u8]=b"/shrink\0";
+    let inode=ICACHE.create(path, xv6fs::disk_inode::InodeType::File, 2, 1).unwrap();
+    let mut inode_data=inode.lock();
+    inode_data.truncate(&inode).unwrap();
+    LOG_MANAGER.commit_log();
+    let free=xv6fs::bitmap::free_blocks(0);
+    // Far enough to need the doubly indirect block, a block at a time to fit the log.
+    let data:Vec<u8>=(0..40000u32).map(|i| i as u8).collect();
+    for (i,chunk) in data.chunks(BSIZE).enumerate() {
+        inode_data.write(chunk.as_ptr() as usize, (i*BSIZE) as u32, chunk.len() as u32).unwrap();
+        LOG_MANAGER.commit_log();
+    }
+    // 79 data blocks, the indirect block, the doubly indirect one and the one below it.
+    assert_eq!(xv6fs::bitmap::free_blocks(0), free-82);
+    // Each cut frees the blocks past the new end only.
+    for (size,used) in [(38000u32,78u32),(5000,11),(700,2)] {
+        inode_data.shrink(&inode, size).unwrap();
+        LOG_MANAGER.commit_log();
+        assert_eq!(inode_data.dinode.size, size);
+        assert_eq!(xv6fs::bitmap::free_blocks(0), free-used);
+    }
+    let mut buf=[0u8;700];
+    assert_eq!(inode_data.read(buf.as_mut_ptr() as usize, 0, 700), Ok(700));
+    assert_eq!(buf[..], data[..700]);
+    // Growing again reads zeros where the cut data was in the last block.
+    inode_data.write([1u8].as_ptr() as usize, 1000, 1).unwrap();
+    let mut buf=[9u8;300];
+    assert_eq!(inode_data.read(buf.as_mut_ptr() as usize, 700, 300), Ok(300));
+    assert!(buf.iter().all(|&b| b==0));
+    drop(inode_data);
+    drop(inode);
+    ICACHE.remove(path).unwrap();
+    LOG_MANAGER.commit_log();
+    Ok(())
+}
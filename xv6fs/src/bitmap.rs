@@ -10,6 +10,7 @@ use bit_field::BitField;
 use crate::{superblock::SUPER_BLOCK, block_dev::BlockNone};
 use crate::log::LOG_MANAGER;
 use crate::buffer_cache::BLOCK_CACHE_MANAGER;
+use crate::file::ENOSPC;
 use super::{ InodeType, DiskInode };
 
 
@@ -48,8 +49,9 @@ fn locate_inode_offset(inum: u32) -> usize {
 // }
 
 
-/// Allocate a zeroed disk block 
-pub fn balloc(dev: u32) -> u32 {
+/// Allocate a zeroed disk block.
+/// Fails with `ENOSPC` when every block is in use.
+pub fn balloc(dev: u32) -> Result<u32, &'static str> {
     let mut b = 0;
     let sb_size = unsafe{ SUPER_BLOCK.size() };
     while b < sb_size {
@@ -66,16 +68,16 @@ pub fn balloc(dev: u32) -> u32 {
                 let new_val:u8=buf_val|m;
                 unsafe{ ptr::write(buf_ptr, new_val) };
                 debug!("[Xv6fs] balloc: inum is {}",bi);
-                LOG_MANAGER.write(buf);
+                LOG_MANAGER.write(buf)?;
                 // drop(buf);
                 // bzero(dev, b + bi);
-                return b + bi
+                return Ok(b + bi)
             }
         }
         drop(buf);
         b += BPB;
     }
-    panic!("balloc: out of the block ranges.")
+    Err(ENOSPC)
 }
 
 pub fn bisalloc(blockno:u32)->bool{
@@ -110,17 +112,18 @@ pub fn bfree(devno:u32,blockno:u32)->Result<(),&'static str>{
     let buf_val=unsafe {ptr::read(buf_ptr)};
     //info!("buf val is {}",buf_val);
     if buf_val&(1<<bi)==0{
-        panic!("this bit is not alloc yet");
+        return Err("bfree: block is not allocated");
     }
     let new_val=buf_val^(1<<bi);
     //info!("new val is {}",new_val);
     unsafe{ptr::write(buf_ptr, new_val)};
     //unsafe{info!("buf is {:?}",buf.raw_data().as_ref().unwrap())};
-    LOG_MANAGER.write(buf);
-    Ok(())
+    LOG_MANAGER.write(buf)
 }
 
-pub fn inode_alloc(dev: u32, itype: InodeType) -> u32 {
+/// Allocate an on-disk inode of type `itype`.
+/// Fails with `ENOSPC` when every inode is in use.
+pub fn inode_alloc(dev: u32, itype: InodeType) -> Result<u32, &'static str> {
     let size = unsafe { SUPER_BLOCK.ninodes() };
     for inum in 1..size {
         let blockno = unsafe { SUPER_BLOCK.locate_inode(inum) };
@@ -131,10 +134,48 @@ pub fn inode_alloc(dev: u32, itype: InodeType) -> u32 {
         let dinode = unsafe { &mut *dinode };
         if dinode.try_alloc(itype).is_ok() {
             info!("[Xv6fs] inode alloc: inum is {} and offset is {}",inum,offset);
-            LOG_MANAGER.write(buf);
-            return inum
+            LOG_MANAGER.write(buf)?;
+            return Ok(inum)
         }
     }
 
-    panic!("not enough inode to alloc");
-}
\ No newline at end of file
+    Err(ENOSPC)
+}
+/// Number of blocks not marked as used in the bitmap.
+pub fn free_blocks(dev: u32) -> u32 {
+    let sb_size = unsafe { SUPER_BLOCK.size() };
+    let mut free = 0;
+    let mut b = 0;
+    while b < sb_size {
+        let bm_blockno = unsafe { SUPER_BLOCK.bitmap_blockno(b) };
+        let buf = BLOCK_CACHE_MANAGER.bread(dev, bm_blockno);
+        let bytes = buf.raw_data() as *const u8;
+        let mut bi = 0;
+        while bi < BPB && b + bi < sb_size {
+            let byte = unsafe { ptr::read(bytes.offset((bi / 8) as isize)) };
+            if !byte.get_bit((bi % 8) as usize) {
+                free += 1;
+            }
+            bi += 1;
+        }
+        drop(buf);
+        b += BPB;
+    }
+    free
+}
+
+/// Number of inodes not in use.
+pub fn free_inodes(dev: u32) -> u32 {
+    let size = unsafe { SUPER_BLOCK.ninodes() };
+    let mut free = 0;
+    for inum in 1..size {
+        let blockno = unsafe { SUPER_BLOCK.locate_inode(inum) };
+        let offset = locate_inode_offset(inum) as isize;
+        let buf = BLOCK_CACHE_MANAGER.bread(dev, blockno);
+        let dinode = unsafe { &*(buf.raw_data() as *const DiskInode).offset(offset) };
+        if dinode.itype == InodeType::Empty {
+            free += 1;
+        }
+    }
+    free
+}
@@ -1,10 +1,31 @@
 use core::any::Any;
 
+use crate::fs_const::BSIZE;
+
 /// Trait for block devices
 /// which reads and writes data in the unit of blocks
 pub trait BlockDevice : Send + Sync + Any {
     fn read_block(&self, _block_id: usize, _buf: &mut [u8]);
     fn write_block(&self, _block_id: usize, _buf: &[u8]);
+
+    /// Read `buf.len() / BSIZE` consecutive blocks starting at `start_block`.
+    /// Devices that can do this in one request should override it.
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) {
+        for (i, block) in buf.chunks_mut(BSIZE).enumerate() {
+            self.read_block(start_block + i, block);
+        }
+    }
+
+    /// Write `buf.len() / BSIZE` consecutive blocks starting at `start_block`.
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) {
+        for (i, block) in buf.chunks(BSIZE).enumerate() {
+            self.write_block(start_block + i, block);
+        }
+    }
+
+    /// Wait until every block written so far is on stable storage.
+    /// Devices without a volatile write cache can keep the default.
+    fn flush(&self) {}
 }
 
 pub struct BlockNone;
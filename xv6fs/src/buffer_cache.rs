@@ -11,14 +11,14 @@ use array_macro::array;
 
 use core::ptr;
 use core::ops::{Deref, DerefMut};
-use core::sync::atomic::{Ordering, AtomicBool};
+use core::sync::atomic::{Ordering, AtomicBool, AtomicUsize};
 
 use spin::{Mutex, MutexGuard};
 use crate::{SleepLock, SleepLockGuard, init_lock};
 use crate::block_dev::BlockNone;
 
 use super::{BlockDevice,NBUF, BSIZE};
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec, vec::Vec};
 use lazy_static::*;
 use crate::sync::UPSafeCell;
 // lazy_static!{
@@ -34,6 +34,23 @@ pub struct BlockCacheManager {
     ctrl: Mutex<BufLru>,
     bufs: [BufInner; NBUF],
     inner: UPSafeCell<BlockCacheManagerInner>,
+    /// only bufs with an index below this are used
+    limit: AtomicUsize,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// Counters reported by [`BlockCacheManager::stats`].
+#[derive(Clone, Copy, Debug)]
+pub struct BufCacheStats {
+    /// Number of bufs the cache may use.
+    pub capacity: usize,
+    /// Bufs currently referenced.
+    pub in_use: usize,
+    /// `bread` calls served from the cache.
+    pub hits: usize,
+    /// `bread` calls that went to the device.
+    pub misses: usize,
 }
 
 pub struct BlockCacheManagerInner{
@@ -52,9 +69,17 @@ impl BlockCacheManager {
             ctrl: Mutex::new(BufLru::new()),
             bufs: array![_ => BufInner::new(); NBUF],
             inner:unsafe{UPSafeCell::new(BlockCacheManagerInner::new())},
+            limit: AtomicUsize::new(NBUF),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
         }
     }
 
+    /// Use only the first `limit` bufs of the cache.
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit.min(NBUF), Ordering::Relaxed);
+    }
+
     pub fn set_block_device(&self,block_device: Arc<dyn BlockDevice>){
         self.inner.exclusive_access().block_device=Arc::clone(&block_device);
     }
@@ -108,7 +133,7 @@ impl BlockCacheManager {
                 // not cached
                 // recycle the least recently used (LRU) unused buffer
                 //debug!("bget recycle blockno {}",blockno);
-                match ctrl.recycle(dev, blockno) {
+                match ctrl.recycle(dev, blockno, self.limit.load(Ordering::Relaxed)) {
                     Some((index, rc_ptr)) => {
                         self.bufs[index].valid.store(false, Ordering::Relaxed);
                         drop(ctrl);
@@ -137,11 +162,95 @@ impl BlockCacheManager {
             //info!("not find block {} in cache!",block_id);
             inner.block_device.read_block(block_id as usize, b.data.as_mut().unwrap().0.as_mut());
             self.bufs[b.index].valid.store(true, Ordering::Relaxed);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.hits.fetch_add(1, Ordering::Relaxed);
         }
         drop(inner);
         b
     }
 
+    /// Read blocks into the cache before they are asked for.
+    /// Blocks already cached are skipped, and runs of consecutive block
+    /// numbers are fetched with a single `read_blocks` call, which is one
+    /// request only on devices that override it.
+    /// It gives up early rather than wait when no buffer is free.
+    pub fn prefetch(&self, dev: u32, blocks: &[u32]) {
+        let inner = self.inner.exclusive_access();
+        let mut run: Vec<Buf<'_>> = Vec::new();
+        for &blockno in blocks {
+            if run.last().map_or(false, |b| b.block_id + 1 != blockno) {
+                self.fill_run(&inner.block_device, &mut run);
+            }
+            let mut ctrl = self.ctrl.lock();
+            if ctrl.is_cached(dev, blockno) {
+                continue;
+            }
+            match ctrl.recycle(dev, blockno, self.limit.load(Ordering::Relaxed)) {
+                Some((index, rc_ptr)) => {
+                    self.bufs[index].valid.store(false, Ordering::Relaxed);
+                    drop(ctrl);
+                    run.push(Buf {
+                        index,
+                        dev,
+                        block_device: Arc::clone(&inner.block_device),
+                        block_id: blockno,
+                        rc_ptr,
+                        data: Some(self.bufs[index].data.lock()),
+                    });
+                }
+                None => break,
+            }
+        }
+        self.fill_run(&inner.block_device, &mut run);
+        drop(inner);
+    }
+
+    /// Read a run of consecutive, not yet valid bufs in one device request.
+    fn fill_run(&self, block_device: &Arc<dyn BlockDevice>, run: &mut Vec<Buf<'_>>) {
+        if run.is_empty() {
+            return;
+        }
+        let mut data = vec![0u8; run.len() * BSIZE];
+        block_device.read_blocks(run[0].block_id as usize, &mut data);
+        for (mut buf, block) in run.drain(..).zip(data.chunks(BSIZE)) {
+            buf.data.as_mut().unwrap().0.copy_from_slice(block);
+            self.bufs[buf.index].valid.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Forget every cached block and the block device, for the next mount.
+    /// No buf may be in use.
+    pub fn reset(&self) {
+        let mut ctrl = self.ctrl.lock();
+        debug_assert!(ctrl.inner.iter().all(|b| b.refcnt == 0));
+        // Back to how `new` left them, so no block counts as cached.
+        for (b, buf) in ctrl.inner.iter_mut().zip(self.bufs.iter()) {
+            b.dev = 0;
+            b.blockno = 0;
+            buf.valid.store(false, Ordering::Relaxed);
+        }
+        drop(ctrl);
+        self.set_block_device(Arc::new(BlockNone));
+    }
+
+    /// Flush the write cache of the underlying block device.
+    pub fn flush(&self) {
+        let inner = self.inner.exclusive_access();
+        inner.block_device.flush();
+        drop(inner);
+    }
+
+    pub fn stats(&self) -> BufCacheStats {
+        let ctrl = self.ctrl.lock();
+        BufCacheStats {
+            capacity: self.limit.load(Ordering::Relaxed),
+            in_use: ctrl.inner.iter().filter(|b| b.refcnt > 0).count(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
     /// Move an unlocked buf to the head of the most-recently-used list.
     fn brelse(&self, index: usize) {
         self.ctrl.lock().move_if_no_ref(index);
@@ -245,14 +354,27 @@ impl BufLru {
         None
     }
 
-    /// Recycle an unused buffer from the tail.
+    /// Whether the requested block is cached, without taking a reference.
+    fn is_cached(&self, _dev: u32, blockno: u32) -> bool {
+        let mut b = self.head;
+        while !b.is_null() {
+            let bref = unsafe { b.as_ref().unwrap() };
+            if bref.blockno == blockno {
+                return true;
+            }
+            b = bref.next;
+        }
+        false
+    }
+
+    /// Recycle an unused buffer with an index below `limit` from the tail.
     /// Return its index if found.
-    fn recycle(&self, dev: u32, blockno: u32) -> Option<(usize, *mut usize)> {
+    fn recycle(&self, dev: u32, blockno: u32, limit: usize) -> Option<(usize, *mut usize)> {
         debug!("[Xv6fs] BLOCK CACHE MANAGER: recycle unused buffer {}",blockno);
         let mut b = self.tail;
         while !b.is_null() {
             let bref = unsafe { b.as_mut().unwrap() };
-            if bref.refcnt == 0 {
+            if bref.refcnt == 0 && bref.index < limit {
                 bref.dev = dev;
                 bref.blockno = blockno;
                 bref.refcnt += 1;
@@ -1,6 +1,7 @@
 use core::ptr;
 
-use crate::fs_const::{ NDIRECT, DIRSIZ };
+use crate::fs_const::{ NDIRECT, DIRSIZ, FS_FEATURE_INLINE, IFLAG_INLINE, INLINE_MAX };
+use crate::superblock::SUPER_BLOCK;
 
 #[repr(u16)]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -8,7 +9,8 @@ pub enum InodeType {
     Empty = 0,
     Directory = 1,
     File = 2,
-    Device = 3
+    Device = 3,
+    Fifo = 4
 }
 
 /// On-disk inode structure
@@ -20,7 +22,9 @@ pub struct DiskInode {
     pub minor: i16, // Minor device number (T_DEVICE only)
     pub nlink: i16, // Number of links to inode in file system
     pub size: u32, // Size of file (bytes)
-    pub addrs: [u32; NDIRECT+2] // Data block addresses
+    pub flags: u32, // Inode flags (IFLAG_*)
+    pub addrs: [u32; NDIRECT+2], // Data block addresses
+    pub xattr: u32, // Block holding extended attributes, 0 if none
 }
 
 #[repr(C)]
@@ -38,7 +42,9 @@ impl DiskInode {
             minor: 0,
             nlink: 0,
             size: 0,
-            addrs: [0; NDIRECT+2]
+            flags: 0,
+            addrs: [0; NDIRECT+2],
+            xattr: 0
         }
     }
 
@@ -46,11 +52,28 @@ impl DiskInode {
         if self.itype == InodeType::Empty {
             unsafe { ptr::write_bytes(self, 0, 1); }
             self.itype = itype;
+            let inline_fs = unsafe { SUPER_BLOCK.has_feature(FS_FEATURE_INLINE) };
+            if inline_fs && (itype == InodeType::File || itype == InodeType::Directory) {
+                self.flags = IFLAG_INLINE;
+            }
             Ok(())
         } else {
             Err(())
         }
     }
+
+    /// Whether the file data lives in the `addrs` area instead of data blocks.
+    pub fn is_inline(&self) -> bool {
+        self.flags & IFLAG_INLINE != 0
+    }
+
+    pub fn inline_data(&self) -> &[u8; INLINE_MAX] {
+        unsafe { &*(self.addrs.as_ptr() as *const [u8; INLINE_MAX]) }
+    }
+
+    pub fn inline_data_mut(&mut self) -> &mut [u8; INLINE_MAX] {
+        unsafe { &mut *(self.addrs.as_mut_ptr() as *mut [u8; INLINE_MAX]) }
+    }
 }
 
 impl DirEntry {
@@ -1,20 +1,52 @@
 use crate::SleepLock;
 use crate::bitmap::inode_alloc;
 use crate::disk_inode::{InodeType};
-use crate::fs_const::{ BSIZE, MAXOPBLOCKS, DIRSIZ };
-use crate::inode::{ICACHE,Inode, InodeData};
+use crate::fs_const::{ BSIZE, MAXOPBLOCKS, DIRSIZ, ROOTINUM };
+use crate::inode::{ICACHE,Inode};
 use super::stat::Stat;
 use crate::log::LOG_MANAGER;
+use crate::options::{check_writable, mount_options};
 use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::sync::Arc;
-use axlog::info;
+use axlog::{info, warn};
 use axtask::spawn;
 use core::sync::atomic::AtomicI32;
 use crate::sync::sleeplock::init_lock;
 use core::mem::size_of;
 use crate::xv6fs::Xv6FileSystem;
 
+// Errors of the path based operations, for callers that need to tell
+// them apart. Changes on a read-only mount fail with `options::EROFS`.
+pub const ENOENT: &str = "no such file or directory";
+pub const EEXIST: &str = "file exists";
+pub const ENOTDIR: &str = "not a directory";
+pub const EISDIR: &str = "is a directory";
+pub const ENOTEMPTY: &str = "directory not empty";
+pub const EINVAL: &str = "invalid argument";
+pub const EPERM: &str = "operation not permitted";
+pub const ENAMETOOLONG: &str = "file name too long";
+pub const ENOSPC: &str = "no space left on device";
+pub const ENOMEM: &str = "cannot allocate memory";
+
+/// `name` as a directory entry name, padded with NULs.
+fn dir_name(name: &str) -> Result<[u8; DIRSIZ], &'static str> {
+    let name = name.trim_end_matches('\0').as_bytes();
+    if name.len() >= DIRSIZ {
+        return Err(ENAMETOOLONG);
+    }
+    let mut padded = [0u8; DIRSIZ];
+    padded[..name.len()].copy_from_slice(name);
+    Ok(padded)
+}
+
+/// Entry name of the parent directory.
+fn dotdot() -> [u8; DIRSIZ] {
+    let mut name = [0u8; DIRSIZ];
+    name[..2].copy_from_slice(b"..");
+    name
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(u16)]
 pub enum FileType {
@@ -112,6 +144,7 @@ impl VFile {
         len: usize
     ) -> Result<usize, &'static str> {
         let ret; 
+        check_writable()?;
         if !self.vfile_writeable() {
             panic!("file can't be written")
         }
@@ -173,6 +206,7 @@ impl VFile {
         len: usize
     ) -> Result<usize, &'static str> {
         let ret; 
+        check_writable()?;
         if !self.vfile_writeable() {
             panic!("file can't be written")
         }
@@ -221,9 +255,8 @@ impl VFile {
     /// addr is a user virtual address, pointing to a struct stat. 
     pub fn vfile_stat(&self) -> Result<Stat, &'static str> {
         let mut stat: Stat = Stat::new();
-        match self.ftype {
-            FileType::File|FileType::Directory => {
-                let inode = self.inode.as_ref().unwrap();
+        match self.inode.as_ref() {
+            Some(inode) => {
                 
                 #[cfg(feature = "debug")]
                 info!("[Kernel] stat: inode index: {}, dev: {}, inum: {}", inode.index, inode.dev, inode.inum);
@@ -239,8 +272,8 @@ impl VFile {
                 Ok(stat)
             },  
 
-            _ => {
-                Err("")
+            None => {
+                Err(EINVAL)
             }
         }
     }
@@ -259,52 +292,276 @@ impl VFile {
         false
     }
 
+    /// Whether this file is a named pipe (FIFO inode).
+    pub fn vfile_is_fifo(&self)->bool{
+        self.ftype==FileType::Pipe
+    }
+
+    /// Device number (major, minor) if this file is a device inode.
+    pub fn vfile_rdev(&self)->Option<(i16,i16)>{
+        if self.ftype!=FileType::Device{
+            return None;
+        }
+        let idata=self.inode.as_ref().unwrap().lock();
+        Some((idata.dinode.major, idata.dinode.minor))
+    }
+
+    /// Inode number of this file.
+    pub fn vfile_inum(&self)->u32{
+        self.inode.as_ref().map_or(0, |inode| inode.inum)
+    }
+
     pub fn vfile_open(path:&str,readable:bool,writeable:bool)->Option<Self>{
         //info!("vfile open: path is {}",path);
-        let inode=ICACHE.create(path.as_bytes(),crate::disk_inode::InodeType::File, 2, 1).unwrap();
-        Some(Self { ftype: FileType::File, readable, writeable, inode:Some(inode), offset:0, major:2})
+        // Opening a missing path creates it, which a read-only mount cannot do.
+        if mount_options().read_only {
+            return Self::vfile_lookup(path, readable, writeable);
+        }
+        let inode=ICACHE.create(path.as_bytes(),crate::disk_inode::InodeType::File, 2, 1).ok()?;
+        Some(Self::from_inode(inode, readable, writeable))
+    }
+
+    /// Open an existing file, without creating it if the path is missing.
+    pub fn vfile_lookup(path:&str,readable:bool,writeable:bool)->Option<Self>{
+        let inode=ICACHE.namei(path.as_bytes()).ok()?;
+        Some(Self::from_inode(inode, readable, writeable))
+    }
+
+    fn from_inode(inode:Inode,readable:bool,writeable:bool)->Self{
+        let ftype=match inode.lock().dinode.itype {
+            InodeType::Directory => FileType::Directory,
+            InodeType::Device => FileType::Device,
+            InodeType::Fifo => FileType::Pipe,
+            _ => FileType::File,
+        };
+        Self { ftype, readable, writeable, inode:Some(inode), offset:0, major:2}
+    }
+
+    /// Open the existing file at `path`, relative to this directory.
+    pub fn vfile_lookup_at(&self,path:&str)->Result<Self,&'static str>{
+        let dir=self.inode.as_ref().ok_or(ENOTDIR)?;
+        let inode=ICACHE.namei_at(dir, path.as_bytes())?;
+        Ok(Self::from_inode(inode, true, true))
+    }
+
+    /// The directory holding `path`, relative to this directory,
+    /// and the last component of `path`.
+    fn vfile_parent(&self,path:&str)->Result<(Inode,[u8;DIRSIZ]),&'static str>{
+        let dir=self.inode.as_ref().ok_or(ENOTDIR)?;
+        let mut name=[0u8;DIRSIZ];
+        let parent=ICACHE.namei_parent_at(dir, path.as_bytes(), &mut name)?;
+        if name==dir_name(".")? || name==dotdot() {
+            return Err(EINVAL);
+        }
+        Ok((parent,name))
+    }
+
+    /// Create a file of type `itype` at `path`, relative to this directory.
+    /// Fails if the path exists.
+    pub fn vfile_create_at(&self,path:&str,itype:InodeType,major:i16,minor:i16)->Result<Self,&'static str>{
+        check_writable()?;
+        let (parent,name)=self.vfile_parent(path)?;
+        Self::from_inode(parent, true, true).vfile_create_node(&name, itype, major, minor)
+    }
+
+    /// Read from byte `offset` of the file. Returns 0 at the end of the file.
+    pub fn vfile_read_at(&self,offset:usize,buf:&mut [u8])->Result<usize,&'static str>{
+        let inode=self.inode.as_ref().ok_or(EINVAL)?;
+        let mut idata=inode.lock();
+        let size=idata.dinode.size as usize;
+        if offset>=size {
+            return Ok(0);
+        }
+        let len=buf.len().min(size-offset);
+        idata.read(buf.as_mut_ptr() as usize, offset as u32, len as u32)
+    }
+
+    /// Write at byte `offset` of the file. A gap after the current end
+    /// of the file is filled with zeros.
+    pub fn vfile_write_at(&self,offset:usize,data:&[u8])->Result<usize,&'static str>{
+        check_writable()?;
+        let inode=self.inode.as_ref().ok_or(EINVAL)?;
+        let size=inode.lock().dinode.size as usize;
+        if offset>size {
+            Self::write_zeros(inode, size, offset)?;
+        }
+        // Same split as `vfile_write`, to keep each transaction small.
+        let max = ((MAXOPBLOCKS -1 -1 -2) / 2) * BSIZE;
+        let mut count=0;
+        while count<data.len() {
+            let write_bytes=(data.len()-count).min(max);
+            let mut idata=inode.lock();
+            idata.write(
+                data[count..].as_ptr() as usize,
+                (offset+count) as u32,
+                write_bytes as u32
+            )?;
+            drop(idata);
+            LOG_MANAGER.commit_log();
+            count+=write_bytes;
+        }
+        Ok(count)
+    }
+
+    fn write_zeros(inode:&Inode,from:usize,to:usize)->Result<(),&'static str>{
+        let zeros=[0u8;BSIZE];
+        let mut offset=from;
+        while offset<to {
+            let write_bytes=(to-offset).min(BSIZE);
+            let mut idata=inode.lock();
+            idata.write(zeros.as_ptr() as usize, offset as u32, write_bytes as u32)?;
+            drop(idata);
+            LOG_MANAGER.commit_log();
+            offset+=write_bytes;
+        }
+        Ok(())
+    }
+
+    /// Cut or zero-extend the file to `size` bytes.
+    pub fn vfile_truncate(&self,size:usize)->Result<(),&'static str>{
+        check_writable()?;
+        if self.ftype==FileType::Directory {
+            return Err(EISDIR);
+        }
+        let inode=self.inode.as_ref().ok_or(EINVAL)?;
+        let mut idata=inode.lock();
+        let old_size=idata.dinode.size as usize;
+        if size>old_size {
+            drop(idata);
+            return Self::write_zeros(inode, old_size, size);
+        }
+        // One transaction, so a crash leaves the old file or the cut one.
+        let result=idata.shrink(inode, size as u32);
+        drop(idata);
+        LOG_MANAGER.commit_log();
+        result
+    }
+
+    /// Names, inode numbers and types of the entries of this directory,
+    /// leaving out "." and "..".
+    pub fn vfile_dir_entries(&self)->Result<Vec<(String,u32,InodeType)>,&'static str>{
+        if self.ftype!=FileType::Directory {
+            return Err(ENOTDIR);
+        }
+        let inode=self.inode.as_ref().ok_or(ENOTDIR)?;
+        let entries=inode.lock().dir_entries()?;
+        entries
+            .into_iter()
+            .map(|(name,inum)| {
+                let itype=ICACHE.get(inode.dev, inum)?.lock().dinode.itype;
+                Ok((name,inum,itype))
+            })
+            .collect()
     }
 
     pub fn vfile_readdir(&self)->Option<Vec<String>>{
         if self.ftype!=FileType::Directory{
-            panic!("this is not a directory!");
+            return None;
         }
-        let mut inode_data=self.inode.as_ref().unwrap().lock();
-        inode_data.ls()
+        let mut inode_data=self.inode.as_ref()?.lock();
+        inode_data.ls().ok()
     }
 
     pub fn vfile_remove(&self,path:&str){
-        ICACHE.remove(path.as_bytes());
+        if let Err(e)=check_writable(){
+            warn!("[Xv6fs] vfile remove {}: {}",path,e);
+            return;
+        }
+        if let Err(e)=ICACHE.remove(path.as_bytes()){
+            warn!("[Xv6fs] vfile remove {}: {}",path,e);
+        }
         LOG_MANAGER.commit_log();
     }
 
-    pub fn vfile_create_under_dir(&self,file_name:&str,itype:InodeType)->Self{
-        info!("vfile create: path is {}",file_name);
-        let self_inode=self.inode.as_ref().unwrap();
+    pub fn vfile_create_under_dir(&self,file_name:&str,itype:InodeType)->Result<Self,&'static str>{
+        self.vfile_create_node(&dir_name(file_name)?, itype, 2, 1)
+    }
+
+    /// Create a device inode with the given device number under this directory.
+    pub fn vfile_mknod(&self,file_name:&str,major:i16,minor:i16)->Result<Self,&'static str>{
+        self.vfile_create_node(&dir_name(file_name)?, InodeType::Device, major, minor)
+    }
+
+    fn vfile_create_node(&self,name:&[u8;DIRSIZ],itype:InodeType,major:i16,minor:i16)->Result<Self,&'static str>{
+        info!("vfile create: name is {:?}",core::str::from_utf8(name));
+        check_writable()?;
+        if self.ftype!=FileType::Directory {
+            return Err(ENOTDIR);
+        }
+        let self_inode=self.inode.as_ref().ok_or(ENOTDIR)?;
         let mut self_idata=self_inode.lock();
+        // Checked with the directory locked, so only one of two racing
+        // creates gets the name.
+        if self_idata.dir_lookup(name)?.is_some() {
+            return Err(EEXIST);
+        }
         let dev=self_inode.dev;
-        let inum=inode_alloc(dev,itype);
+        let inum=inode_alloc(dev,itype)?;
         info!("vfile create: inum is {}",inum);
-        let inode=ICACHE.get(dev, inum);
+        let inode=ICACHE.get(dev, inum)?;
         let mut idata=inode.lock();
-        idata.dinode.major=2;
-        idata.dinode.minor=1;
+        idata.dinode.major=major;
+        idata.dinode.minor=minor;
         idata.dinode.nlink=1;
-        idata.update();
+        idata.update()?;
         let mut ftype=FileType::File;
+        if itype==InodeType::Fifo{
+            ftype=FileType::Pipe;
+        }
+        if itype==InodeType::Device{
+            ftype=FileType::Device;
+        }
         if itype==InodeType::Directory{
             ftype=FileType::Directory;
             idata.dinode.nlink+=1;
-            idata.update();
-            idata.dir_link(".".as_bytes(), inum);
-            idata.dir_link("..".as_bytes(), self_inode.inum);
+            idata.update()?;
+            idata.dir_link(".".as_bytes(), inum)?;
+            idata.dir_link("..".as_bytes(), self_inode.inum)?;
         }
-        self_idata.dir_link(file_name.as_bytes(), inode.inum).expect("parent inode fail to link");
+        self_idata.dir_link(name, inode.inum)?;
         drop(idata);
         drop(self_idata);
         LOG_MANAGER.commit_log();
-        VFile { ftype, readable:true, writeable:true, inode:Some(inode), offset:0, major:2}
-        
+        Ok(VFile { ftype, readable:true, writeable:true, inode:Some(inode), offset:0, major:2})
+    }
+
+    /// Get the value of the extended attribute `name`.
+    pub fn vfile_getxattr(&self,name:&str)->Result<Vec<u8>,&'static str>{
+        let idata=self.inode.as_ref().ok_or("vfile: no inode")?.lock();
+        idata.xattr_get(name)
+    }
+
+    /// Set the extended attribute `name`, creating it if needed.
+    pub fn vfile_setxattr(&self,name:&str,value:&[u8])->Result<(),&'static str>{
+        check_writable()?;
+        let mut idata=self.inode.as_ref().ok_or("vfile: no inode")?.lock();
+        let ret=idata.xattr_set(name, value);
+        drop(idata);
+        LOG_MANAGER.commit_log();
+        ret
+    }
+
+    /// List the names of all extended attributes.
+    pub fn vfile_listxattr(&self)->Result<Vec<String>,&'static str>{
+        let idata=self.inode.as_ref().ok_or("vfile: no inode")?.lock();
+        idata.xattr_list()
+    }
+
+    /// Remove the extended attribute `name`.
+    pub fn vfile_removexattr(&self,name:&str)->Result<(),&'static str>{
+        check_writable()?;
+        let mut idata=self.inode.as_ref().ok_or("vfile: no inode")?.lock();
+        let ret=idata.xattr_remove(name);
+        drop(idata);
+        LOG_MANAGER.commit_log();
+        ret
+    }
+
+    /// Make everything written so far durable.
+    /// All metadata and data go through the one global log, so this syncs
+    /// the whole file system rather than just this file.
+    pub fn vfile_fsync(&self){
+        LOG_MANAGER.sync();
     }
 
     pub fn vfile_size(&self)->usize{
@@ -313,78 +570,142 @@ impl VFile {
         idata.dinode.size as usize
     }
 
-    pub fn vfile_link(&self,src_path:&str,dir_path:&str){
-        let inode=match ICACHE.namei(src_path.as_bytes()) {
-            Some(cur)=>{
-                cur
-            },
-            None=>{
-                panic!("[Xv6fs] vfile_link: not find src path");
-            }
-        };
-        let mut inode_guard=inode.lock();
-        if inode_guard.dinode.itype == InodeType::Directory {
-            panic!("[Xv6fs] vfile_link: cannot link directory");
+    /// Add `dst_path` as another name for the file at `src_path`,
+    /// both relative to this directory.
+    pub fn vfile_link(&self,src_path:&str,dst_path:&str)->Result<(),&'static str>{
+        check_writable()?;
+        let dir=self.inode.as_ref().ok_or(ENOTDIR)?;
+        let inode=ICACHE.namei_at(dir, src_path.as_bytes())?;
+        if inode.lock().dinode.itype == InodeType::Directory {
+            return Err(EPERM);
         }
-        inode_guard.dinode.nlink+=1;
-        let mut name = [0u8; DIRSIZ];
-        let parent=match ICACHE.namei_parent(&dir_path.as_bytes(), &mut name) {
-            Some(cur)=>{
-                cur
-            },
-            None => {
-                panic!("[Xv6fs] vfile_link: not find dir path");
-            }
-        };
+        let (parent,name)=self.vfile_parent(dst_path)?;
         let mut parent_guard=parent.lock();
-        parent_guard.dir_link(&name, inode.inum);
-        inode_guard.update();
-        parent_guard.update();
+        if parent_guard.dir_lookup(&name)?.is_some() {
+            return Err(EEXIST);
+        }
+        parent_guard.dir_link(&name, inode.inum)?;
         drop(parent_guard);
+        let mut inode_guard=inode.lock();
+        inode_guard.dinode.nlink+=1;
+        inode_guard.update()?;
         drop(inode_guard);
         LOG_MANAGER.commit_log();
+        Ok(())
     }
 
-    pub fn vfile_unlink(&self,path:&str){//目录没有删掉dir entry
+    /// Remove the name `path`, relative to this directory, of a file that
+    /// is not a directory. The file is freed with its last name and handle.
+    pub fn vfile_unlink(&self,path:&str)->Result<(),&'static str>{
         info!("[Xv6fs] vfile unlink: unlink {}",path);
-        let mut name = [0u8; DIRSIZ];
-        let parent=match ICACHE.namei_parent(&path.as_bytes(), &mut name) {
-            Some(cur)=>cur,
-            None=>panic!("[Xv6fs] vfile_unlink: not find path")
-        };
+        check_writable()?;
+        let (parent,name)=self.vfile_parent(path)?;
         let mut parent_guard=parent.lock();
-        let inode=match parent_guard.dir_lookup(&name) {
-            Some(cur) => {
-                cur
-            },
-            _ => {
-                panic!("[Xv6fs] vfile_unlink: not find name");
-            }
-        };
+        let inode=parent_guard.dir_lookup(&name)?.ok_or(ENOENT)?;
         let mut inode_guard=inode.lock();
         if inode_guard.dinode.itype==InodeType::Directory{
-            panic!("[Xv6fs] vfile_link: cannot unlink directory");
+            return Err(EISDIR);
         }
+        // The entry goes first: if dropping the link count then fails,
+        // the file keeps a count too high, which only leaks it, instead of
+        // being freed while a name still points at it.
+        parent_guard.dir_unlink(&name)?;
+        drop(parent_guard);
         inode_guard.dinode.nlink-=1;
-        info!("now disk inode nlink is {}",inode_guard.dinode.nlink);
-        let flag=match inode_guard.dinode.nlink {
-            0=>true,
-            _=>false
-        };
-        inode_guard.update();
+        inode_guard.update()?;
         drop(inode_guard);
-        if flag{
-            drop(parent_guard);
-            self.vfile_remove(path);
-        }else{
-            parent_guard.dir_unlink(&name);
-            drop(parent_guard);
+        drop(inode);
+        LOG_MANAGER.commit_log();
+        Ok(())
+    }
+
+    /// Remove the empty directory `path`, relative to this directory.
+    pub fn vfile_rmdir(&self,path:&str)->Result<(),&'static str>{
+        info!("[Xv6fs] vfile rmdir: rmdir {}",path);
+        check_writable()?;
+        let (parent,name)=self.vfile_parent(path)?;
+        let mut parent_guard=parent.lock();
+        let inode=parent_guard.dir_lookup(&name)?.ok_or(ENOENT)?;
+        let mut inode_guard=inode.lock();
+        if inode_guard.dinode.itype!=InodeType::Directory{
+            return Err(ENOTDIR);
+        }
+        if !inode_guard.is_dir_empty()?{
+            return Err(ENOTEMPTY);
         }
+        // Entry first, as in `vfile_unlink`.
+        parent_guard.dir_unlink(&name)?;
+        drop(parent_guard);
+        inode_guard.dinode.nlink=0;
+        inode_guard.update()?;
+        drop(inode_guard);
+        drop(inode);
         LOG_MANAGER.commit_log();
+        Ok(())
     }
 
-    pub fn vfile_rename(&self,path:&str,new_name:&str){
-        InodeData::rename(path, new_name);
+    /// Move `src_path` to `dst_path`, both relative to this directory.
+    /// An existing `dst_path` is replaced if it is of the same kind,
+    /// and, for a directory, empty.
+    ///
+    /// Everything that can be refused is checked before the first write,
+    /// and that first write is the only one that may allocate: it adds the
+    /// new entry, or points an existing one at the moved inode. The rest
+    /// rewrite entries and inodes in place.
+    pub fn vfile_rename(&self,src_path:&str,dst_path:&str)->Result<(),&'static str>{
+        check_writable()?;
+        let (src_parent,src_name)=self.vfile_parent(src_path)?;
+        let (dst_parent,dst_name)=self.vfile_parent(dst_path)?;
+        let inode=src_parent.lock().dir_lookup(&src_name)?.ok_or(ENOENT)?;
+        let is_dir=inode.lock().dinode.itype==InodeType::Directory;
+        let moves_dir=is_dir && src_parent.inum!=dst_parent.inum;
+        if moves_dir {
+            // Walk up from the new parent: a directory may not be moved
+            // below itself.
+            let mut cur=dst_parent.clone();
+            while cur.inum!=ROOTINUM {
+                if cur.inum==inode.inum {
+                    return Err(EINVAL);
+                }
+                let up=cur.lock().dir_lookup(&dotdot())?.ok_or(ENOENT)?;
+                cur=up;
+            }
+        }
+        let mut dst_guard=dst_parent.lock();
+        let old=dst_guard.dir_lookup(&dst_name)?;
+        if let Some(old)=&old {
+            if old.inum==inode.inum {
+                return Ok(());
+            }
+            let mut old_guard=old.lock();
+            let old_is_dir=old_guard.dinode.itype==InodeType::Directory;
+            if old_is_dir && !is_dir {
+                return Err(EISDIR);
+            }
+            if !old_is_dir && is_dir {
+                return Err(ENOTDIR);
+            }
+            if old_is_dir && !old_guard.is_dir_empty()? {
+                return Err(ENOTEMPTY);
+            }
+        }
+        match &old {
+            Some(_)=>dst_guard.dir_relink(&dst_name, inode.inum)?,
+            None=>dst_guard.dir_link(&dst_name, inode.inum)?,
+        }
+        drop(dst_guard);
+        src_parent.lock().dir_unlink(&src_name)?;
+        if let Some(old)=old {
+            let mut old_guard=old.lock();
+            let old_is_dir=old_guard.dinode.itype==InodeType::Directory;
+            old_guard.dinode.nlink=if old_is_dir { 0 } else { old_guard.dinode.nlink-1 };
+            old_guard.update()?;
+        }
+        if moves_dir {
+            inode.lock().dir_relink(&dotdot(), dst_parent.inum)?;
+        }
+        LOG_MANAGER.commit_log();
+        Ok(())
     }
 
     pub fn test_sleep_lock(){
@@ -421,7 +742,7 @@ impl VFile {
 }
 
 pub fn test_link_unlink(){
-    let inode=ICACHE.get_root_dir();
+    let inode=ICACHE.get_root_dir().expect("can't get root directory");
     let idata=inode.lock();
     let mut ftype=FileType::Directory;
     drop(idata);
@@ -439,20 +760,20 @@ pub fn test_link_unlink(){
         }
     })
     .expect("can't read root directory");
-    root.vfile_create_under_dir("test\0", InodeType::File);
+    root.vfile_create_under_dir("test", InodeType::File).unwrap();
     root.vfile_readdir().map(|x| {
         for file_name in x {
             info!("{}", file_name);
         }
     })
     .expect("can't read root directory");
-    //root.vfile_remove("/test\0");
-    root.vfile_link("/test\0", "/test1\0");
+    //root.vfile_remove("/test");
+    root.vfile_link("/test", "/test1").expect("can't link test file");
     let data="hello".as_bytes();
-    let test1=VFile::vfile_open("/test1\0", true, true).unwrap();
+    let test1=VFile::vfile_open("/test1", true, true).unwrap();
     test1.vfile_write(data.as_ptr() as usize, data.len());
-    root.vfile_unlink("/test1\0");
-    root.vfile_unlink("/test\0");
+    root.vfile_unlink("/test1").expect("can't unlink test1");
+    root.vfile_unlink("/test").expect("can't unlink test");
     root.vfile_readdir().map(|x| {
         for file_name in x {
             info!("{}", file_name);
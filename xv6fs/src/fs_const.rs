@@ -2,12 +2,23 @@ use core::mem::size_of;
 use super::DiskInode;
 /// magic number indentifying this specific file system
 pub const FSMAGIC: u32 = 0x10203040;
+/// super block feature: the super block carries a crc32c checksum
+pub const FS_FEATURE_CSUM: u32 = 0x1;
+/// super block feature: new files and directories may keep their data inline
+pub const FS_FEATURE_INLINE: u32 = 0x2;
+/// super block feature: inodes have the flags and xattr fields and NDIRECT
+/// direct blocks. Required: older images put block addresses there.
+pub const FS_FEATURE_INODE_EXT: u32 = 0x4;
 /// size of disk block
 pub const BSIZE: usize = 512;
 /// Maxinum of blocks an FS op can write
 pub const MAXOPBLOCKS: usize = 10;
 /// size of buffer cache for block
 pub const NBUF: usize = MAXOPBLOCKS * 3;
+/// first readahead window, in blocks, once sequential reading is detected
+pub const RA_MIN_BLOCKS: usize = 2;
+/// largest readahead window, kept well below NBUF
+pub const RA_MAX_BLOCKS: usize = 8;
 /// size of log space in disk
 pub const LOGSIZE: usize = MAXOPBLOCKS * 3;
 
@@ -28,11 +39,16 @@ pub const FSSIZE: usize = 1000;
 
 pub const ROOTINUM: u32 = 1;
 
-pub const NDIRECT: usize = 11;
+pub const NDIRECT: usize = 9;
 pub const NINDIRECT: usize =  BSIZE / 8;
 pub const NININDIRECT: usize = BSIZE / 8 * NINDIRECT;
 pub const MAXFILE: usize = NDIRECT + NINDIRECT + NININDIRECT;
 
+/// inode flag: the file data is stored in the `addrs` area of the inode
+pub const IFLAG_INLINE: u32 = 0x1;
+/// Bytes of data that fit inline, i.e. the size of the `addrs` area
+pub const INLINE_MAX: usize = (NDIRECT + 2) * size_of::<u32>();
+
 /// Directory is a file containing a sequence of dirent structures
 pub const DIRSIZ: usize = 14;
 
@@ -8,9 +8,12 @@ use std::{println as info, println as warn}; // Workaround to use prinltn! for l
 
 use crate::{SleepLock, init_lock, SleepLockGuard};
 use crate::fs_const::{BSIZE, DIRSIZ, IPB, NDIRECT, NINDIRECT, NINODE, ROOTDEV, ROOTINUM, NININDIRECT};
+use crate::fs_const::{FS_FEATURE_INLINE, IFLAG_INLINE, INLINE_MAX, RA_MIN_BLOCKS, RA_MAX_BLOCKS};
 use crate::log::LOG_MANAGER;
+use crate::options::{mount_options, DataMode};
 use crate::bitmap::{inode_alloc, bisalloc};
 use crate::misc::{min, mem_set};
+use crate::file::{EEXIST, ENAMETOOLONG, ENOENT, ENOMEM, ENOTDIR};
 use crate::interface::INTERFACE_MANAGER;
 
 use spin::{Mutex,MutexGuard};
@@ -34,6 +37,14 @@ pub static ICACHE: LazyInit<InodeCache> = LazyInit::new();
 type BlockNo = u32;
 
  
+/// Counters reported by [`InodeCache::stats`].
+#[derive(Clone, Copy, Debug)]
+pub struct InodeCacheStats {
+    pub capacity: usize,
+    /// Slots holding a referenced inode.
+    pub in_use: usize,
+}
+
 pub struct InodeCache {
     meta: Mutex<[InodeMeta; NINODE]>,
     data: [SleepLock<InodeData>; NINODE]
@@ -48,6 +59,14 @@ impl InodeCache {
     }
 
 
+    pub fn stats(&self) -> InodeCacheStats {
+        let guard = self.meta.lock();
+        InodeCacheStats {
+            capacity: NINODE,
+            in_use: guard.iter().filter(|meta| meta.refs > 0).count(),
+        }
+    }
+
     /// Clone an inode by just increment its reference count by 1. 
     fn dup(&self, inode: &Inode) -> Inode {
         let mut guard = self.meta.lock();
@@ -79,7 +98,10 @@ impl InodeCache {
             } else {
                 drop(guard);
                 idata.dinode.itype = InodeType::Empty;
-                idata.truncate(inode);
+                // Called from Drop, so errors can only be reported.
+                if let Err(e) = idata.free_xattr().and_then(|_| idata.truncate(inode)) {
+                    warn!("[Xv6fs] inode put: freeing inode {}: {}", inode.inum, e);
+                }
                 idata.valid = false;
                 drop(idata);
 
@@ -120,8 +142,8 @@ impl InodeCache {
             let dinode = unsafe{ &mut *dinode };
             // Find a empty inode
             if dinode.try_alloc(itype).is_ok() {
-                LOG_MANAGER.write(block);
-                return Some(self.get(dev, inum))
+                LOG_MANAGER.write(block).ok()?;
+                return self.get(dev, inum).ok()
             }
             // drop(block);
         }
@@ -132,7 +154,8 @@ impl InodeCache {
     /// If found, return an handle. 
     /// If not found, alloc an in-memory location in the cache, 
     /// but not fetch it from the disk yet. 
-    pub fn get(&self, dev: u32, inum: u32) -> Inode {
+    /// Fails with `ENOMEM` when all NINODE slots are referenced.
+    pub fn get(&self, dev: u32, inum: u32) -> Result<Inode, &'static str> {
         let mut guard = self.meta.lock();
 
         // lookup in the cache 
@@ -141,11 +164,11 @@ impl InodeCache {
             if guard[i].inum == inum && guard[i].refs > 0 && guard[i].dev == dev {
                 guard[i].refs += 1;
                 // info!("[Debug] 获取Inode");
-                return Inode {
+                return Ok(Inode {
                     dev,
                     inum,
                     index: i,
-                }
+                })
             }
             if empty_i.is_none() && guard[i].refs == 0 {
                 empty_i = Some(i);
@@ -153,60 +176,62 @@ impl InodeCache {
         }
 
         // not found 
-        let empty_i = match empty_i {
-            Some(i) => i,
-            None => panic!("inode: not enough"),
-        };
+        let empty_i = empty_i.ok_or(ENOMEM)?;
         guard[empty_i].dev = dev;
         guard[empty_i].inum = inum;
         guard[empty_i].refs = 1;
         // 此时 Inode Cache 应当是无效的
         let idata = self.data[empty_i].lock();
         assert!(idata.valid == false, "此时 idata 应当无效");
-        Inode {
+        Ok(Inode {
             dev,
             inum,
             index: empty_i
-        }
+        })
     }
 
     /// Helper function for 'namei' and 'namei_parent'
+    /// A relative path starts at `start`, or at the current directory if
+    /// there is none.
     fn namex(
         &self, 
         path: &[u8], 
         name: &mut [u8;DIRSIZ], 
-        is_parent: bool
-    ) -> Option<Inode> {
+        is_parent: bool,
+        start: Option<&Inode>
+    ) -> Result<Inode, &'static str> {
         let mut inode: Inode;
-        if path[0] == b'/' {
-            inode = self.get(ROOTDEV, ROOTINUM);
+        if path.first() == Some(&b'/') {
+            inode = self.get(ROOTDEV, ROOTINUM)?;
             //info!("path 0 is /");
+        } else if let Some(start) = start {
+            inode = self.dup(start);
         } else {
             //这里是要获取当前目录的名称
-            inode=self.dup(INTERFACE_MANAGER.interface.as_ref().get_cur_dir_inode().as_ref().unwrap());
+            inode=self.dup(INTERFACE_MANAGER.interface.as_ref().get_cur_dir_inode().as_ref().ok_or(ENOENT)?);
         }
         let mut cur: usize = 0;
         loop {
-            cur = skip_path(path, cur, name);//这里name获取了/后面的第一个路径名
+            cur = skip_path(path, cur, name)?;//这里name获取了/后面的第一个路径名
             if cur == 0 { break; }
             //info!("cur is {:?}, and name is {:?}",cur,String::from_utf8(name.to_vec()).unwrap());
             let mut data_guard = inode.lock();
             //info!("acquire lock");
             if data_guard.dinode.itype != InodeType::Directory {
                 drop(data_guard);
-                return None
+                return Err(ENOTDIR)
             }
-            if is_parent && path[cur] == 0 {
+            if is_parent && path_byte(path, cur) == 0 {
                 //info!("is is parent and path[cur]=0");
                 drop(data_guard);
-                return Some(inode)
+                return Ok(inode)
             }
 
-            match data_guard.dir_lookup(name) {
+            match data_guard.dir_lookup(name)? {
                 None => {
                     drop(data_guard);
                     // info!("[Kernel] name: {}", String::from_utf8(name.to_vec()).unwrap());
-                    return None
+                    return Err(ENOENT)
                 },
                 Some(last_inode) => {
                     drop(data_guard);
@@ -218,25 +243,37 @@ impl InodeCache {
         if is_parent {
             // only when querying root inode's parent 
             //info!("[Kernel] Warning: namex querying root inode's parent");
-            None 
+            Err(ENOENT)
         } else {
-            Some(inode)
+            Ok(inode)
         }
     }
 
     /// namei interprets the path argument as an pathname to Unix file. 
-    /// It will return an [`inode`] if succeed, Err(()) if fail. 
+    /// It will return an [`inode`] if succeed, and `ENOENT`, `ENOTDIR` or
+    /// `ENAMETOOLONG` if the path does not lead to one. 
     /// It must be called inside a transaction(i.e.,'begin_op' and `end_op`) since it calls `put`.
-    /// Note: the path should end with 0u8, otherwise it might panic due to out-of-bound. 
-    pub fn namei(&self, path: &[u8]) -> Option<Inode> {
+    /// The path ends at the end of the slice or at the first 0u8, whichever comes first.
+    pub fn namei(&self, path: &[u8]) -> Result<Inode, &'static str> {
         let mut name: [u8;DIRSIZ] = [0;DIRSIZ];
-        self.namex(path, &mut name, false)
+        self.namex(path, &mut name, false, None)
+    }
+
+    /// Same as `namei`, but a relative path starts at the directory `dir`.
+    pub fn namei_at(&self, dir: &Inode, path: &[u8]) -> Result<Inode, &'static str> {
+        let mut name: [u8;DIRSIZ] = [0;DIRSIZ];
+        self.namex(path, &mut name, false, Some(dir))
     }
 
     /// Same behavior as `namei`, but return the parent of the inode, 
     /// and copy the end path into name. 
-    pub fn namei_parent(&self, path: &[u8], name: &mut [u8;DIRSIZ]) -> Option<Inode> {
-        self.namex(path, name, true)
+    pub fn namei_parent(&self, path: &[u8], name: &mut [u8;DIRSIZ]) -> Result<Inode, &'static str> {
+        self.namex(path, name, true, None)
+    }
+
+    /// Same as `namei_parent`, but a relative path starts at the directory `dir`.
+    pub fn namei_parent_at(&self, dir: &Inode, path: &[u8], name: &mut [u8;DIRSIZ]) -> Result<Inode, &'static str> {
+        self.namex(path, name, true, Some(dir))
     }
 
     pub fn create(
@@ -248,14 +285,14 @@ impl InodeCache {
     ) -> Result<Inode, &'static str> {
         info!("[Xv6fs] create file/dir: path: {}", String::from_utf8(path.to_vec()).unwrap());
         let mut name: [u8; DIRSIZ] = [0; DIRSIZ];
-        let dirinode = self.namei_parent(path, &mut name).unwrap();
+        let dirinode = self.namei_parent(path, &mut name)?;
         let mut dirinode_guard = dirinode.lock();
-        match dirinode_guard.dir_lookup(&name) {
+        match dirinode_guard.dir_lookup(&name)? {
             Some(inode) => {
                 drop(dirinode_guard);
                 let inode_guard = inode.lock();
                 match inode_guard.dinode.itype {
-                    InodeType::Directory| InodeType::Device | InodeType::File => {
+                    InodeType::Directory| InodeType::Device | InodeType::File | InodeType::Fifo => {
                         if itype == InodeType::File || itype == InodeType::Directory {
                             drop(inode_guard);
                             return Ok(inode)
@@ -273,8 +310,8 @@ impl InodeCache {
         }
         // Allocate a new inode to create file
         let dev = dirinode_guard.dev;
-        let inum = inode_alloc(dev, itype);
-        let inode = self.get(dev, inum);
+        let inum = inode_alloc(dev, itype)?;
+        let inode = self.get(dev, inum)?;
         
         let mut inode_guard = inode.lock();
         // initialize new allocated inode
@@ -282,28 +319,26 @@ impl InodeCache {
         inode_guard.dinode.minor = minor;
         inode_guard.dinode.nlink = 1;
         // Write back to disk
-        inode_guard.update();
+        inode_guard.update()?;
         debug_assert_eq!(inode_guard.dinode.itype, itype);
     
         // Directory, create .. 
         if itype == InodeType::Directory {
             // Create . and .. entries. 
             inode_guard.dinode.nlink += 1;
-            inode_guard.update();
+            inode_guard.update()?;
             // No nlink++ for . to avoid recycle ref count. 
             inode_guard.dir_link(".".as_bytes(), inode.inum)?;
             inode_guard.dir_link("..".as_bytes(), dirinode_guard.inum)?;
         }
-        dirinode_guard
-            .dir_link(&name, inode_guard.inum)
-            .expect("Parent inode fail to link");
+        dirinode_guard.dir_link(&name, inode_guard.inum)?;
 
         drop(inode_guard);
         drop(dirinode_guard);
         Ok(inode)
     }
 
-    pub fn get_root_dir(&self)->Inode{
+    pub fn get_root_dir(&self)->Result<Inode, &'static str>{
         self.get(ROOTDEV, ROOTINUM)
     }
 
@@ -311,33 +346,35 @@ impl InodeCache {
         //info!("begin remove");
         info!("[Xv6fs] remove file/dir, path is {:?}",core::str::from_utf8(path));
         let mut name: [u8; DIRSIZ] = [0; DIRSIZ];
-        let dirinode = self.namei_parent(path, &mut name).unwrap();
+        let dirinode = self.namei_parent(path, &mut name)?;
         //info!("name is {:?} as {:?}",&name,String::from_utf8(name.to_vec()));
         let mut dirinode_guard = dirinode.lock();
         //info!("get locked dirinode!");
-        match dirinode_guard.dir_lookup(&name) {
+        match dirinode_guard.dir_lookup(&name)? {
             Some(inode) => {
                 let mut idata = inode.lock();
                 //info!("get locked inode!");
                 match idata.dinode.itype {
                     InodeType::Directory=> {
-                        idata.clear_dir();
+                        idata.clear_dir()?;
                         idata.dinode.itype=InodeType::Empty;
-                        idata.truncate(&inode);
+                        idata.free_xattr()?;
+                        idata.truncate(&inode)?;
                         idata.valid=false;
                         drop(idata);
-                        dirinode_guard.dir_unlink(&name);
-                        dirinode_guard.update();
+                        dirinode_guard.dir_unlink(&name)?;
+                        dirinode_guard.update()?;
                         return Ok(());
                     },
 
-                    InodeType::File=>{
+                    InodeType::File | InodeType::Fifo | InodeType::Device=>{
                         idata.dinode.itype=InodeType::Empty;
-                        idata.truncate(&inode);
+                        idata.free_xattr()?;
+                        idata.truncate(&inode)?;
                         idata.valid=false;
                         drop(idata);
-                        dirinode_guard.dir_unlink(&name);
-                        dirinode_guard.update();
+                        dirinode_guard.dir_unlink(&name)?;
+                        dirinode_guard.update()?;
                         return Ok(());
                     },
     
@@ -348,48 +385,52 @@ impl InodeCache {
             },
     
             None => {
-                return  Err("remove: error path.");
+                return Err(ENOENT);
             }
         }
     }
 }
 
+/// The byte of `path` at `cur`, or 0 past its end, so that a path
+/// ends either at a 0u8 or at the end of the slice.
+fn path_byte(path: &[u8], cur: usize) -> u8 {
+    path.get(cur).copied().unwrap_or(0)
+}
+
 /// Skip the path starting at cur by b'/'s. 
 /// It will copy the skipped content to name. 
-/// Return the current offset after skiping. 
+/// Return the current offset after skiping, or `ENAMETOOLONG` if the
+/// component does not fit in name. 
 fn skip_path(
     path: &[u8], 
     mut cur: usize, 
     name: &mut [u8; DIRSIZ]
-) -> usize {
+) -> Result<usize, &'static str> {
     // skip preceding b'/'
-    while path[cur] == b'/' {
+    while path_byte(path, cur) == b'/' {
         cur += 1;
     }
-    if path[cur] == 0 {
-        return 0
+    if path_byte(path, cur) == 0 {
+        return Ok(0)
     }
 
     let start = cur;
-    while path[cur] != b'/' && path[cur] != 0 {
+    while path_byte(path, cur) != b'/' && path_byte(path, cur) != 0 {
         cur += 1;
     }
 
-    let mut count = cur - start; 
+    let count = cur - start; 
     if count >= name.len() {
-        debug_assert!(false);
-        count = name.len() - 1;
-    }
-    unsafe{
-        ptr::copy(path.as_ptr().offset(start as isize), name.as_mut_ptr(), count);
+        return Err(ENAMETOOLONG)
     }
+    name[..count].copy_from_slice(&path[start..start + count]);
     name[count] = 0;
 
     // skip succeeding b'/'
-    while path[cur] == b'/' {
+    while path_byte(path, cur) == b'/' {
         cur += 1;
     }
-    cur
+    Ok(cur)
 }
 
 
@@ -420,7 +461,43 @@ pub struct InodeData {
     pub valid: bool,
     pub dev: u32,
     pub inum: u32,
-    pub dinode: DiskInode
+    pub dinode: DiskInode,
+    ra: Readahead,
+}
+
+/// Sequential read detection of one inode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Readahead {
+    /// block index the next sequential read is expected to start at
+    next: u32,
+    /// current readahead window in blocks, 0 while access looks random
+    window: u32,
+}
+
+impl Readahead {
+    const fn new() -> Self {
+        Self { next: 0, window: 0 }
+    }
+
+    /// Note a read of blocks `first..=last` and return the blocks to
+    /// prefetch after it, if any. The window doubles every time a
+    /// sequential read moves into new blocks, up to RA_MAX_BLOCKS, and a
+    /// random access turns readahead off again.
+    fn advance(&mut self, first: u32, last: u32) -> Option<core::ops::Range<u32>> {
+        // Small reads may start inside the block the previous one ended in.
+        let sequential = first == self.next || first + 1 == self.next;
+        if !sequential {
+            self.window = 0;
+            self.next = last + 1;
+            return None;
+        }
+        if last + 1 <= self.next {
+            return None;
+        }
+        self.window = (self.window * 2).clamp(RA_MIN_BLOCKS as u32, RA_MAX_BLOCKS as u32);
+        self.next = last + 1;
+        Some(self.next..self.next + self.window)
+    }
 }
 
 impl InodeData {
@@ -429,7 +506,8 @@ impl InodeData {
             valid: false,
             dev: 0,
             inum: 0,
-            dinode: DiskInode::new()
+            dinode: DiskInode::new(),
+            ra: Readahead::new(),
         }
     }
 
@@ -443,75 +521,146 @@ impl InodeData {
         stat.size = self.dinode.size as usize;
     }
 
-    pub fn clear_block(dev:u32,block_id:u32){
+    pub fn clear_block(dev:u32,block_id:u32)->Result<(), &'static str>{
         //debug!("clear block blockid is {}",block_id);
         let mut buf=BLOCK_CACHE_MANAGER.bread(dev, block_id);
         let buf_ptr=unsafe{(buf.raw_data_mut() as *mut u8).offset(0)};
         let empty_block:[u8;BSIZE]=[0;BSIZE];
         unsafe{ptr::copy(&empty_block as *const u8, buf_ptr, BSIZE)};
-        LOG_MANAGER.write(buf);
+        LOG_MANAGER.write(buf)
     }
 
     /// Discard the inode data/content. 
-    pub fn truncate(&mut self, inode: &Inode) {
-        // direct block
-        for i in 0..NDIRECT {
+    pub fn truncate(&mut self, inode: &Inode) -> Result<(), &'static str> {
+        if self.dinode.is_inline() {
+            self.dinode.addrs = [0; NDIRECT+2];
+            self.dinode.size = 0;
+            return self.update();
+        }
+
+        self.free_blocks_from(inode.dev, 0)?;
+
+        // An emptied file can go back to keeping its data inline.
+        let inline_fs = unsafe { SUPER_BLOCK.has_feature(FS_FEATURE_INLINE) };
+        if inline_fs && (self.dinode.itype == InodeType::File || self.dinode.itype == InodeType::Directory) {
+            self.dinode.flags |= IFLAG_INLINE;
+        }
+        self.dinode.size = 0;
+        self.update()
+    }
+
+    /// Cut the file to `size` bytes, no more than its size, in place: free
+    /// the blocks past the new end and zero the rest of the last one, so a
+    /// later grow reads zeros there. It all goes in the caller's transaction.
+    pub fn shrink(&mut self, inode: &Inode, size: u32) -> Result<(), &'static str> {
+        if size >= self.dinode.size {
+            return Ok(());
+        }
+        if size == 0 {
+            return self.truncate(inode);
+        }
+        if self.dinode.is_inline() {
+            self.dinode.inline_data_mut()[size as usize..].fill(0);
+        } else {
+            let keep = (size as usize + BSIZE - 1) / BSIZE;
+            self.free_blocks_from(inode.dev, keep)?;
+            let tail = size as usize % BSIZE;
+            if let Some(bn) = self.bmap_lookup(keep - 1).filter(|_| tail > 0) {
+                let mut buf = BLOCK_CACHE_MANAGER.bread(inode.dev, bn);
+                unsafe { ptr::write_bytes((buf.raw_data_mut() as *mut u8).add(tail), 0, BSIZE - tail) };
+                LOG_MANAGER.write(buf)?;
+            }
+        }
+        self.dinode.size = size;
+        self.update()
+    }
+
+    /// Free every data block from the `keep`th on, and the indirect blocks
+    /// left with nothing to map.
+    fn free_blocks_from(&mut self, dev: u32, keep: usize) -> Result<(), &'static str> {
+        for i in keep.min(NDIRECT)..NDIRECT {
             if self.dinode.addrs[i] > 0 {
-                bfree(inode.dev, self.dinode.addrs[i]);
+                bfree(dev, self.dinode.addrs[i])?;
                 self.dinode.addrs[i] = 0;
             }
         }
 
-        // indirect block
-        if self.dinode.addrs[NDIRECT] > 0 {
-            //debug!("truncate bread indirect block ");
-            let buf = BLOCK_CACHE_MANAGER.bread(inode.dev, self.dinode.addrs[NDIRECT]);
-            let buf_ptr = buf.raw_data() as *const BlockNo;
-            for i in 0..NINDIRECT {
-                let bn = unsafe{ read(buf_ptr.offset(i as isize)) };
-                if bn > 0 {
-                    bfree(inode.dev, bn);
-                }
+        let from = keep.saturating_sub(NDIRECT);
+        let iaddr = self.dinode.addrs[NDIRECT];
+        if iaddr > 0 && from < NINDIRECT {
+            Self::free_indirect_from(dev, iaddr, from)?;
+            if from == 0 {
+                bfree(dev, iaddr)?;
+                self.dinode.addrs[NDIRECT] = 0;
             }
-            drop(buf);
-            bfree(inode.dev, self.dinode.addrs[NDIRECT]);//这里要清空这个页面才行
-            self.dinode.addrs[NDIRECT] = 0;
         }
 
-        if self.dinode.addrs[NDIRECT+1] > 0 {
-            //debug!("truncate bread inindirect block");
-            let buf = BLOCK_CACHE_MANAGER.bread(inode.dev, self.dinode.addrs[NDIRECT+1]);
-            let buf_ptr=buf.raw_data() as *const BlockNo;
-            for i in 0..NINDIRECT{
-                let ibn=unsafe { read(buf_ptr.offset(i as isize))};
-                info!("[Xv6fs] inode truncate: indirect block no is {}",ibn);
-                if ibn > 0{
-                    //debug!("ibn is {}",ibn);
-                    let ibuf=BLOCK_CACHE_MANAGER.bread(inode.dev, ibn);
-                    let ibuf_ptr=ibuf.raw_data() as *const BlockNo;
-                    for j in 0..NINDIRECT{
-                        let bn = unsafe{ read(ibuf_ptr.offset(j as isize)) };
-                        info!("[Xv6fs] inode truncate: direct block no is {}",bn);
-                        if bn > 0 {
-                            bfree(inode.dev, bn);
-                        }
-                    }
-                    drop(ibuf);
-                    bfree(inode.dev, ibn);
+        let from = keep.saturating_sub(NDIRECT + NINDIRECT);
+        let iiaddr = self.dinode.addrs[NDIRECT+1];
+        if iiaddr > 0 {
+            let mut buf = BLOCK_CACHE_MANAGER.bread(dev, iiaddr);
+            let entries = buf.raw_data_mut() as *mut BlockNo;
+            for i in from / NINDIRECT..NINDIRECT {
+                let ibn = unsafe { read(entries.add(i)) };
+                if ibn == 0 {
+                    continue;
+                }
+                let first = from.saturating_sub(i * NINDIRECT);
+                Self::free_indirect_from(dev, ibn, first)?;
+                if first == 0 {
+                    bfree(dev, ibn)?;
+                    unsafe { write(entries.add(i), 0) };
                 }
             }
-            drop(buf);
-            bfree(inode.dev, self.dinode.addrs[NDIRECT+1]);
-            self.dinode.addrs[NDIRECT+1]=0;
+            if from == 0 {
+                drop(buf);
+                bfree(dev, iiaddr)?;
+                self.dinode.addrs[NDIRECT+1] = 0;
+            } else {
+                LOG_MANAGER.write(buf)?;
+            }
         }
+        Ok(())
+    }
 
-        self.dinode.size = 0;
-        self.update();
+    /// Free the blocks that entries `from..` of indirect block `block` map.
+    /// A block freed whole is not written back.
+    fn free_indirect_from(dev: u32, block: u32, from: usize) -> Result<(), &'static str> {
+        let mut buf = BLOCK_CACHE_MANAGER.bread(dev, block);
+        let entries = buf.raw_data_mut() as *mut BlockNo;
+        for i in from..NINDIRECT {
+            let bn = unsafe { read(entries.add(i)) };
+            if bn > 0 {
+                bfree(dev, bn)?;
+                unsafe { write(entries.add(i), 0) };
+            }
+        }
+        if from > 0 {
+            LOG_MANAGER.write(buf)?;
+        }
+        Ok(())
+    }
+
+    /// Move inline data out to a real data block,
+    /// called when a write would not fit in the inode any more.
+    fn expand_inline(&mut self) -> Result<(), &'static str> {
+        let data = *self.dinode.inline_data();
+        self.dinode.addrs = [0; NDIRECT+2];
+        self.dinode.flags &= !IFLAG_INLINE;
+        if self.dinode.size > 0 {
+            let block_no = self.bmap(0, true)?;
+            let mut buf = BLOCK_CACHE_MANAGER.bread(self.dev, block_no);
+            let mut block = [0u8; BSIZE];
+            block[..INLINE_MAX].copy_from_slice(&data);
+            unsafe { ptr::copy_nonoverlapping(block.as_ptr(), buf.raw_data_mut() as *mut u8, BSIZE) };
+            LOG_MANAGER.write(buf)?;
+        }
+        self.update()
     }
 
     /// Update a modified in-memory inode to disk. 
     /// Typically called after changing the content of inode info. 
-    pub fn update(&mut self) {
+    pub fn update(&mut self) -> Result<(), &'static str> {
         //info!("update: begin update");
         let mut buf = BLOCK_CACHE_MANAGER.bread(
             self.dev, 
@@ -521,23 +670,27 @@ impl InodeData {
         let dinode = unsafe{ (buf.raw_data_mut() as *mut DiskInode).offset(offset) };
         unsafe{ write(dinode, self.dinode) };
         //info!("update: self.dindoe: {:?}", self.dinode);
-        LOG_MANAGER.write(buf);
+        LOG_MANAGER.write(buf)
     }
 
     /// The content (data) associated with each inode is stored
     /// in blocks on the disk. The first NDIRECT block numbers
     /// are listed in self.dinode.addrs, The next NINDIRECT blocks are 
     /// listed in block self.dinode.addrs[NDIRECT]. 
+    /// Inodes with inline data have no blocks to map.
     /// 
     /// Return the disk block address of the nth block in inode. 
     /// If there is no such block, bmap allocates one. 
     pub fn bmap(&mut self, offset_bn: u32, balloc_flag: bool) -> Result<u32, &'static str> {
+        if self.dinode.is_inline() {
+            return Err("inode bmap: inode data is inline");
+        }
         let mut addr;
         let mut iaddr:u32;
         let offset_bn = offset_bn as usize;
         if offset_bn < NDIRECT {
             if self.dinode.addrs[offset_bn] == 0 {
-                addr = balloc(self.dev);
+                addr = balloc(self.dev)?;
                 self.dinode.addrs[offset_bn] = addr;
                 return Ok(addr)
             } else {
@@ -548,9 +701,9 @@ impl InodeData {
             // Load indirect block, allocating if necessary. 
             let count = offset_bn - NDIRECT;
             if self.dinode.addrs[NDIRECT] == 0 {
-                iaddr = balloc(self.dev);
+                iaddr = balloc(self.dev)?;
                 self.dinode.addrs[NDIRECT] = iaddr;
-                Self::clear_block(self.dev, iaddr);
+                Self::clear_block(self.dev, iaddr)?;
             } else {
                 iaddr = self.dinode.addrs[NDIRECT]
             }
@@ -560,11 +713,11 @@ impl InodeData {
             addr = unsafe{ read(buf_data.offset(count as isize)) };
             debug!("[Xv6fs] bmap: addr is {}",addr);
             if addr == 0 || !(bisalloc(addr)) || balloc_flag{
+                addr = balloc(self.dev)?;
                 unsafe{
-                    addr = balloc(self.dev);
                     write(buf_data.offset(count as isize), addr);
                 }
-                LOG_MANAGER.write(buf);//这里是个什么玩意啊，裂开
+                LOG_MANAGER.write(buf)?;//这里是个什么玩意啊，裂开
             }
             // drop(buf);
             return Ok(addr)
@@ -572,9 +725,9 @@ impl InodeData {
         if offset_bn < NINDIRECT+NDIRECT+NININDIRECT{
             let count=offset_bn-NDIRECT-NINDIRECT;
             if self.dinode.addrs[NDIRECT+1]==0{
-                addr=balloc(self.dev);
+                addr=balloc(self.dev)?;
                 self.dinode.addrs[NDIRECT+1]=addr;
-                Self::clear_block(self.dev, addr);
+                Self::clear_block(self.dev, addr)?;
             }else {
                 addr=self.dinode.addrs[NDIRECT+1];
             }
@@ -589,12 +742,12 @@ impl InodeData {
                 //panic!("balloc flag is not same with !bisalloc");
             }
             if iaddr == 0 || !(bisalloc(iaddr)) /*|| balloc_flag*/{
+                iaddr=balloc(self.dev)?;
                 unsafe{
-                    iaddr=balloc(self.dev);
                     write(buf_data.offset(indirect_count as isize), iaddr);
-                    Self::clear_block(self.dev, iaddr);
                 }
-                LOG_MANAGER.write(buf);
+                Self::clear_block(self.dev, iaddr)?;
+                LOG_MANAGER.write(buf)?;
                 drop(buf_data);
             }
             //debug!("bread indirect iaddr {}",iaddr);
@@ -603,15 +756,58 @@ impl InodeData {
             addr=unsafe { read(ibuf_data.offset(indirect_offset as isize))};
             //debug!("[Xv6fs] bmap: addr is {}, balloc_flag is {}, bisalloc is {}",addr,balloc_flag,bisalloc(addr));
             if addr ==0 || !(bisalloc(addr)) /*|| balloc_flag*/{
+                addr=balloc(self.dev)?;
                 unsafe{
-                    addr=balloc(self.dev);
                     write(ibuf_data.offset(indirect_offset as isize), addr);
                 }
-                LOG_MANAGER.write(ibuf);
+                LOG_MANAGER.write(ibuf)?;
             }
             return Ok(addr);
         }
-        panic!("inode bmap: out of range.");
+        Err("inode bmap: out of range")
+    }
+
+    /// Look up the disk block of the nth block in inode without allocating.
+    /// Returns None for holes.
+    fn bmap_lookup(&self, offset_bn: usize) -> Option<u32> {
+        if self.dinode.is_inline() {
+            return None;
+        }
+        let read_entry = |block: u32, index: usize| -> u32 {
+            let buf = BLOCK_CACHE_MANAGER.bread(self.dev, block);
+            unsafe { read((buf.raw_data() as *const u32).add(index)) }
+        };
+        let addr = if offset_bn < NDIRECT {
+            self.dinode.addrs[offset_bn]
+        } else if offset_bn < NDIRECT + NINDIRECT {
+            let iaddr = self.dinode.addrs[NDIRECT];
+            if iaddr == 0 { return None; }
+            read_entry(iaddr, offset_bn - NDIRECT)
+        } else if offset_bn < NDIRECT + NINDIRECT + NININDIRECT {
+            let count = offset_bn - NDIRECT - NINDIRECT;
+            let iiaddr = self.dinode.addrs[NDIRECT+1];
+            if iiaddr == 0 { return None; }
+            let iaddr = read_entry(iiaddr, count / NINDIRECT);
+            if iaddr == 0 { return None; }
+            read_entry(iaddr, count % NINDIRECT)
+        } else {
+            0
+        };
+        if addr == 0 { None } else { Some(addr) }
+    }
+
+    /// Track sequential access and prefetch the blocks following a read
+    /// of blocks `first..=last`.
+    fn readahead(&mut self, first: u32, last: u32) {
+        let Some(window) = self.ra.advance(first, last) else {
+            return;
+        };
+        let nblocks = (self.dinode.size as usize + BSIZE - 1) / BSIZE;
+        let end = min(window.end as usize, nblocks);
+        let blocks: Vec<u32> = (window.start as usize..end)
+            .filter_map(|bn| self.bmap_lookup(bn))
+            .collect();
+        BLOCK_CACHE_MANAGER.prefetch(self.dev, &blocks);
     }
 
     /// Read data from inode. 
@@ -632,6 +828,11 @@ impl InodeData {
             // info!("[Kernel] read: end: {}, dinode.size: {}", end, self.dinode.size);
             return Err("inode read: end is more than diskinode's size.")
         }
+        if self.dinode.is_inline() {
+            let src = &self.dinode.inline_data()[offset as usize..end as usize];
+            unsafe { ptr::copy(src.as_ptr(), dst as *mut u8, src.len()) };
+            return Ok(src.len())
+        }
 
         let mut total: usize = 0;
         let mut offset = offset as usize;
@@ -663,6 +864,9 @@ impl InodeData {
             block_basic = offset / BSIZE;
             block_offset = offset % BSIZE;
         }
+        if count > 0 {
+            self.readahead((offset - total) as u32 / BSIZE as u32, (offset - 1) as u32 / BSIZE as u32);
+        }
         Ok(total)
     }
 
@@ -686,6 +890,19 @@ impl InodeData {
         //     return Err("inode write: end is more than diskinode's size.")
         // }
         info!("[Xv6fs] inode write file/dir: begin inode write");
+        if self.dinode.is_inline() {
+            let end = offset.checked_add(count).ok_or("Fail to add count.")?;
+            if end as usize <= INLINE_MAX {
+                let dst = &mut self.dinode.inline_data_mut()[offset as usize..end as usize];
+                unsafe { ptr::copy(src as *const u8, dst.as_mut_ptr(), dst.len()) };
+                if self.dinode.size < end {
+                    self.dinode.size = end;
+                }
+                self.update()?;
+                return Ok(count as usize)
+            }
+            self.expand_inline()?;
+        }
         let mut offset = offset as usize;
         info!("[Xv6fs] inode write file/dir: write block offset is {}",offset);
         let count = count as usize;
@@ -693,6 +910,9 @@ impl InodeData {
         let mut block_basic = offset / BSIZE;
         let mut block_offset = offset % BSIZE;
         let mut balloc_flag=false;
+        // Directory contents are metadata and always go through the log.
+        let ordered = self.dinode.itype == InodeType::File
+            && mount_options().data_mode == DataMode::Ordered;
         while total < count {
             let surplus_len = count - total;
             let write_len = min(surplus_len, BSIZE - block_offset);
@@ -713,26 +933,28 @@ impl InodeData {
             block_basic = offset / BSIZE;
             block_offset = offset % BSIZE;
 
-            LOG_MANAGER.write(buf);
+            if ordered {
+                buf.bwrite();
+            } else {
+                LOG_MANAGER.write(buf)?;
+            }
         }
 
         if self.dinode.size < offset as u32 {
             self.dinode.size = offset as u32;
         }
 
-        self.update();
+        self.update()?;
         
         // info!("[Kernel] Write end");
         Ok(total)
     }
 
-    /// Look for an inode entry in this directory according the name. 
-    /// Panics if this is not a directory. 
-    pub fn dir_lookup(&mut self, name: &[u8]) -> Option<Inode> {
-        // assert!(name.len() == DIRSIZ);
-        info!("[Xv6fs] dir lookup: name is {:?}",core::str::from_utf8(name));
+    /// Find the entry `name` in this directory and the offset it is at.
+    /// Fails with `ENOTDIR` if this is not a directory.
+    fn dir_find(&mut self, name: &[u8]) -> Result<Option<(u32, DirEntry)>, &'static str> {
         if self.dinode.itype != InodeType::Directory {
-            panic!("inode type is not directory");
+            return Err(ENOTDIR);
         }
         let de_size = size_of::<DirEntry>();
         let mut dir_entry = DirEntry::new();
@@ -742,29 +964,40 @@ impl InodeData {
                 dir_entry_ptr as usize, 
                 offset, 
                 de_size as u32
-            ).expect("Cannot read entry in this dir");
+            )?;
             if dir_entry.inum == 0 {
                 continue;
             }
             //info!("dir_entry_name: {}, name: {}, inum: {}", String::from_utf8(dir_entry.name.to_vec()).unwrap(), String::from_utf8(name.to_vec()).unwrap(),dir_entry.inum);
             for i in 0..DIRSIZ {
-                if dir_entry.name[i] != name[i] {
+                if dir_entry.name[i] != path_byte(name, i) {
                     break;
                 }
                 if dir_entry.name[i] == 0 {
                     //info!("find you!");
-                    return Some(ICACHE.get(self.dev, dir_entry.inum as u32))
+                    return Ok(Some((offset, dir_entry)))
                 }
             }
         }
-        None
+        Ok(None)
+    }
+
+    /// Look for an inode entry in this directory according the name. 
+    /// Fails with `ENOTDIR` if this is not a directory. 
+    pub fn dir_lookup(&mut self, name: &[u8]) -> Result<Option<Inode>, &'static str> {
+        // assert!(name.len() == DIRSIZ);
+        info!("[Xv6fs] dir lookup: name is {:?}",core::str::from_utf8(name));
+        match self.dir_find(name)? {
+            Some((_, dir_entry)) => Ok(Some(ICACHE.get(self.dev, dir_entry.inum as u32)?)),
+            None => Ok(None),
+        }
     }
 
     /// Write s new directory entry (name, inum) into the directory
     pub fn dir_link(&mut self, name: &[u8], inum: u32) -> Result<(), &'static str>{
-        info!("[Xv6fs] dir link: path is {:?}",String::from_utf8(name.to_vec()).unwrap());
-        if self.dir_lookup(name).is_some() {
-            return Err("It's incorrect to find entry in disk")
+        info!("[Xv6fs] dir link: path is {:?}",String::from_utf8_lossy(name));
+        if self.dir_find(name)?.is_some() {
+            return Err(EEXIST)
         }
         let mut dir_entry = DirEntry::new();
         // look for an empty dir_entry
@@ -782,6 +1015,7 @@ impl InodeData {
             }
             entry_offset += size_of::<DirEntry>() as u32;
         }
+        dir_entry = DirEntry::new();
         unsafe {
             ptr::copy(name.as_ptr(), dir_entry.name.as_mut_ptr(), name.len());
         }
@@ -795,135 +1029,129 @@ impl InodeData {
         Ok(())
     }
 
+    /// Point the existing entry `name` at inode `inum`. The entry is
+    /// rewritten in place, so unlike `dir_link` this never allocates.
+    pub fn dir_relink(&mut self, name: &[u8], inum: u32) -> Result<(), &'static str> {
+        let (offset, mut dir_entry) = self.dir_find(name)?.ok_or(ENOENT)?;
+        dir_entry.inum = inum as u16;
+        self.write(
+            (&dir_entry) as *const _ as usize, 
+            offset, 
+            size_of::<DirEntry>() as u32
+        )?;
+        Ok(())
+    }
+
     /// Is the directory empty execpt for "." and ".." ?
-    pub fn is_dir_empty(&mut self) -> bool {
+    pub fn is_dir_empty(&mut self) -> Result<bool, &'static str> {
         let mut dir_entry = DirEntry::new();
         // "." and ".." size
         let init_size = 2 * size_of::<DirEntry>() as u32;
         let final_size = self.dinode.size;
         for offset in (init_size..final_size).step_by(size_of::<DirEntry>()) {
             // Check each direntry, foreach step by size of DirEntry. 
-            if self.read(
+            self.read(
                 &mut dir_entry as *mut DirEntry as usize, 
                 offset, 
                 size_of::<DirEntry>() as u32
-            ).is_err() {
-                panic!("is_dir_empty(): Fail to read dir content");
-            }
+            )?;
 
             if dir_entry.inum != 0 {
-                return true
+                return Ok(false)
             }
         }
-        false
+        Ok(true)
     }
 
-    pub fn rename(path:&str,new_name:&str){
-        let mut flag=false;
+    pub fn rename(path:&str,new_name:&str)->Result<(), &'static str>{
         let mut old_name = [0u8; DIRSIZ];
-        let parent=match ICACHE.namei_parent(&path.as_bytes(), &mut old_name) {
-            Some(cur)=>cur,
-            None=>panic!("[Xv6fs] vfile_unlink: not find path")
-        };
+        let parent=ICACHE.namei_parent(&path.as_bytes(), &mut old_name)?;
+        let new_name=new_name.trim_end_matches('\0').as_bytes();
+        if new_name.len() >= DIRSIZ {
+            return Err(ENAMETOOLONG);
+        }
         let mut parent_guard=parent.lock();
+        let (offset, mut dir_entry)=parent_guard.dir_find(&old_name)?.ok_or(ENOENT)?;
+        dir_entry.name=[0; DIRSIZ];
+        dir_entry.name[..new_name.len()].copy_from_slice(new_name);
+        parent_guard.write(
+            (&dir_entry) as *const _ as usize, 
+            offset, 
+            size_of::<DirEntry>() as u32
+        )?;
+        drop(parent_guard);
+        LOG_MANAGER.commit_log();
+        Ok(())
+    }
+
+    pub fn ls(&mut self)->Result<Vec<String>, &'static str>{
+        if self.dinode.itype!=InodeType::Directory{
+            return Err(ENOTDIR);
+        }
+        let mut v=Vec::new();
         let de_size = size_of::<DirEntry>();
         let mut dir_entry = DirEntry::new();
         let dir_entry_ptr = &mut dir_entry as *mut _ as *mut u8;
-        for offset in (0..parent_guard.dinode.size).step_by(de_size) {
-            parent_guard.read(
+        for offset in (0..self.dinode.size).step_by(de_size) {
+            self.read(
                 dir_entry_ptr as usize, 
                 offset, 
                 de_size as u32
-            ).expect("Cannot read entry in this dir");
+            )?;
             if dir_entry.inum == 0 {
                 continue;
             }
-            //info!("dir_entry_name: {}, name: {}, inum: {}", String::from_utf8(dir_entry.name.to_vec()).unwrap(), String::from_utf8(name.to_vec()).unwrap(),dir_entry.inum);
-            for i in 0..DIRSIZ {
-                if dir_entry.name[i] != old_name[i] {
-                    break;
-                }
-                if dir_entry.name[i] == 0 {
-                    let name=&new_name.as_bytes();
-                    for i in 0..DIRSIZ {
-                        dir_entry.name[i]=name[i];
-                        if name[i]==0{
-                            break;
-                        }
-                    }
-                    parent_guard.write(dir_entry_ptr as usize, offset, de_size as u32);
-                    LOG_MANAGER.commit_log();
-                    return;
-                }
-            }
-        }
-        panic!("[Xv6fs] inode rename: not find dir entry");
-    }
-
-    pub fn ls(&mut self)->Option<Vec<String>>{
-        if self.dinode.itype!=InodeType::Directory{
-            None
-        }else{
-           let mut v=Vec::new();
-           let de_size = size_of::<DirEntry>();
-           let mut dir_entry = DirEntry::new();
-           let dir_entry_ptr = &mut dir_entry as *mut _ as *mut u8;
-           for offset in (0..self.dinode.size).step_by(de_size) {
-               self.read(
-                   dir_entry_ptr as usize, 
-                   offset, 
-                   de_size as u32
-               ).expect("Cannot read entry in this dir");
-               if dir_entry.inum == 0 {
-                   continue;
-               }
-               // info!("dir_entry_name: {}, name: {}", String::from_utf8(dir_entry.name.to_vec()).unwrap(), String::from_utf8(name.to_vec()).unwrap());
-               let name=String::from_utf8(dir_entry.name.to_vec()).unwrap();
-               v.push(name);
-           }
-           Some(v)
+            // info!("dir_entry_name: {}, name: {}", String::from_utf8(dir_entry.name.to_vec()).unwrap(), String::from_utf8(name.to_vec()).unwrap());
+            let name=String::from_utf8_lossy(&dir_entry.name).into_owned();
+            v.push(name);
         }
+        Ok(v)
     }
 
-    pub fn dir_unlink(&mut self, name: &[u8]) -> Result<(),&'static str> {
-        // assert!(name.len() == DIRSIZ);
-        info!("[Xv6fs] dir unlink: path is {}",String::from_utf8(name.to_vec()).unwrap());
+    /// Names and inode numbers of the entries of this directory,
+    /// leaving out "." and "..".
+    pub fn dir_entries(&mut self) -> Result<Vec<(String, u32)>, &'static str> {
         if self.dinode.itype != InodeType::Directory {
-            panic!("inode type is not directory");
+            return Err(ENOTDIR);
         }
+        let mut entries = Vec::new();
         let de_size = size_of::<DirEntry>();
         let mut dir_entry = DirEntry::new();
         let dir_entry_ptr = &mut dir_entry as *mut _ as *mut u8;
         for offset in (0..self.dinode.size).step_by(de_size) {
-            self.read(
-                dir_entry_ptr as usize, 
-                offset, 
-                de_size as u32
-            ).expect("Cannot read entry in this dir");
+            self.read(dir_entry_ptr as usize, offset, de_size as u32)?;
             if dir_entry.inum == 0 {
                 continue;
             }
-            //info!("dir_entry_name: {}, name: {}", String::from_utf8(dir_entry.name.to_vec()).unwrap(), String::from_utf8(name.to_vec()).unwrap());
-            for i in 0..DIRSIZ {
-                if dir_entry.name[i] != name[i] {
-                    break;
-                }
-                if dir_entry.name[i] == 0 {
-                    //info!("find you!!!");
-                    dir_entry.inum=0;
-                    dir_entry.name=[0;DIRSIZ];
-                    self.write(dir_entry_ptr as usize, offset, de_size as u32);
-                    return Ok(());
-                }
+            let len = dir_entry.name.iter().position(|&b| b == 0).unwrap_or(DIRSIZ);
+            let name = String::from_utf8_lossy(&dir_entry.name[..len]);
+            if name != "." && name != ".." {
+                entries.push((name.into_owned(), dir_entry.inum as u32));
             }
         }
-        Err("not find this file in the directory")
+        Ok(entries)
+    }
+
+    /// Remove the entry `name` from this directory.
+    /// Fails with `ENOENT` if there is none.
+    pub fn dir_unlink(&mut self, name: &[u8]) -> Result<(),&'static str> {
+        // assert!(name.len() == DIRSIZ);
+        info!("[Xv6fs] dir unlink: path is {}",String::from_utf8_lossy(name));
+        let (offset, _) = self.dir_find(name)?.ok_or(ENOENT)?;
+        //info!("find you!!!");
+        let dir_entry = DirEntry::new();
+        self.write(
+            (&dir_entry) as *const _ as usize, 
+            offset, 
+            size_of::<DirEntry>() as u32
+        )?;
+        Ok(())
     }
 
     pub fn clear_dir(&mut self) -> Result<(),&'static str> {
         // assert!(name.len() == DIRSIZ);
         if self.dinode.itype != InodeType::Directory {
-            panic!("inode type is not directory");
+            return Err(ENOTDIR);
         }
         let de_size = size_of::<DirEntry>();
         let mut dir_entry = DirEntry::new();
@@ -933,37 +1161,38 @@ impl InodeData {
                 dir_entry_ptr as usize, 
                 offset, 
                 de_size as u32
-            ).expect("Cannot read entry in this dir");
+            )?;
             if dir_entry.inum == 0 || offset/(de_size as u32) < 2{
                 continue;
             }
             // info!("dir_entry_name: {}, name: {}", String::from_utf8(dir_entry.name.to_vec()).unwrap(), String::from_utf8(name.to_vec()).unwrap());
-            let mut child_inode=ICACHE.get(self.dev, dir_entry.inum as u32);
+            let child_inode=ICACHE.get(self.dev, dir_entry.inum as u32)?;
             let mut cdata=child_inode.lock();
             match cdata.dinode.itype {
-                InodeType::File=>{
+                InodeType::File | InodeType::Fifo | InodeType::Device=>{
                     cdata.dinode.itype=InodeType::Empty;
-                    cdata.truncate(&child_inode);
+                    cdata.free_xattr()?;
+                    cdata.truncate(&child_inode)?;
                     cdata.valid=false;
                     drop(cdata);
-                    self.dir_unlink(&dir_entry.name);
+                    self.dir_unlink(&dir_entry.name)?;
                 },
                 InodeType::Directory=>{
-                    cdata.clear_dir();
+                    cdata.clear_dir()?;
                     cdata.dinode.itype=InodeType::Empty;
-                    cdata.truncate(&child_inode);
+                    cdata.free_xattr()?;
+                    cdata.truncate(&child_inode)?;
                     cdata.valid=false;
                     drop(cdata);
-                    self.dir_unlink(&dir_entry.name);
+                    self.dir_unlink(&dir_entry.name)?;
                 },
 
                 _=>{
-                    panic!("this is not shoud be in the directory!");
+                    return Err("clear_dir: entry of an unknown type");
                 }
             }
         }
-        self.update();
-        Ok(())
+        self.update()
     }
 }
 
@@ -1019,6 +1248,7 @@ impl Inode {
             guard.valid = true;
             guard.dev = self.dev;
             guard.inum = self.inum;
+            guard.ra = Readahead::new();
             if guard.dinode.itype == InodeType::Empty {
                 panic!("inode lock: trying to lock an inode whose type is empty.")
             }
@@ -1043,3 +1273,58 @@ impl Drop for Inode {
 fn locate_inode_offset(inum: u32) -> usize {
     inum as usize % IPB
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_unterminated_path() {
+        let mut name = [0u8; DIRSIZ];
+        let path = b"//usr//bin";
+        let cur = skip_path(path, 0, &mut name).unwrap();
+        assert_eq!(&name[..4], b"usr\0");
+        assert_eq!(cur, 7);
+        let cur = skip_path(path, cur, &mut name).unwrap();
+        assert_eq!(&name[..4], b"bin\0");
+        assert_eq!(cur, path.len());
+        assert_eq!(skip_path(path, cur, &mut name), Ok(0));
+        // A NUL still ends the path early.
+        assert_eq!(skip_path(b"/a\0/b", 2, &mut name), Ok(0));
+        assert_eq!(skip_path(b"", 0, &mut name), Ok(0));
+    }
+
+    #[test]
+    fn skip_long_component() {
+        let mut name = [0u8; DIRSIZ];
+        let mut path = [b'a'; DIRSIZ + 2];
+        path[0] = b'/';
+        assert_eq!(skip_path(&path[..DIRSIZ], 0, &mut name), Ok(DIRSIZ));
+        assert_eq!(skip_path(&path, 0, &mut name), Err(ENAMETOOLONG));
+    }
+
+    #[test]
+    fn readahead_window() {
+        let mut ra = Readahead::new();
+        // The first read of a file starts at block 0, which counts as sequential.
+        assert_eq!(ra.advance(0, 0), Some(1..1 + RA_MIN_BLOCKS as u32));
+        // A second small read in the same block moves into nothing new.
+        assert_eq!(ra.advance(0, 0), None);
+        assert_eq!(ra.advance(1, 1), Some(2..2 + 2 * RA_MIN_BLOCKS as u32));
+        let mut last = 1;
+        for _ in 0..8 {
+            last += 1;
+            ra.advance(last, last);
+        }
+        assert_eq!(ra.window, RA_MAX_BLOCKS as u32);
+        // A read that starts where the previous one ended, inside its block.
+        assert_eq!(
+            ra.advance(last, last + 1),
+            Some(last + 2..last + 2 + RA_MAX_BLOCKS as u32)
+        );
+        // A seek turns readahead off until reads are sequential again.
+        assert_eq!(ra.advance(100, 100), None);
+        assert_eq!(ra.window, 0);
+        assert_eq!(ra.advance(101, 102), Some(103..103 + RA_MIN_BLOCKS as u32));
+    }
+}
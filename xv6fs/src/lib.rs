@@ -10,6 +10,7 @@ use std::{println as info, println as warn}; // Workaround to use prinltn! for l
 extern crate alloc;
 
 pub mod block_dev;
+pub mod crc32c;
 pub mod fs_const;
 pub mod buffer_cache;
 pub mod log;
@@ -23,11 +24,14 @@ pub mod file;
 pub mod interface;
 pub mod sync;
 pub mod xv6fs;
+pub mod xattr;
+pub mod options;
 
 use core::ops::DerefMut;
 
 use alloc::sync::Arc;
 pub use block_dev::BlockDevice;
+pub use options::{DataMode, MountOptions};
 use buffer_cache::BLOCK_CACHE_MANAGER;
 use fs_const::{NBUF,BSIZE};
 use disk_inode::{InodeType,DiskInode};
@@ -40,14 +44,48 @@ use core::mem::size_of;
 
 use crate::inode::{ICACHE, InodeCache};
 
-pub unsafe fn init(block_dev:Arc<dyn BlockDevice>,dev:u32) {
+/// Mount the file system on `block_dev` with default options, replaying the log if needed.
+/// Return an error if the super block or the log is corrupted.
+pub unsafe fn init(block_dev:Arc<dyn BlockDevice>,dev:u32) -> Result<(), &'static str> {
+    init_with_options(block_dev, dev, MountOptions::default())
+}
+
+/// Mount the file system on `block_dev` with the given options.
+/// A read-only mount fails instead of replaying a non-empty log.
+/// Nothing stays mounted when it fails.
+pub unsafe fn init_with_options(block_dev:Arc<dyn BlockDevice>,dev:u32,options:MountOptions) -> Result<(), &'static str> {
+    options.check()?;
+    options::set_mount_options(options);
     BLOCK_CACHE_MANAGER.set_block_device(Arc::clone(&block_dev));
     BLOCK_CACHE_MANAGER.binit();
-    let icache=InodeCache::new();
-    ICACHE.init_by(icache);
-    SUPER_BLOCK.init(dev);
-    let log=LOG_MANAGER.log.lock().deref_mut() as *mut Log;
-    log.as_mut().unwrap().init(dev);
+    BLOCK_CACHE_MANAGER.set_limit(options.cache_blocks);
+    // A remount finds the inode cache left over, with no inode in use.
+    if !ICACHE.is_init() {
+        ICACHE.init_by(InodeCache::new());
+    }
+    let result=SUPER_BLOCK.init(dev).and_then(|_| {
+        let log=LOG_MANAGER.log.lock().deref_mut() as *mut Log;
+        log.as_mut().unwrap().init(dev)
+    });
+    if result.is_err() {
+        reset();
+        return result;
+    }
     info!("block size:{}, disk inode size:{}, log header size:{}",BSIZE,size_of::<DiskInode>(),size_of::<LogHeader>());
     info!("file system: setup done!");
+    Ok(())
+}
+
+/// Unmount the file system: commit the log, write everything back and let
+/// go of the device, so that `init` may mount one again.
+/// SAFETY: no inode or buf may be in use.
+pub unsafe fn shutdown() {
+    LOG_MANAGER.sync();
+    reset();
+}
+
+/// Forget the cached blocks, the device and the super block.
+unsafe fn reset() {
+    BLOCK_CACHE_MANAGER.reset();
+    SUPER_BLOCK.reset();
 }
\ No newline at end of file
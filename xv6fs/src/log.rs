@@ -13,8 +13,11 @@ use spin::Mutex;
 use lazy_static::*;
 
 //use crate::{fs_const::{MAXOPBLOCKS, LOGSIZE, BSIZE}, block_dev::BlockDevice};
-use crate::fs_const::{LOGSIZE, BSIZE};
+use crate::fs_const::{LOGSIZE, BSIZE, MAXOPBLOCKS};
+use crate::options::mount_options;
 use crate::buffer_cache::{BLOCK_CACHE_MANAGER, Buf, BufData};
+use crate::crc32c::crc32c;
+use crate::file::ENOSPC;
 //use crate::block_dev::BlockDevice;
 use crate::superblock::SUPER_BLOCK;
 
@@ -40,6 +43,8 @@ pub struct Log {
     size: u32,
     dev: u32,
     outstanding: u32,
+    /// operations since the last commit
+    pending_ops: u32,
     /// not allow any fs op when the log is committing
     committing: bool,
     lh: LogHeader,
@@ -52,8 +57,9 @@ impl Log {
             size: 0,
             dev: 0,
             outstanding: 0,
+            pending_ops: 0,
             committing: false,
-            lh: LogHeader { len: 0, blocknos: [0; LOGSIZE-1] },
+            lh: LogHeader::empty(),
         }
     }
 
@@ -61,28 +67,56 @@ impl Log {
     /// Recover the fs if necessary.
     /// SAFETY: It must be called without holding any locks,
     ///         because it will call disk rw, which might sleep.
+    /// Return an error if the log on disk is corrupted.
     /// 这里的dev要再考虑一下
-    pub unsafe fn init(&mut self, dev: u32) {
+    pub unsafe fn init(&mut self, dev: u32) -> Result<(), &'static str> {
         debug_assert!(mem::size_of::<LogHeader>() < BSIZE);
         debug_assert_eq!(mem::align_of::<BufData>() % mem::align_of::<LogHeader>(), 0);
         let (start, size) = SUPER_BLOCK.read_log();
         self.start = start;
         self.size = size;
         self.dev = dev;
-        self.recover();
+        self.recover()
     }
 
     /// Recover the file system from log if necessary.
-    fn recover(&mut self) {
+    /// Nothing is installed unless the header and every logged block pass their checksums.
+    /// A read-only mount cannot replay the log, so it fails instead.
+    fn recover(&mut self) -> Result<(), &'static str> {
         //info!("file system: checking logs");
         self.read_head();
         if self.lh.len > 0 {
-            //info!("file system: recovering from logs");
+            if mount_options().read_only {
+                return Err("log: replay needed, cannot mount read-only");
+            }
+            self.verify_log()?;
+            info!("[Xv6fs] log: replaying transaction {} ({} blocks)", self.lh.seq, self.lh.len);
             self.install_trans(true);
             self.empty_head();
         } else {
             //info!("file system: no need to recover");
         }
+        Ok(())
+    }
+
+    /// Check the in-memory log header and the logged blocks on disk against their checksums.
+    fn verify_log(&self) -> Result<(), &'static str> {
+        if self.lh.len as usize >= LOGSIZE || self.lh.len >= self.size {
+            return Err("log: header length out of range");
+        }
+        if self.lh.checksum != self.lh.compute_checksum() {
+            return Err("log: header checksum mismatch");
+        }
+        for i in 0..self.lh.len {
+            let log_buf = BLOCK_CACHE_MANAGER.bread(self.dev, self.start+1+i);
+            let sum = crc32c(unsafe { &*(log_buf.raw_data() as *const [u8; BSIZE]) });
+            drop(log_buf);
+            if sum != self.lh.checksums[i as usize] {
+                warn!("[Xv6fs] log: block {} of transaction {} is corrupted", i, self.lh.seq);
+                return Err("log: block checksum mismatch");
+            }
+        }
+        Ok(())
     }
 
     /// Read the log header from disk into the in-memory log header.
@@ -101,6 +135,7 @@ impl Log {
     /// Write in-memory log header to disk.
     /// This is the true point at which the current transaction commits.
     fn write_head(&mut self) {
+        self.lh.checksum = self.lh.compute_checksum();
         let mut buf = BLOCK_CACHE_MANAGER.bread(self.dev, self.start);
         unsafe {
             ptr::copy_nonoverlapping(
@@ -117,11 +152,7 @@ impl Log {
     /// setting the len of log(both in-memory and in-disk) to zero.
     fn empty_head(&mut self) {
         self.lh.len = 0;
-        let mut buf = BLOCK_CACHE_MANAGER.bread(self.dev, self.start);
-        let raw_lh = buf.raw_data_mut() as *mut LogHeader;
-        unsafe { raw_lh.as_mut().unwrap().len = 0; }
-        buf.bwrite();
-        drop(buf);
+        self.write_head();
     }
 
     /// Copy committed blocks from log to their home location.
@@ -147,6 +178,9 @@ impl Log {
     }
 
     /// Commit the log.
+    /// The device is flushed after the log blocks, after the header and after
+    /// the install, so that the header never reaches the disk before the blocks
+    /// it describes, and the log is never emptied before the install is durable.
     /// SAFETY: It must be called while the committing field is set.
     pub unsafe fn commit(&mut self) {
         self.committing=true;
@@ -155,15 +189,20 @@ impl Log {
         }
         // debug_assert!(self.lh.len > 0);     // it should have some log to commit
         if self.lh.len > 0 {
+            self.lh.seq = self.lh.seq.wrapping_add(1);
             self.write_log();
+            BLOCK_CACHE_MANAGER.flush();
             self.write_head();
+            BLOCK_CACHE_MANAGER.flush();
             self.install_trans(false);
+            BLOCK_CACHE_MANAGER.flush();
             self.empty_head();
         }
         self.committing=false;
     }
 
-    /// Copy the log content from buffer cache to disk.
+    /// Copy the log content from buffer cache to disk,
+    /// recording the checksum of each block in the header.
     fn write_log(&mut self) {
         for i in 0..self.lh.len {
             let mut log_buf  = BLOCK_CACHE_MANAGER.bread(self.dev, self.start+1+i);
@@ -175,6 +214,7 @@ impl Log {
                     1,
                 );
             }
+            self.lh.checksums[i as usize] = crc32c(unsafe { &*(cache_buf.raw_data() as *const [u8; BSIZE]) });
             log_buf.bwrite();
             drop(cache_buf);
             drop(log_buf);
@@ -205,11 +245,13 @@ impl LogManager {
 
     /// Accept a buffer, write it into the log and then release the buffer.
     /// This function will pin this buf in the cache until the log commits.
-    pub fn write(&self, buf: Buf) {
+    /// Fails with `ENOSPC` if the open transaction has filled the log.
+    pub fn write(&self, buf: Buf) -> Result<(), &'static str> {
         let mut guard = self.log.lock();
         
         if (guard.lh.len+1) as usize >= LOGSIZE || guard.lh.len+1 >= guard.size {
-            panic!("log: not enough space for ongoing transactions");
+            warn!("[Xv6fs] log: not enough space for ongoing transactions");
+            return Err(ENOSPC);
         }
         // if guard.outstanding < 1 {
         //     panic!("log: this log write is out of recording");
@@ -221,11 +263,12 @@ impl LogManager {
                 //info!("buf blockno {} is in the lh.blocknos, and now len is {}",guard.lh.blocknos[i as usize],guard.lh.len);
                 drop(guard);
                 drop(buf);
-                return;
+                return Ok(());
             }
         }
         if (guard.lh.len+2) as usize >= LOGSIZE || guard.lh.len+2 >= guard.size {
-            panic!("log: not enough space for this transaction");
+            warn!("[Xv6fs] log: not enough space for this transaction");
+            return Err(ENOSPC);
         }
         unsafe { buf.pin(); }
         let len = guard.lh.len as usize;
@@ -234,20 +277,91 @@ impl LogManager {
         //info!("insert blockno {},Log Header len +1, and now len is {}",buf.read_blockno(),guard.lh.len);
         drop(guard);
         drop(buf);
+        Ok(())
     }
 
     // It should be called at the end of file system call.
-    // It will commit the log if this is the last outstanding op.
+    // It commits the log once `commit_interval` operations have gathered,
+    // or earlier if another operation might not fit in the log or the cache.
     pub fn commit_log(&self) {
+        let options = mount_options();
+        let mut guard = self.log.lock();
+        guard.pending_ops += 1;
+        let room = (LOGSIZE - 1).min(options.cache_blocks - MAXOPBLOCKS);
+        if !options.sync
+            && guard.pending_ops < options.commit_interval
+            && guard.lh.len as usize + MAXOPBLOCKS < room
+        {
+            return;
+        }
+        guard.pending_ops = 0;
+        unsafe{guard.commit()};
+        drop(guard);
+    }
+
+    pub fn stats(&self) -> LogStats {
+        let guard = self.log.lock();
+        LogStats {
+            size: guard.size as usize,
+            used: guard.lh.len as usize,
+            pending_ops: guard.pending_ops as usize,
+            seq: guard.lh.seq,
+        }
+    }
+
+    /// Commit whatever is in the log and flush the device,
+    /// so that everything written before is on stable storage.
+    pub fn sync(&self) {
         let mut guard = self.log.lock();
+        guard.pending_ops = 0;
         unsafe{guard.commit()};
         drop(guard);
+        BLOCK_CACHE_MANAGER.flush();
     }
 }
 
+/// Counters reported by [`LogManager::stats`].
+#[derive(Clone, Copy, Debug)]
+pub struct LogStats {
+    /// Blocks in the on-disk log, including the header.
+    pub size: usize,
+    /// Blocks the open transaction has logged so far.
+    pub used: usize,
+    /// Operations waiting for the next commit.
+    pub pending_ops: usize,
+    /// Sequence number of the last committed transaction.
+    pub seq: u32,
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct LogHeader {
     len: u32,                       // current len of blocknos array
     blocknos: [u32; LOGSIZE-1],     // LOGSIZE-1: one block left for log info
+    seq: u32,                       // sequence number of the last committed transaction
+    checksums: [u32; LOGSIZE-1],    // crc32c of each logged block
+    checksum: u32,                  // crc32c of all the fields above, must stay last
+}
+
+impl LogHeader {
+    const fn empty() -> Self {
+        Self {
+            len: 0,
+            blocknos: [0; LOGSIZE-1],
+            seq: 0,
+            checksums: [0; LOGSIZE-1],
+            checksum: 0,
+        }
+    }
+
+    /// Checksum of the header, not including the checksum field itself.
+    fn compute_checksum(&self) -> u32 {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                mem::size_of::<Self>() - mem::size_of::<u32>(),
+            )
+        };
+        crc32c(bytes)
+    }
 }
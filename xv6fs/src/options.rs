@@ -0,0 +1,86 @@
+//! Mount options.
+
+use spin::RwLock;
+
+use crate::fs_const::{MAXOPBLOCKS, NBUF};
+
+/// Options in effect for the mounted file system, set once by `init_with_options`.
+static MOUNT_OPTIONS: RwLock<MountOptions> = RwLock::new(MountOptions::new());
+
+/// Error returned by every change on a read-only mount.
+pub const EROFS: &str = "read-only file system";
+
+/// Smallest usable buffer cache: room for one full transaction plus the
+/// blocks an operation reads while building it.
+pub const MIN_CACHE_BLOCKS: usize = MAXOPBLOCKS * 2;
+
+/// How file data is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataMode {
+    /// Data blocks go through the log together with the metadata.
+    Journal,
+    /// Data blocks are written in place, before the metadata that
+    /// points to them is committed. Saves log space, but an overwrite
+    /// interrupted by a crash may be left half done.
+    Ordered,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MountOptions {
+    /// Refuse every change, and refuse to mount if the log needs replaying.
+    pub read_only: bool,
+    /// Commit and flush after every operation, whatever `commit_interval` is.
+    pub sync: bool,
+    /// Commit the log after this many operations. Operations in between
+    /// may be lost on a crash, but never half applied.
+    pub commit_interval: u32,
+    pub data_mode: DataMode,
+    /// Number of buffer cache blocks to use, between MIN_CACHE_BLOCKS and NBUF.
+    pub cache_blocks: usize,
+}
+
+impl MountOptions {
+    pub const fn new() -> Self {
+        Self {
+            read_only: false,
+            sync: false,
+            commit_interval: 1,
+            data_mode: DataMode::Journal,
+            cache_blocks: NBUF,
+        }
+    }
+
+    pub fn check(&self) -> Result<(), &'static str> {
+        if self.commit_interval == 0 {
+            return Err("mount: commit interval must be at least 1");
+        }
+        if self.cache_blocks < MIN_CACHE_BLOCKS || self.cache_blocks > NBUF {
+            return Err("mount: cache size out of range");
+        }
+        Ok(())
+    }
+}
+
+impl Default for MountOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Options the file system is mounted with.
+pub fn mount_options() -> MountOptions {
+    *MOUNT_OPTIONS.read()
+}
+
+pub(crate) fn set_mount_options(options: MountOptions) {
+    *MOUNT_OPTIONS.write() = options;
+}
+
+/// Fail with EROFS on a read-only mount.
+pub(crate) fn check_writable() -> Result<(), &'static str> {
+    if MOUNT_OPTIONS.read().read_only {
+        Err(EROFS)
+    } else {
+        Ok(())
+    }
+}
@@ -9,7 +9,8 @@ use core::ptr;
 use core::mem::{self, MaybeUninit};
 use core::sync::atomic::{AtomicBool, Ordering};
 
-use crate::fs_const::{ FSMAGIC, IPB, BPB };
+use crate::fs_const::{ FSMAGIC, FS_FEATURE_CSUM, FS_FEATURE_INODE_EXT, IPB, BPB };
+use crate::crc32c::crc32c;
 use crate::buffer_cache::{ BLOCK_CACHE_MANAGER, BufData };
 
 pub static mut SUPER_BLOCK: SuperBlock = SuperBlock::uninit();
@@ -32,11 +33,13 @@ impl SuperBlock {
     }
 
     /// Read and init the super block from disk into memory.
+    /// Return an error if the magic number or the checksum is wrong, or the
+    /// image predates the current inode layout.
     /// SAFETY: it should only be called by the first regular process alone.
-    pub unsafe fn init(&mut self, dev: u32) {
+    pub unsafe fn init(&mut self, dev: u32) -> Result<(), &'static str> {
         debug_assert_eq!(mem::align_of::<BufData>() % mem::align_of::<RawSuperBlock>(), 0);
         if self.initialized.load(Ordering::Relaxed) {
-            return
+            return Ok(())
         }
         let buf = BLOCK_CACHE_MANAGER.bread(dev, 1);
         ptr::copy_nonoverlapping(
@@ -44,16 +47,29 @@ impl SuperBlock {
             self.data.as_mut_ptr(),
             1,
         );
+        drop(buf);
         //info!("check magic number");
-        if self.data.as_ptr().as_ref().unwrap().magic != FSMAGIC {
-            panic!("invalid file system magic num");
+        let sb = self.data.as_ptr().as_ref().unwrap();
+        if sb.magic != FSMAGIC {
+            return Err("superblock: invalid file system magic num");
+        }
+        if sb.features & FS_FEATURE_CSUM != 0 && sb.checksum != sb.compute_checksum() {
+            return Err("superblock: checksum mismatch");
+        }
+        if sb.features & FS_FEATURE_INODE_EXT == 0 {
+            return Err("superblock: old inode layout, remake the image with xv6mkfs");
         }
-        info!("superblock init data {:?}",self.data.as_ptr().as_ref().unwrap());
+        info!("superblock init data {:?}",sb);
         self.initialized.store(true, Ordering::SeqCst);
-        drop(buf);
 
         #[cfg(feature = "verbose_init_info")]
         info!("super block data: {:?}", self.data.as_ptr().as_ref().unwrap());
+        Ok(())
+    }
+
+    /// Forget the super block, so that `init` reads it again.
+    pub fn reset(&self) {
+        self.initialized.store(false, Ordering::SeqCst);
     }
 
     /// Read the info of super block.
@@ -89,6 +105,11 @@ impl SuperBlock {
         sb.bmapstart
     }
 
+    /// Whether the file system was made with the given FS_FEATURE_* flag.
+    pub fn has_feature(&self, feature: u32) -> bool {
+        self.read().features & feature != 0
+    }
+
     /// inode numbers
     pub fn ninodes(&self) -> u32 {
         self.read().ninodes
@@ -130,11 +151,24 @@ pub struct RawSuperBlock {
     pub logstart: u32,   // Block number of first log block
     pub inodestart: u32, // Block number of first inode block
     pub bmapstart: u32,  // Block number of first free map block
+    pub features: u32,   // Optional features (FS_FEATURE_*), zero on old images
+    pub checksum: u32,   // crc32c of the fields above if FS_FEATURE_CSUM is set, must stay last
 }
 
 impl RawSuperBlock {
     pub fn new()->Self{
         RawSuperBlock { magic: 0, size: 0, nblocks: 0, ninodes: 0, 
-            nlog: 0, logstart: 0, inodestart: 0, bmapstart: 0 }
+            nlog: 0, logstart: 0, inodestart: 0, bmapstart: 0, features: 0, checksum: 0 }
+    }
+
+    /// Checksum of the super block, not including the checksum field itself.
+    pub fn compute_checksum(&self) -> u32 {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                mem::size_of::<Self>() - mem::size_of::<u32>(),
+            )
+        };
+        crc32c(bytes)
     }
 }
\ No newline at end of file
@@ -0,0 +1,177 @@
+//! Extended attributes.
+//!
+//! All attributes of an inode live in one block pointed to by `DiskInode::xattr`.
+//! The block starts with `XATTR_MAGIC`, followed by entries of
+//! `name_len: u8, value_len: u16 (LE), name, value`, ended by a zero `name_len`.
+//! The block goes through the log like any other metadata block.
+
+use alloc::{string::String, vec::Vec};
+use core::ptr;
+
+use crate::bitmap::{balloc, bfree};
+use crate::buffer_cache::BLOCK_CACHE_MANAGER;
+use crate::fs_const::BSIZE;
+use crate::inode::InodeData;
+use crate::log::LOG_MANAGER;
+
+/// Magic number at the start of every xattr block.
+pub const XATTR_MAGIC: u32 = 0xEA02_0000;
+/// Supported attribute namespaces.
+pub const XATTR_NAMESPACES: [&str; 2] = ["user.", "system."];
+/// Longest attribute name, including the namespace prefix.
+pub const XATTR_NAME_MAX: usize = 255;
+
+type XattrList = Vec<(String, Vec<u8>)>;
+
+/// Check that the name is in a supported namespace and not too long.
+pub fn check_name(name: &str) -> Result<(), &'static str> {
+    if name.len() > XATTR_NAME_MAX {
+        return Err("xattr: name too long");
+    }
+    match XATTR_NAMESPACES.iter().find(|ns| name.starts_with(*ns)) {
+        Some(ns) if name.len() > ns.len() => Ok(()),
+        _ => Err("xattr: unsupported namespace"),
+    }
+}
+
+fn decode(block: &[u8]) -> Result<XattrList, &'static str> {
+    if u32::from_le_bytes(block[0..4].try_into().unwrap()) != XATTR_MAGIC {
+        return Err("xattr: bad xattr block");
+    }
+    let mut entries = Vec::new();
+    let mut pos = 4;
+    while pos + 3 <= block.len() && block[pos] != 0 {
+        let name_len = block[pos] as usize;
+        let value_len = u16::from_le_bytes([block[pos + 1], block[pos + 2]]) as usize;
+        let start = pos + 3;
+        let end = start + name_len + value_len;
+        if end > block.len() {
+            return Err("xattr: bad xattr block");
+        }
+        let name = core::str::from_utf8(&block[start..start + name_len])
+            .map_err(|_| "xattr: bad xattr block")?;
+        entries.push((String::from(name), block[start + name_len..end].to_vec()));
+        pos = end;
+    }
+    Ok(entries)
+}
+
+fn encode(entries: &XattrList, block: &mut [u8]) -> Result<(), &'static str> {
+    block.fill(0);
+    block[0..4].copy_from_slice(&XATTR_MAGIC.to_le_bytes());
+    let mut pos = 4;
+    for (name, value) in entries {
+        let end = pos + 3 + name.len() + value.len();
+        // keep one byte for the terminator
+        if end >= block.len() {
+            return Err("xattr: no space left in xattr block");
+        }
+        block[pos] = name.len() as u8;
+        block[pos + 1..pos + 3].copy_from_slice(&(value.len() as u16).to_le_bytes());
+        block[pos + 3..pos + 3 + name.len()].copy_from_slice(name.as_bytes());
+        block[pos + 3 + name.len()..end].copy_from_slice(value);
+        pos = end;
+    }
+    Ok(())
+}
+
+impl InodeData {
+    fn load_xattrs(&self) -> Result<XattrList, &'static str> {
+        if self.dinode.xattr == 0 {
+            return Ok(Vec::new());
+        }
+        let buf = BLOCK_CACHE_MANAGER.bread(self.dev, self.dinode.xattr);
+        let block = unsafe { &*(buf.raw_data() as *const [u8; BSIZE]) };
+        decode(block)
+    }
+
+    fn store_xattrs(&mut self, entries: &XattrList) -> Result<(), &'static str> {
+        if entries.is_empty() {
+            return self.free_xattr();
+        }
+        let mut block = [0u8; BSIZE];
+        encode(entries, &mut block)?;
+        if self.dinode.xattr == 0 {
+            self.dinode.xattr = balloc(self.dev)?;
+            self.update()?;
+        }
+        let mut buf = BLOCK_CACHE_MANAGER.bread(self.dev, self.dinode.xattr);
+        unsafe { ptr::copy_nonoverlapping(block.as_ptr(), buf.raw_data_mut() as *mut u8, BSIZE) };
+        LOG_MANAGER.write(buf)
+    }
+
+    /// Get the value of an extended attribute.
+    pub fn xattr_get(&self, name: &str) -> Result<Vec<u8>, &'static str> {
+        check_name(name)?;
+        self.load_xattrs()?
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v)
+            .ok_or("xattr: no such attribute")
+    }
+
+    /// Create or replace an extended attribute.
+    pub fn xattr_set(&mut self, name: &str, value: &[u8]) -> Result<(), &'static str> {
+        check_name(name)?;
+        let mut entries = self.load_xattrs()?;
+        match entries.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_vec(),
+            None => entries.push((String::from(name), value.to_vec())),
+        }
+        self.store_xattrs(&entries)
+    }
+
+    /// Names of all extended attributes.
+    pub fn xattr_list(&self) -> Result<Vec<String>, &'static str> {
+        Ok(self.load_xattrs()?.into_iter().map(|(n, _)| n).collect())
+    }
+
+    /// Remove an extended attribute.
+    pub fn xattr_remove(&mut self, name: &str) -> Result<(), &'static str> {
+        check_name(name)?;
+        let mut entries = self.load_xattrs()?;
+        let len = entries.len();
+        entries.retain(|(n, _)| n != name);
+        if entries.len() == len {
+            return Err("xattr: no such attribute");
+        }
+        self.store_xattrs(&entries)
+    }
+
+    /// Release the xattr block, if any. Called when the inode is freed.
+    pub fn free_xattr(&mut self) -> Result<(), &'static str> {
+        if self.dinode.xattr != 0 {
+            bfree(self.dev, self.dinode.xattr)?;
+            self.dinode.xattr = 0;
+            self.update()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces() {
+        assert!(check_name("user.sha256").is_ok());
+        assert!(check_name("system.provenance").is_ok());
+        assert!(check_name("user.").is_err());
+        assert!(check_name("trusted.x").is_err());
+    }
+
+    #[test]
+    fn encode_decode() {
+        let entries = vec![
+            (String::from("user.a"), b"1".to_vec()),
+            (String::from("user.empty"), Vec::new()),
+        ];
+        let mut block = [0u8; BSIZE];
+        encode(&entries, &mut block).unwrap();
+        assert_eq!(decode(&block).unwrap(), entries);
+
+        let big = vec![(String::from("user.big"), vec![0u8; BSIZE])];
+        assert!(encode(&big, &mut block).is_err());
+    }
+}
@@ -8,7 +8,7 @@ use crate::file::{VFile,FileType};
 use crate::inode::{ICACHE,Inode};
 use crate::misc::mem_set;
 use crate::superblock::RawSuperBlock;
-use crate::fs_const::{FSMAGIC,NDIRECT,MAXFILE,BSIZE,BPB,IPB,DIRSIZ,ROOTINUM,FSSIZE,NDINODES, LOGSIZE, ROOTDEV};
+use crate::fs_const::{FSMAGIC,FS_FEATURE_INODE_EXT,NDIRECT,MAXFILE,BSIZE,BPB,IPB,DIRSIZ,ROOTINUM,FSSIZE,NDINODES, LOGSIZE, ROOTDEV};
 
 
 static mut FREEBLOCK:usize=0;
@@ -54,6 +54,7 @@ impl Xv6FileSystem {
         raw_superblock.logstart=2;
         raw_superblock.inodestart=2+self.nlog as u32;
         raw_superblock.bmapstart=(2+self.nlog+self.ninodeblocks) as u32;
+        raw_superblock.features=FS_FEATURE_INODE_EXT;
         let mut buf=[0 as u8;BSIZE];
         for i in 0..FSSIZE{
             block_device.write_block(i, &buf);
@@ -100,23 +101,23 @@ impl Xv6FileSystem {
         todo!()//inode_append也是涉及到mkds的翻译的，这里是为了在directory中添加目录项
     }
 
-    pub fn get_root_inode(&mut self)->Inode{
+    pub fn get_root_inode(&mut self)->Result<Inode,&'static str>{
         ICACHE.get_root_dir()
     }
 
-    pub fn get_root_vfile(&self)->VFile{
-        let inode=ICACHE.get_root_dir();
+    pub fn get_root_vfile(&self)->Result<VFile,&'static str>{
+        let inode=ICACHE.get_root_dir()?;
         let idata=inode.lock();
         let mut ftype=FileType::Directory;
         drop(idata);
-        VFile { 
+        Ok(VFile { 
             ftype,
             readable:true, 
             writeable:true, 
             inode:Some(inode), 
             offset:0,
             major:2 
-        }
+        })
     }
     
 
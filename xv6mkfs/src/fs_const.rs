@@ -2,6 +2,13 @@ use core::mem::size_of;
 use crate::structs::DiskInode;
 /// magic number indentifying this specific file system
 pub const FSMAGIC: u32 = 0x10203040;
+/// super block feature: the super block carries a crc32c checksum
+pub const FS_FEATURE_CSUM: u32 = 0x1;
+/// super block feature: new files and directories may keep their data inline
+pub const FS_FEATURE_INLINE: u32 = 0x2;
+/// super block feature: inodes have the flags and xattr fields and NDIRECT
+/// direct blocks. Required: older images put block addresses there.
+pub const FS_FEATURE_INODE_EXT: u32 = 0x4;
 /// size of disk block
 pub const BSIZE: usize = 512;
 /// Maxinum of blocks an FS op can write
@@ -14,7 +21,15 @@ pub const NDINODES: usize = 200;
 /// size of file system in blocks
 pub const FSSIZE: usize = 1000; 
 
-pub const NDIRECT: usize = 11;
+pub const NDIRECT: usize = 9;
+
+/// Device nodes created under /dev: (name, major, minor)
+pub const DEV_NODES: [(&str, i16, i16); 4] = [
+    ("null", 1, 3),
+    ("zero", 1, 5),
+    ("random", 1, 8),
+    ("console", 5, 1),
+];
 
 /// Directory is a file containing a sequence of dirent structures
 pub const DIRSIZ: usize = 14;
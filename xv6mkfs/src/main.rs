@@ -3,39 +3,31 @@ use crate::fs_const::*;
 
 mod structs;
 mod fs_const;
+mod crc32c;
 
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use driver_block::{BlockDriverOps, FileDisk};
 use std::mem::size_of;
 use std::sync::Arc;
-use std::sync::Mutex;
 
 use std::ptr::copy_nonoverlapping;
 
-/// Use a block size of 512 bytes
-const BLOCK_SZ: usize = 512;
-const BLOCK_NUM:usize = 1000;
+const BLOCK_NUM:u64 = 1000;
 
 
 static mut FREEBLOCK:usize=0;
 static mut FREEINODE:usize=1;
 
-struct BlockFile(Mutex<File>);
+/// The image being built, in the block driver shared with the kernel.
+struct BlockFile(FileDisk);
 
 impl BlockDevice for BlockFile {
     /// Read a block from file
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
-        let mut file = self.0.lock().unwrap();
-        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
-            .expect("Error when seeking!");
-        assert_eq!(file.read(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+        self.0.read_block(block_id, buf).expect("can't read block");
     }
     /// Write a block into file
     fn write_block(&self, block_id: usize, buf: &[u8]) {
-        let mut file = self.0.lock().unwrap();
-        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
-            .expect("Error when seeking!");
-        assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+        self.0.write_block(block_id, buf).expect("can't write block");
     }
 }
 
@@ -57,6 +49,49 @@ fn ialloc(itype:InodeType)->DiskInode{
     
 }
 
+/// Allocate a device inode, which owns no data blocks.
+fn ialloc_dev(major:i16,minor:i16)->DiskInode{
+    let mut dinode=DiskInode::new();
+    dinode.itype=InodeType::Device;
+    dinode.nlink=1;
+    dinode.major=major;
+    dinode.minor=minor;
+    dinode
+}
+
+/// Write a disk inode into its slot of the inode blocks.
+fn write_inode(block_file:&BlockFile,inum:usize,dinode:&DiskInode,inodestart:usize){
+    let mut buf=[0 as u8;BSIZE];
+    let block_id=iblock(inum, inodestart);
+    block_file.read_block(block_id, &mut buf);
+    unsafe{
+        copy_nonoverlapping(
+            dinode as *const DiskInode, 
+            (buf.as_mut_ptr() as usize + (inum%IPB)*core::mem::size_of::<DiskInode>()) as *mut DiskInode, 
+            1
+        );
+    }
+    block_file.write_block(block_id, &buf);
+}
+
+/// Write the slot-th directory entry into the first block of a directory.
+fn write_dirent(block_file:&BlockFile,dir:&DiskInode,slot:usize,name:&str,inum:usize){
+    let mut buf=[0 as u8;BSIZE];
+    let mut dir_entry=DirEntry::new();
+    dir_entry.name[..name.len()].copy_from_slice(name.as_bytes());
+    dir_entry.inum=inum as u16;
+    let block_id=dir.addrs[0];
+    block_file.read_block(block_id as usize, &mut buf);
+    unsafe{
+        copy_nonoverlapping(
+            &dir_entry as *const DirEntry,
+            (buf.as_mut_ptr() as usize + slot*size_of::<DirEntry>()) as *mut DirEntry,
+            1
+        );
+    }
+    block_file.write_block(block_id as usize, &buf);
+}
+
 fn main() {
     //let nbitmap= FSSIZE/(BSIZE*8) + 1;
     let ninodeblocks= NDINODES/IPB + 1;
@@ -73,18 +108,14 @@ fn main() {
     raw_superblock.logstart=2;
     raw_superblock.inodestart=2+nlog as u32;
     raw_superblock.bmapstart=(2+30+ninodeblocks) as u32;
+    raw_superblock.features=FS_FEATURE_CSUM | FS_FEATURE_INLINE | FS_FEATURE_INODE_EXT;
+    raw_superblock.checksum=raw_superblock.compute_checksum();
 
     //memset disk to 0
     let mut buf=[0 as u8;BSIZE];
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/disk.img").unwrap();
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
-        f
-    })));
+    let block_file = Arc::new(BlockFile(
+        FileDisk::create("target/disk.img", BLOCK_NUM).expect("can't create target/disk.img"),
+    ));
     for i in 0..FSSIZE{
         block_file.write_block(i, &buf);
     }
@@ -143,6 +174,21 @@ fn main() {
     }
     block_file.write_block(block_id as usize, &buf);
 
+    //create /dev with the built-in device nodes
+    let dev_inum=unsafe{FREEINODE};
+    unsafe{FREEINODE+=1;}
+    let dev_dinode=ialloc(InodeType::Directory);
+    write_inode(&block_file, dev_inum, &dev_dinode, raw_superblock.inodestart as usize);
+    write_dirent(&block_file, &dev_dinode, 0, ".", dev_inum);
+    write_dirent(&block_file, &dev_dinode, 1, "..", rinum);
+    write_dirent(&block_file, &dinode, 2, "dev", dev_inum);
+    for (i,(name,major,minor)) in DEV_NODES.iter().enumerate(){
+        let inum=unsafe{FREEINODE};
+        unsafe{FREEINODE+=1;}
+        write_inode(&block_file, inum, &ialloc_dev(*major, *minor), raw_superblock.inodestart as usize);
+        write_dirent(&block_file, &dev_dinode, 2+i, name, inum);
+    }
+
     //write bitmap
     let bblock_id=raw_superblock.bmapstart;
     println!("bitmap start is {}",bblock_id);
@@ -1,5 +1,7 @@
 use crate::fs_const::*;
+use crate::crc32c::crc32c;
 use core::any::Any;
+#[repr(C)]
 pub struct RawSuperBlock {
     pub magic: u32,      // Must be FSMAGIC
     pub size: u32,       // Size of file system image (blocks)
@@ -9,19 +11,34 @@ pub struct RawSuperBlock {
     pub logstart: u32,   // Block number of first log block
     pub inodestart: u32, // Block number of first inode block
     pub bmapstart: u32,  // Block number of first free map block
+    pub features: u32,   // Optional features (FS_FEATURE_*)
+    pub checksum: u32,   // crc32c of the fields above if FS_FEATURE_CSUM is set, must stay last
 }
 
 impl RawSuperBlock {
     pub fn new()->Self{
         RawSuperBlock { magic: 0, size: 0, nblocks: 0, ninodes: 0, 
-            nlog: 0, logstart: 0, inodestart: 0, bmapstart: 0 }
+            nlog: 0, logstart: 0, inodestart: 0, bmapstart: 0, features: 0, checksum: 0 }
+    }
+
+    /// Checksum of the super block, not including the checksum field itself.
+    pub fn compute_checksum(&self) -> u32 {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>() - core::mem::size_of::<u32>(),
+            )
+        };
+        crc32c(bytes)
     }
 }
 
 #[derive(Clone, Copy, Debug)]
+#[repr(u16)]
 pub enum InodeType {
     Empty = 0,
     Directory = 1,
+    Device = 3,
 }
 
 /// On-disk inode structure
@@ -33,7 +50,9 @@ pub struct DiskInode {
     pub minor: i16, // Minor device number (T_DEVICE only)
     pub nlink: i16, // Number of links to inode in file system
     pub size: u32, // Size of file (bytes)
-    pub addrs: [u32; NDIRECT+2] // Data block addresses
+    pub flags: u32, // Inode flags, mkfs never writes inline data
+    pub addrs: [u32; NDIRECT+2], // Data block addresses
+    pub xattr: u32, // Block holding extended attributes, 0 if none
 }
 
 impl DiskInode {
@@ -44,7 +63,9 @@ impl DiskInode {
             minor: 0,
             nlink: 0,
             size: 0,
-            addrs: [0; NDIRECT+2]
+            flags: 0,
+            addrs: [0; NDIRECT+2],
+            xattr: 0
         }
     }
 }